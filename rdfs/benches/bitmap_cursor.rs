@@ -0,0 +1,39 @@
+//! Sequential single block allocation on a mostly full bitmap, starting the first fit scan at
+//! `BitmapsBlock::next_free` versus rescanning from block 0 every time.
+//!
+//! Run with `cargo bench -p rdfs --bench bitmap_cursor`.
+
+use criterion::{Criterion, black_box, criterion_group, criterion_main};
+use rdfs::prelude::*;
+
+const BLOCKS: u64 = 4 * 1024 * 1024;
+const USED: u64 = BLOCKS / 10 * 9;
+
+fn full_bitmap() -> BitmapsBlock {
+    let mut bitmaps = BitmapsBlock::new(BLOCKS, 0);
+    bitmaps.set_range(0, USED as usize);
+    bitmaps
+}
+
+/// Allocates one block, freeing the free tail again once it is used up.
+fn allocate_one(bitmaps: &mut BitmapsBlock, rescan: bool) -> Vec<FileContent> {
+    if bitmaps.free_blocks == 0 {
+        bitmaps.clear_range(USED as usize, (BLOCKS - USED) as usize);
+    }
+    if rescan {
+        bitmaps.next_free = 0;
+    }
+    bitmaps.allocate(black_box(1)).unwrap()
+}
+
+fn sequential_allocate(c: &mut Criterion) {
+    let mut group = c.benchmark_group("sequential_allocate");
+    let mut bitmaps = full_bitmap();
+    group.bench_function("cursor", |b| b.iter(|| allocate_one(&mut bitmaps, false)));
+    let mut bitmaps = full_bitmap();
+    group.bench_function("rescan", |b| b.iter(|| allocate_one(&mut bitmaps, true)));
+    group.finish();
+}
+
+criterion_group!(benches, sequential_allocate);
+criterion_main!(benches);
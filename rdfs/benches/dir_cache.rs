@@ -0,0 +1,36 @@
+//! Repeated path resolution under a hot directory, with and without `RDFS::with_cache`.
+//!
+//! Run with `cargo bench -p rdfs --bench dir_cache`.
+
+use criterion::{Criterion, black_box, criterion_group, criterion_main};
+use rdfs::prelude::*;
+use std::fs;
+
+const FILES: usize = 200;
+
+fn hot_drive() -> RDFS {
+    let dir = std::env::temp_dir().join("rdfs_bench_dir_cache");
+    let _ = fs::remove_dir_all(&dir);
+    fs::create_dir_all(&dir).unwrap();
+    let rdfs = RDFS::new(dir, FileSystemType::Shared, [255; 32], [2; 32], 8 * 1048576, 100, 1, 4096).unwrap();
+    let root = rdfs.system.inode_pointer;
+    let hot = rdfs.mkdir(root, "hot").unwrap();
+    for i in 0..FILES {
+        rdfs.create_file(hot, &format!("file_{i}.txt"), b"x").unwrap();
+    }
+    rdfs
+}
+
+fn resolve_path(c: &mut Criterion) {
+    let rdfs = hot_drive();
+    let last = format!("/hot/file_{}.txt", FILES - 1);
+
+    let mut group = c.benchmark_group("resolve_path");
+    group.bench_function("uncached", |b| b.iter(|| rdfs.resolve_path(black_box(&last)).unwrap()));
+    let cached = rdfs.clone().with_cache();
+    group.bench_function("cached", |b| b.iter(|| cached.resolve_path(black_box(&last)).unwrap()));
+    group.finish();
+}
+
+criterion_group!(benches, resolve_path);
+criterion_main!(benches);
@@ -0,0 +1,50 @@
+//! Streaming blocks spread over a 1GB drive with `RDFS::read_blocks`, reading the file for every
+//! block versus going through the memory mapping of `RDFS::with_mmap`.
+//!
+//! Run with `cargo bench -p rdfs --bench mapped_read_blocks`.
+
+use criterion::{Criterion, Throughput, black_box, criterion_group, criterion_main};
+use rdfs::prelude::*;
+use std::fs;
+
+const STORAGE: u64 = 1 << 30;
+const BLOCK_SIZE: u64 = 4096;
+const STRIDE: u64 = 64; // one block read every 256KB, 4096 blocks over the whole drive
+
+fn large_drive() -> RDFS {
+    let dir = std::env::temp_dir().join("rdfs_bench_mapped_read_blocks");
+    let _ = fs::remove_dir_all(&dir);
+    fs::create_dir_all(&dir).unwrap();
+    RDFS::new(dir, FileSystemType::Shared, [255; 32], [3; 32], STORAGE, 100, 1, BLOCK_SIZE).unwrap()
+}
+
+/// Single block ranges every `STRIDE` blocks, the root inode excluded.
+fn spread_ranges(rdfs: &RDFS) -> Vec<FileContent> {
+    (0..rdfs.system.total_blocks - 1)
+        .step_by(STRIDE as usize)
+        .map(|index| FileContent {
+            pointer: rdfs.system.block_pointer(index).unwrap(),
+            blocks: 1,
+        })
+        .collect()
+}
+
+fn read_all(rdfs: &RDFS, ranges: &[FileContent]) -> usize {
+    rdfs.read_blocks(black_box(ranges.to_vec())).map(|block| block.unwrap().len()).sum()
+}
+
+fn read_blocks(c: &mut Criterion) {
+    let rdfs = large_drive();
+    let ranges = spread_ranges(&rdfs);
+
+    let mut group = c.benchmark_group("read_blocks_1gb");
+    group.throughput(Throughput::Bytes(ranges.len() as u64 * BLOCK_SIZE));
+    group.sample_size(20);
+    group.bench_function("file", |b| b.iter(|| read_all(&rdfs, &ranges)));
+    let mapped = rdfs.clone().with_mmap().unwrap();
+    group.bench_function("mapped", |b| b.iter(|| read_all(&mapped, &ranges)));
+    group.finish();
+}
+
+criterion_group!(benches, read_blocks);
+criterion_main!(benches);
@@ -1 +1,212 @@
+//! # RDFS Client Module
+//!
+//! The `Client` fetches the RaptorQ blocks of a client block from many storage nodes in
+//! parallel and decodes them as soon as enough packets arrived, so the slowest nodes are
+//! never waited for. A node that cannot be reached is retried, then replaced by another
+//! location of the same client block, the redundancy of the encoding covers the gap.
+//!
+//! Copyrights © 2025 RDFS Contributors. All rights reserved.
 
+use super::constants::{Address, RESERVED_CDB};
+use super::core::addresses_block::AddressesBlock;
+use super::core::data_block::DataBlock;
+use super::core::erasure_codec::{ErasureCodec, RaptorQ};
+use super::core::super_block::SuperBlock;
+use super::server::protocol::{Request, Response, read_frame, write_frame};
+use anyhow::{Result, anyhow};
+use std::net::{SocketAddr, TcpStream};
+use std::sync::mpsc;
+use std::thread;
+use std::time::Duration;
+
+/// Where one RaptorQ block of a client block is stored: the node slot and the block pointer on its drive.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ChunkLocation {
+    pub node: usize,
+    pub pointer: u64,
+}
+
+#[derive(Debug, Clone)]
+pub struct Client {
+    pub nodes: Vec<SocketAddr>, // node slot index -> socket address, same order as the `AddressesBlock`
+    pub redundancy: u64,        // blocks requested up front, in percent of the source symbols
+    pub retries: u32,           // extra attempts for a node before failing over
+    pub timeout: Duration,      // connect and read timeout of a single request
+}
+
+impl Client {
+    pub fn new(nodes: Vec<SocketAddr>) -> Self {
+        Self {
+            nodes,
+            redundancy: 100,
+            retries: 1,
+            timeout: Duration::from_secs(5),
+        }
+    }
+
+    /// Builds the node list from the public keys of `addresses`, keeping the slot order.
+    /// `resolve` maps a node public key to where it is listening.
+    pub fn from_addresses(addresses: &AddressesBlock, resolve: impl Fn(&Address) -> Option<SocketAddr>) -> Result<Self> {
+        let nodes = addresses
+            .addresses
+            .iter()
+            .map(|address| resolve(address).ok_or_else(|| anyhow!("No socket address for node {address:02x?}")))
+            .collect::<Result<_>>()?;
+        Ok(Self::new(nodes))
+    }
+
+    /// Number of blocks requested before any of them failed, `redundancy` percent of the
+    /// source symbols. More than 100 trades bandwidth for not waiting on failovers.
+    pub fn with_redundancy(mut self, redundancy: u64) -> Self {
+        self.redundancy = redundancy.max(100);
+        self
+    }
+
+    pub fn with_retries(mut self, retries: u32) -> Self {
+        self.retries = retries;
+        self
+    }
+
+    pub fn with_timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = timeout;
+        self
+    }
+
+    /// Reads the block at `pointer` from node `node`, retrying on connection failures.
+    /// An error status of the node is returned immediately since retrying would not change it.
+    pub fn read_block(&self, node: usize, pointer: u64) -> Result<Vec<u8>> {
+        let address = self.nodes.get(node).ok_or_else(|| anyhow!("No node in slot {node}"))?;
+        let mut attempt = 0;
+        loop {
+            match self.request(address, &Request::ReadBlock { pointer }) {
+                Ok(response) if response.is_ok() => return Ok(response.data),
+                Ok(response) => return Err(anyhow!("Node {node} failed with status {}", response.status)),
+                Err(error) if attempt >= self.retries => return Err(error),
+                Err(_) => attempt += 1,
+            }
+        }
+    }
+
+    /// Fetches the blocks of one client block in parallel and returns the decoded client data,
+    /// `client_block_size` bytes long, as soon as the RaptorQ decoder has enough packets.
+    /// Every failed location starts a request for one of the locations not asked yet.
+    pub fn fetch_client_block(&self, chunks: &[ChunkLocation], config: &SuperBlock) -> Result<Vec<u8>> {
+        self.fetch_client_block_with::<RaptorQ>(chunks, config)
+    }
+
+    /// Same as `fetch_client_block` for a drive encoded with another `ErasureCodec`.
+    pub fn fetch_client_block_with<C: ErasureCodec>(&self, chunks: &[ChunkLocation], config: &SuperBlock) -> Result<Vec<u8>> {
+        let source_symbols = config.client_block_size.div_ceil(config.block_size - RESERVED_CDB as u64);
+        let first_wave = (source_symbols * self.redundancy).div_ceil(100) as usize;
+
+        let (sender, receiver) = mpsc::channel();
+        let mut pending = chunks.iter().copied();
+        let mut in_flight = 0;
+        let spawn = |chunk: ChunkLocation| {
+            let client = self.clone();
+            let sender = sender.clone();
+            let block_size = config.block_size as usize;
+            thread::spawn(move || {
+                let block = client
+                    .read_block(chunk.node, chunk.pointer)
+                    .and_then(|bytes| DataBlock::from_bytes(&bytes, block_size));
+                // the receiver is gone once the block was decoded
+                let _ = sender.send(block.ok());
+            });
+        };
+        for chunk in pending.by_ref().take(first_wave) {
+            spawn(chunk);
+            in_flight += 1;
+        }
+
+        let blocks = std::iter::from_fn(|| {
+            while in_flight > 0 {
+                in_flight -= 1;
+                match receiver.recv().ok()? {
+                    Some(block) => return Some(block),
+                    None => {
+                        if let Some(chunk) = pending.next() {
+                            spawn(chunk);
+                            in_flight += 1;
+                        }
+                    }
+                }
+            }
+            None
+        });
+
+        DataBlock::decode_with_codec::<C>(blocks, config)
+    }
+
+    fn request(&self, address: &SocketAddr, request: &Request) -> Result<Response> {
+        let mut stream = TcpStream::connect_timeout(address, self.timeout)?;
+        stream.set_read_timeout(Some(self.timeout))?;
+        write_frame(&mut stream, &request.to_bytes())?;
+        let body = read_frame(&mut stream)?.ok_or_else(|| anyhow!("Node {address} closed the connection"))?;
+        Response::from_bytes(&body)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::core::super_block::FileSystemType;
+    use crate::file_system::RDFS;
+    use crate::server::node::Node;
+    use std::fs;
+    use std::net::TcpListener;
+
+    #[test]
+    fn fetch_client_block_test() {
+        let mut drives = Vec::new();
+        for node in 0..4 {
+            let dir = std::env::temp_dir().join(format!("rdfs_test_client_node_{node}"));
+            let _ = fs::remove_dir_all(&dir);
+            fs::create_dir_all(&dir).unwrap();
+            drives.push(RDFS::new(dir, FileSystemType::Shared, [255; 32], [1; 32], 4 * 1048576, 200, 4, 4096).unwrap());
+        }
+        let config = drives[0].system.clone();
+
+        // one RaptorQ block per node, stored in its first data block
+        let data: Vec<u8> = (0..config.client_block_size).map(|byte| (byte % 229) as u8).collect();
+        let blocks = DataBlock::encode_with_raptorq(&data, &config).unwrap();
+        assert_eq!(blocks.len(), 4);
+        let pointer = config.data_pointer;
+        for (drive, block) in drives.iter().zip(&blocks) {
+            drive.write_block(pointer, &block.to_bytes(4096)).unwrap();
+        }
+
+        // node 1 is down: its port is closed before anyone connects
+        let mut nodes = Vec::new();
+        for (i, drive) in drives.iter().enumerate() {
+            if i == 1 {
+                nodes.push(TcpListener::bind("127.0.0.1:0").unwrap().local_addr().unwrap());
+                continue;
+            }
+            let node = Node::new(drive.clone(), "127.0.0.1:0").unwrap();
+            nodes.push(node.local_addr().unwrap());
+            thread::spawn(move || node.serve());
+        }
+
+        let chunks: Vec<ChunkLocation> = (0..4).map(|node| ChunkLocation { node, pointer }).collect();
+        let client = Client::new(nodes).with_timeout(Duration::from_secs(1));
+        assert_eq!(client.fetch_client_block(&chunks, &config).unwrap(), data);
+
+        // asking for every block up front gives the same result
+        let client = client.with_redundancy(200);
+        assert_eq!(client.fetch_client_block(&chunks, &config).unwrap(), data);
+
+        // without the healthy nodes there are fewer blocks than source symbols
+        let error = client.fetch_client_block(&chunks[..2], &config).unwrap_err();
+        assert!(error.to_string().contains("not enough blocks"));
+    }
+
+    #[test]
+    fn from_addresses_test() {
+        let addresses = AddressesBlock::new(vec![[1; 32], [2; 32]], [0; 64]);
+        let resolve = |address: &Address| Some(SocketAddr::from(([127, 0, 0, 1], 9000 + address[0] as u16)));
+        let client = Client::from_addresses(&addresses, resolve).unwrap();
+        assert_eq!(client.nodes[1].port(), 9002);
+        assert!(Client::from_addresses(&addresses, |_| None).is_err());
+    }
+}
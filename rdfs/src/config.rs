@@ -5,11 +5,15 @@
 //!
 //! The configuration is stored as a TOML file (`RDFSConfig.toml`) and provides
 //! mechanisms for loading, saving, and querying space-aware paths.
+//! `load`/`save` use the platform config directory (e.g. `~/.config/RDFS/` on Linux),
+//! unless an `RDFSConfig.toml` already exists in the working directory.
 //!
 //! ## Features
 //! - Serialize and deserialize paths with associated available space
 //! - Dynamically determine disk space availability using `sysinfo`
 //! - Add, remove, and query storage paths based on space requirements
+//! - Pick the current path among checked, writable directories
+//! - Discover the `.RDFS` drives stored under the search paths
 //! - Designed for persistence across application runs
 //!
 //! ## Design Goals
@@ -19,15 +23,22 @@
 //!
 //! Copyrights © 2025 RDFS Contributors. All rights reserved.
 
+use crate::core::super_block::FileSystemType;
+use crate::file_system::RDFS;
+use directories::ProjectDirs;
 use serde::{Deserialize, Serialize};
+use std::collections::HashSet;
 use std::fs::{self, File};
-use std::io::Write;
+use std::io::{self, Write};
 use std::path::{Path, PathBuf};
 use sysinfo::Disks;
 
+const CONFIG_FILE: &str = "RDFSConfig.toml";
+const DRIVE_EXTENSION: &str = "RDFS";
+
 #[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq, Default)]
 pub struct RDFSConfig {
-    pub currant_path: Option<RDFSPath>,
+    pub currant_path: Option<RDFSPath>, // kept misspelled for existing config files, see `current_path`
     pub search_paths: Vec<RDFSPath>,
 }
 
@@ -38,12 +49,67 @@ pub struct RDFSPath {
 }
 
 impl RDFSConfig {
+    /// Loads the config from `config_path`.
     pub fn load() -> std::io::Result<RDFSConfig> {
-        read_toml_file("RDFSConfig.toml")
+        Self::load_from(Self::config_path())
     }
 
+    /// Saves the config to `config_path`.
     pub fn save(&self) -> std::io::Result<()> {
-        write_toml_file("RDFSConfig.toml", self)
+        self.save_to(Self::config_path())
+    }
+
+    pub fn load_from<P: AsRef<Path>>(path: P) -> std::io::Result<RDFSConfig> {
+        read_toml_file(path.as_ref())
+    }
+
+    /// Saves the config to `path`, creating its parent directories if needed.
+    pub fn save_to<P: AsRef<Path>>(&self, path: P) -> std::io::Result<()> {
+        if let Some(parent) = path.as_ref().parent().filter(|parent| !parent.as_os_str().is_empty()) {
+            fs::create_dir_all(parent)?;
+        }
+        write_toml_file(path.as_ref(), self)
+    }
+
+    /// Path used by `load` and `save`: `./RDFSConfig.toml` if it exists, for drives set up
+    /// before the config moved, otherwise `RDFSConfig.toml` in the platform config directory.
+    pub fn config_path() -> PathBuf {
+        let config_dir = ProjectDirs::from("", "", "RDFS").map(|dirs| dirs.config_dir().to_path_buf());
+        resolve_config_path(Path::new(CONFIG_FILE), config_dir)
+    }
+
+    /// The directory new drives go to, set with `set_current_path`.
+    pub fn current_path(&self) -> Option<&RDFSPath> {
+        self.currant_path.as_ref()
+    }
+
+    /// Makes `path` the current path after checking it is an existing directory that can be
+    /// written to, adding it to the search paths if it isn't one yet. The available space is
+    /// queried for both entries. On error the config is left as it was.
+    pub fn set_current_path<P: AsRef<Path>>(&mut self, path: P) -> std::io::Result<()> {
+        let path = path.as_ref();
+        if !fs::metadata(path)?.is_dir() {
+            return Err(io::Error::new(
+                io::ErrorKind::NotADirectory,
+                format!("{} is not a directory", path.display()),
+            ));
+        }
+        // permission bits don't tell about read only mounts or ACLs, try it instead. The name
+        // is unique, so a probe left by a crash or a concurrent call never gets in the way
+        let probe = path.join(format!(".rdfs_write_probe_{}_{:016x}", std::process::id(), rand::random::<u64>()));
+        File::create_new(&probe)?;
+        fs::remove_file(&probe)?;
+
+        let current = RDFSPath {
+            path: path.to_path_buf(),
+            available: get_free_space(path).unwrap_or(0),
+        };
+        match self.search_paths.iter_mut().find(|p| p.path == path) {
+            Some(existing) => existing.available = current.available,
+            None => self.search_paths.push(current.clone()),
+        }
+        self.currant_path = Some(current);
+        Ok(())
     }
 
     pub fn add_path<P: AsRef<Path>>(&mut self, path: P) {
@@ -67,30 +133,91 @@ impl RDFSConfig {
     pub fn get_path_with_space(&self, min_space: u64) -> Option<&Path> {
         self.search_paths.iter().find(|p| p.available >= min_space).map(|p| p.path.as_path())
     }
-}
 
-fn get_free_space(path: &Path) -> Option<u64> {
-    let disks = Disks::new_with_refreshed_list();
+    /// Same as `get_path_with_space`, refreshing the available space of every path first.
+    pub fn get_path_with_space_fresh(&mut self, min_space: u64) -> Option<&Path> {
+        self.refresh_space();
+        self.get_path_with_space(min_space)
+    }
+
+    /// Re-queries the available space of the current path and every search path.
+    /// A path whose disk is no longer mounted gets `available = 0`.
+    pub fn refresh_space(&mut self) {
+        let disks = Disks::new_with_refreshed_list();
+        for path in self.currant_path.iter_mut().chain(self.search_paths.iter_mut()) {
+            path.available = free_space_in(&disks, &path.path).unwrap_or(0);
+        }
+    }
 
-    for disk in disks.iter() {
-        if path.starts_with(disk.mount_point()) {
-            return Some(disk.available_space());
+    /// Walks every search path recursively for `*.RDFS` files and classifies them with
+    /// `RDFS::peek_type`. Unreadable directories and files that are not RDFS drives are
+    /// skipped, a drive reachable from several search paths is listed once by its canonical path.
+    pub fn discover_drives(&self) -> Vec<(PathBuf, FileSystemType)> {
+        let mut seen = HashSet::new();
+        let mut drives = Vec::new();
+        let mut pending: Vec<PathBuf> = self.search_paths.iter().map(|p| p.path.clone()).collect();
+        pending.reverse(); // scan in the configured order
+
+        while let Some(dir) = pending.pop() {
+            let Ok(entries) = fs::read_dir(&dir) else { continue };
+            for entry in entries.flatten() {
+                let Ok(file_type) = entry.file_type() else { continue };
+                let path = entry.path();
+                // symlinked directories are not followed, they may loop
+                if file_type.is_dir() {
+                    pending.push(path);
+                    continue;
+                }
+                if path.extension().is_none_or(|extension| extension != DRIVE_EXTENSION) {
+                    continue;
+                }
+                let Ok(path) = fs::canonicalize(&path) else { continue };
+                if seen.contains(&path) {
+                    continue;
+                }
+                if let Ok(magic) = RDFS::peek_type(&path) {
+                    seen.insert(path.clone());
+                    drives.push((path, magic));
+                }
+            }
         }
+        drives
     }
+}
+
+fn get_free_space(path: &Path) -> Option<u64> {
+    free_space_in(&Disks::new_with_refreshed_list(), path)
+}
+
+/// Available space of the disk holding `path`, the most specific mount point wins
+/// so a path on `/mnt/data` is not reported with the space of `/`.
+fn free_space_in(disks: &Disks, path: &Path) -> Option<u64> {
+    disks
+        .iter()
+        .filter(|disk| path.starts_with(disk.mount_point()))
+        .max_by_key(|disk| disk.mount_point().components().count())
+        .map(|disk| disk.available_space())
+}
 
-    None
+/// The legacy `local` file wins when it exists, the working directory is the last resort
+/// when the platform has no config directory (no home directory).
+fn resolve_config_path(local: &Path, config_dir: Option<PathBuf>) -> PathBuf {
+    match config_dir {
+        Some(dir) if !local.exists() => dir.join(CONFIG_FILE),
+        _ => local.to_path_buf(),
+    }
 }
 
-fn write_toml_file(path: &str, config: &RDFSConfig) -> std::io::Result<()> {
-    let toml_string = toml::to_string(config).expect("Failed to serialize to TOML");
+fn write_toml_file(path: &Path, config: &RDFSConfig) -> std::io::Result<()> {
+    let toml_string = toml::to_string(config).map_err(io::Error::other)?;
     let mut file = File::create(path)?;
     file.write_all(toml_string.as_bytes())?;
     Ok(())
 }
 
-fn read_toml_file(path: &str) -> std::io::Result<RDFSConfig> {
+fn read_toml_file(path: &Path) -> std::io::Result<RDFSConfig> {
     let contents = fs::read_to_string(path)?;
-    let config: RDFSConfig = toml::from_str(&contents).expect("Failed to parse TOML");
+    let config: RDFSConfig = toml::from_str(&contents).map_err(|error| io::Error::new(io::ErrorKind::InvalidData, error))?;
     Ok(config)
 }
 
@@ -104,20 +231,37 @@ mod test {
         let current_dir = env::current_dir().expect("Failed to get current directory");
 
         let mut config = RDFSConfig::default();
-        config.add_path(&current_dir);
-        config.currant_path = Some(RDFSPath {
-            available: get_free_space(&current_dir).unwrap_or(0),
-            path: current_dir.clone(),
-        });
+        config.set_current_path(&current_dir).unwrap();
 
-        config.save().expect("Failed to save config");
+        let path = env::temp_dir().join("rdfs_config_test").join("nested").join(CONFIG_FILE);
+        let _ = fs::remove_file(&path);
+        config.save_to(&path).expect("Failed to save config");
 
-        let loaded = RDFSConfig::load().expect("Failed to load config");
+        let loaded = RDFSConfig::load_from(&path).expect("Failed to load config");
 
         assert_eq!(loaded.currant_path.as_ref().unwrap().path, current_dir);
         assert!(loaded.search_paths.iter().any(|p| p.path == current_dir));
     }
 
+    #[test]
+    fn test_config_path() {
+        let dir = env::temp_dir().join("rdfs_config_path_test");
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        let local = dir.join(CONFIG_FILE);
+        let platform = dir.join("platform");
+
+        assert_eq!(resolve_config_path(&local, Some(platform.clone())), platform.join(CONFIG_FILE));
+        assert_eq!(resolve_config_path(&local, None), local);
+
+        // an existing config in the working directory keeps being used
+        RDFSConfig::default().save_to(&local).unwrap();
+        assert_eq!(resolve_config_path(&local, Some(platform)), local);
+
+        fs::write(&local, "not = [toml").unwrap();
+        assert_eq!(RDFSConfig::load_from(&local).unwrap_err().kind(), io::ErrorKind::InvalidData);
+    }
+
     #[test]
     fn test_add_and_remove_path() {
         let current_dir = env::current_dir().unwrap();
@@ -160,4 +304,99 @@ mod test {
         let result = config.get_path_with_space(10_000_000_000); // 10 GB
         assert!(result.is_none());
     }
+
+    #[test]
+    fn test_refresh_space() {
+        let current_dir = env::current_dir().unwrap();
+        let unmounted = PathBuf::from("relative/path/on/no/disk");
+
+        let mut config = RDFSConfig::default();
+        config.search_paths.push(RDFSPath {
+            path: unmounted.clone(),
+            available: u64::MAX, // stale value
+        });
+        config.search_paths.push(RDFSPath {
+            path: current_dir.clone(),
+            available: 0, // stale value
+        });
+
+        config.refresh_space();
+        assert_eq!(config.search_paths[0].available, 0);
+        // other tests write to the same disk, only check the stale value was replaced
+        let expected = get_free_space(&current_dir).is_some_and(|space| space > 0);
+        assert_eq!(config.search_paths[1].available > 0, expected);
+
+        // the stale entry can not be handed out anymore
+        config.search_paths[0].available = u64::MAX;
+        assert_eq!(config.get_path_with_space(u64::MAX), Some(unmounted.as_path()));
+        assert_eq!(config.get_path_with_space_fresh(u64::MAX), None);
+    }
+
+    #[test]
+    fn test_discover_drives() {
+        let dir = env::temp_dir().join("rdfs_discover_drives_test");
+        let _ = fs::remove_dir_all(&dir);
+        let nested = dir.join("nested");
+        fs::create_dir_all(&nested).unwrap();
+
+        let shared = RDFS::new(&dir, FileSystemType::Shared, [255; 32], [1; 32], 1048576, 100, 1, 4096).unwrap();
+        let private = RDFS::new(&nested, FileSystemType::Private, [255; 32], [2; 32], 1048576, 100, 1, 4096).unwrap();
+        fs::write(dir.join("fake.RDFS"), b"not a drive").unwrap();
+        fs::write(dir.join("short.RDFS"), b"RDFS").unwrap();
+        fs::write(dir.join("notes.txt"), b"skipped").unwrap();
+
+        let mut config = RDFSConfig::default();
+        config.add_path(&dir);
+        config.add_path(&nested); // overlaps with `dir`
+        config.add_path(&dir); // listed twice
+        config.add_path(dir.join("missing"));
+
+        let mut drives = config.discover_drives();
+        drives.sort_by(|a, b| a.0.cmp(&b.0));
+        let mut expected = vec![
+            (fs::canonicalize(&shared.path).unwrap(), FileSystemType::Shared),
+            (fs::canonicalize(&private.path).unwrap(), FileSystemType::Private),
+        ];
+        expected.sort_by(|a, b| a.0.cmp(&b.0));
+        assert_eq!(drives, expected);
+    }
+
+    #[test]
+    fn test_set_current_path() {
+        let dir = env::temp_dir().join("rdfs_set_current_path_test");
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+
+        let mut config = RDFSConfig::default();
+        config.set_current_path(&dir).unwrap();
+        assert_eq!(config.current_path().unwrap().path, dir);
+        assert_eq!(config.current_path(), config.currant_path.as_ref());
+        assert_eq!(config.search_paths, [config.current_path().unwrap().clone()]);
+
+        // an existing search path is reused, not listed twice
+        let other = dir.join("other");
+        fs::create_dir_all(&other).unwrap();
+        config.add_path(&other);
+        config.set_current_path(&other).unwrap();
+        config.set_current_path(&dir).unwrap();
+        assert_eq!(config.search_paths.len(), 2);
+        assert_eq!(config.current_path().unwrap().path, dir);
+        let probes = |dir: &Path| {
+            let entries = fs::read_dir(dir).unwrap().map(|entry| entry.unwrap().file_name().into_string().unwrap());
+            entries.filter(|name| name.starts_with(".rdfs_write_probe")).count()
+        };
+        assert_eq!(probes(&dir), 0);
+
+        // a probe left behind by a crashed or concurrent call doesn't block the directory
+        fs::write(dir.join(".rdfs_write_probe"), b"").unwrap();
+        config.set_current_path(&dir).unwrap();
+        assert_eq!(probes(&dir), 1);
+
+        let file = dir.join("file.txt");
+        fs::write(&file, b"not a directory").unwrap();
+        let before = config.clone();
+        assert_eq!(config.set_current_path(&file).unwrap_err().kind(), io::ErrorKind::NotADirectory);
+        assert_eq!(config.set_current_path(dir.join("missing")).unwrap_err().kind(), io::ErrorKind::NotFound);
+        assert_eq!(config, before);
+    }
 }
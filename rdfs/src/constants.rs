@@ -6,6 +6,8 @@
 //!
 //! In particular, cryptographic primitives are isolated here so they can be
 //! swapped out easily—e.g., for migrating to a post-quantum secure signature scheme.
+//! `PK_SIZE`, `SK_SIZE` and `SIG_SIZE` are the sizes of the `Ed25519` `SignatureScheme`
+//! implemented in `block_signature`.
 //!
 //! ## Purpose
 //! - Improve maintainability and readability
@@ -18,13 +20,28 @@ pub const PK_SIZE: usize = 32;
 pub const SK_SIZE: usize = 32;
 pub const SIG_SIZE: usize = 64;
 
-pub const SB_SIZE: usize = 16 * 8 + PK_SIZE + PK_SIZE + SIG_SIZE;
+pub const SB_SIZE: usize = 20 * 8 + 2 + 2 + 2 + 4 + PK_SIZE + PK_SIZE + SIG_SIZE; // 2 bytes each for the on-disk format version, the byte order mark and the codec, 4 for the checksum
+pub const SB_SIZE_V6: usize = 20 * 8 + 2 + 2 + 2 + PK_SIZE + PK_SIZE + SIG_SIZE; // version 6 has no checksum
+pub const SB_SIZE_V5: usize = 20 * 8 + 2 + 2 + PK_SIZE + PK_SIZE + SIG_SIZE; // version 5 has no codec
+pub const SB_SIZE_V4: usize = 19 * 8 + 2 + 2 + PK_SIZE + PK_SIZE + SIG_SIZE; // version 4 has no flags
+pub const SB_SIZE_V3: usize = 19 * 8 + 2 + PK_SIZE + PK_SIZE + SIG_SIZE; // version 3 has no byte order mark
+pub const SB_SIZE_V2: usize = 18 * 8 + 2 + PK_SIZE + PK_SIZE + SIG_SIZE; // version 2 has no snapshot pointer
+pub const SB_SIZE_V1: usize = 16 * 8 + 2 + PK_SIZE + PK_SIZE + SIG_SIZE; // version 1 has no refcount pointer and size
+pub const SB_VERSION: u16 = 8;
+pub const BYTE_ORDER_MARK: u16 = 0xFEFF; // stored little endian, read back as 0xFFFE by a big endian decoder
+pub const SB_FLAG_ENCRYPTED: u64 = 1; // data block payloads are encrypted, private drives only
 pub const RESERVED_AB: usize = 72;
 pub const RESERVED_BB: usize = 96;
 pub const RESERVED_DB: usize = 88;
 pub const RESERVED_CDB: usize = 92; // -> additional 4 bytes for client due to RaptorQ code encoding
 pub const RESERVED_IB: usize = 1136;
 pub const RESERVED_LIB: usize = 80;
+pub const RESERVED_RB: usize = 16;
+pub const REFCOUNT_SIZE: usize = 2; // u16 reference count per block
+pub const DB_LENGTH_MASK: u32 = 0x00FF_FFFF; // data block length field, the top byte holds the compression flag
+pub const AEAD_TAG_SIZE: usize = 16; // authentication tag appended to an encrypted data block payload
+pub const AEAD_NONCE_SIZE: usize = 24; // random nonce stored in front of an encrypted data block payload
+pub const MAX_BLOCK_SIZE: u64 = DB_LENGTH_MASK as u64 + 1; // 16MB, every payload length fits the masked field
 
 pub const CONTENT_SIZE: usize = 16; // (pointer, type) or (pointer, size)
 
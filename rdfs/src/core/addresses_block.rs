@@ -31,7 +31,9 @@
 
 use super::super::constants::{Address, PK_SIZE, RESERVED_AB, SIG_SIZE, Signature};
 use super::super::rdfs_errors::RDFSError;
+use super::block_signature::verify_signature;
 use anyhow::Result;
+use std::collections::HashSet;
 
 #[derive(Debug, Clone)]
 pub struct AddressesBlock {
@@ -54,6 +56,51 @@ impl AddressesBlock {
         self.signature = signature;
     }
 
+    /// Returns the address of node slot `index`.
+    pub fn get_address(&self, index: usize) -> Option<&Address> {
+        self.addresses.get(index)
+    }
+
+    /// Overwrites the address of node slot `index`.
+    pub fn set_address(&mut self, index: usize, address: Address) -> Result<()> {
+        let slot = self.addresses.get_mut(index).ok_or(RDFSError::AddressIndexOutOfRange(index))?;
+        *slot = address;
+        Ok(())
+    }
+
+    /// Returns the slot index of `address`, letting a node find its own slot by public key.
+    pub fn find(&self, address: &Address) -> Option<usize> {
+        self.addresses.iter().position(|candidate| candidate == address)
+    }
+
+    /// Hands the slot of the departing node `old` over to `new` and returns the slot index.
+    /// The order of the other slots is preserved since a slot index maps to chunk assignment.
+    pub fn replace_address(&mut self, old: &Address, new: Address) -> Result<usize> {
+        if self.find(&new).is_some() {
+            return Err(RDFSError::DuplicateAddress.into());
+        }
+        let index = self.find(old).ok_or(RDFSError::AddressNotFound)?;
+        self.addresses[index] = new;
+        Ok(index)
+    }
+
+    /// Returns whether at least `threshold` distinct nodes signed `message`. Each
+    /// `(index, signature)` pair is checked against the public key of slot `index`,
+    /// pairs with an unknown slot or a bad signature are not counted. A set repeating
+    /// an index is rejected outright so one node can't stand in for several.
+    pub fn verify_quorum(&self, signatures: &[(usize, Signature)], message: &[u8], threshold: usize) -> bool {
+        let mut seen = HashSet::with_capacity(signatures.len());
+        if !signatures.iter().all(|(index, _)| seen.insert(*index)) {
+            return false;
+        }
+
+        let signed = |(index, signature): &&(usize, Signature)| match self.get_address(*index) {
+            Some(address) => verify_signature(address, signature, message),
+            None => false,
+        };
+        signatures.iter().filter(signed).count() >= threshold
+    }
+
     /// Serialize to a flat byte array
     pub fn to_bytes(&self) -> Vec<u8> {
         let nodes_address_size = RESERVED_AB + PK_SIZE * self.addresses.len();
@@ -70,13 +117,22 @@ impl AddressesBlock {
 
     pub fn from_bytes(data: &[u8], nodes_address_size: usize) -> Result<Self> {
         if data.len() != nodes_address_size {
-            return Err(RDFSError::InvalidAddressBlockLength.into());
+            return Err(RDFSError::InvalidAddressBlockLength {
+                got: data.len(),
+                expected: nodes_address_size,
+            }
+            .into());
         }
 
         let length = u64::from_le_bytes(data[..8].try_into().unwrap()) as usize;
 
-        if RESERVED_AB + PK_SIZE * length != nodes_address_size {
-            return Err(RDFSError::InvalidEncodedAddressBlockLength.into());
+        let encoded = PK_SIZE.saturating_mul(length).saturating_add(RESERVED_AB);
+        if encoded != nodes_address_size {
+            return Err(RDFSError::InvalidEncodedAddressBlockLength {
+                got: encoded,
+                expected: nodes_address_size,
+            }
+            .into());
         }
 
         let mut addresses = Vec::with_capacity(length);
@@ -115,4 +171,52 @@ mod test {
         assert_eq!(block.addresses, deserialized.addresses);
         assert_eq!(block.signature, deserialized.signature);
     }
+
+    #[test]
+    fn addresses_lookup_test() {
+        let mut block = AddressesBlock::new(vec![[1u8; PK_SIZE], [2u8; PK_SIZE], [3u8; PK_SIZE]], [0; SIG_SIZE]);
+
+        assert_eq!(block.get_address(1), Some(&[2u8; PK_SIZE]));
+        assert_eq!(block.get_address(3), None);
+        assert_eq!(block.find(&[3u8; PK_SIZE]), Some(2));
+        assert_eq!(block.find(&[9u8; PK_SIZE]), None);
+
+        block.set_address(0, [7u8; PK_SIZE]).unwrap();
+        assert_eq!(block.find(&[7u8; PK_SIZE]), Some(0));
+        let error = block.set_address(3, [7u8; PK_SIZE]).unwrap_err();
+        assert!(matches!(error.downcast_ref::<RDFSError>(), Some(RDFSError::AddressIndexOutOfRange(3))));
+
+        assert_eq!(block.replace_address(&[2u8; PK_SIZE], [8u8; PK_SIZE]).unwrap(), 1);
+        assert_eq!(block.addresses, vec![[7u8; PK_SIZE], [8u8; PK_SIZE], [3u8; PK_SIZE]]);
+        assert!(block.replace_address(&[2u8; PK_SIZE], [9u8; PK_SIZE]).is_err());
+        assert!(block.replace_address(&[3u8; PK_SIZE], [7u8; PK_SIZE]).is_err());
+    }
+
+    #[test]
+    fn verify_quorum_test() {
+        use super::super::block_signature::sign_message;
+        use ed25519_dalek::SigningKey;
+
+        let keys: Vec<[u8; 32]> = (1..=4u8).map(|i| [i; 32]).collect();
+        let addresses = keys.iter().map(|key| SigningKey::from_bytes(key).verifying_key().to_bytes()).collect();
+        let block = AddressesBlock::new(addresses, [0; SIG_SIZE]);
+        let message = b"super block update";
+        let signed = |index: usize| (index, sign_message(&keys[index], message));
+
+        // exactly the threshold, and one short of it
+        let signatures = [signed(0), signed(2), signed(3)];
+        assert!(block.verify_quorum(&signatures, message, 3));
+        assert!(!block.verify_quorum(&signatures[..2], message, 3));
+
+        // signatures for the wrong slot, an unknown slot or another message don't count
+        let wrong_slot = (1, sign_message(&keys[3], message));
+        let unknown_slot = (9, sign_message(&keys[1], message));
+        let forged = [signed(0), signed(2), wrong_slot, unknown_slot];
+        assert!(!block.verify_quorum(&forged, message, 3));
+        assert!(!block.verify_quorum(&signatures, b"another update", 1));
+
+        // a repeated index is rejected even when the quorum would be reached without it
+        let repeated = [signed(0), signed(2), signed(3), signed(0)];
+        assert!(!block.verify_quorum(&repeated, message, 2));
+    }
 }
@@ -12,12 +12,24 @@
 //! ```text
 //! [8 bytes: total_blocks]
 //! [8 bytes: free_blocks]
-//! [8 bytes: last_modify_timestamp]
-//! [8 bytes: bit_field length]
-//! [N bytes: bit_field (N = total_blocks / 8)]
+//! [8 bytes: last_modify_timestamp, milliseconds]
+//! [4 bytes: bit_field length]
+//! [4 bytes: next_free / 8]
+//! [N bytes: bit_field (N = ceil(total_blocks / 8))]
 //! [64 bytes: signature]
 //! ```
 //!
+//! ## Free Block Cursor
+//! `next_free` is where first fit allocation starts scanning: every block below it is in
+//! use. Allocating moves it to the byte of the last block handed out and freeing a block
+//! below it moves it back, so sequential allocation on a mostly full drive skips the used
+//! prefix instead of rescanning it from block 0, with the same blocks chosen. The scan
+//! wraps around to block 0 when nothing is free past the cursor, so a stale cursor (e.g.
+//! after `bit_field` was edited directly) costs a rescan, never a failed allocation.
+//! The cursor takes the upper half of the length word, which never needs more than 32 bits
+//! below 32G blocks, as a byte index; older bitmaps read back with the cursor at 0. Larger
+//! bit fields keep the whole word for the length and start at 0 after a reload.
+//!
 //! ## Features
 //! - Efficient per-block allocation tracking
 //! - Self-contained timestamp for last modification
@@ -32,22 +44,40 @@
 //! - Shared RDFS only; not used in Private RDFS
 //! - Signature is not auto-generated—intended for external prover logic
 //! - Modifying bit flags updates the last-modified timestamp automatically
+//! - Padding bits of the last byte beyond `total_blocks` are never allocated or counted
 //!
 //! Copyrights © 2025 RDFS Contributors. All rights reserved.
 
 use super::super::constants::{RESERVED_BB, SIG_SIZE, Signature};
-use super::super::utils::current_time_as_u64;
 use super::super::rdfs_errors::RDFSError;
+use super::super::utils::current_time_millis_as_u64;
+use super::inode_block::FileContent;
 use anyhow::Result;
+use std::collections::BTreeMap;
+
+/// Placement policy of `BitmapsBlock::allocate_with`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum AllocStrategy {
+    /// Lowest free blocks first, possibly split over several runs.
+    #[default]
+    FirstFit,
+    /// The smallest single free run that holds every block, keeping large runs intact.
+    /// Falls back to `FirstFit` when no run is large enough.
+    BestFit,
+    /// The first single free run that holds every block, `None` if there is none.
+    Contiguous,
+}
 
 /// A block representing a bitmap for tracking allocation of blocks/nodes.
 /// Internally stores a `Vec<u8>` of size `block_size`.
-#[derive(Debug, Clone)]
+/// Equality compares every field, `last_modify` and the signature included.
+#[derive(Debug, Clone, PartialEq, Eq)]
 pub struct BitmapsBlock {
-    // 96 + total_blocks / 8 bytes
+    // 96 + ceil(total_blocks / 8) bytes
     pub total_blocks: u64, // Total number of blocks in the filesystem
     pub free_blocks: u64,  // Number of free blocks available
     pub last_modify: u64,  // Timestamp of the last modification
+    pub next_free: u64,    // First fit scans start here, a multiple of 8 with no free block below
     pub bit_field: Vec<u8>,
     pub signature: Signature,
 }
@@ -59,7 +89,8 @@ impl BitmapsBlock {
             total_blocks,
             free_blocks: total_blocks,
             last_modify: timestamp,
-            bit_field: vec![0; (total_blocks / 8) as usize],
+            next_free: 0,
+            bit_field: vec![0; total_blocks.div_ceil(8) as usize],
             signature: [0; SIG_SIZE],
         }
     }
@@ -75,7 +106,7 @@ impl BitmapsBlock {
     pub fn get_bit(&self, bit_index: usize) -> bool {
         let byte = bit_index / 8;
         let bit = bit_index % 8;
-        if byte >= self.bit_field.len() {
+        if byte >= self.bit_field.len() || !self.in_range(bit_index) {
             return false;
         }
         (self.bit_field[byte] & (1 << bit)) != 0
@@ -85,12 +116,12 @@ impl BitmapsBlock {
     pub fn set_bit(&mut self, bit_index: usize) {
         let byte = bit_index / 8;
         let bit = bit_index % 8;
-        if byte < self.bit_field.len() {
+        if byte < self.bit_field.len() && self.in_range(bit_index) {
             let mask = 1 << bit;
             if self.bit_field[byte] & mask == 0 {
                 self.bit_field[byte] |= mask;
                 self.free_blocks -= 1;
-                if let Ok(time) = current_time_as_u64() {
+                if let Ok(time) = current_time_millis_as_u64() {
                     self.last_modify = time
                 }
             }
@@ -98,30 +129,283 @@ impl BitmapsBlock {
     }
 
     /// Clears the bit at `bit_index` to 0, and decrements free_blocks only if it was 1.
+    /// Rewinds `next_free` when the block lies below it.
     pub fn clear_bit(&mut self, bit_index: usize) {
         let byte = bit_index / 8;
         let bit = bit_index % 8;
-        if byte < self.bit_field.len() {
+        if byte < self.bit_field.len() && self.in_range(bit_index) {
             let mask = 1 << bit;
             if self.bit_field[byte] & mask != 0 {
                 self.bit_field[byte] &= !mask;
                 self.free_blocks += 1;
-                if let Ok(time) = current_time_as_u64() {
+                self.rewind(bit_index);
+                if let Ok(time) = current_time_millis_as_u64() {
                     self.last_modify = time
                 }
             }
         }
     }
 
+    /// Sets the `count` bits from `start` to 1 like repeated `set_bit` calls, whole bytes at
+    /// once. `free_blocks` only drops by the bits that were 0.
+    pub fn set_range(&mut self, start: usize, count: usize) {
+        let flipped = self.update_range(start, count, true);
+        self.free_blocks -= flipped;
+    }
+
+    /// Clears the `count` bits from `start` to 0 like repeated `clear_bit` calls, whole bytes
+    /// at once. `free_blocks` only grows by the bits that were 1.
+    pub fn clear_range(&mut self, start: usize, count: usize) {
+        let flipped = self.update_range(start, count, false);
+        self.free_blocks += flipped;
+        if flipped > 0 {
+            self.rewind(start);
+        }
+    }
+
+    /// Moves `next_free` back to the byte of a block that was just freed below it.
+    fn rewind(&mut self, bit_index: usize) {
+        self.next_free = self.next_free.min((bit_index / 8 * 8) as u64);
+    }
+
+    /// Number of set bits among the `count` bits from `start`, padding bits excluded.
+    pub fn count_set_in(&self, start: usize, count: usize) -> u64 {
+        let end = start.saturating_add(count).min(self.total_blocks as usize);
+        let mut set = 0;
+        let mut index = start;
+        while index < end {
+            // fast path: a whole byte inside the range
+            if index.is_multiple_of(8) && index + 8 <= end {
+                set += self.bit_field[index / 8].count_ones() as u64;
+                index += 8;
+                continue;
+            }
+            set += self.get_bit(index) as u64;
+            index += 1;
+        }
+        set
+    }
+
+    /// Writes `value` to the bits of `[start, start + count)` within `total_blocks`, a whole
+    /// byte at a time except at the unaligned ends. Returns the number of bits that flipped.
+    fn update_range(&mut self, start: usize, count: usize, value: bool) -> u64 {
+        let end = start.saturating_add(count).min(self.total_blocks as usize);
+        let mut flipped = 0;
+        let mut index = start;
+        while index < end {
+            let byte = index / 8;
+            if index.is_multiple_of(8) && index + 8 <= end {
+                let new = if value { 0xFF } else { 0x00 };
+                flipped += (self.bit_field[byte] ^ new).count_ones() as u64;
+                self.bit_field[byte] = new;
+                index += 8;
+                continue;
+            }
+            let mask = 1 << (index % 8);
+            if (self.bit_field[byte] & mask != 0) != value {
+                self.bit_field[byte] ^= mask;
+                flipped += 1;
+            }
+            index += 1;
+        }
+        if flipped == 0 {
+            return 0;
+        }
+        if let Ok(time) = current_time_millis_as_u64() {
+            self.last_modify = time
+        }
+        flipped
+    }
+
+    /// Finds `count` free blocks, marks them as used and returns them as the contiguous
+    /// runs `FileContent { pointer: block index, blocks }` in ascending order, so adjacent
+    /// blocks always collapse into a single run.
+    /// Returns `None` without modifying the bitmap if `count` blocks are not available.
+    pub fn allocate(&mut self, count: u64) -> Option<Vec<FileContent>> {
+        self.allocate_with(count, AllocStrategy::FirstFit)
+    }
+
+    /// Same as `allocate`, placing the blocks according to `strategy`.
+    pub fn allocate_with(&mut self, count: u64, strategy: AllocStrategy) -> Option<Vec<FileContent>> {
+        if self.free_blocks < count {
+            return None;
+        }
+        if count == 0 {
+            return Some(vec![]);
+        }
+
+        // first fit scans from `next_free` by itself, only the others need every free run
+        let fitting = || self.free_runs().into_iter().filter(|run| run.blocks >= count);
+        let run = match strategy {
+            AllocStrategy::FirstFit => None,
+            AllocStrategy::BestFit => fitting().min_by_key(|run| run.blocks),
+            AllocStrategy::Contiguous => Some(fitting().next()?),
+        };
+        match run {
+            Some(run) => {
+                self.set_range(run.pointer as usize, count as usize);
+                Some(vec![FileContent {
+                    pointer: run.pointer,
+                    blocks: count,
+                }])
+            }
+            // first fit, also the fallback of best fit when no single run is large enough
+            None => self.allocate_first_fit(count),
+        }
+    }
+
+    /// Takes the first `count` free blocks from `next_free` on, wrapping around to block 0,
+    /// and leaves the cursor on the byte of the last block taken.
+    fn allocate_first_fit(&mut self, count: u64) -> Option<Vec<FileContent>> {
+        let mut runs: Vec<FileContent> = Vec::new();
+        let mut remaining = count;
+        let length = self.bit_field.len();
+        let start = (self.next_free / 8) as usize;
+        let start = if start < length { start } else { 0 };
+        let mut last = start;
+        for byte in (start..length).chain(0..start) {
+            if remaining == 0 {
+                break;
+            }
+            // fast path: fully used bytes hold no free block
+            if self.bit_field[byte] == 0xFF {
+                continue;
+            }
+            for bit in 0..8 {
+                let mask = 1 << bit;
+                if remaining > 0 && self.bit_field[byte] & mask == 0 && self.in_range(byte * 8 + bit) {
+                    self.bit_field[byte] |= mask;
+                    let index = (byte * 8 + bit) as u64;
+                    match runs.last_mut() {
+                        Some(run) if run.pointer + run.blocks == index => run.blocks += 1,
+                        _ => runs.push(FileContent { pointer: index, blocks: 1 }),
+                    }
+                    remaining -= 1;
+                    last = byte;
+                }
+            }
+        }
+
+        // `free_blocks` disagrees with the bit field, undo the partial allocation
+        if remaining > 0 {
+            for index in runs.iter().flat_map(|run| run.pointer..run.pointer + run.blocks) {
+                self.bit_field[index as usize / 8] &= !(1 << (index % 8));
+            }
+            return None;
+        }
+
+        // blocks taken after wrapping around come first
+        runs.sort_unstable_by_key(|run| run.pointer);
+        self.next_free = (last * 8) as u64;
+        self.free_blocks -= count;
+        if let Ok(time) = current_time_millis_as_u64() {
+            self.last_modify = time
+        }
+        Some(runs)
+    }
+
+    /// Maps every contiguous free-run length to the number of such runs, in a single
+    /// pass over `bit_field`. An empty map means the bitmap has no free block.
+    pub fn free_run_histogram(&self) -> BTreeMap<u64, u64> {
+        let mut histogram = BTreeMap::new();
+        for run in self.free_runs() {
+            *histogram.entry(run.blocks).or_insert(0) += 1;
+        }
+        histogram
+    }
+
+    /// Length of the longest contiguous free run, the largest file that can be placed
+    /// without fragmentation.
+    pub fn largest_free_run(&self) -> u64 {
+        self.free_run_histogram().last_key_value().map_or(0, |(&length, _)| length)
+    }
+
+    /// Indices of the free blocks in ascending order. The bitmap is walked lazily, so nothing
+    /// is allocated even for a huge drive, and padding bits of the last byte are never yielded.
+    pub fn free_blocks_iter(&self) -> impl Iterator<Item = u64> + '_ {
+        self.blocks_iter(false)
+    }
+
+    /// Indices of the used blocks in ascending order, lazily like `free_blocks_iter`.
+    pub fn used_blocks_iter(&self) -> impl Iterator<Item = u64> + '_ {
+        self.blocks_iter(true)
+    }
+
+    /// Indices of the blocks whose bit is `used`, skipping whole bytes without such a bit.
+    fn blocks_iter(&self, used: bool) -> impl Iterator<Item = u64> + '_ {
+        let skipped = if used { 0x00 } else { 0xFF };
+        self.bit_field
+            .iter()
+            .enumerate()
+            .filter(move |(_, byte)| **byte != skipped)
+            .flat_map(move |(index, &byte)| {
+                (0..8)
+                    .filter(move |bit| (byte & (1 << bit) != 0) == used)
+                    .map(move |bit| (index * 8 + bit) as u64)
+            })
+            .take_while(|index| *index < self.total_blocks)
+    }
+
+    /// Every contiguous free run as `FileContent { pointer: block index, blocks }`,
+    /// in ascending order, collected in a single pass over `bit_field`.
+    fn free_runs(&self) -> Vec<FileContent> {
+        let mut runs = Vec::new();
+        let mut run = FileContent { pointer: 0, blocks: 0 };
+        for (index, &byte) in self.bit_field.iter().enumerate() {
+            // fast paths: whole bytes extend or end the current run
+            match byte {
+                0x00 if run.blocks > 0 => run.blocks += 8,
+                0xFF if run.blocks == 0 => {}
+                _ => {
+                    for bit in 0..8 {
+                        if !self.in_range(index * 8 + bit) {
+                            break;
+                        }
+                        if byte & (1 << bit) == 0 {
+                            if run.blocks == 0 {
+                                run.pointer = (index * 8 + bit) as u64;
+                            }
+                            run.blocks += 1;
+                        } else if run.blocks > 0 {
+                            runs.push(run);
+                            run = FileContent { pointer: 0, blocks: 0 };
+                        }
+                    }
+                }
+            }
+        }
+        if run.blocks > 0 {
+            // a whole-byte fast path may have counted padding bits of the last byte
+            run.blocks = run.blocks.min(self.total_blocks - run.pointer);
+            runs.push(run);
+        }
+        runs
+    }
+
+    /// Whether `bit_index` maps to a real block and not to a padding bit of the last byte.
+    fn in_range(&self, bit_index: usize) -> bool {
+        (bit_index as u64) < self.total_blocks
+    }
+
+    /// The bit field length with the byte index of `next_free` in its upper half, or the
+    /// plain length when it needs more than 32 bits.
+    fn length_word(&self) -> u64 {
+        let length = self.bit_field.len() as u64;
+        match u32::try_from(length) {
+            Ok(_) => length | (self.next_free / 8) << 32,
+            Err(_) => length,
+        }
+    }
+
     /// Serialize the entire bitmap to bytes.
     pub fn to_bytes(&self) -> Vec<u8> {
-        let bitmaps_size = RESERVED_BB + (self.total_blocks / 8) as usize;
+        let bitmaps_size = RESERVED_BB + self.total_blocks.div_ceil(8) as usize;
         let mut encoded = Vec::with_capacity(bitmaps_size);
 
         encoded.extend_from_slice(&self.total_blocks.to_le_bytes());
         encoded.extend_from_slice(&self.free_blocks.to_le_bytes());
         encoded.extend_from_slice(&self.last_modify.to_le_bytes());
-        encoded.extend_from_slice(&(self.bit_field.len() as u64).to_le_bytes());
+        encoded.extend_from_slice(&self.length_word().to_le_bytes());
         encoded.extend_from_slice(&self.bit_field);
         encoded.extend_from_slice(&self.signature);
 
@@ -131,16 +415,30 @@ impl BitmapsBlock {
     /// Deserialize a BitmapsBlock from raw bytes.
     pub fn from_bytes(data: &[u8], bitmaps_size: usize) -> Result<Self> {
         if data.len() != bitmaps_size {
-            return Err(RDFSError::InvalidBitmapsBlockLength.into());
+            return Err(RDFSError::InvalidBitmapsBlockLength {
+                got: data.len(),
+                expected: bitmaps_size,
+            }
+            .into());
         }
 
         let total_blocks = u64::from_le_bytes(data[..8].try_into().unwrap());
         let free_blocks = u64::from_le_bytes(data[8..16].try_into().unwrap());
         let last_modify = u64::from_le_bytes(data[16..24].try_into().unwrap());
-        let length = u64::from_le_bytes(data[24..32].try_into().unwrap()) as usize;
+        let word = u64::from_le_bytes(data[24..32].try_into().unwrap());
+        let expected = total_blocks.div_ceil(8);
+        let (length, next_free) = match word & u32::MAX as u64 {
+            _ if word == expected => (expected as usize, 0),
+            low if low == expected && (word >> 32) < expected => (low as usize, (word >> 32) * 8),
+            _ => (word as usize, 0),
+        };
 
-        if 96 + length != bitmaps_size {
-            return Err(RDFSError::InvalidEncodedBitmapsBlockLength.into());
+        if length.saturating_add(RESERVED_BB) != bitmaps_size || length as u64 != expected {
+            return Err(RDFSError::InvalidEncodedBitmapsBlockLength {
+                got: length.saturating_add(RESERVED_BB),
+                expected: bitmaps_size,
+            }
+            .into());
         }
 
         let mut bit_field = Vec::with_capacity(length);
@@ -151,6 +449,7 @@ impl BitmapsBlock {
             total_blocks,
             free_blocks,
             last_modify,
+            next_free,
             bit_field,
             signature,
         })
@@ -177,12 +476,259 @@ mod test {
         println!("Serialized BitmapsBlock: {:?}", serialized.len());
 
         // Deserialize back to a block
-        let deserialized = BitmapsBlock::from_bytes(&serialized, RESERVED_BB + (total_blocks / 8) as usize).unwrap();
+        let deserialized = BitmapsBlock::from_bytes(&serialized, RESERVED_BB + total_blocks.div_ceil(8) as usize).unwrap();
 
         // Check if the original and deserialized blocks are equal
-        assert_eq!(block.total_blocks, deserialized.total_blocks);
-        assert_eq!(block.free_blocks, deserialized.free_blocks);
-        assert_eq!(block.last_modify, deserialized.last_modify);
-        assert_eq!(block.bit_field, deserialized.bit_field);
+        assert_eq!(block, deserialized);
+    }
+    #[test]
+    fn allocate_test() {
+        let mut block = BitmapsBlock::new(64, 0);
+        for index in 0..9 {
+            block.set_bit(index);
+        }
+        block.set_bit(12);
+
+        let runs = block.allocate(6).unwrap();
+        assert_eq!(runs, vec![FileContent { pointer: 9, blocks: 3 }, FileContent { pointer: 13, blocks: 3 }]);
+        assert_eq!(block.free_blocks, 64 - 10 - 6);
+        assert!((9..12).chain(13..16).all(|index| block.get_bit(index)));
+
+        assert_eq!(block.allocate(0), Some(vec![]));
+    }
+
+    #[test]
+    fn allocate_not_enough_space_test() {
+        let mut block = BitmapsBlock::new(16, 0);
+        block.set_bit(3);
+        let before = block.clone();
+
+        assert!(block.allocate(16).is_none());
+        assert_eq!(block.bit_field, before.bit_field);
+        assert_eq!(block.free_blocks, before.free_blocks);
+
+        // a stale `free_blocks` counter must not leave a partial allocation behind
+        block.free_blocks = 16;
+        assert!(block.allocate(16).is_none());
+        assert_eq!(block.bit_field, before.bit_field);
+    }
+
+    #[test]
+    fn free_run_histogram_test() {
+        let mut block = BitmapsBlock::new(64, 0);
+        assert_eq!(block.free_run_histogram(), BTreeMap::from([(64, 1)]));
+        assert_eq!(block.largest_free_run(), 64);
+
+        // free runs: 0..2, 3..4, 5..8, 9..10, 11..12, 24..64
+        for index in [2, 4, 8, 10] {
+            block.set_bit(index);
+        }
+        for index in 12..24 {
+            block.set_bit(index);
+        }
+        assert_eq!(block.free_run_histogram(), BTreeMap::from([(1, 3), (2, 1), (3, 1), (40, 1)]));
+        assert_eq!(block.largest_free_run(), 40);
+
+        for index in 24..64 {
+            block.set_bit(index);
+        }
+        assert_eq!(block.largest_free_run(), 3);
+
+        for index in [0, 1, 3, 5, 6, 7, 9, 11] {
+            block.set_bit(index);
+        }
+        assert!(block.free_run_histogram().is_empty());
+        assert_eq!(block.largest_free_run(), 0);
+    }
+
+    #[test]
+    fn allocate_with_test() {
+        // free runs: 0..4, 5..7, 8..14, 16..64
+        let mut block = BitmapsBlock::new(64, 0);
+        for index in [4, 7, 14, 15] {
+            block.set_bit(index);
+        }
+
+        let mut first_fit = block.clone();
+        let runs = first_fit.allocate_with(5, AllocStrategy::FirstFit).unwrap();
+        assert_eq!(runs, vec![FileContent { pointer: 0, blocks: 4 }, FileContent { pointer: 5, blocks: 1 }]);
+
+        let mut best_fit = block.clone();
+        let runs = best_fit.allocate_with(5, AllocStrategy::BestFit).unwrap();
+        assert_eq!(runs, vec![FileContent { pointer: 8, blocks: 5 }]);
+        assert_eq!(best_fit.free_blocks, 64 - 4 - 5);
+        assert_eq!(best_fit.largest_free_run(), 48);
+
+        let mut contiguous = block.clone();
+        let runs = contiguous.allocate_with(5, AllocStrategy::Contiguous).unwrap();
+        assert_eq!(runs, vec![FileContent { pointer: 8, blocks: 5 }]);
+        let runs = contiguous.allocate_with(3, AllocStrategy::Contiguous).unwrap();
+        assert_eq!(runs, vec![FileContent { pointer: 0, blocks: 3 }]);
+
+        // best fit prefers the exact 2-block hole over the head of the large run
+        let mut best_fit = block.clone();
+        let runs = best_fit.allocate_with(2, AllocStrategy::BestFit).unwrap();
+        assert_eq!(runs, vec![FileContent { pointer: 5, blocks: 2 }]);
+
+        // no single run of 50 blocks: contiguous refuses, best fit falls back to first fit
+        let mut contiguous = block.clone();
+        assert!(contiguous.allocate_with(50, AllocStrategy::Contiguous).is_none());
+        assert_eq!(contiguous.bit_field, block.bit_field);
+        assert_eq!(contiguous.free_blocks, block.free_blocks);
+
+        let mut best_fit = block.clone();
+        let mut first_fit = block.clone();
+        assert_eq!(
+            best_fit.allocate_with(50, AllocStrategy::BestFit),
+            first_fit.allocate_with(50, AllocStrategy::FirstFit)
+        );
+        assert_eq!(best_fit.free_blocks, 10);
+    }
+
+    #[test]
+    fn partial_last_byte_test() {
+        let total_blocks = 1020;
+        let mut block = BitmapsBlock::new(total_blocks, 0);
+        assert_eq!(block.bit_field.len(), 128);
+
+        // padding bits are neither allocatable nor settable
+        block.set_bit(1020);
+        assert!(!block.get_bit(1020));
+        assert_eq!(block.free_blocks, total_blocks);
+        assert_eq!(block.largest_free_run(), total_blocks);
+
+        let runs = block.allocate(1016).unwrap();
+        assert_eq!(runs, vec![FileContent { pointer: 0, blocks: 1016 }]);
+        assert_eq!(block.largest_free_run(), 4);
+
+        // the top blocks are allocatable, then the drive is full
+        let top = block.allocate_with(4, AllocStrategy::Contiguous).unwrap();
+        assert_eq!(top, vec![FileContent { pointer: 1016, blocks: 4 }]);
+        assert_eq!(block.free_blocks, 0);
+        assert_eq!(block.allocate(1), None);
+        assert!(!block.get_bit(1023));
+
+        let serialized = block.to_bytes();
+        let size = RESERVED_BB + total_blocks.div_ceil(8) as usize;
+        assert_eq!(serialized.len(), size);
+        assert_eq!(BitmapsBlock::from_bytes(&serialized, size).unwrap().bit_field, block.bit_field);
+    }
+
+    #[test]
+    fn range_ops_test() {
+        // unaligned ends, whole bytes, and ranges running into the padding of the last byte
+        let ranges = [(0, 0), (3, 2), (5, 19), (8, 16), (13, 100), (1000, 40), (1019, 1), (1020, 5)];
+        let mut bulk = BitmapsBlock::new(1020, 0);
+        for index in (0..1020).step_by(3) {
+            bulk.set_bit(index);
+        }
+        let mut single = bulk.clone();
+
+        for (start, count) in ranges {
+            let expected = (start..start + count).filter(|index| single.get_bit(*index)).count() as u64;
+            assert_eq!(bulk.count_set_in(start, count), expected);
+
+            bulk.set_range(start, count);
+            (start..start + count).for_each(|index| single.set_bit(index));
+            assert_eq!((&bulk.bit_field, bulk.free_blocks), (&single.bit_field, single.free_blocks));
+
+            bulk.clear_range(start + 1, count);
+            (start + 1..start + 1 + count).for_each(|index| single.clear_bit(index));
+            assert_eq!((&bulk.bit_field, bulk.free_blocks), (&single.bit_field, single.free_blocks));
+        }
+
+        assert_eq!(bulk.count_set_in(0, 1020), 1020 - bulk.free_blocks);
+        bulk.set_range(0, 2000);
+        assert_eq!((bulk.free_blocks, bulk.count_set_in(0, 2000)), (0, 1020));
+        assert_eq!(bulk.bit_field[127], 0x0F);
+        bulk.clear_range(0, 2000);
+        assert_eq!(bulk.free_blocks, 1020);
+        assert!(bulk.bit_field.iter().all(|byte| *byte == 0));
+    }
+
+    #[test]
+    fn blocks_iter_test() {
+        let mut bitmaps = BitmapsBlock::new(21, 0);
+        for index in [0, 3, 8, 9, 10, 11, 12, 13, 14, 15, 20] {
+            bitmaps.set_bit(index);
+        }
+        // padding bits of the last byte are not blocks
+        bitmaps.bit_field[2] |= 0xE0;
+
+        let used: Vec<u64> = bitmaps.used_blocks_iter().collect();
+        let free: Vec<u64> = bitmaps.free_blocks_iter().collect();
+        assert_eq!(used, vec![0, 3, 8, 9, 10, 11, 12, 13, 14, 15, 20]);
+        assert_eq!(free, vec![1, 2, 4, 5, 6, 7, 16, 17, 18, 19]);
+        assert_eq!(free.len() as u64, bitmaps.free_blocks);
+
+        let mut all: Vec<u64> = used.into_iter().chain(free).collect();
+        all.sort_unstable();
+        assert_eq!(all, (0..21).collect::<Vec<u64>>());
+
+        let empty = BitmapsBlock::new(12, 0);
+        assert_eq!(empty.used_blocks_iter().count(), 0);
+        assert_eq!(empty.free_blocks_iter().count(), 12);
+    }
+
+    #[test]
+    fn injected_clock_test() {
+        use super::super::super::utils::{ManualClock, current_time_as_u64, set_clock};
+        use std::sync::Arc;
+
+        let clock = Arc::new(ManualClock::new(1_633_036_800_123));
+        set_clock(Some(clock.clone()));
+
+        let mut block = BitmapsBlock::new(64, 0);
+        block.set_bit(3);
+        assert_eq!(block.last_modify, 1_633_036_800_123);
+        assert_eq!(current_time_as_u64().unwrap(), 1_633_036_800);
+
+        clock.advance(250);
+        block.set_bit(3); // already set, timestamp untouched
+        assert_eq!(block.last_modify, 1_633_036_800_123);
+        block.clear_bit(3);
+        assert_eq!(block.last_modify, 1_633_036_800_373);
+
+        set_clock(None);
+        block.set_bit(5);
+        assert!(block.last_modify > 1_633_036_800_373);
+    }
+
+    #[test]
+    fn next_free_test() {
+        let mut block = BitmapsBlock::new(100, 0);
+        assert_eq!(block.allocate(20).unwrap(), vec![FileContent { pointer: 0, blocks: 20 }]);
+        assert_eq!(block.next_free, 16);
+        assert_eq!(block.allocate(5).unwrap(), vec![FileContent { pointer: 20, blocks: 5 }]);
+        assert_eq!(block.next_free, 24);
+
+        // freeing below the cursor rewinds it, first fit still picks the lowest block
+        block.clear_bit(9);
+        assert_eq!(block.next_free, 8);
+        assert_eq!(
+            block.allocate(2).unwrap(),
+            vec![FileContent { pointer: 9, blocks: 1 }, FileContent { pointer: 25, blocks: 1 }]
+        );
+        block.clear_range(30, 4); // already free
+        assert_eq!(block.next_free, 24);
+
+        // persisted in the upper half of the length word, older bitmaps read back at 0
+        let size = RESERVED_BB + 13;
+        let encoded = block.to_bytes();
+        assert_eq!(BitmapsBlock::from_bytes(&encoded, size).unwrap(), block);
+        let mut old = encoded.clone();
+        old[28..32].fill(0);
+        assert_eq!(BitmapsBlock::from_bytes(&old, size).unwrap().next_free, 0);
+        old[28..32].copy_from_slice(&13u32.to_le_bytes());
+        assert!(BitmapsBlock::from_bytes(&old, size).is_err());
+
+        // a stale cursor wraps around instead of failing
+        block.bit_field[0] = 0;
+        block.free_blocks += 8;
+        block.next_free = 96;
+        let runs = block.allocate(82).unwrap();
+        assert_eq!(runs.first(), Some(&FileContent { pointer: 0, blocks: 8 }));
+        assert_eq!(block.free_blocks, 0);
+        assert!(block.allocate(1).is_none());
     }
 }
@@ -1,4 +1,138 @@
+use super::super::constants::{PK_SIZE, SIG_SIZE, SK_SIZE};
+use super::super::rdfs_errors::RDFSError;
+use super::data_block::DataBlock;
+use anyhow::Result;
 use ed25519_dalek::{Signature, Signer, SigningKey, Verifier, VerifyingKey};
+#[cfg(feature = "rayon")]
+use rayon::prelude::*;
+use sha2::{Digest, Sha256};
+use std::fmt;
+use zeroize::Zeroize;
+
+/// Batches smaller than this are signed on the current thread even with the `rayon` feature.
+#[cfg(feature = "rayon")]
+const PARALLEL_THRESHOLD: usize = 64;
+
+/// A signature algorithm blocks can be signed with, keys and signatures passed as raw
+/// bytes of the sizes below. `Ed25519` is the scheme of every drive today and the one the
+/// fixed-size `Signature`/`Address` types and `PK_SIZE`/`SIG_SIZE` constants are sized for,
+/// the `*_with` functions let a post-quantum scheme be plugged in next to it.
+pub trait SignatureScheme {
+    const PK_SIZE: usize;
+    const SK_SIZE: usize;
+    const SIG_SIZE: usize;
+
+    /// Signs `message`, returning `SIG_SIZE` bytes. Fails with `InvalidKeyLength`
+    /// unless `private_key` is `SK_SIZE` bytes.
+    fn sign(private_key: &[u8], message: &[u8]) -> Result<Vec<u8>>;
+
+    /// Returns `true` if `signature` is a valid signature of `message` by `public_key`,
+    /// keys and signatures of the wrong size never are.
+    fn verify(public_key: &[u8], signature: &[u8], message: &[u8]) -> bool;
+}
+
+/// The ed25519 backend of `SignatureScheme`, what `sign_message`/`verify_signature` use.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Ed25519;
+
+impl SignatureScheme for Ed25519 {
+    const PK_SIZE: usize = PK_SIZE;
+    const SK_SIZE: usize = SK_SIZE;
+    const SIG_SIZE: usize = SIG_SIZE;
+
+    fn sign(private_key: &[u8], message: &[u8]) -> Result<Vec<u8>> {
+        let private_key: &[u8; SK_SIZE] = private_key.try_into().map_err(|_| RDFSError::InvalidKeyLength {
+            got: private_key.len(),
+            expected: SK_SIZE,
+        })?;
+        Ok(sign_message(private_key, message).to_vec())
+    }
+
+    fn verify(public_key: &[u8], signature: &[u8], message: &[u8]) -> bool {
+        match (public_key.try_into(), signature.try_into()) {
+            (Ok(public_key), Ok(signature)) => verify_signature(public_key, signature, message),
+            _ => false,
+        }
+    }
+}
+
+/// `sign_bytes` for any `SignatureScheme`, replacing the trailing `S::SIG_SIZE` bytes.
+/// Buffers too short to hold a signature are left untouched.
+pub fn sign_bytes_with<S: SignatureScheme>(private_key: &[u8], bytes: &mut [u8]) -> Result<()> {
+    if bytes.len() < S::SIG_SIZE {
+        return Ok(());
+    }
+    let length = bytes.len() - S::SIG_SIZE;
+    let signature = S::sign(private_key, &bytes[..length])?;
+    bytes[length..].copy_from_slice(&signature);
+    Ok(())
+}
+
+/// `verify_bytes` for any `SignatureScheme`, the trailing `S::SIG_SIZE` bytes being the signature.
+pub fn verify_bytes_with<S: SignatureScheme>(public_key: &[u8], bytes: &[u8]) -> bool {
+    if bytes.len() < S::SIG_SIZE {
+        return false;
+    }
+    let length = bytes.len() - S::SIG_SIZE;
+    S::verify(public_key, &bytes[length..], &bytes[..length])
+}
+
+/// An ed25519 private key that is wiped from memory when dropped, so key material
+/// doesn't linger in freed memory or core dumps.
+pub struct SecretKey([u8; 32]);
+
+impl SecretKey {
+    pub fn new(bytes: [u8; 32]) -> Self {
+        Self(bytes)
+    }
+
+    /// Moves the key out of `bytes`, zeroing the caller's buffer.
+    pub fn take(bytes: &mut [u8; 32]) -> Self {
+        let key = Self(*bytes);
+        bytes.zeroize();
+        key
+    }
+
+    pub fn as_bytes(&self) -> &[u8; 32] {
+        &self.0
+    }
+}
+
+impl From<[u8; 32]> for SecretKey {
+    fn from(bytes: [u8; 32]) -> Self {
+        Self::new(bytes)
+    }
+}
+
+impl Drop for SecretKey {
+    fn drop(&mut self) {
+        self.0.zeroize();
+    }
+}
+
+impl fmt::Debug for SecretKey {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("SecretKey(..)")
+    }
+}
+
+/// Private key accepted by the signing functions, a `SecretKey` or, for compatibility,
+/// the raw `[u8; 32]` which is then left for the caller to wipe.
+pub trait SigningSecret {
+    fn secret_bytes(&self) -> &[u8; 32];
+}
+
+impl SigningSecret for SecretKey {
+    fn secret_bytes(&self) -> &[u8; 32] {
+        self.as_bytes()
+    }
+}
+
+impl SigningSecret for [u8; 32] {
+    fn secret_bytes(&self) -> &[u8; 32] {
+        self
+    }
+}
 
 pub fn verify_signature(public_key: &[u8; 32], signature_bytes: &[u8; 64], message: &[u8]) -> bool {
     let verifying_key = match VerifyingKey::from_bytes(public_key) {
@@ -11,21 +145,17 @@ pub fn verify_signature(public_key: &[u8; 32], signature_bytes: &[u8; 64], messa
     verifying_key.verify(message, &signature).is_ok()
 }
 
-pub fn sign_message(private_key: &[u8; 32], message: &[u8]) -> [u8; 64] {
-    let signing_key = SigningKey::from_bytes(private_key);
+pub fn sign_message(private_key: &(impl SigningSecret + ?Sized), message: &[u8]) -> [u8; 64] {
+    let signing_key = SigningKey::from_bytes(private_key.secret_bytes());
     let signature: Signature = signing_key.sign(message);
     signature.to_bytes()
 }
 
 pub fn verify_bytes(public_key: &[u8; 32], bytes: &[u8]) -> bool {
-    if bytes.len() < 64 {
-        return false;
-    }
-    let length = bytes.len() - 64;
-    verify_signature(public_key, bytes[length..].try_into().unwrap(), &bytes[..length])
+    verify_bytes_with::<Ed25519>(public_key, bytes)
 }
 
-pub fn sign_bytes(private_key: &[u8; 32], bytes: &mut [u8]) {
+pub fn sign_bytes(private_key: &(impl SigningSecret + ?Sized), bytes: &mut [u8]) {
     if bytes.len() < 64 {
         return;
     }
@@ -34,6 +164,80 @@ pub fn sign_bytes(private_key: &[u8; 32], bytes: &mut [u8]) {
     bytes[length..].copy_from_slice(&signature);
 }
 
+/// Signs every block in place like `sign_bytes`, replacing its trailing 64 bytes.
+/// The key is expanded once for the whole batch, and with the `rayon` feature large
+/// batches are signed in parallel.
+pub fn sign_blocks(private_key: &(impl SigningSecret + ?Sized), blocks: &mut [Vec<u8>]) {
+    let signing_key = SigningKey::from_bytes(private_key.secret_bytes());
+    let sign = |bytes: &mut Vec<u8>| {
+        if bytes.len() < 64 {
+            return;
+        }
+        let length = bytes.len() - 64;
+        let signature: Signature = signing_key.sign(&bytes[..length]);
+        bytes[length..].copy_from_slice(&signature.to_bytes());
+    };
+
+    #[cfg(feature = "rayon")]
+    if blocks.len() >= PARALLEL_THRESHOLD {
+        blocks.par_iter_mut().for_each(sign);
+        return;
+    }
+    blocks.iter_mut().for_each(sign);
+}
+
+/// Verifies every block like `verify_bytes` and returns one result per block, in order.
+/// With the `rayon` feature large batches are verified in parallel.
+pub fn verify_blocks(public_key: &[u8; 32], blocks: &[Vec<u8>]) -> Vec<bool> {
+    let verifying_key = match VerifyingKey::from_bytes(public_key) {
+        Ok(key) => key,
+        Err(_) => return vec![false; blocks.len()],
+    };
+    let verify = |bytes: &Vec<u8>| {
+        if bytes.len() < 64 {
+            return false;
+        }
+        let length = bytes.len() - 64;
+        let signature = Signature::from_bytes(bytes[length..].try_into().unwrap());
+        verifying_key.verify(&bytes[..length], &signature).is_ok()
+    };
+
+    #[cfg(feature = "rayon")]
+    if blocks.len() >= PARALLEL_THRESHOLD {
+        return blocks.par_iter().map(verify).collect();
+    }
+    blocks.iter().map(verify).collect()
+}
+
+/// Hash of a block's data as committed to by `prove_spacetime`, a verifier records it
+/// when the block is written so later challenges never need the data itself.
+pub fn data_hash(data: &[u8]) -> [u8; 32] {
+    Sha256::digest(data).into()
+}
+
+/// Proof that the node holding `private_key` still has `block` when it receives `challenge`,
+/// a signature over `block_number || timestamp || challenge || data_hash(data)`.
+/// The challenge must be fresh (e.g. random) for the proof to bind to the challenge time.
+pub fn prove_spacetime(private_key: &(impl SigningSecret + ?Sized), block: &DataBlock, challenge: &[u8]) -> [u8; 64] {
+    let message = spacetime_message(block.block_number, block.timestamp, challenge, &data_hash(&block.data));
+    sign_message(private_key, &message)
+}
+
+/// Checks a `prove_spacetime` proof against the block metadata and the data hash the
+/// verifier recorded, without transferring the block.
+pub fn verify_spacetime(public_key: &[u8; 32], block_number: u64, timestamp: u64, data_hash: &[u8; 32], challenge: &[u8], proof: &[u8; 64]) -> bool {
+    verify_signature(public_key, proof, &spacetime_message(block_number, timestamp, challenge, data_hash))
+}
+
+fn spacetime_message(block_number: u64, timestamp: u64, challenge: &[u8], data_hash: &[u8; 32]) -> Vec<u8> {
+    let mut message = Vec::with_capacity(48 + challenge.len());
+    message.extend_from_slice(&block_number.to_le_bytes());
+    message.extend_from_slice(&timestamp.to_le_bytes());
+    message.extend_from_slice(challenge);
+    message.extend_from_slice(data_hash);
+    message
+}
+
 #[cfg(test)]
 mod test {
     use super::*;
@@ -89,4 +293,110 @@ mod test {
         let valid = verify_signature(&VerifyingKey::from(&key2).to_bytes(), &signature, message);
         assert!(!valid, "Verification with wrong key should fail");
     }
+
+    #[test]
+    fn test_sign_and_verify_blocks() {
+        let signing_key = SigningKey::from_bytes(&[7u8; 32]);
+        let public_bytes = VerifyingKey::from(&signing_key).to_bytes();
+
+        let mut blocks: Vec<Vec<u8>> = (0..10_000u32)
+            .map(|i| {
+                let mut block = vec![0u8; 128];
+                block[..4].copy_from_slice(&i.to_le_bytes());
+                block
+            })
+            .collect();
+        sign_blocks(&signing_key.to_bytes(), &mut blocks);
+        assert!(verify_bytes(&public_bytes, &blocks[42]));
+        assert!(verify_blocks(&public_bytes, &blocks).iter().all(|&valid| valid));
+
+        blocks[1234][10] ^= 1;
+        let results = verify_blocks(&public_bytes, &blocks);
+        assert_eq!(results.iter().filter(|&&valid| !valid).count(), 1);
+        assert!(!results[1234]);
+    }
+
+    #[test]
+    fn test_spacetime_proof() {
+        let signing_key = SigningKey::from_bytes(&[9u8; 32]);
+        let public_bytes = VerifyingKey::from(&signing_key).to_bytes();
+        let block = DataBlock::new(42, 1633036800, &[5u8; 1000]);
+
+        // the verifier keeps only the metadata and the hash
+        let hash = data_hash(&block.data);
+        let challenge = b"fresh challenge";
+        let proof = prove_spacetime(&signing_key.to_bytes(), &block, challenge);
+        assert!(verify_spacetime(&public_bytes, 42, 1633036800, &hash, challenge, &proof));
+
+        // a proof is bound to its challenge and its block metadata
+        assert!(!verify_spacetime(&public_bytes, 42, 1633036800, &hash, b"next challenge", &proof));
+        assert!(!verify_spacetime(&public_bytes, 43, 1633036800, &hash, challenge, &proof));
+
+        // a node that deleted the data only knows the metadata, it can not rebuild the proof
+        let deleted = DataBlock::new(42, 1633036800, &[]);
+        let forged = prove_spacetime(&signing_key.to_bytes(), &deleted, challenge);
+        assert!(!verify_spacetime(&public_bytes, 42, 1633036800, &hash, challenge, &forged));
+    }
+
+    #[test]
+    fn test_secret_key() {
+        let message = b"this is a test message";
+        let mut bytes = [7u8; 32];
+        let public_bytes = VerifyingKey::from(&SigningKey::from_bytes(&bytes)).to_bytes();
+
+        // the raw key and the wrapped one sign identically
+        let raw = sign_message(&bytes, message);
+        let key = SecretKey::take(&mut bytes);
+        assert_eq!(bytes, [0u8; 32]);
+        assert_eq!(sign_message(&key, message), raw);
+        assert!(verify_signature(&public_bytes, &raw, message));
+        assert_eq!(format!("{key:?}"), "SecretKey(..)");
+
+        // dropping the key wipes its buffer, which stays readable behind `ManuallyDrop`
+        let mut key = std::mem::ManuallyDrop::new(key);
+        unsafe { std::mem::ManuallyDrop::drop(&mut key) };
+        assert_eq!(key.0, [0u8; 32]);
+    }
+
+    #[test]
+    fn test_signature_scheme() {
+        let private_key = [5u8; 32];
+        let public_key = VerifyingKey::from(&SigningKey::from_bytes(&private_key)).to_bytes();
+        let mut block = vec![1u8; 256];
+
+        // the generic functions over `Ed25519` match the ed25519 ones
+        sign_bytes_with::<Ed25519>(&private_key, &mut block).unwrap();
+        assert!(verify_bytes(&public_key, &block));
+        let mut expected = vec![1u8; 256];
+        sign_bytes(&private_key, &mut expected);
+        assert_eq!(block, expected);
+        block[0] ^= 1;
+        assert!(!verify_bytes_with::<Ed25519>(&public_key, &block));
+
+        let error = sign_bytes_with::<Ed25519>(&private_key[..31], &mut block).unwrap_err();
+        assert!(matches!(error.downcast_ref::<RDFSError>(), Some(RDFSError::InvalidKeyLength { .. })));
+        assert!(!Ed25519::verify(&public_key[..31], &block[192..], &block[..192]));
+
+        // another scheme with its own sizes plugs into the same functions
+        struct Checksum;
+        impl SignatureScheme for Checksum {
+            const PK_SIZE: usize = 1;
+            const SK_SIZE: usize = 1;
+            const SIG_SIZE: usize = 2;
+
+            fn sign(private_key: &[u8], message: &[u8]) -> Result<Vec<u8>> {
+                let sum = message.iter().fold(private_key[0], |sum, byte| sum.wrapping_add(*byte));
+                Ok(vec![sum, !sum])
+            }
+
+            fn verify(public_key: &[u8], signature: &[u8], message: &[u8]) -> bool {
+                Self::sign(public_key, message).is_ok_and(|expected| expected == signature)
+            }
+        }
+        let mut block = vec![7u8; 10];
+        sign_bytes_with::<Checksum>(&[3], &mut block).unwrap();
+        assert_eq!(&block[..8], &[7; 8]);
+        assert!(verify_bytes_with::<Checksum>(&[3], &block));
+        assert!(!verify_bytes_with::<Checksum>(&[4], &block));
+    }
 }
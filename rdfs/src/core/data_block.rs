@@ -15,9 +15,11 @@
 //! The encoded layout (total = `block_size` bytes):
 //! ```text
 //! [8 bytes: block_number]
-//! [8 bytes: timestamp]
-//! [8 bytes: data length]
-//! [N bytes: data]
+//! [8 bytes: timestamp, milliseconds]
+//! [3 bytes: data length]
+//! [1 byte : compression, top bit set when encrypted]
+//! [4 bytes: CRC32 checksum]
+//! [N bytes: data, compressed when flagged]
 //! [padding up to block_size - 64]
 //! [64 bytes: signature]
 //! ```
@@ -29,14 +31,167 @@
 //!
 //! ## Notes
 //! - Signature must be externally generated and inserted using `add_signature`
+//! - The CRC32 checksum covers `block_number || timestamp || data` and only detects
+//!   accidental corruption (bit-rot); tampering is the signature's job
 //! - RaptorQ-related metadata (for erasure coding) is stored inside the `data` payload
+//!
+//! ## Compression
+//! `new_compressed` marks a block to be written with `Zstd` or `Lz4`. `data` always holds
+//! the logical bytes: `to_bytes` compresses them and `from_bytes` decompresses, so the
+//! stored length is the compressed one while inode sizes stay logical. The checksum covers
+//! the logical bytes, a damaged compressed stream fails as `CorruptDataBlock` either way.
+//! A payload that doesn't shrink is written as is with the `None` flag.
+//!
+//! ## Encryption
+//! Private drives flagged `SB_FLAG_ENCRYPTED` keep their payloads encrypted with
+//! XChaCha20-Poly1305 under a user supplied `EncryptionKey`. `encrypt` compresses first
+//! (ciphertext doesn't compress), then replaces `data` with a random `AEAD_NONCE_SIZE` byte
+//! nonce, the ciphertext and its `AEAD_TAG_SIZE` byte tag; `decrypt` reverses both:
+//! ```text
+//! [24 bytes: nonce][ciphertext][16 bytes: tag]
+//! ```
+//! A nonce must not repeat under one key. It is drawn at random for every encryption, so it
+//! doesn't depend on the block number, the timestamp or the clock: 192 random bits never
+//! collide in practice, even for blocks numbered alike or rewritten within one millisecond.
+//! The block number and timestamp are authenticated as associated data, changing either
+//! fails decryption. Checksum and signature are computed over the stored bytes, nonce
+//! included, so an encrypted block can be scrubbed and verified without the key.
+//!
+//! Erasure coding happens after compression: compress the client data, then hand it to
+//! `encode_with_raptorq`. RaptorQ packets look random to a compressor, so the blocks it
+//! returns are plain `None` blocks, and dropping some of them still rebuilds the compressed
+//! client block exactly.
+//!
+//! ## RaptorQ Payload
+//! Client blocks produced by `encode_with_raptorq` carry one serialized RaptorQ
+//! packet as their `data`: a 4-byte payload id (source block number + encoding
+//! symbol id) followed by one symbol of `block_size - RESERVED_CDB` bytes. The
+//! 88 bytes of `RESERVED_DB` stay untouched, so the packet fills the payload exactly:
+//! ```text
+//! [24 bytes: block_number, timestamp, data length, checksum]
+//! [ 4 bytes: RaptorQ payload id]                    <- RESERVED_CDB - RESERVED_DB
+//! [block_size - 92 bytes: RaptorQ symbol]
+//! [64 bytes: signature]
+//! ```
 //! - This block is reusable across shared and private file systems
 //!
+//! Other erasure codes go through `encode_with_codec`/`decode_with_codec` with an
+//! `ErasureCodec` implementation, see the `erasure_codec` module; the RaptorQ functions
+//! above are those with `RaptorQ`.
+//!
 //! Copyrights © 2025 RDFS Contributors. All rights reserved.
 
-use super::super::constants::{RESERVED_DB, SIG_SIZE, Signature};
+use super::super::constants::{AEAD_NONCE_SIZE, DB_LENGTH_MASK, RESERVED_DB, SIG_SIZE, Signature};
 use super::super::rdfs_errors::RDFSError;
+use super::super::utils::current_time_millis_as_u64;
+use super::erasure_codec::{ErasureCodec, RaptorQ};
+use super::super_block::SuperBlock;
 use anyhow::Result;
+use chacha20poly1305::aead::{Aead, KeyInit, Payload};
+use chacha20poly1305::{XChaCha20Poly1305, XNonce};
+use std::fmt;
+use std::io::Read;
+use zeroize::Zeroize;
+
+const ZSTD_LEVEL: i32 = 3;
+const ENCRYPTED_FLAG: u8 = 0x80;
+
+/// A 256-bit data block encryption key that is wiped from memory when dropped.
+pub struct EncryptionKey([u8; 32]);
+
+impl EncryptionKey {
+    pub fn new(bytes: [u8; 32]) -> Self {
+        Self(bytes)
+    }
+
+    /// Moves the key out of `bytes`, zeroing the caller's buffer.
+    pub fn take(bytes: &mut [u8; 32]) -> Self {
+        let key = Self(*bytes);
+        bytes.zeroize();
+        key
+    }
+
+    pub fn as_bytes(&self) -> &[u8; 32] {
+        &self.0
+    }
+}
+
+impl From<[u8; 32]> for EncryptionKey {
+    fn from(bytes: [u8; 32]) -> Self {
+        Self::new(bytes)
+    }
+}
+
+impl Drop for EncryptionKey {
+    fn drop(&mut self) {
+        self.0.zeroize();
+    }
+}
+
+impl fmt::Debug for EncryptionKey {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("EncryptionKey(..)")
+    }
+}
+
+/// Compression of a data block payload, stored in the top byte of the length field.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Compression {
+    #[default]
+    None = 0,
+    Zstd = 1,
+    Lz4 = 2,
+}
+
+impl Compression {
+    fn from_flag(flag: u8) -> Result<Self> {
+        match flag {
+            0 => Ok(Self::None),
+            1 => Ok(Self::Zstd),
+            2 => Ok(Self::Lz4),
+            _ => Err(RDFSError::CorruptDataBlock.into()),
+        }
+    }
+
+    /// Compresses `data`, `None` when the algorithm fails or the result isn't smaller.
+    fn compress(self, data: &[u8]) -> Option<Vec<u8>> {
+        let compressed = match self {
+            Self::None => return None,
+            Self::Zstd => zstd::bulk::compress(data, ZSTD_LEVEL).ok()?,
+            Self::Lz4 => lz4_flex::compress_prepend_size(data),
+        };
+        (compressed.len() < data.len()).then_some(compressed)
+    }
+
+    /// Inverse of `compress`, refusing output beyond the 16MB a length field can describe.
+    fn decompress(self, data: &[u8]) -> Result<Vec<u8>> {
+        let corrupt = |_| RDFSError::CorruptDataBlock;
+        let limit = DB_LENGTH_MASK as usize;
+        let decompressed = match self {
+            Self::None => data.to_vec(),
+            Self::Zstd => {
+                let mut decompressed = Vec::new();
+                zstd::stream::read::Decoder::new(data)
+                    .map_err(corrupt)?
+                    .take(limit as u64 + 1)
+                    .read_to_end(&mut decompressed)
+                    .map_err(corrupt)?;
+                decompressed
+            }
+            Self::Lz4 => {
+                let size = data.get(..4).ok_or(RDFSError::CorruptDataBlock)?;
+                if u32::from_le_bytes(size.try_into().unwrap()) as usize > limit {
+                    return Err(RDFSError::CorruptDataBlock.into());
+                }
+                lz4_flex::decompress_size_prepended(data).map_err(|_| RDFSError::CorruptDataBlock)?
+            }
+        };
+        if decompressed.len() > limit {
+            return Err(RDFSError::CorruptDataBlock.into());
+        }
+        Ok(decompressed)
+    }
+}
 
 #[derive(Debug, Clone)]
 pub struct DataBlock {
@@ -44,19 +199,88 @@ pub struct DataBlock {
     pub block_number: u64, // Nonce for the block, used for proof of spacetime (block id) "first dimension".
     pub timestamp: u64,    // Timestamp for the block, used for proof of spacetime "second dimension".
     pub data: Vec<u8>,     // third dimension is integrated in RaptorQ first 4 bytes.
+    pub compression: Compression,
+    pub encrypted: bool, // `data` holds ciphertext, see `encrypt`
     pub signature: Signature,
 }
 
 impl DataBlock {
     pub fn new(block_number: u64, timestamp: u64, data: &[u8]) -> Self {
+        Self::new_compressed(block_number, timestamp, data, Compression::None)
+    }
+
+    /// Same as `new`, with the payload compressed by `algo` when written out.
+    pub fn new_compressed(block_number: u64, timestamp: u64, data: &[u8], algo: Compression) -> Self {
         Self {
             block_number,
             timestamp,
             data: data.to_vec(),
+            compression: algo,
+            encrypted: false,
             signature: [0; SIG_SIZE],
         }
     }
 
+    /// Compresses and encrypts `data` in place with `key` under a fresh random nonce, stored
+    /// in front of the ciphertext. Sign the block afterwards, the signature covers the ciphertext.
+    pub fn encrypt(&mut self, key: &EncryptionKey) -> Result<()> {
+        if self.encrypted {
+            return Err(RDFSError::EncryptionFailed("block is already encrypted".to_string()).into());
+        }
+        let nonce: [u8; AEAD_NONCE_SIZE] = rand::random();
+        let plaintext = match self.compression.compress(&self.data) {
+            Some(compressed) => compressed,
+            None => {
+                self.compression = Compression::None;
+                std::mem::take(&mut self.data)
+            }
+        };
+        let payload = Payload {
+            msg: &plaintext,
+            aad: &self.associated_data(),
+        };
+        let ciphertext = cipher(key)
+            .encrypt(XNonce::from_slice(&nonce), payload)
+            .map_err(|_| RDFSError::EncryptionFailed("payload can't be encrypted".to_string()))?;
+        self.data = [&nonce[..], &ciphertext].concat();
+        self.encrypted = true;
+        Ok(())
+    }
+
+    /// Inverse of `encrypt`. A wrong key or a tampered ciphertext, block number or timestamp
+    /// fails with `EncryptionFailed` and leaves the block untouched.
+    pub fn decrypt(&mut self, key: &EncryptionKey) -> Result<()> {
+        if !self.encrypted {
+            return Err(RDFSError::EncryptionFailed("block is not encrypted".to_string()).into());
+        }
+        let tampered = || RDFSError::EncryptionFailed("wrong key or tampered block".to_string());
+        if self.data.len() < AEAD_NONCE_SIZE {
+            return Err(tampered().into());
+        }
+        let (nonce, ciphertext) = self.data.split_at(AEAD_NONCE_SIZE);
+        let payload = Payload {
+            msg: ciphertext,
+            aad: &self.associated_data(),
+        };
+        let plaintext = cipher(key).decrypt(XNonce::from_slice(nonce), payload).map_err(|_| tampered())?;
+        self.data = self.compression.decompress(&plaintext)?;
+        self.encrypted = false;
+        Ok(())
+    }
+
+    /// The nonce an encrypted block was sealed with, `None` for a plain block.
+    pub fn nonce(&self) -> Option<&[u8]> {
+        self.data.get(..AEAD_NONCE_SIZE).filter(|_| self.encrypted)
+    }
+
+    /// `block_number || timestamp`, authenticated along with the ciphertext.
+    fn associated_data(&self) -> [u8; 16] {
+        let mut aad = [0; 16];
+        aad[..8].copy_from_slice(&self.block_number.to_le_bytes());
+        aad[8..].copy_from_slice(&self.timestamp.to_le_bytes());
+        aad
+    }
+
     /// signing algorithm is not included in the file system.
     /// add your signature after removing last 64 bytes and
     /// exchange it with your signature
@@ -66,11 +290,21 @@ impl DataBlock {
 
     pub fn to_bytes(&self, block_size: usize) -> Vec<u8> {
         let mut encoded = Vec::with_capacity(block_size);
+        let (payload, flag) = if self.encrypted {
+            (self.data.clone(), self.compression as u8 | ENCRYPTED_FLAG)
+        } else {
+            match self.compression.compress(&self.data) {
+                Some(compressed) => (compressed, self.compression as u8),
+                None => (self.data.clone(), Compression::None as u8),
+            }
+        };
+        let length = payload.len() as u32 & DB_LENGTH_MASK | (flag as u32) << 24;
 
         encoded.extend_from_slice(&self.block_number.to_le_bytes());
         encoded.extend_from_slice(&self.timestamp.to_le_bytes());
-        encoded.extend_from_slice(&(self.data.len() as u64).to_le_bytes());
-        encoded.extend_from_slice(&self.data);
+        encoded.extend_from_slice(&length.to_le_bytes());
+        encoded.extend_from_slice(&self.checksum().to_le_bytes());
+        encoded.extend_from_slice(&payload);
         encoded.resize(block_size - SIG_SIZE, 0);
         encoded.extend_from_slice(&self.signature);
 
@@ -79,26 +313,360 @@ impl DataBlock {
 
     pub fn from_bytes(data: &[u8], block_size: usize) -> Result<Self> {
         if data.len() != block_size {
-            return Err(RDFSError::InvalidDataBlockLength.into());
+            return Err(RDFSError::InvalidDataBlockLength {
+                got: data.len(),
+                expected: block_size,
+            }
+            .into());
         }
 
         let block_number = u64::from_le_bytes(data[..8].try_into().unwrap());
         let timestamp = u64::from_le_bytes(data[8..16].try_into().unwrap());
 
-        let length = u64::from_le_bytes(data[16..24].try_into().unwrap()) as usize;
+        let length = u32::from_le_bytes(data[16..20].try_into().unwrap());
+        let flag = (length >> 24) as u8;
+        let encrypted = flag & ENCRYPTED_FLAG != 0;
+        let compression = Compression::from_flag(flag & !ENCRYPTED_FLAG)?;
+        let length = (length & DB_LENGTH_MASK) as usize;
         if length > block_size - RESERVED_DB {
-            return Err(RDFSError::InvalidEncodedDataBlockLength.into());
+            return Err(RDFSError::InvalidEncodedDataBlockLength {
+                length,
+                max: block_size - RESERVED_DB,
+            }
+            .into());
         }
+        let checksum = u32::from_le_bytes(data[20..24].try_into().unwrap());
 
-        let mut content = Vec::with_capacity(length);
-        content.extend_from_slice(&data[24..data.len() - SIG_SIZE]);
+        let content = match encrypted {
+            true => data[24..24 + length].to_vec(),
+            false => compression.decompress(&data[24..24 + length])?,
+        };
         let signature: Signature = data[block_size - SIG_SIZE..].try_into().unwrap();
 
-        Ok(Self {
+        let block = Self {
             block_number,
             timestamp,
             data: content,
+            compression,
+            encrypted,
             signature,
-        })
+        };
+        if !block.verify_checksum(checksum) {
+            return Err(RDFSError::CorruptDataBlock.into());
+        }
+        Ok(block)
+    }
+
+    /// Returns `true` when the payload is full and all zeros, the kind of block a sparse
+    /// drive stores as a hole instead (see `RDFS::with_sparse`).
+    pub fn is_empty(&self, block_size: usize) -> bool {
+        Self::is_empty_payload(&self.data, block_size)
+    }
+
+    /// Same as `is_empty` for a payload not wrapped in a `DataBlock` yet.
+    pub fn is_empty_payload(data: &[u8], block_size: usize) -> bool {
+        data.len() == block_size - RESERVED_DB && data.iter().all(|byte| *byte == 0)
+    }
+
+    /// CRC32 over `block_number || timestamp || data` (logical bytes), written into the header by `to_bytes`.
+    /// Cheap enough to scrub a whole drive for bit-rot without any public key.
+    pub fn checksum(&self) -> u32 {
+        let mut hasher = crc32fast::Hasher::new();
+        hasher.update(&self.block_number.to_le_bytes());
+        hasher.update(&self.timestamp.to_le_bytes());
+        hasher.update(&self.data);
+        hasher.finalize()
+    }
+
+    /// Checks a stored checksum against the block content.
+    pub fn verify_checksum(&self, checksum: u32) -> bool {
+        self.checksum() == checksum
+    }
+
+    /// Encodes one client block into RaptorQ packets, one `DataBlock` per packet.
+    ///
+    /// `client_data` is zero padded up to `client_block_size`, split into symbols of
+    /// `block_size - RESERVED_CDB` bytes and extended with repair symbols until the
+    /// packet count reaches `redundancy` percent of the source symbols. `block_number`
+    /// is the packet index, so blocks can be spread across `nodes` in order.
+    pub fn encode_with_raptorq(client_data: &[u8], config: &SuperBlock) -> Result<Vec<DataBlock>> {
+        Self::encode_with_redundancy(client_data, config, config.redundancy)
+    }
+
+    /// Same as `encode_with_raptorq` with `redundancy` in place of the drive's, e.g. the
+    /// override of a file (`InodeFile::redundancy_or`). Decoding needs nothing else, the
+    /// source symbol count only depends on `client_block_size`.
+    pub fn encode_with_redundancy(client_data: &[u8], config: &SuperBlock, redundancy: u64) -> Result<Vec<DataBlock>> {
+        Self::encode_with_codec::<RaptorQ>(client_data, config, redundancy)
+    }
+
+    /// Encodes one client block with any `ErasureCodec`, one `DataBlock` per chunk numbered
+    /// by its index. Fails with `CodecMismatch` unless the drive was created with `C`.
+    pub fn encode_with_codec<C: ErasureCodec>(client_data: &[u8], config: &SuperBlock, redundancy: u64) -> Result<Vec<DataBlock>> {
+        check_codec::<C>(config)?;
+        let timestamp = current_time_millis_as_u64()?;
+        let blocks = C::encode(client_data, config, redundancy)?
+            .iter()
+            .enumerate()
+            .map(|(i, chunk)| DataBlock::new(i as u64, timestamp, chunk))
+            .collect();
+
+        Ok(blocks)
+    }
+
+    /// Rebuilds a client block from RaptorQ blocks gathered from any nodes, in any order.
+    ///
+    /// Decoding stops as soon as enough packets arrived, so the iterator is only drained
+    /// as far as needed. Any `source_symbols` packets are almost always enough, which lets
+    /// up to `redundancy - 100` percent (of the source symbols) of the blocks go missing.
+    /// The result is always `client_block_size` bytes long, including the zero padding
+    /// added by `encode_with_raptorq`.
+    pub fn decode_from_raptorq(blocks: impl Iterator<Item = DataBlock>, config: &SuperBlock) -> Result<Vec<u8>> {
+        Self::decode_with_codec::<RaptorQ>(blocks, config)
+    }
+
+    /// Rebuilds a client block encoded by `encode_with_codec` with the same codec.
+    /// Fails with `CodecMismatch` unless the drive was created with `C`.
+    pub fn decode_with_codec<C: ErasureCodec>(blocks: impl Iterator<Item = DataBlock>, config: &SuperBlock) -> Result<Vec<u8>> {
+        check_codec::<C>(config)?;
+        C::decode(blocks.map(|block| block.data), config)
+    }
+}
+
+fn check_codec<C: ErasureCodec>(config: &SuperBlock) -> Result<()> {
+    if config.codec != C::ID {
+        return Err(RDFSError::CodecMismatch {
+            drive: config.codec,
+            codec: C::ID,
+        }
+        .into());
+    }
+    Ok(())
+}
+
+fn cipher(key: &EncryptionKey) -> XChaCha20Poly1305 {
+    XChaCha20Poly1305::new(key.as_bytes().into())
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::constants::RESERVED_CDB;
+    use crate::core::super_block::FileSystemType;
+    use rand::seq::SliceRandom;
+
+    #[test]
+    fn checksum_test() {
+        let block = DataBlock::new(7, 42, b"hello rdfs");
+        assert!(block.verify_checksum(block.checksum()));
+        assert_ne!(block.checksum(), DataBlock::new(8, 42, b"hello rdfs").checksum());
+        assert_ne!(block.checksum(), DataBlock::new(7, 43, b"hello rdfs").checksum());
+
+        let mut encoded = block.to_bytes(4096);
+        let decoded = DataBlock::from_bytes(&encoded, 4096).unwrap();
+        assert_eq!(decoded.checksum(), block.checksum());
+
+        // a single flipped bit in the payload is caught
+        encoded[30] ^= 0x01;
+        let err = DataBlock::from_bytes(&encoded, 4096).unwrap_err();
+        assert!(matches!(err.downcast_ref::<RDFSError>(), Some(RDFSError::CorruptDataBlock)));
+
+        // the signature is not covered by the checksum
+        let mut encoded = block.to_bytes(4096);
+        encoded[4095] ^= 0xFF;
+        assert!(DataBlock::from_bytes(&encoded, 4096).is_ok());
+    }
+
+    #[test]
+    fn encode_with_raptorq_test() {
+        let config = SuperBlock::new(FileSystemType::Shared, [1; 32], [2; 32], 1048576, 300, 12, 4096).unwrap();
+        let data: Vec<u8> = (0..config.client_block_size).map(|i| i as u8).collect();
+
+        let blocks = DataBlock::encode_with_raptorq(&data, &config).unwrap();
+        let source_symbols = config.client_block_size.div_ceil(4096 - RESERVED_CDB as u64);
+        assert_eq!(blocks.len() as u64, source_symbols * 3);
+        for (i, block) in blocks.iter().enumerate() {
+            assert_eq!(block.block_number, i as u64);
+            assert_eq!(block.data.len(), 4096 - RESERVED_DB);
+            let decoded = DataBlock::from_bytes(&block.to_bytes(4096), 4096).unwrap();
+            assert_eq!(decoded.data, block.data);
+        }
+
+        // source packets carry the client data verbatim after the 4-byte header
+        assert_eq!(&blocks[0].data[4..], &data[..4096 - RESERVED_CDB]);
+
+        let too_big = vec![0; config.client_block_size as usize + 1];
+        assert!(DataBlock::encode_with_raptorq(&too_big, &config).is_err());
+    }
+
+    #[test]
+    fn decode_from_raptorq_test() {
+        let config = SuperBlock::new(FileSystemType::Shared, [1; 32], [2; 32], 1048576, 300, 12, 4096).unwrap();
+        let data: Vec<u8> = (0..config.client_block_size / 2).map(|i| (i * 7) as u8).collect();
+        let mut blocks = DataBlock::encode_with_raptorq(&data, &config).unwrap();
+        let source_symbols = config.client_block_size.div_ceil(4096 - RESERVED_CDB as u64) as usize;
+
+        // lose a random subset of the repair budget, keeping a small decoding overhead
+        blocks.shuffle(&mut rand::rng());
+        blocks.truncate(source_symbols + 2);
+        let decoded = DataBlock::decode_from_raptorq(blocks.into_iter(), &config).unwrap();
+        assert_eq!(decoded.len() as u64, config.client_block_size);
+        assert_eq!(&decoded[..data.len()], &data[..]);
+        assert!(decoded[data.len()..].iter().all(|&b| b == 0));
+
+        // every source block missing, only repair blocks left
+        let blocks = DataBlock::encode_with_raptorq(&data, &config).unwrap();
+        let decoded = DataBlock::decode_from_raptorq(blocks.into_iter().skip(source_symbols), &config).unwrap();
+        assert_eq!(&decoded[..data.len()], &data[..]);
+
+        // fewer blocks than source symbols can never be decoded
+        let blocks = DataBlock::encode_with_raptorq(&data, &config).unwrap();
+        let result = DataBlock::decode_from_raptorq(blocks.into_iter().take(source_symbols - 1), &config);
+        assert!(result.is_err());
+    }
+
+    #[test]
+    fn is_empty_test() {
+        let payload = 4096 - RESERVED_DB;
+        assert!(DataBlock::new(1, 0, &vec![0; payload]).is_empty(4096));
+        assert!(!DataBlock::new(1, 0, &vec![0; payload - 1]).is_empty(4096));
+        assert!(!DataBlock::new(1, 0, &[]).is_empty(4096));
+
+        let mut data = vec![0; payload];
+        data[payload - 1] = 1;
+        assert!(!DataBlock::new(1, 0, &data).is_empty(4096));
+    }
+
+    #[test]
+    fn compression_round_trip_test() {
+        let text: Vec<u8> = b"raptorq distributed file system ".iter().cycle().take(4000).copied().collect();
+        for algo in [Compression::None, Compression::Zstd, Compression::Lz4] {
+            let block = DataBlock::new_compressed(3, 42, &text, algo);
+            let encoded = block.to_bytes(4096);
+            let stored = (u32::from_le_bytes(encoded[16..20].try_into().unwrap()) & DB_LENGTH_MASK) as usize;
+            assert_eq!(encoded[19], algo as u8);
+
+            let decoded = DataBlock::from_bytes(&encoded, 4096).unwrap();
+            assert_eq!(decoded.compression, algo);
+            assert_eq!(decoded.data, text);
+            assert_eq!(decoded.checksum(), block.checksum());
+            if algo == Compression::None {
+                assert_eq!(stored, text.len());
+            } else {
+                assert!(stored < text.len() / 4);
+            }
+        }
+
+        // logical data larger than the payload fits once compressed
+        let zeros = vec![0; 64 * 1024];
+        let block = DataBlock::new_compressed(3, 42, &zeros, Compression::Zstd);
+        assert_eq!(DataBlock::from_bytes(&block.to_bytes(4096), 4096).unwrap().data, zeros);
+
+        // incompressible payloads are written as they are
+        let noise: Vec<u8> = (0..4000).map(|_| rand::random::<u8>()).collect();
+        let block = DataBlock::new_compressed(3, 42, &noise, Compression::Lz4);
+        let decoded = DataBlock::from_bytes(&block.to_bytes(4096), 4096).unwrap();
+        assert_eq!(decoded.compression, Compression::None);
+        assert_eq!(decoded.data, noise);
+    }
+
+    #[test]
+    fn corrupt_compressed_block_test() {
+        let text: Vec<u8> = b"rdfs ".iter().cycle().take(2000).copied().collect();
+        for algo in [Compression::Zstd, Compression::Lz4] {
+            let mut encoded = DataBlock::new_compressed(1, 2, &text, algo).to_bytes(4096);
+            encoded[30] ^= 0xFF;
+            let err = DataBlock::from_bytes(&encoded, 4096).unwrap_err();
+            assert!(matches!(err.downcast_ref::<RDFSError>(), Some(RDFSError::CorruptDataBlock)));
+        }
+
+        // unknown compression flag
+        let mut encoded = DataBlock::new(1, 2, &text).to_bytes(4096);
+        encoded[19] = 9;
+        let err = DataBlock::from_bytes(&encoded, 4096).unwrap_err();
+        assert!(matches!(err.downcast_ref::<RDFSError>(), Some(RDFSError::CorruptDataBlock)));
+    }
+
+    #[test]
+    fn compress_before_raptorq_test() {
+        let config = SuperBlock::new(FileSystemType::Shared, [1; 32], [2; 32], 1048576, 300, 12, 4096).unwrap();
+        let data: Vec<u8> = b"client block ".iter().cycle().take(config.client_block_size as usize).copied().collect();
+        let compressed = zstd::bulk::compress(&data, ZSTD_LEVEL).unwrap();
+
+        let mut blocks = DataBlock::encode_with_raptorq(&compressed, &config).unwrap();
+        assert!(blocks.iter().all(|block| block.compression == Compression::None));
+        blocks.shuffle(&mut rand::rng());
+        let source_symbols = config.client_block_size.div_ceil(4096 - RESERVED_CDB as u64) as usize;
+        blocks.truncate(source_symbols + 2);
+
+        let decoded = DataBlock::decode_from_raptorq(blocks.into_iter(), &config).unwrap();
+        let restored = Compression::Zstd.decompress(&decoded[..compressed.len()]).unwrap();
+        assert_eq!(restored, data);
+    }
+
+    #[test]
+    fn encryption_test() {
+        use crate::core::block_signature::{sign_bytes, verify_bytes};
+        use ed25519_dalek::SigningKey;
+
+        let key = EncryptionKey::new([9; 32]);
+        let text: Vec<u8> = b"private rdfs block ".iter().cycle().take(3000).copied().collect();
+        for algo in [Compression::None, Compression::Zstd, Compression::Lz4] {
+            let mut block = DataBlock::new_compressed(5, 42, &text, algo);
+            block.encrypt(&key).unwrap();
+            assert!(block.encrypted && block.timestamp == 42);
+            assert_eq!(block.nonce().unwrap().len(), AEAD_NONCE_SIZE);
+            assert!(!block.data.windows(19).any(|window| window == b"private rdfs block "));
+            assert!(block.encrypt(&key).is_err());
+
+            // checksum and signature cover the ciphertext, no key needed to check them
+            let signing_key = [3; 32];
+            let public_key = SigningKey::from_bytes(&signing_key).verifying_key().to_bytes();
+            let mut encoded = block.to_bytes(4096);
+            sign_bytes(&signing_key, &mut encoded);
+            assert!(verify_bytes(&public_key, &encoded));
+            let mut decoded = DataBlock::from_bytes(&encoded, 4096).unwrap();
+            assert!(decoded.encrypted);
+            assert_eq!(decoded.data, block.data);
+
+            decoded.decrypt(&key).unwrap();
+            assert!(!decoded.encrypted);
+            assert_eq!(decoded.data, text);
+            assert!(decoded.decrypt(&key).is_err());
+        }
+
+        // wrong key or a tampered header never decrypts
+        let mut block = DataBlock::new(5, 42, &text);
+        block.encrypt(&key).unwrap();
+        for (mut tampered, other_key) in [
+            (block.clone(), EncryptionKey::new([8; 32])),
+            (
+                DataBlock {
+                    block_number: 6,
+                    ..block.clone()
+                },
+                EncryptionKey::new([9; 32]),
+            ),
+            (
+                DataBlock {
+                    timestamp: block.timestamp + 1,
+                    ..block.clone()
+                },
+                EncryptionKey::new([9; 32]),
+            ),
+        ] {
+            let error = tampered.decrypt(&other_key).unwrap_err();
+            assert!(matches!(error.downcast_ref::<RDFSError>(), Some(RDFSError::EncryptionFailed(_))));
+            assert!(tampered.encrypted);
+        }
+
+        // encrypting the same block again never reuses a nonce, whatever its number and timestamp
+        let mut again = block.clone();
+        again.decrypt(&key).unwrap();
+        again.encrypt(&key).unwrap();
+        assert_eq!((again.block_number, again.timestamp), (block.block_number, block.timestamp));
+        assert_ne!(again.nonce(), block.nonce());
+        assert_ne!(again.data, block.data);
+        assert!(DataBlock::new(5, 42, &text).nonce().is_none());
     }
 }
@@ -0,0 +1,160 @@
+//! # RDFS ErasureCodec Module
+//!
+//! This module defines the `ErasureCodec` trait, the erasure code a shared drive spreads its
+//! client blocks across nodes with, and `RaptorQ`, the codec of every drive so far.
+//!
+//! A codec turns one client block of `client_block_size` bytes into chunks that each fill
+//! the payload of a `DataBlock`, `redundancy` percent of the source chunks in total, and
+//! rebuilds the client block from any large enough subset of them, in any order. The drive
+//! records which codec it uses in `SuperBlock::codec` (version 6 and later, older drives
+//! are RaptorQ), and `DataBlock::encode_with_codec`/`DataBlock::decode_with_codec` refuse
+//! a codec other than the drive's. Like `SignatureScheme`, the codec is a type parameter:
+//! the `*_with_codec` functions take any implementation and the RaptorQ ones are the default.
+//!
+//! ## Adding a Codec
+//! - Implement `ErasureCodec` with a new `ID`
+//! - Add the `ID` to `SUPPORTED_CODECS` so `SuperBlock::validate` accepts drives using it
+//! - Create drives with `SuperBlock::with_codec`
+//!
+//! Copyrights © 2025 RDFS Contributors. All rights reserved.
+
+use super::super::constants::{RESERVED_CDB, RESERVED_DB};
+use super::super::rdfs_errors::RDFSError;
+use super::super_block::SuperBlock;
+use anyhow::Result;
+use raptorq::{Decoder, Encoder, EncodingPacket, ObjectTransmissionInformation};
+
+/// Ids of the codecs implemented by this crate, any other `SuperBlock::codec` is refused.
+pub const SUPPORTED_CODECS: [u16; 1] = [RaptorQ::ID];
+
+/// An erasure code client blocks can be encoded with. The block size, node count and
+/// `client_block_size` come from the drive's `SuperBlock`, so decoding needs nothing but
+/// the chunks and the drive.
+pub trait ErasureCodec {
+    /// Stored in `SuperBlock::codec` for drives encoded with this codec.
+    const ID: u16;
+
+    /// Encodes `client_data`, at most `client_block_size` bytes, into chunks of at most
+    /// `block_size - RESERVED_DB` bytes, `redundancy` percent of the source chunks in total.
+    /// The chunk index is its place in the returned vector.
+    fn encode(client_data: &[u8], config: &SuperBlock, redundancy: u64) -> Result<Vec<Vec<u8>>>;
+
+    /// Rebuilds the client block, `client_block_size` bytes including any padding, from
+    /// chunks gathered in any order, reading `chunks` only as far as needed.
+    fn decode(chunks: impl Iterator<Item = Vec<u8>>, config: &SuperBlock) -> Result<Vec<u8>>;
+}
+
+/// The RaptorQ fountain code, the codec of every drive older than version 6. Each chunk is
+/// one serialized packet: a 4-byte payload id followed by a symbol of `block_size - RESERVED_CDB`
+/// bytes, and any `source_symbols` packets almost always rebuild the block.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct RaptorQ;
+
+impl ErasureCodec for RaptorQ {
+    const ID: u16 = 0;
+
+    /// `client_data` is zero padded up to `client_block_size` and extended with repair
+    /// symbols until the packet count reaches `redundancy` percent of the source symbols.
+    fn encode(client_data: &[u8], config: &SuperBlock, redundancy: u64) -> Result<Vec<Vec<u8>>> {
+        if client_data.len() as u64 > config.client_block_size {
+            return Err(RDFSError::InvalidClientBlockLength.into());
+        }
+
+        let raptorq_config = raptorq_config(config)?;
+        let source_symbols = config.client_block_size.div_ceil(raptorq_config.symbol_size() as u64);
+        let total_packets = (source_symbols * redundancy).div_ceil(100);
+        let repair_packets = total_packets.saturating_sub(source_symbols) as u32;
+
+        let mut padded = client_data.to_vec();
+        padded.resize(config.client_block_size as usize, 0);
+
+        let packets = Encoder::new(&padded, raptorq_config).get_encoded_packets(repair_packets);
+        Ok(packets.iter().map(EncodingPacket::serialize).collect())
+    }
+
+    fn decode(chunks: impl Iterator<Item = Vec<u8>>, config: &SuperBlock) -> Result<Vec<u8>> {
+        let mut decoder = Decoder::new(raptorq_config(config)?);
+
+        for chunk in chunks {
+            if chunk.len() <= 4 {
+                return Err(RDFSError::InvalidEncodedDataBlockLength {
+                    length: chunk.len(),
+                    max: config.block_size as usize - RESERVED_DB,
+                }
+                .into());
+            }
+            if let Some(data) = decoder.decode(EncodingPacket::deserialize(&chunk)) {
+                return Ok(data);
+            }
+        }
+
+        Err(RDFSError::NotEnoughRaptorQBlocks.into())
+    }
+}
+
+/// RaptorQ parameters shared by every client block of the file system:
+/// a single source block whose symbols fill a data block after the 4-byte header.
+fn raptorq_config(config: &SuperBlock) -> Result<ObjectTransmissionInformation> {
+    if config.client_block_size == 0 {
+        return Err(RDFSError::InvalidClientBlockLength.into());
+    }
+    let symbol_size = u16::try_from(config.block_size - RESERVED_CDB as u64).map_err(|_| RDFSError::InvalidRaptorQSymbolSize)?;
+    Ok(ObjectTransmissionInformation::new(config.client_block_size, symbol_size, 1, 1, 1))
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::core::data_block::DataBlock;
+    use crate::core::super_block::FileSystemType;
+
+    /// Plain replication, every chunk is a full copy of the client block.
+    struct Replication;
+
+    impl ErasureCodec for Replication {
+        const ID: u16 = 7;
+
+        fn encode(client_data: &[u8], config: &SuperBlock, redundancy: u64) -> Result<Vec<Vec<u8>>> {
+            let mut padded = client_data.to_vec();
+            padded.resize(config.data_payload_size() as usize, 0);
+            Ok(vec![padded; redundancy.div_ceil(100) as usize])
+        }
+
+        fn decode(mut chunks: impl Iterator<Item = Vec<u8>>, _: &SuperBlock) -> Result<Vec<u8>> {
+            chunks.next().ok_or_else(|| RDFSError::NotEnoughRaptorQBlocks.into())
+        }
+    }
+
+    #[test]
+    fn erasure_codec_test() {
+        let config = SuperBlock::new(FileSystemType::Shared, [255; 32], [1; 32], 16777216, 300, 12, 4096).unwrap();
+        assert_eq!(config.codec, RaptorQ::ID);
+        let data: Vec<u8> = (0..config.client_block_size).map(|i| (i % 251) as u8).collect();
+
+        // the trait and the `DataBlock` wrappers produce the same chunks
+        let chunks = RaptorQ::encode(&data, &config, config.redundancy).unwrap();
+        let blocks = DataBlock::encode_with_codec::<RaptorQ>(&data, &config, config.redundancy).unwrap();
+        assert_eq!(chunks, blocks.iter().map(|block| block.data.clone()).collect::<Vec<_>>());
+        assert_eq!(RaptorQ::decode(chunks.into_iter().rev().take(4), &config).unwrap(), data);
+
+        // another codec only runs on a drive recorded with it
+        let replicated = config.clone().with_codec::<Replication>();
+        assert_eq!(replicated.codec, Replication::ID);
+        let blocks = DataBlock::encode_with_codec::<Replication>(b"copies", &replicated, 300).unwrap();
+        assert_eq!(blocks.len(), 3);
+        assert_eq!(blocks[2].block_number, 2);
+        let decoded = DataBlock::decode_with_codec::<Replication>(blocks.clone().into_iter().skip(2), &replicated).unwrap();
+        assert_eq!(&decoded[..6], b"copies");
+
+        let error = DataBlock::decode_with_codec::<RaptorQ>(blocks.into_iter(), &replicated).unwrap_err();
+        assert!(matches!(
+            error.downcast_ref::<RDFSError>(),
+            Some(RDFSError::CodecMismatch { drive: 7, codec: 0 })
+        ));
+        assert!(DataBlock::encode_with_raptorq(&data, &replicated).is_err());
+
+        // a drive naming a codec this build doesn't know is refused
+        let error = replicated.validate().unwrap_err();
+        assert!(matches!(error.downcast_ref::<RDFSError>(), Some(RDFSError::InconsistentSuperBlock(field)) if field == "codec"));
+    }
+}
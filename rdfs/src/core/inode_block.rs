@@ -19,13 +19,14 @@
 //! ## Layout Summary
 //! ### InodeDir / InodeFile (typical layout: 1136 bytes + content + signature)
 //! ```text
-//! - ContentName (1024 bytes)
+//! - ContentName (1024 bytes, `InodeFile` keeps `links` in bytes 2..4)
 //! - created (8 bytes)
 //! - modify (8 bytes)
 //! - size (8 bytes)
 //! - total_blocks (8 bytes)
 //! - linked (8 bytes)
-//! - content length (8 bytes)
+//! - content length (4 bytes)
+//! - redundancy (4 bytes, `InodeFile` only, 0 for the drive's)
 //! - [Vec<Content>] (N * 16 bytes)
 //! - signature (64 bytes)
 //! ```
@@ -38,9 +39,65 @@
 //! - signature (64 bytes)
 //! ```
 //!
+//! ## Size Semantics
+//! - `InodeDir::size` is the number of entries of the directory, entries spilled into its
+//!   `InodeLinkedDir` chain included. `add_entry`/`remove_entry` keep it for the block itself
+//!   and `RDFS::add_dir_entry`/`RDFS::remove_dir_entry` for the linked blocks, read it with
+//!   `InodeDir::entry_count`
+//! - `InodeFile::size` is the number of payload bytes of the file, without the `DataBlock`
+//!   metadata nor the padding of its last block
+//!
+//! ## Per-File Redundancy
+//! `InodeFile::redundancy` overrides `SuperBlock::redundancy` for the RaptorQ chunks of one
+//! file, see `RDFS::create_file_with_redundancy`. It takes the upper half of the content
+//! length word, which never needs more than 32 bits, so older inodes read back as 0.
+//!
+//! ## Link Count
+//! `InodeFile::links` counts the directory entries referencing the file, 1 unless hard links
+//! were added with `RDFS::link`. A name never needs more than 8 bits of its 32-bit length, so
+//! the count takes bytes 2..4 of the `ContentName` length word, at most `u16::MAX`. Older
+//! inodes store 0 there and read back as 1.
+//!
+//! ## Link Names
+//! The name of an entry is the one stored in its inode, except for the entries added by
+//! `RDFS::link`: each one has its own name, a `ContentName` alone in a block of its own whose
+//! pointer is `DirContent::name`. It takes the type word of the entry above its low byte,
+//! ```text
+//! - pointer (8 bytes)
+//! - inode_type (low byte) | name << 8 (8 bytes)
+//! ```
+//! so a name block must lie in the first 2^56 bytes of the drive. Entries written before
+//! links had names store 0 there and go by the name of their inode.
+//!
+//! ## Sparse Holes
+//! A `FileContent` whose `pointer` is 0 is a hole of `blocks` all-zero blocks with no block
+//! behind it, written by a sparse drive (`RDFS::with_sparse`) and read back as zeros. It can't
+//! collide with a data block since `data_pointer` is never 0. `total_blocks` counts holes too.
+//!
+//! ## Range Validation
+//! The data block ranges of a file must be disjoint and lie inside the data blocks of the
+//! drive, block aligned. `RDFS` checks them with `validate_file_ranges` before writing a file
+//! inode, so a malformed inode can't make two files (or one file twice) own the same blocks.
+//! `InodeFile::validate_ranges` checks the ranges held by the inode block itself.
+//!
+//! ## Building Inodes
+//! `InodeFile::build` and `InodeDir::build` split a whole content list the way it is stored:
+//! the first `max_content_pointers` entries in the inode block, the rest in chunks of
+//! `max_linked_content_pointers` in linked blocks. The linked blocks don't exist yet when
+//! the chain is built, so every `linked` is left 0; once the caller allocated one block per
+//! linked inode, `link_chain` points the inode at the first block and each block at the next.
+//! ```text
+//! let (mut inode, mut chain) = InodeFile::build(name, timestamp, content, &super_block);
+//! let pointers = /* chain.len() free blocks */;
+//! inode.link_chain(&mut chain, &pointers)?;
+//! // write inode, then chain[i] at pointers[i]
+//! ```
+//!
 //! ## Notes
 //! - All serialization logic pads to `block_size` and appends a 64-byte `signature`
 //! - `ContentName` uses `u32`-based UTF to support non-ASCII characters with cross-platform consistency
+//! - `ContentName::try_new` refuses names with a `/`, a null or another control character, so
+//!   every name created through `RDFS` can be reached by path
 //! - `DirContent` uses `inode_type` to differentiate internal references (file vs. directory)
 //!
 //! ## Security
@@ -50,9 +107,13 @@
 //! Copyrights © 2025 RDFS Contributors. All rights reserved.
 
 use super::super::constants::{CONTENT_SIZE, RESERVED_IB, RESERVED_LIB, SIG_SIZE, Signature};
-use std::fmt;
 use super::super::rdfs_errors::RDFSError;
+use super::super::utils::current_time_millis_as_u64;
+use super::super_block::SuperBlock;
 use anyhow::Result;
+use std::fmt;
+use std::hash::{Hash, Hasher};
+use unicode_normalization::UnicodeNormalization;
 
 /// Represents an inode in the filesystem, which can be a directory.
 /// Inodes are used to store metadata about files and directories, such as their names, sizes, timestamps, and content pointers.
@@ -62,7 +123,7 @@ pub struct InodeDir {
     pub name: ContentName,
     pub created: u64,
     pub modify: u64,
-    pub size: u64,
+    pub size: u64, // number of entries, linked blocks included
     pub total_blocks: u64,
     pub content: Vec<DirContent>, // (pointer, Inode type)
     pub linked: u64,              // Pointer to the linked directory or file, 0 if not linked
@@ -81,21 +142,25 @@ pub struct InodeLinkedDir {
 pub struct DirContent {
     pub pointer: u64,
     pub inode_type: InodeType,
+    pub name: u64, // Pointer to the name block of a hard link, 0 for the name of the inode
 }
 
 impl DirContent {
     pub fn to_bytes(&self) -> Vec<u8> {
         let mut data = Vec::with_capacity(CONTENT_SIZE);
         data.extend_from_slice(&self.pointer.to_le_bytes());
-        data.extend_from_slice(&(self.inode_type as u64).to_le_bytes());
+        data.extend_from_slice(&(self.inode_type as u64 | self.name << 8).to_le_bytes());
         data
     }
 
-    pub fn from_bytes(data: &[u8]) -> Self {
-        Self {
+    /// Fails with `InvalidInodeType` on a corrupted type instead of guessing one.
+    pub fn from_bytes(data: &[u8]) -> Result<Self> {
+        let word = u64::from_le_bytes(data[8..].try_into().unwrap());
+        Ok(Self {
             pointer: u64::from_le_bytes(data[..8].try_into().unwrap()),
-            inode_type: InodeType::from(u64::from_le_bytes(data[8..].try_into().unwrap())),
-        }
+            inode_type: InodeType::try_from(word & 0xff)?,
+            name: word >> 8,
+        })
     }
 }
 
@@ -106,6 +171,19 @@ pub enum InodeType {
     File = 1, // Regular file
 }
 
+impl InodeType {
+    /// Strict decoding of a stored type, anything but 0 or 1 fails with `InvalidInodeType`.
+    pub fn try_from(value: u64) -> Result<Self, RDFSError> {
+        match value {
+            0 => Ok(InodeType::Dir),
+            1 => Ok(InodeType::File),
+            _ => Err(RDFSError::InvalidInodeType(value)),
+        }
+    }
+}
+
+/// Lossy conversion kept for compatibility, any non-zero value becomes `File`.
+/// Decoding a block uses the strict `InodeType::try_from` instead.
 impl From<u64> for InodeType {
     fn from(value: u64) -> Self {
         match value {
@@ -123,10 +201,12 @@ pub struct InodeFile {
     pub name: ContentName,
     pub created: u64,
     pub modify: u64,
-    pub size: u64,
+    pub size: u64, // payload bytes
     pub total_blocks: u64,
     pub content: Vec<FileContent>, // (pointer, size in blocks)
     pub linked: u64,               // Pointer to the linked directory or file, 0 if not linked
+    pub redundancy: u64,           // RaptorQ redundancy in percent for this file, 0 to use the drive's
+    pub links: u64,                // Directory entries referencing this inode, see `RDFS::link`
     pub signature: Signature,      // Signature for the inode, used for verification
 }
 
@@ -145,6 +225,15 @@ pub struct FileContent {
 }
 
 impl FileContent {
+    /// A sparse hole of `blocks` all-zero blocks.
+    pub fn hole(blocks: u64) -> Self {
+        Self { pointer: 0, blocks }
+    }
+
+    pub fn is_hole(&self) -> bool {
+        self.pointer == 0
+    }
+
     pub fn to_bytes(&self) -> Vec<u8> {
         let mut data = Vec::with_capacity(CONTENT_SIZE);
         data.extend_from_slice(&self.pointer.to_le_bytes());
@@ -163,7 +252,8 @@ impl FileContent {
 /// Any content (directory or file) is named in UTF-32, because English is not the only language used and using UTF-8
 /// in other systems scrambling the names of your contents if not named in english, the most suitable solution for this
 /// is using 32 bit code for more additional uni codes now you can write in any different language or even uses Emoji 👍.
-#[derive(Debug, Clone, PartialEq, Eq)]
+/// Equality and hashing only look at the first `length` characters, whatever is left in the rest of the buffer.
+#[derive(Debug, Clone)]
 pub struct ContentName {
     // UTF-32     1024 bytes
     pub length: u32,
@@ -171,6 +261,8 @@ pub struct ContentName {
 }
 
 impl ContentName {
+    /// Builds a name from `s`, silently truncated to the first 255 characters.
+    /// Use `try_new` when the name comes from an untrusted source.
     pub fn new(s: &str) -> Self {
         let mut name = [0u32; 255];
         let chars: Vec<u32> = s.chars().take(255).map(|c| c as u32).collect();
@@ -183,14 +275,97 @@ impl ContentName {
         }
     }
 
+    /// Builds a name from `s`, failing instead of truncating when it is longer than 255
+    /// characters (`NameTooLong`) and when it could not be a path segment (`InvalidName`, see `validate`).
+    pub fn try_new(s: &str) -> Result<Self, RDFSError> {
+        if s.chars().count() > 255 {
+            return Err(RDFSError::NameTooLong);
+        }
+        let name = Self::new(s);
+        name.validate()?;
+        Ok(name)
+    }
+
+    /// Fails with `InvalidName` if the name holds a `/`, which no path could ever resolve to,
+    /// a null or any other control character, or a code point that is not a `char`.
+    /// Names already on a drive are read whatever they hold, only new names are checked.
+    pub fn validate(&self) -> Result<(), RDFSError> {
+        let forbidden = |c: &u32| *c == '/' as u32 || char::from_u32(*c).is_none_or(char::is_control);
+        match self.name[..self.char_len()].iter().any(forbidden) {
+            true => Err(RDFSError::InvalidName(self.as_string())),
+            false => Ok(()),
+        }
+    }
+
+    /// Builds a name from raw bytes, replacing invalid UTF-8 sequences with `U+FFFD`.
+    pub fn from_utf8_lossy(bytes: &[u8]) -> Self {
+        Self::new(&String::from_utf8_lossy(bytes))
+    }
+
+    /// Number of characters in the name, never more than 255 even if the stored length is corrupted.
+    pub fn char_len(&self) -> usize {
+        (self.length as usize).min(self.name.len())
+    }
+
+    /// Replaces the stored name in place, clearing any leftover characters of the old one.
+    pub fn set(&mut self, s: &str) {
+        *self = Self::new(s);
+    }
+
     /// Returns the actual file name as a String
     pub fn as_string(&self) -> String {
-        self.name[..(self.length as usize)]
+        self.name[..self.char_len()]
             .iter()
             .map(|&c| char::from_u32(c).unwrap_or('\u{FFFD}')) // safe fallback
             .collect()
     }
 
+    /// Returns `true` if the name begins with `prefix`, comparing code points in place.
+    pub fn starts_with(&self, prefix: &str) -> bool {
+        let mut name = self.name[..self.char_len()].iter();
+        prefix.chars().all(|c| name.next() == Some(&(c as u32)))
+    }
+
+    /// Returns `true` if `needle` appears anywhere in the name, comparing code points in place.
+    pub fn contains(&self, needle: &str) -> bool {
+        let name = &self.name[..self.char_len()];
+        (0..=name.len()).any(|start| {
+            let mut rest = name[start..].iter();
+            needle.chars().all(|c| rest.next() == Some(&(c as u32)))
+        })
+    }
+
+    /// Matches the whole name against `pattern`, where `*` stands for any run of characters
+    /// (none included) and `?` for exactly one, every other character for itself. Code
+    /// points are compared as stored, whatever the `NamePolicy`. Nothing is allocated: on a
+    /// mismatch the last `*` takes one more character and matching resumes after it.
+    pub fn matches_glob(&self, pattern: &str) -> bool {
+        let name = &self.name[..self.char_len()];
+        let (mut p, mut n) = (0, 0); // byte offset in `pattern`, character index in `name`
+        let mut star = None; // pattern offset after the last `*` and the name index it resumes at
+        while n < name.len() {
+            match pattern[p..].chars().next() {
+                Some('*') => {
+                    p += 1;
+                    star = Some((p, n));
+                }
+                Some(c) if c == '?' || c as u32 == name[n] => {
+                    p += c.len_utf8();
+                    n += 1;
+                }
+                _ => match star {
+                    Some((after, resume)) => {
+                        p = after;
+                        n = resume + 1;
+                        star = Some((after, n));
+                    }
+                    None => return false,
+                },
+            }
+        }
+        pattern[p..].chars().all(|c| c == '*')
+    }
+
     pub fn to_bytes(&self) -> Vec<u8> {
         let mut buf = Vec::with_capacity(1024);
         buf.extend(&self.length.to_le_bytes());
@@ -213,9 +388,23 @@ impl ContentName {
     }
 }
 
+impl PartialEq for ContentName {
+    fn eq(&self, other: &Self) -> bool {
+        self.name[..self.char_len()] == other.name[..other.char_len()]
+    }
+}
+
+impl Eq for ContentName {}
+
+impl Hash for ContentName {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.name[..self.char_len()].hash(state);
+    }
+}
+
 impl fmt::Display for ContentName {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
-        let name: String = self.name[..(self.length as usize)]
+        let name: String = self.name[..self.char_len()]
             .iter()
             .map(|&c| char::from_u32(c).unwrap_or('\u{FFFD}'))
             .collect();
@@ -223,6 +412,30 @@ impl fmt::Display for ContentName {
     }
 }
 
+/// How two content names are compared when looking up or creating directory entries.
+/// Names are always stored as given, the policy only decides which names collide.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum NamePolicy {
+    /// Raw code point equality, "Photo.JPG" and "photo.jpg" are different entries.
+    #[default]
+    CaseSensitive,
+    /// Unicode lowercase equality, "Photo.JPG" and "photo.jpg" collide.
+    CaseInsensitive,
+    /// Equality after NFC normalization, a precomposed "é" and "e" + U+0301 collide.
+    Nfc,
+}
+
+impl NamePolicy {
+    /// Returns `true` if `a` and `b` name the same entry under this policy.
+    pub fn matches(&self, a: &str, b: &str) -> bool {
+        match self {
+            Self::CaseSensitive => a == b,
+            Self::CaseInsensitive => a.to_lowercase() == b.to_lowercase(),
+            Self::Nfc => a.nfc().eq(b.nfc()),
+        }
+    }
+}
+
 impl InodeDir {
     pub fn new(name: ContentName, timestamp: u64, size: u64, total_blocks: u64, content: Vec<DirContent>, linked: u64) -> Self {
         Self {
@@ -237,6 +450,27 @@ impl InodeDir {
         }
     }
 
+    /// Directory inode holding `contents`, one block like `RDFS::mkdir` makes, with the
+    /// entries past `max_content_pointers` split into an `InodeLinkedDir` chain whose links
+    /// are left 0, see `link_chain`. `size` counts every entry.
+    pub fn build(name: ContentName, timestamp: u64, contents: Vec<DirContent>, super_block: &SuperBlock) -> (InodeDir, Vec<InodeLinkedDir>) {
+        let size = contents.len() as u64;
+        let (content, chain) = split_content(contents, super_block);
+        let chain = chain.into_iter().map(|content| InodeLinkedDir::new(content, 0)).collect();
+        (InodeDir::new(name, timestamp, size, 1, content, 0), chain)
+    }
+
+    /// Links this inode and the `chain` built with it to the blocks allocated for the chain,
+    /// `pointers[i]` being where `chain[i]` is written. Fails with `LinkedChainMismatch` unless
+    /// there is exactly one pointer per linked block.
+    pub fn link_chain(&mut self, chain: &mut [InodeLinkedDir], pointers: &[u64]) -> Result<()> {
+        self.linked = link_pointers(chain.len(), pointers)?;
+        for (i, linked) in chain.iter_mut().enumerate() {
+            linked.linked = pointers.get(i + 1).copied().unwrap_or(0);
+        }
+        Ok(())
+    }
+
     /// signing algorithm is not included in the file system.
     /// add your signature after removing last 64 bytes and
     /// exchange it with your signature
@@ -244,6 +478,42 @@ impl InodeDir {
         self.signature = signature;
     }
 
+    /// Number of entries of the directory, including those stored in its `InodeLinkedDir` chain.
+    pub fn entry_count(&self) -> u64 {
+        self.size
+    }
+
+    /// Adds `entry` to this block if it holds less than `max_content_pointers` entries,
+    /// updating `size` and `modify`. A full block hands the entry back so the caller
+    /// can spill it into the `InodeLinkedDir` chain.
+    pub fn add_entry(&mut self, entry: DirContent, max_content_pointers: u64) -> Result<(), DirContent> {
+        if self.content.len() as u64 >= max_content_pointers {
+            return Err(entry);
+        }
+        self.content.push(entry);
+        self.size += 1;
+        if let Ok(time) = current_time_millis_as_u64() {
+            self.modify = time
+        }
+        Ok(())
+    }
+
+    /// Removes `entry` from this block, updating `size` and `modify`.
+    /// Returns `false` if the entry is not stored in this block.
+    pub fn remove_entry(&mut self, entry: &DirContent) -> bool {
+        match self.content.iter().position(|content| content == entry) {
+            Some(index) => {
+                self.content.remove(index);
+                self.size = self.size.saturating_sub(1);
+                if let Ok(time) = current_time_millis_as_u64() {
+                    self.modify = time
+                }
+                true
+            }
+            None => false,
+        }
+    }
+
     pub fn to_bytes(&self, block_size: usize) -> Vec<u8> {
         let mut encoded = Vec::with_capacity(block_size);
 
@@ -265,7 +535,11 @@ impl InodeDir {
 
     pub fn from_bytes(data: &[u8], block_size: usize) -> Result<Self> {
         if data.len() != block_size {
-            return Err(RDFSError::InvalidInodeBlockLength.into());
+            return Err(RDFSError::InvalidInodeBlockLength {
+                got: data.len(),
+                expected: block_size,
+            }
+            .into());
         }
 
         let name = ContentName::from_bytes(&data[..1024]);
@@ -277,13 +551,17 @@ impl InodeDir {
 
         let length = u64::from_le_bytes(data[1064..1072].try_into().unwrap()) as usize;
         if length > block_size - RESERVED_IB {
-            return Err(RDFSError::InvalidEncodedInodeBlockLength.into());
+            return Err(RDFSError::InvalidEncodedInodeBlockLength {
+                length,
+                max: block_size - RESERVED_IB,
+            }
+            .into());
         }
 
         let mut content = Vec::with_capacity(length);
         for i in 0..length {
             let start = 1072 + (i * CONTENT_SIZE);
-            content.push(DirContent::from_bytes(&data[start..start + CONTENT_SIZE]));
+            content.push(DirContent::from_bytes(&data[start..start + CONTENT_SIZE])?);
         }
         let signature: Signature = data[block_size - SIG_SIZE..].try_into().unwrap();
 
@@ -316,6 +594,28 @@ impl InodeLinkedDir {
         self.signature = signature;
     }
 
+    /// Adds `entry` to this block if it holds less than `max_linked_content_pointers` entries,
+    /// otherwise the entry is handed back so the caller can continue down the chain.
+    pub fn add_entry(&mut self, entry: DirContent, max_linked_content_pointers: u64) -> Result<(), DirContent> {
+        if self.content.len() as u64 >= max_linked_content_pointers {
+            return Err(entry);
+        }
+        self.content.push(entry);
+        Ok(())
+    }
+
+    /// Removes `entry` from this block.
+    /// Returns `false` if the entry is not stored in this block.
+    pub fn remove_entry(&mut self, entry: &DirContent) -> bool {
+        match self.content.iter().position(|content| content == entry) {
+            Some(index) => {
+                self.content.remove(index);
+                true
+            }
+            None => false,
+        }
+    }
+
     pub fn to_bytes(&self, block_size: usize) -> Vec<u8> {
         let mut encoded = Vec::with_capacity(block_size);
 
@@ -332,19 +632,27 @@ impl InodeLinkedDir {
 
     pub fn from_bytes(data: &[u8], block_size: usize) -> Result<Self> {
         if data.len() != block_size {
-            return Err(RDFSError::InvalidInodeBlockLength.into());
+            return Err(RDFSError::InvalidInodeBlockLength {
+                got: data.len(),
+                expected: block_size,
+            }
+            .into());
         }
         let linked = u64::from_le_bytes(data[..8].try_into().unwrap());
 
         let length = u64::from_le_bytes(data[8..16].try_into().unwrap()) as usize;
         if length > block_size - RESERVED_LIB {
-            return Err(RDFSError::InvalidEncodedInodeBlockLength.into());
+            return Err(RDFSError::InvalidEncodedInodeBlockLength {
+                length,
+                max: block_size - RESERVED_LIB,
+            }
+            .into());
         }
 
         let mut content = Vec::with_capacity(length);
         for i in 0..length {
             let start = 16 + (i * CONTENT_SIZE);
-            content.push(DirContent::from_bytes(&data[start..start + CONTENT_SIZE]));
+            content.push(DirContent::from_bytes(&data[start..start + CONTENT_SIZE])?);
         }
         let signature: Signature = data[block_size - SIG_SIZE..].try_into().unwrap();
 
@@ -362,10 +670,58 @@ impl InodeFile {
             total_blocks,
             content,
             linked,
+            redundancy: 0,
+            links: 1,
             signature: [0; SIG_SIZE],
         }
     }
 
+    /// File inode holding the ranges `contents`, `total_blocks` being their block count holes
+    /// included, with the ranges past `max_content_pointers` split into an `InodeLinkedFile`
+    /// chain whose links are left 0, see `link_chain`. `size` is 0, only the caller knows how
+    /// much of the last block is used.
+    pub fn build(name: ContentName, timestamp: u64, contents: Vec<FileContent>, super_block: &SuperBlock) -> (InodeFile, Vec<InodeLinkedFile>) {
+        let total_blocks = contents.iter().map(|range| range.blocks).sum();
+        let mut inode = InodeFile::new(name, timestamp, 0, total_blocks, vec![], 0);
+        let chain = inode.set_content(contents, super_block);
+        (inode, chain)
+    }
+
+    /// Replaces the ranges of the inode with `contents`, keeping the first `max_content_pointers`
+    /// and returning the rest as an unlinked `InodeLinkedFile` chain, see `link_chain`.
+    /// `total_blocks` and `size` are left to the caller.
+    pub fn set_content(&mut self, contents: Vec<FileContent>, super_block: &SuperBlock) -> Vec<InodeLinkedFile> {
+        let (content, chain) = split_content(contents, super_block);
+        self.content = content;
+        self.linked = 0;
+        chain.into_iter().map(|content| InodeLinkedFile::new(content, 0)).collect()
+    }
+
+    /// Links this inode and the `chain` built with it to the blocks allocated for the chain,
+    /// `pointers[i]` being where `chain[i]` is written. Fails with `LinkedChainMismatch` unless
+    /// there is exactly one pointer per linked block.
+    pub fn link_chain(&mut self, chain: &mut [InodeLinkedFile], pointers: &[u64]) -> Result<()> {
+        self.linked = link_pointers(chain.len(), pointers)?;
+        for (i, linked) in chain.iter_mut().enumerate() {
+            linked.linked = pointers.get(i + 1).copied().unwrap_or(0);
+        }
+        Ok(())
+    }
+
+    /// Redundancy the chunks of this file are encoded with, `drive_redundancy` unless overridden.
+    pub fn redundancy_or(&self, drive_redundancy: u64) -> u64 {
+        match self.redundancy {
+            0 => drive_redundancy,
+            redundancy => redundancy,
+        }
+    }
+
+    /// Checks the ranges of the inode block (not its `InodeLinkedFile` chain) with
+    /// `validate_file_ranges`.
+    pub fn validate_ranges(&self, super_block: &SuperBlock) -> Result<()> {
+        validate_file_ranges(&self.content, super_block)
+    }
+
     /// signing algorithm is not included in the file system.
     /// add your signature after removing last 64 bytes and
     /// exchange it with your signature
@@ -377,12 +733,14 @@ impl InodeFile {
         let mut encoded = Vec::with_capacity(block_size);
 
         encoded.extend_from_slice(&self.name.to_bytes());
+        encoded[2..4].copy_from_slice(&(self.links.min(u16::MAX as u64) as u16).to_le_bytes());
         encoded.extend_from_slice(&self.created.to_le_bytes());
         encoded.extend_from_slice(&self.modify.to_le_bytes());
         encoded.extend_from_slice(&self.size.to_le_bytes());
         encoded.extend_from_slice(&self.total_blocks.to_le_bytes());
         encoded.extend_from_slice(&self.linked.to_le_bytes());
-        encoded.extend_from_slice(&(self.content.len() as u64).to_le_bytes());
+        encoded.extend_from_slice(&(self.content.len() as u32).to_le_bytes());
+        encoded.extend_from_slice(&(self.redundancy as u32).to_le_bytes());
         for content in self.content.iter() {
             encoded.extend_from_slice(&content.to_bytes());
         }
@@ -394,19 +752,30 @@ impl InodeFile {
 
     pub fn from_bytes(data: &[u8], block_size: usize) -> Result<Self> {
         if data.len() != block_size {
-            return Err(RDFSError::InvalidInodeBlockLength.into());
+            return Err(RDFSError::InvalidInodeBlockLength {
+                got: data.len(),
+                expected: block_size,
+            }
+            .into());
         }
 
-        let name = ContentName::from_bytes(&data[..1024]);
+        let mut name = ContentName::from_bytes(&data[..1024]);
+        name.length &= 0xFFFF;
+        let links = u16::from_le_bytes(data[2..4].try_into().unwrap()).max(1) as u64;
         let created = u64::from_le_bytes(data[1024..1032].try_into().unwrap());
         let modify = u64::from_le_bytes(data[1032..1040].try_into().unwrap());
         let size = u64::from_le_bytes(data[1040..1048].try_into().unwrap());
         let total_blocks = u64::from_le_bytes(data[1048..1056].try_into().unwrap());
         let linked = u64::from_le_bytes(data[1056..1064].try_into().unwrap());
 
-        let length = u64::from_le_bytes(data[1064..1072].try_into().unwrap()) as usize;
+        let length = u32::from_le_bytes(data[1064..1068].try_into().unwrap()) as usize;
+        let redundancy = u32::from_le_bytes(data[1068..1072].try_into().unwrap()) as u64;
         if length > block_size - RESERVED_IB {
-            return Err(RDFSError::InvalidEncodedInodeBlockLength.into());
+            return Err(RDFSError::InvalidEncodedInodeBlockLength {
+                length,
+                max: block_size - RESERVED_IB,
+            }
+            .into());
         }
 
         let mut content = Vec::with_capacity(length);
@@ -424,6 +793,8 @@ impl InodeFile {
             total_blocks,
             content,
             linked,
+            redundancy,
+            links,
             signature,
         })
     }
@@ -461,13 +832,21 @@ impl InodeLinkedFile {
 
     pub fn from_bytes(data: &[u8], block_size: usize) -> Result<Self> {
         if data.len() != block_size {
-            return Err(RDFSError::InvalidInodeBlockLength.into());
+            return Err(RDFSError::InvalidInodeBlockLength {
+                got: data.len(),
+                expected: block_size,
+            }
+            .into());
         }
         let linked = u64::from_le_bytes(data[..8].try_into().unwrap());
 
         let length = u64::from_le_bytes(data[8..16].try_into().unwrap()) as usize;
         if length > block_size - RESERVED_LIB {
-            return Err(RDFSError::InvalidEncodedInodeBlockLength.into());
+            return Err(RDFSError::InvalidEncodedInodeBlockLength {
+                length,
+                max: block_size - RESERVED_LIB,
+            }
+            .into());
         }
 
         let mut content = Vec::with_capacity(length);
@@ -481,9 +860,64 @@ impl InodeLinkedFile {
     }
 }
 
+/// Splits a content list into what fits the inode block and the chunks of its linked blocks.
+fn split_content<T: Clone>(mut contents: Vec<T>, super_block: &SuperBlock) -> (Vec<T>, Vec<Vec<T>>) {
+    let overflow = contents.split_off(contents.len().min(super_block.max_content_pointers as usize));
+    let chain = overflow
+        .chunks(super_block.max_linked_content_pointers as usize)
+        .map(<[T]>::to_vec)
+        .collect();
+    (contents, chain)
+}
+
+/// The `linked` of an inode whose chain of `blocks` linked blocks is stored at `pointers`.
+fn link_pointers(blocks: usize, pointers: &[u64]) -> Result<u64> {
+    if pointers.len() != blocks {
+        return Err(RDFSError::LinkedChainMismatch {
+            blocks,
+            pointers: pointers.len(),
+        }
+        .into());
+    }
+    Ok(pointers.first().copied().unwrap_or(0))
+}
+
+/// Checks that the data block ranges `content` of a file are pairwise disjoint and that
+/// every range starts on a block boundary and ends within the `total_blocks` of the drive.
+/// Holes own no block and are skipped. Fails with `OverlappingFileContent` naming the first
+/// bad range.
+pub fn validate_file_ranges(content: &[FileContent], super_block: &SuperBlock) -> Result<()> {
+    let invalid = |range: &FileContent| RDFSError::OverlappingFileContent {
+        pointer: range.pointer,
+        blocks: range.blocks,
+    };
+
+    let mut spans = Vec::with_capacity(content.len());
+    for range in content.iter().filter(|range| !range.is_hole()) {
+        let offset = range.pointer.checked_sub(super_block.data_pointer).ok_or_else(|| invalid(range))?;
+        if !offset.is_multiple_of(super_block.block_size) {
+            return Err(invalid(range).into());
+        }
+        let first = offset / super_block.block_size;
+        match first.checked_add(range.blocks) {
+            Some(end) if end <= super_block.total_blocks => spans.push((first, end, range)),
+            _ => return Err(invalid(range).into()),
+        }
+    }
+
+    spans.sort_unstable_by_key(|(first, _, _)| *first);
+    for pair in spans.windows(2) {
+        if pair[1].0 < pair[0].1 {
+            return Err(invalid(pair[1].2).into());
+        }
+    }
+    Ok(())
+}
+
 #[cfg(test)]
 mod test {
     use super::*;
+    use crate::core::super_block::FileSystemType;
 
     #[test]
     fn test_inode() {
@@ -492,6 +926,7 @@ mod test {
         let content = DirContent {
             pointer: 3,
             inode_type: InodeType::Dir,
+            name: 0,
         };
         let mut inode = InodeDir::new(file_name.clone(), 7, 11, 1, vec![content.clone(), content], 0);
         inode.add_signature([255; 64]);
@@ -522,6 +957,9 @@ mod test {
         let content = FileContent { pointer: 3, blocks: 10 };
         let mut inode = InodeFile::new(file_name.clone(), 7, 11, 1, vec![content.clone(), content], 0);
         inode.add_signature([255; 64]);
+        assert_eq!(inode.redundancy_or(300), 300);
+        inode.redundancy = 500;
+        inode.links = 3;
 
         // Serialize the inode
         let serialized = inode.to_bytes(block_size);
@@ -540,6 +978,79 @@ mod test {
         assert_eq!(inode.content, deserialized.content);
         assert_eq!(inode.linked, deserialized.linked);
         assert_eq!(inode.signature, deserialized.signature);
+        assert_eq!(deserialized.redundancy_or(300), 500);
+        assert_eq!((deserialized.links, deserialized.name.length), (3, 13));
+
+        // inodes written before the override and the link count existed read back with the drive's and 1
+        let mut legacy = serialized;
+        legacy[1068..1072].fill(0);
+        legacy[2..4].fill(0);
+        let deserialized = InodeFile::from_bytes(&legacy, block_size).unwrap();
+        assert_eq!((deserialized.content.len(), deserialized.redundancy, deserialized.links), (2, 0, 1));
+    }
+
+    #[test]
+    fn test_dir_entries() {
+        let entry = |pointer| DirContent {
+            pointer,
+            inode_type: InodeType::File,
+            name: 0,
+        };
+        let mut inode = InodeDir::new(ContentName::new("dir"), 7, 0, 1, vec![], 0);
+
+        assert_eq!(inode.add_entry(entry(1), 2), Ok(()));
+        assert_eq!(inode.add_entry(entry(2), 2), Ok(()));
+        assert_eq!(inode.add_entry(entry(3), 2), Err(entry(3)));
+        assert_eq!(inode.size, 2);
+        assert!(inode.modify >= 7);
+
+        assert!(inode.remove_entry(&entry(1)));
+        assert!(!inode.remove_entry(&entry(1)));
+        assert_eq!(inode.content, vec![entry(2)]);
+        assert_eq!(inode.entry_count(), 1);
+        let decoded = InodeDir::from_bytes(&inode.to_bytes(4096), 4096).unwrap();
+        assert_eq!(decoded.entry_count(), 1);
+
+        let mut linked = InodeLinkedDir::new(vec![], 0);
+        assert_eq!(linked.add_entry(entry(4), 1), Ok(()));
+        assert_eq!(linked.add_entry(entry(5), 1), Err(entry(5)));
+        assert!(linked.remove_entry(&entry(4)));
+        assert!(linked.content.is_empty());
+    }
+
+    #[test]
+    fn test_inode_type() {
+        assert_eq!(InodeType::try_from(0).unwrap(), InodeType::Dir);
+        assert_eq!(InodeType::try_from(1).unwrap(), InodeType::File);
+        assert!(matches!(InodeType::try_from(42), Err(RDFSError::InvalidInodeType(42))));
+        assert_eq!(InodeType::from(42), InodeType::File);
+
+        let entry = DirContent {
+            pointer: 9,
+            inode_type: InodeType::File,
+            name: 0,
+        };
+        let inode = InodeDir::new(ContentName::new("dir"), 7, 0, 1, vec![entry.clone()], 0);
+        assert_eq!(DirContent::from_bytes(&entry.to_bytes()).unwrap(), entry);
+
+        // the name block of a link shares the type word, entries without one decode unchanged
+        let link = DirContent {
+            name: 1 << 40,
+            ..entry.clone()
+        };
+        assert_eq!(DirContent::from_bytes(&link.to_bytes()).unwrap(), link);
+        assert_eq!(link.to_bytes()[8], InodeType::File as u8);
+        assert_eq!(entry.to_bytes()[9..], [0; 7]);
+
+        // a bogus type byte in the first entry of a directory block
+        let mut data = inode.to_bytes(4096);
+        data[1072 + 8] = 42;
+        let error = InodeDir::from_bytes(&data, 4096).unwrap_err();
+        assert!(matches!(error.downcast_ref::<RDFSError>(), Some(RDFSError::InvalidInodeType(42))));
+
+        let mut linked = InodeLinkedDir::new(vec![entry], 0).to_bytes(4096);
+        linked[16 + 8] = 2;
+        assert!(InodeLinkedDir::from_bytes(&linked, 4096).is_err());
     }
 
     #[test]
@@ -559,4 +1070,182 @@ mod test {
         assert_eq!(linked_inode.linked, deserialized.linked);
         assert_eq!(linked_inode.signature, deserialized.signature);
     }
+
+    #[test]
+    fn test_content_name() {
+        let name = ContentName::try_new("تقرير 👍.txt").unwrap();
+        assert_eq!(name.char_len(), 11);
+        assert_eq!(name.as_string(), "تقرير 👍.txt");
+
+        let long = "a".repeat(256);
+        assert!(matches!(ContentName::try_new(&long), Err(RDFSError::NameTooLong)));
+        assert_eq!(ContentName::new(&long).char_len(), 255);
+        assert!(ContentName::try_new(&long[1..]).is_ok());
+
+        // names that can't be a path segment
+        for name in ["a/b", "/", "nul\0", "\0", "tab\there", "bell\u{7}", "del\u{7F}", "\u{85}next"] {
+            assert!(
+                matches!(ContentName::try_new(name), Err(RDFSError::InvalidName(invalid)) if invalid == name),
+                "{name:?}"
+            );
+        }
+        let mut surrogate = ContentName::new("x");
+        surrogate.name[0] = 0xD800;
+        assert!(surrogate.validate().is_err());
+        assert!(ContentName::try_new("..").is_ok());
+        assert!(ContentName::new("./").validate().is_err());
+
+        let lossy = ContentName::from_utf8_lossy(b"bad\xFFname");
+        assert_eq!(lossy.as_string(), "bad\u{FFFD}name");
+
+        // a corrupted length never reads past the name buffer
+        let mut corrupted = ContentName::new("x");
+        corrupted.length = 1000;
+        assert_eq!(corrupted.char_len(), 255);
+    }
+
+    #[test]
+    fn test_content_name_eq_hash() {
+        use std::collections::HashMap;
+
+        // same prefix, garbage past `length` as left by a hand built or corrupted name
+        let name = ContentName::new("report.txt");
+        let mut garbage = name.clone();
+        garbage.name[10..].fill(0xABCD);
+        assert_ne!(name.name, garbage.name);
+        assert_eq!(name, garbage);
+
+        let decoded = ContentName::from_bytes(&garbage.to_bytes());
+        let mut names = HashMap::new();
+        names.insert(name.clone(), 1);
+        assert_eq!(names.get(&decoded), Some(&1));
+
+        let mut shorter = garbage;
+        shorter.length = 6;
+        assert_ne!(name, shorter);
+        assert_eq!(shorter, ContentName::new("report"));
+    }
+
+    #[test]
+    fn test_name_policy() {
+        assert!(!NamePolicy::default().matches("Photo.JPG", "photo.jpg"));
+        assert!(NamePolicy::CaseInsensitive.matches("Photo.JPG", "photo.jpg"));
+        assert!(NamePolicy::CaseInsensitive.matches("ÉTÉ", "été"));
+        assert!(!NamePolicy::CaseSensitive.matches("caf\u{e9}", "cafe\u{301}"));
+        assert!(NamePolicy::Nfc.matches("caf\u{e9}", "cafe\u{301}"));
+        assert!(!NamePolicy::Nfc.matches("Cafe", "cafe"));
+    }
+
+    #[test]
+    fn validate_ranges_test() {
+        let system = SuperBlock::new(FileSystemType::Shared, [255; 32], [1; 32], 1048576, 100, 1, 4096).unwrap();
+        let block = |index: u64, blocks: u64| FileContent {
+            pointer: system.data_pointer + index * system.block_size,
+            blocks,
+        };
+        let mut inode = InodeFile::new(ContentName::new("file"), 0, 0, 0, vec![block(4, 2), FileContent::hole(3), block(0, 4)], 0);
+        assert!(inode.validate_ranges(&system).is_ok());
+
+        let last = system.total_blocks - 1;
+        let unaligned = FileContent {
+            pointer: system.data_pointer + 1,
+            blocks: 1,
+        };
+        let invalid = [
+            vec![block(0, 4), block(3, 1)],              // overlapping
+            vec![block(2, 1), block(2, 1)],              // the same block twice
+            vec![block(last, 2)],                        // past the last block
+            vec![block(0, u64::MAX)],                    // overflowing
+            vec![FileContent { pointer: 8, blocks: 1 }], // before the data blocks
+            vec![block(1, 1), unaligned],                // not on a block boundary
+        ];
+        for content in invalid {
+            let bad = content.last().unwrap().clone();
+            inode.content = content;
+            let error = inode.validate_ranges(&system).unwrap_err();
+            assert!(matches!(
+                error.downcast_ref::<RDFSError>(),
+                Some(RDFSError::OverlappingFileContent { pointer, blocks }) if *pointer == bad.pointer && *blocks == bad.blocks
+            ));
+        }
+    }
+
+    #[test]
+    fn build_test() {
+        let super_block = SuperBlock::new(FileSystemType::Shared, [255; 32], [1; 32], 4194304, 100, 1, 4096).unwrap();
+        let max = super_block.max_content_pointers as usize;
+        let max_linked = super_block.max_linked_content_pointers as usize;
+        let ranges: Vec<FileContent> = (0..max + max_linked + 3)
+            .map(|i| FileContent {
+                pointer: super_block.data_pointer + 2 * i as u64 * super_block.block_size,
+                blocks: 1,
+            })
+            .collect();
+
+        let (mut inode, mut chain) = InodeFile::build(ContentName::new("big.bin"), 7, ranges.clone(), &super_block);
+        assert_eq!((inode.created, inode.size, inode.total_blocks), (7, 0, ranges.len() as u64));
+        assert_eq!(inode.content, ranges[..max]);
+        assert_eq!(chain.len(), 2);
+        assert_eq!(chain[0].content, ranges[max..max + max_linked]);
+        assert_eq!(chain[1].content, ranges[max + max_linked..]);
+        assert!(inode.linked == 0 && chain.iter().all(|linked| linked.linked == 0));
+
+        let error = inode.link_chain(&mut chain, &[9]).unwrap_err();
+        assert!(matches!(
+            error.downcast_ref::<RDFSError>(),
+            Some(RDFSError::LinkedChainMismatch { blocks: 2, pointers: 1 })
+        ));
+        inode.link_chain(&mut chain, &[9, 5]).unwrap();
+        assert_eq!((inode.linked, chain[0].linked, chain[1].linked), (9, 5, 0));
+
+        // what fits the inode needs no chain
+        let (mut small, mut none) = InodeFile::build(ContentName::new("small.bin"), 7, ranges[..2].to_vec(), &super_block);
+        assert!(none.is_empty());
+        small.link_chain(&mut none, &[]).unwrap();
+        assert_eq!(small.linked, 0);
+
+        let entries: Vec<DirContent> = (0..max as u64 + 1)
+            .map(|pointer| DirContent {
+                pointer,
+                inode_type: InodeType::File,
+                name: 0,
+            })
+            .collect();
+        let (mut dir, mut chain) = InodeDir::build(ContentName::new("dir"), 7, entries.clone(), &super_block);
+        assert_eq!((dir.entry_count(), dir.content.len(), chain.len()), (max as u64 + 1, max, 1));
+        assert_eq!(chain[0].content, entries[max..]);
+        dir.link_chain(&mut chain, &[3]).unwrap();
+        assert_eq!((dir.linked, chain[0].linked), (3, 0));
+    }
+
+    #[test]
+    fn content_name_match_test() {
+        let name = ContentName::new("日本語_report_📊.txt");
+        assert!(name.starts_with("日本語") && name.starts_with("") && name.starts_with("日本語_report_📊.txt"));
+        assert!(!name.starts_with("本") && !name.starts_with("日本語_report_📊.txt!"));
+        assert!(name.contains("📊") && name.contains("report") && name.contains(".txt") && name.contains(""));
+        assert!(!name.contains("📈") && !name.contains("txt.") && !name.contains("Report"));
+
+        for pattern in [
+            "*",
+            "*.txt",
+            "日本語*",
+            "*📊*",
+            "日本語_??????_?.txt",
+            "*_*_*",
+            "**.txt",
+            "日*語_report_📊.txt",
+        ] {
+            assert!(name.matches_glob(pattern), "{pattern}");
+        }
+        for pattern in ["", "*.TXT", "日本語", "?日本語*", "*📈*", "日本語_??????_??.txt", "*_*_*_*"] {
+            assert!(!name.matches_glob(pattern), "{pattern}");
+        }
+
+        // only the stored characters take part, not what is left in the buffer
+        let mut short = ContentName::new("ab👍cd");
+        short.set("a👍");
+        assert!(short.matches_glob("a?") && !short.contains("cd") && !short.starts_with("a👍c"));
+        assert!(ContentName::new("").matches_glob("*") && !ContentName::new("").matches_glob("?"));
+    }
 }
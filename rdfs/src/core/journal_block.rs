@@ -0,0 +1,233 @@
+//! # RDFS JournalBlock Module
+//!
+//! This module defines the `JournalBlock` structure, a write-ahead record used by
+//! `RDFS::write_journaled` to update several regions of a drive (super block,
+//! bitmaps block, inodes...) as a single step that survives a crash. A transaction
+//! (`RDFS::begin_txn`) stages the writes of whole operations like `create_file` and
+//! `delete_file` and commits them as one record.
+//!
+//! A record only holds intents: the bytes a write changes, not the regions it was
+//! given. Each write is split at the block boundaries and cut down to its runs of
+//! changed bytes with `changed_runs`, so allocating a block journals a few bytes of the
+//! bitmaps block and refcount table, and a new inode the used part of its block.
+//!
+//! ## Log
+//! The journal is an append-only log right after the drive layout, at
+//! `SuperBlock::node_storage`, so it never shifts the data blocks and drives without a
+//! journal stay valid. Each record follows the previous one with the next `sequence`; a
+//! record that would end past `JOURNAL_LOG_SIZE` starts the log over at its beginning,
+//! every record before it being applied already. Reading the log stops at the first
+//! record that is torn or doesn't continue the sequence, like one left from a previous
+//! round of the log.
+//!
+//! ## Recovery Rule
+//! 1. The record is appended as `Pending` and synced before any target is touched.
+//! 2. The targets are written and synced, then the record is marked `Applied`.
+//! 3. On mount a `Pending` last record with a valid checksum is replayed, writes are
+//!    idempotent so replaying a record that was partially applied is safe.
+//! 4. A record with a bad checksum was torn while being written, none of its
+//!    targets were touched yet, so it is ignored and the next record takes its place.
+//!
+//! ## Encoding Layout
+//! ```text
+//! [8 bytes: magic "RDFS-JNL"]
+//! [8 bytes: sequence]        monotonically increasing across records
+//! [8 bytes: state]           0 = applied, 1 = pending
+//! [8 bytes: payload length]
+//! [4 bytes: CRC32]         over sequence, payload length and payload, not the state
+//! [4 bytes: padding]
+//! [payload: per entry [8 bytes: pointer][8 bytes: length][length bytes: data]]
+//! ```
+//!
+//! Copyrights © 2025 RDFS Contributors. All rights reserved.
+
+pub const JOURNAL_MAGIC: &[u8; 8] = b"RDFS-JNL";
+pub const JOURNAL_HEADER_SIZE: usize = 40;
+pub const JOURNAL_ENTRY_HEADER_SIZE: usize = 16;
+pub const JOURNAL_LOG_SIZE: u64 = 64 * 1024; // bytes the log may take before it starts over
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum JournalState {
+    Applied = 0,
+    Pending = 1,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct JournalBlock {
+    pub sequence: u64,
+    pub state: JournalState,
+    pub entries: Vec<(u64, Vec<u8>)>, // (drive pointer, data) written in order
+}
+
+impl JournalBlock {
+    pub fn new(sequence: u64, entries: Vec<(u64, Vec<u8>)>) -> Self {
+        Self {
+            sequence,
+            state: JournalState::Pending,
+            entries,
+        }
+    }
+
+    /// Size of the payload following the header.
+    pub fn payload_len(&self) -> u64 {
+        self.entries.iter().map(|(_, data)| (JOURNAL_ENTRY_HEADER_SIZE + data.len()) as u64).sum()
+    }
+
+    /// Size of the whole encoded record.
+    pub fn encoded_len(&self) -> u64 {
+        JOURNAL_HEADER_SIZE as u64 + self.payload_len()
+    }
+
+    /// Encodes the header only, used to flip the state of a record already on disk.
+    pub fn header_bytes(&self) -> Vec<u8> {
+        let mut encoded = Vec::with_capacity(JOURNAL_HEADER_SIZE);
+        encoded.extend_from_slice(JOURNAL_MAGIC);
+        encoded.extend_from_slice(&self.sequence.to_le_bytes());
+        encoded.extend_from_slice(&(self.state as u64).to_le_bytes());
+        encoded.extend_from_slice(&self.payload_len().to_le_bytes());
+        encoded.extend_from_slice(&self.checksum().to_le_bytes());
+        encoded.resize(JOURNAL_HEADER_SIZE, 0);
+        encoded
+    }
+
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut encoded = self.header_bytes();
+        encoded.extend_from_slice(&self.payload());
+        encoded
+    }
+
+    /// Reads the payload length from an encoded header, `None` if `header` is not a journal record.
+    pub fn payload_len_from_header(header: &[u8]) -> Option<u64> {
+        if header.len() < JOURNAL_HEADER_SIZE || &header[..8] != JOURNAL_MAGIC {
+            return None;
+        }
+        Some(u64::from_le_bytes(header[24..32].try_into().unwrap()))
+    }
+
+    /// Reads the sequence from an encoded header, even when the record was torn.
+    pub fn sequence_from_header(header: &[u8]) -> Option<u64> {
+        Self::payload_len_from_header(header)?;
+        Some(u64::from_le_bytes(header[8..16].try_into().unwrap()))
+    }
+
+    /// Decodes a whole record (header and payload). Returns `None` when there is no
+    /// record or when it was torn (bad checksum or truncated payload).
+    pub fn from_bytes(data: &[u8]) -> Option<Self> {
+        let payload_len = Self::payload_len_from_header(data)?;
+        if (data.len() as u64) < JOURNAL_HEADER_SIZE as u64 + payload_len {
+            return None;
+        }
+
+        let sequence = u64::from_le_bytes(data[8..16].try_into().unwrap());
+        let state = match u64::from_le_bytes(data[16..24].try_into().unwrap()) {
+            0 => JournalState::Applied,
+            _ => JournalState::Pending,
+        };
+        let checksum = u32::from_le_bytes(data[32..36].try_into().unwrap());
+
+        let payload = &data[JOURNAL_HEADER_SIZE..JOURNAL_HEADER_SIZE + payload_len as usize];
+        let mut entries = Vec::new();
+        let mut offset = 0;
+        while offset < payload.len() {
+            let header = payload.get(offset..offset + 16)?;
+            let pointer = u64::from_le_bytes(header[..8].try_into().unwrap());
+            let length = u64::from_le_bytes(header[8..16].try_into().unwrap()) as usize;
+            let entry = payload.get(offset + 16..(offset + 16).saturating_add(length))?;
+            entries.push((pointer, entry.to_vec()));
+            offset += 16 + length;
+        }
+
+        let block = Self { sequence, state, entries };
+        (block.checksum() == checksum).then_some(block)
+    }
+
+    /// CRC32 over everything but the state, so flipping a record to `Applied` keeps it valid.
+    fn checksum(&self) -> u32 {
+        let mut hasher = crc32fast::Hasher::new();
+        hasher.update(&self.sequence.to_le_bytes());
+        hasher.update(&self.payload_len().to_le_bytes());
+        hasher.update(&self.payload());
+        hasher.finalize()
+    }
+
+    fn payload(&self) -> Vec<u8> {
+        let mut payload = Vec::with_capacity(self.payload_len() as usize);
+        for (pointer, data) in &self.entries {
+            payload.extend_from_slice(&pointer.to_le_bytes());
+            payload.extend_from_slice(&(data.len() as u64).to_le_bytes());
+            payload.extend_from_slice(data);
+        }
+        payload
+    }
+}
+
+/// The runs of `new` that differ from `old`, the bytes it replaces at `pointer`, as
+/// (pointer, data) entries. Runs closer than an entry header are merged since a separate
+/// entry would take more room, an unchanged write gives none.
+pub fn changed_runs(pointer: u64, old: &[u8], new: &[u8]) -> Vec<(u64, Vec<u8>)> {
+    let differs = |index: usize| old.get(index) != Some(&new[index]);
+    let mut runs = Vec::new();
+    let mut index = 0;
+    while index < new.len() {
+        if !differs(index) {
+            index += 1;
+            continue;
+        }
+        let start = index;
+        let mut end = index + 1;
+        let mut next = end;
+        while next < new.len() && next - end < JOURNAL_ENTRY_HEADER_SIZE {
+            if differs(next) {
+                end = next + 1;
+            }
+            next += 1;
+        }
+        runs.push((pointer + start as u64, new[start..end].to_vec()));
+        index = end;
+    }
+    runs
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn journal_block_test() {
+        let mut block = JournalBlock::new(7, vec![(0, vec![1, 2, 3]), (4096, vec![9; 100])]);
+        let encoded = block.to_bytes();
+        assert_eq!(encoded.len() as u64, JOURNAL_HEADER_SIZE as u64 + block.payload_len());
+        assert_eq!(JournalBlock::from_bytes(&encoded), Some(block.clone()));
+
+        // flipping the state keeps the record valid
+        block.state = JournalState::Applied;
+        let mut applied = block.header_bytes();
+        applied.extend_from_slice(&encoded[JOURNAL_HEADER_SIZE..]);
+        assert_eq!(JournalBlock::from_bytes(&applied).unwrap().state, JournalState::Applied);
+
+        // torn or missing records are ignored
+        let mut torn = encoded.clone();
+        torn[JOURNAL_HEADER_SIZE + 20] ^= 0xFF;
+        assert_eq!(JournalBlock::from_bytes(&torn), None);
+        assert_eq!(JournalBlock::sequence_from_header(&torn), Some(7));
+        assert_eq!(JournalBlock::from_bytes(&encoded[..encoded.len() - 1]), None);
+        assert_eq!(JournalBlock::from_bytes(&[0; JOURNAL_HEADER_SIZE]), None);
+    }
+
+    #[test]
+    fn changed_runs_test() {
+        let old = vec![0u8; 100];
+        assert!(changed_runs(1000, &old, &old).is_empty());
+
+        // close changes share an entry, distant ones get their own
+        let mut new = old.clone();
+        new[10] = 1;
+        new[20] = 2;
+        new[90] = 3;
+        let runs = changed_runs(1000, &old, &new);
+        assert_eq!(runs, vec![(1010, new[10..21].to_vec()), (1090, vec![3])]);
+
+        // bytes past the end of `old` always count as changed
+        assert_eq!(changed_runs(0, &[5], &[5, 0, 0]), vec![(1, vec![0, 0])]);
+    }
+}
@@ -2,5 +2,8 @@ pub mod addresses_block;
 pub mod bitmaps_block;
 pub mod block_signature;
 pub mod data_block;
+pub mod erasure_codec;
 pub mod inode_block;
+pub mod journal_block;
+pub mod refcount_block;
 pub mod super_block;
@@ -0,0 +1,193 @@
+//! # RDFS RefCountBlock Module
+//!
+//! This module defines the `RefCountBlock` structure, an optional region of shared RDFS
+//! drives stored right after the `BitmapsBlock`. It keeps a `u16` reference count per
+//! block so data blocks can be shared between files (reflinks, snapshots) and are only
+//! freed once the last file referencing them is gone.
+//!
+//! ## Layout
+//! The layout of a serialized `RefCountBlock` is:
+//! ```text
+//! [8 bytes: total_blocks]
+//! [8 bytes: last_modify_timestamp, milliseconds]
+//! [2 * total_blocks bytes: counts]
+//! ```
+//!
+//! ## Rules
+//! - A used block has a count of at least 1, a free block a count of 0
+//! - The table follows the bitmaps block, see `sync_with`: newly allocated blocks start
+//!   at 1 and freed blocks drop to 0, so allocation paths only deal with the bitmap
+//! - Only sharing changes a count explicitly: `increment` for a new referent and
+//!   `decrement` when one goes away, the bitmap bit is cleared once it reaches 0
+//!
+//! Copyrights © 2025 RDFS Contributors. All rights reserved.
+
+use super::super::constants::{REFCOUNT_SIZE, RESERVED_RB};
+use super::super::rdfs_errors::RDFSError;
+use super::super::utils::current_time_millis_as_u64;
+use super::bitmaps_block::BitmapsBlock;
+use anyhow::Result;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RefCountBlock {
+    // 16 + 2 * total_blocks bytes
+    pub total_blocks: u64, // Total number of blocks in the filesystem
+    pub last_modify: u64,  // Timestamp of the last modification
+    pub counts: Vec<u16>,  // Reference count of every block, by block index
+}
+
+impl RefCountBlock {
+    /// Creates a new RefCountBlock with every count set to 0.
+    pub fn new(total_blocks: u64, timestamp: u64) -> Self {
+        Self {
+            total_blocks,
+            last_modify: timestamp,
+            counts: vec![0; total_blocks as usize],
+        }
+    }
+
+    /// Returns the count of the block at `index`, 0 outside the table.
+    pub fn get(&self, index: u64) -> u16 {
+        self.counts.get(index as usize).copied().unwrap_or(0)
+    }
+
+    /// Adds a referent to the block at `index` and returns the new count.
+    /// Fails with `RefCountOverflow` without changing the count if it is already `u16::MAX`.
+    pub fn increment(&mut self, index: u64) -> Result<u16> {
+        let count = self.count_mut(index)?;
+        *count = count.checked_add(1).ok_or(RDFSError::RefCountOverflow(index))?;
+        let count = *count;
+        self.touch();
+        Ok(count)
+    }
+
+    /// Removes a referent from the block at `index` and returns the new count, the block
+    /// is free once it reaches 0. Fails with `RefCountUnderflow` if it is already 0.
+    pub fn decrement(&mut self, index: u64) -> Result<u16> {
+        let count = self.count_mut(index)?;
+        *count = count.checked_sub(1).ok_or(RDFSError::RefCountUnderflow(index))?;
+        let count = *count;
+        self.touch();
+        Ok(count)
+    }
+
+    /// Aligns the counts with `bitmaps`: used blocks counted 0 were allocated since the
+    /// last sync and get 1, free blocks still counted were freed and get 0.
+    /// Returns `true` if any count changed.
+    pub fn sync_with(&mut self, bitmaps: &BitmapsBlock) -> bool {
+        let mut changed = false;
+        for (index, count) in self.counts.iter_mut().enumerate() {
+            match (bitmaps.get_bit(index), *count) {
+                (true, 0) => *count = 1,
+                (false, 1..) => *count = 0,
+                _ => continue,
+            }
+            changed = true;
+        }
+        if changed {
+            self.touch();
+        }
+        changed
+    }
+
+    /// Serialize the whole table to bytes.
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut encoded = Vec::with_capacity(RESERVED_RB + REFCOUNT_SIZE * self.counts.len());
+
+        encoded.extend_from_slice(&self.total_blocks.to_le_bytes());
+        encoded.extend_from_slice(&self.last_modify.to_le_bytes());
+        for count in &self.counts {
+            encoded.extend_from_slice(&count.to_le_bytes());
+        }
+
+        encoded
+    }
+
+    /// Deserialize a RefCountBlock from raw bytes.
+    pub fn from_bytes(data: &[u8], refcount_size: usize) -> Result<Self> {
+        if data.len() != refcount_size || data.len() < RESERVED_RB {
+            return Err(RDFSError::InvalidRefCountBlockLength {
+                got: data.len(),
+                expected: refcount_size,
+            }
+            .into());
+        }
+
+        let total_blocks = u64::from_le_bytes(data[..8].try_into().unwrap());
+        let last_modify = u64::from_le_bytes(data[8..16].try_into().unwrap());
+        let expected = (total_blocks as usize).saturating_mul(REFCOUNT_SIZE).saturating_add(RESERVED_RB);
+        if expected != refcount_size {
+            return Err(RDFSError::InvalidRefCountBlockLength {
+                got: expected,
+                expected: refcount_size,
+            }
+            .into());
+        }
+
+        let counts = data[RESERVED_RB..]
+            .chunks_exact(REFCOUNT_SIZE)
+            .map(|count| u16::from_le_bytes(count.try_into().unwrap()))
+            .collect();
+
+        Ok(Self {
+            total_blocks,
+            last_modify,
+            counts,
+        })
+    }
+
+    fn count_mut(&mut self, index: u64) -> Result<&mut u16> {
+        Ok(self.counts.get_mut(index as usize).ok_or(RDFSError::RefCountIndexOutOfRange(index))?)
+    }
+
+    fn touch(&mut self) {
+        if let Ok(time) = current_time_millis_as_u64() {
+            self.last_modify = time
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn refcount_block_test() {
+        let mut block = RefCountBlock::new(64, 0);
+        assert_eq!(block.increment(3).unwrap(), 1);
+        assert_eq!(block.increment(3).unwrap(), 2);
+        assert_eq!(block.decrement(3).unwrap(), 1);
+
+        let encoded = block.to_bytes();
+        assert_eq!(encoded.len(), RESERVED_RB + 2 * 64);
+        assert_eq!(RefCountBlock::from_bytes(&encoded, encoded.len()).unwrap(), block);
+        assert!(RefCountBlock::from_bytes(&encoded[..encoded.len() - 2], encoded.len()).is_err());
+        assert!(RefCountBlock::from_bytes(&encoded, encoded.len() + 2).is_err());
+
+        // the table follows the bitmap
+        let mut bitmaps = BitmapsBlock::new(64, 0);
+        bitmaps.set_bit(5);
+        assert!(block.sync_with(&bitmaps));
+        assert_eq!((block.get(3), block.get(5)), (0, 1));
+        assert!(!block.sync_with(&bitmaps));
+    }
+
+    #[test]
+    fn refcount_overflow_underflow_test() {
+        let mut block = RefCountBlock::new(8, 0);
+
+        let error = block.decrement(2).unwrap_err();
+        assert!(matches!(error.downcast_ref::<RDFSError>(), Some(RDFSError::RefCountUnderflow(2))));
+        assert_eq!(block.get(2), 0);
+
+        block.counts[2] = u16::MAX;
+        let error = block.increment(2).unwrap_err();
+        assert!(matches!(error.downcast_ref::<RDFSError>(), Some(RDFSError::RefCountOverflow(2))));
+        assert_eq!(block.get(2), u16::MAX);
+        assert_eq!(block.decrement(2).unwrap(), u16::MAX - 1);
+
+        let error = block.increment(8).unwrap_err();
+        assert!(matches!(error.downcast_ref::<RDFSError>(), Some(RDFSError::RefCountIndexOutOfRange(8))));
+        assert_eq!(block.get(8), 0);
+    }
+}
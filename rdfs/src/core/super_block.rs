@@ -23,25 +23,63 @@
 //!
 //! ## Key Fields
 //! - `magic`: Distinguishes between Shared and Private drives
-//! - `inode_pointer`: Last block reserved for the root inode directory
+//! - `version`: On-disk format version, drives with an unknown version are rejected.
+//!   Version 2 added `refcount_pointer`/`refcount_size`, version 3 `snapshot_pointer` and
+//!   version 4 the byte order mark, version 5 `flags`, version 6 `codec`, version 7 the
+//!   checksum and version 8 a root inode that stays put on resize (same layout as version 7),
+//!   older drives still mount with their shorter super block
+//! - `flags`: drive wide options, `SB_FLAG_ENCRYPTED` marks a private drive whose data
+//!   block payloads are encrypted (see `DataBlock::encrypt`)
+//! - `codec`: `ErasureCodec::ID` of the codec client blocks are encoded with, RaptorQ for
+//!   every drive older than version 6
+//! - byte order mark: every field is little endian, a super block written or read with the
+//!   other byte order fails with `InvalidByteOrder` instead of yielding garbage fields
+//! - `inode_pointer`: Block of the root inode directory, the last one of a new drive. Before
+//!   version 8 it is always the last block and `RDFS::resize_drive` moves the root there,
+//!   from version 8 it is a stored pointer that keeps its block index (see `has_stable_root`)
 //! - `signature`: Allows the entire super block to be signed/verified externally
+//! - checksum (version 7 and later): CRC32 of every other byte of the encoded super block,
+//!   computed by `to_bytes` and checked by `from_bytes`, a mismatch fails with `CorruptSuperBlock`
+//!
+//! ## Backup Copy
+//! A drive file of version 7 or later ends with a copy of the super block, after the layout and the
+//! journal: the last `SB_SIZE` bytes of the file, whatever its length, so it can be found
+//! without trusting the primary. `RDFS::mount_drive` falls back to it when the primary
+//! fails its checksum or `validate`, and `RDFS::repair_superblock` rewrites whichever copy
+//! is damaged from the other. Every `RDFS` write of the super block refreshes the backup.
+//!
+//! ## Chunk Placement
+//! A logical block of `client_block_size` bytes is RaptorQ encoded into
+//! `ceil(source_symbols * redundancy / 100)` chunks, about one per node. Every node keeps
+//! its chunks at the same block index, and chunk `c` of logical block `b` lives on node
+//! `(b + c) % nodes`, so the placement rotates by one node per block and the source chunks
+//! are not all held by the first nodes. With more chunks than nodes the round-robin wraps
+//! and a node holds every `nodes`-th chunk of the block. `chunk_assignment` and
+//! `blocks_for_node` compute both directions of this mapping, node indices are the slots
+//! of the `AddressesBlock`.
 //!
 //! Copyrights © 2025 RDFS Contributors. All rights reserved.
 
 use super::super::constants::{
-    Address, CONTENT_SIZE, FS_MAGIC_PRIVATE, FS_MAGIC_SHARED, PK_SIZE, RESERVED_AB, RESERVED_BB, RESERVED_CDB, RESERVED_IB, RESERVED_LIB, SB_SIZE,
-    Signature,
+    AEAD_NONCE_SIZE, AEAD_TAG_SIZE, Address, BYTE_ORDER_MARK, CONTENT_SIZE, FS_MAGIC_PRIVATE, FS_MAGIC_SHARED, MAX_BLOCK_SIZE, PK_SIZE,
+    REFCOUNT_SIZE, RESERVED_AB, RESERVED_BB, RESERVED_CDB, RESERVED_DB, RESERVED_IB, RESERVED_LIB, RESERVED_RB, SB_FLAG_ENCRYPTED, SB_SIZE,
+    SB_SIZE_V1, SB_SIZE_V2, SB_SIZE_V3, SB_SIZE_V4, SB_SIZE_V5, SB_SIZE_V6, SB_VERSION, SIG_SIZE, Signature,
 };
+use super::super::rdfs_errors::RDFSError;
+use super::super::utils::{bytes_to_hex, format_bytes};
+use super::erasure_codec::{ErasureCodec, RaptorQ, SUPPORTED_CODECS};
 use anyhow::{Result, anyhow};
 use core::f64::math::{ceil, floor};
-use super::super::rdfs_errors::RDFSError;
+use std::fmt;
 
 /// Represents the SuperBlock — the root metadata structure of the file system.
 /// Stores info about storage, nodes, block layout, some pointer and signature.
-#[derive(Debug, Clone)]
+/// Equality compares every field, the signature included.
+#[derive(Debug, Clone, PartialEq, Eq)]
 pub struct SuperBlock {
-    // 256 bytes
+    // 298 bytes for version 7 and later, 294 bytes for version 6, 292 bytes for version 5, 284 bytes for version 4, 282 bytes for version 3, 274 bytes for version 2 and 258 bytes for version 1
     pub magic: FileSystemType, // Magic word identifies the filesystem b"RDFS-***"
+    pub version: u16,          // On-disk format version of the super block layout
     pub owner: Address,        // Owner of the filesystem, usually the creator's public key
     pub program_id: Address,   // ID of the program that created the filesystem
     pub storage: u64,          // Total storage size in bytes
@@ -56,19 +94,34 @@ pub struct SuperBlock {
     pub nodes_address_pointer: u64, // Pointer to the nodes address list
     pub bitmaps_pointer: u64,       // Pointer to the bitmaps
     pub data_pointer: u64,          // pointer to first data block
-    pub inode_pointer: u64,         // Pointer to the inode table "root directory" (last block in the file system)
+    pub inode_pointer: u64,         // Pointer to the inode table "root directory" (last block before version 8, see `has_stable_root`)
 
     pub nodes_address_size: u64,          // size in bytes starting from address pointer
     pub bitmaps_size: u64,                // size in bytes starting from bitmaps pointer
     pub max_content_pointers: u64,        // Maximum number of pointers inside inode table points to other blocks
     pub max_linked_content_pointers: u64, // Maximum number of pointers inside linked inode table points to other blocks
+    pub refcount_pointer: u64,            // Pointer to the optional `RefCountBlock`, 0 when the drive has none
+    pub refcount_size: u64,               // size in bytes starting from refcount pointer, 0 when the drive has none
+    pub snapshot_pointer: u64,            // Pointer to the `InodeDir` listing the snapshots, 0 until the first `RDFS::snapshot`
+    pub flags: u64,                       // `SB_FLAG_*` bits, 0 before version 5
+    pub codec: u16,                       // `ErasureCodec::ID` client blocks are encoded with, 0 (RaptorQ) before version 6
 
     pub signature: Signature, // Signature for the block, used for verification and proof of spacetime
 }
 
 impl SuperBlock {
-    /// used for the first time when creating new virtual drive
-    pub fn new(magic: FileSystemType, owner: Address, program_id: Address, storage: u64, redundancy: u64, nodes: u64, block_size: u64) -> Self {
+    /// used for the first time when creating new virtual drive, fails with `DriveTooSmall`
+    /// when the storage left to each node after its metadata can't hold a single block and
+    /// with `InvalidBlockSize` unless `check_block_size` accepts `block_size`.
+    pub fn new(
+        magic: FileSystemType,
+        owner: Address,
+        program_id: Address,
+        storage: u64,
+        redundancy: u64,
+        nodes: u64,
+        block_size: u64,
+    ) -> Result<Self> {
         match magic {
             FileSystemType::Shared => Self::new_shared(magic, owner, program_id, storage, redundancy, nodes, block_size),
             FileSystemType::Private => Self::new_private(magic, owner, program_id, storage, redundancy, nodes, block_size),
@@ -83,7 +136,145 @@ impl SuperBlock {
         redundancy: u64,
         nodes: u64,
         block_size: u64,
-    ) -> Self {
+    ) -> Result<Self> {
+        Self::check_block_size(block_size)?;
+        Self::shared_layout(magic, owner, program_id, storage, redundancy, nodes, block_size, SB_VERSION, false)
+    }
+
+    /// The same drive with a `RefCountBlock` region right after the bitmaps block, taking
+    /// `REFCOUNT_SIZE` bytes per block out of the data blocks. Private drives and version 1
+    /// drives have no refcount table and are returned unchanged.
+    pub fn with_refcounts(self) -> Result<Self> {
+        if self.magic == FileSystemType::Private || self.version == 1 {
+            return Ok(self);
+        }
+        let Self {
+            magic,
+            owner,
+            program_id,
+            storage,
+            redundancy,
+            nodes,
+            block_size,
+            version,
+            ..
+        } = self;
+        Self::shared_layout(magic, owner, program_id, storage, redundancy, nodes, block_size, version, true)
+    }
+
+    /// The same private drive with `SB_FLAG_ENCRYPTED` set, its data block payloads lose
+    /// `AEAD_NONCE_SIZE` bytes to the nonce and `AEAD_TAG_SIZE` to the authentication tag. Shared drives and drives older than
+    /// version 5 are returned unchanged.
+    pub fn with_encryption(self) -> Self {
+        if self.magic == FileSystemType::Shared || self.version < 5 {
+            return self;
+        }
+        Self {
+            flags: self.flags | SB_FLAG_ENCRYPTED,
+            ..self
+        }
+    }
+
+    /// The same shared drive with its client blocks encoded by `C`, see `DataBlock::encode_with_codec`.
+    /// Private drives and drives older than version 6 are returned unchanged.
+    pub fn with_codec<C: ErasureCodec>(self) -> Self {
+        if self.magic == FileSystemType::Private || self.version < 6 {
+            return self;
+        }
+        Self { codec: C::ID, ..self }
+    }
+
+    /// Returns `true` if the data block payloads of the drive are encrypted.
+    pub fn is_encrypted(&self) -> bool {
+        self.flags & SB_FLAG_ENCRYPTED != 0
+    }
+
+    /// The layout of this drive recomputed for `storage` bytes, keeping its version, flags, codec and refcount table.
+    /// `snapshot_pointer` is kept as is, it is up to the caller to move it with the blocks.
+    /// A stable root (see `has_stable_root`) keeps its block index, any other one is the last block.
+    pub fn resized(&self, storage: u64) -> Result<Self> {
+        let resized = match self.magic {
+            FileSystemType::Shared => Self::shared_layout(
+                self.magic,
+                self.owner,
+                self.program_id,
+                storage,
+                self.redundancy,
+                self.nodes,
+                self.block_size,
+                self.version,
+                self.has_refcounts(),
+            ),
+            FileSystemType::Private => Self::private_layout(
+                self.magic,
+                self.owner,
+                self.program_id,
+                storage,
+                self.redundancy,
+                self.nodes,
+                self.block_size,
+                self.version,
+            ),
+        }?;
+        let inode_pointer = match self.block_index(self.inode_pointer) {
+            Ok(index) if self.has_stable_root() && index < resized.total_blocks => resized.block_pointer(index)?,
+            _ => resized.inode_pointer,
+        };
+        Ok(Self {
+            inode_pointer,
+            snapshot_pointer: self.snapshot_pointer,
+            flags: self.flags,
+            codec: self.codec,
+            ..resized
+        })
+    }
+
+    /// Returns `true` if the drive keeps a `RefCountBlock`.
+    pub fn has_refcounts(&self) -> bool {
+        self.refcount_size != 0
+    }
+
+    /// Returns `true` if the drive file ends with a backup copy of the super block, version 7 and later.
+    pub fn has_backup(&self) -> bool {
+        self.version >= 7
+    }
+
+    /// Returns `true` if `inode_pointer` is stored rather than derived, version 8 and later:
+    /// the root inode may sit in any data block and keeps its block index when the drive grows.
+    pub fn has_stable_root(&self) -> bool {
+        self.magic == FileSystemType::Shared && self.version >= 8
+    }
+
+    /// Bytes the drive file needs past the layout for the super block backup, 0 without one.
+    pub fn backup_size(&self) -> u64 {
+        if self.has_backup() { SB_SIZE as u64 } else { 0 }
+    }
+
+    /// Size of the encoded super block of `version`.
+    pub fn encoded_size(version: u16) -> usize {
+        match version {
+            1 => SB_SIZE_V1,
+            2 => SB_SIZE_V2,
+            3 => SB_SIZE_V3,
+            4 => SB_SIZE_V4,
+            5 => SB_SIZE_V5,
+            6 => SB_SIZE_V6,
+            _ => SB_SIZE,
+        }
+    }
+
+    #[allow(clippy::too_many_arguments)]
+    fn shared_layout(
+        magic: FileSystemType,
+        owner: Address,
+        program_id: Address,
+        storage: u64,
+        redundancy: u64,
+        nodes: u64,
+        block_size: u64,
+        version: u16,
+        refcounts: bool,
+    ) -> Result<Self> {
         // block_size - (signature + block_number + timestamp + data length + packet number "RaptorQ first 4 bytes")
         let block_size_for_data = block_size - (RESERVED_CDB as u64);
         let redundancy_ratio = redundancy as f64 / 100.0;
@@ -101,33 +292,54 @@ impl SuperBlock {
         // ------------- corrected values to make each byte in bitmap point to 8 blocks -------------
         // total blocks = ceil(total blocks / 8) * 8
         // node storage = super block + address block + bitmaps metadata + (total blocks / 8) + (total blocks * block size)
+        // ------------------------------ with a refcount table -------------------------------------
+        // remain storage also excludes the refcount metadata (16 bytes)
+        // total blocks = remain storage / (block size + 1/8 + 2)
+        // node storage += refcount metadata + (total blocks * 2)
         // ------------------------------------------------------------------------------------------
 
+        let header_size = Self::encoded_size(version) as f64;
+        let (refcount_reserved, refcount_per_block) = match refcounts {
+            true => (RESERVED_RB as u64, REFCOUNT_SIZE as u64),
+            false => (0, 0),
+        };
         let node_storage = storage as f64 * redundancy_ratio / nodes as f64;
-        let remain_storage = node_storage - ((SB_SIZE as f64) + (RESERVED_AB as f64) + (PK_SIZE as f64) * nodes as f64 + (RESERVED_BB as f64));
-        let total_blocks = remain_storage / (block_size as f64 + 0.125);
+        let remain_storage =
+            node_storage - (header_size + (RESERVED_AB as f64) + (PK_SIZE as f64) * nodes as f64 + (RESERVED_BB as f64) + refcount_reserved as f64);
+        let block_cost = block_size as f64 + 0.125 + refcount_per_block as f64;
+        if remain_storage < block_cost {
+            return Err(RDFSError::DriveTooSmall { storage, nodes }.into());
+        }
+        let total_blocks = remain_storage / block_cost;
         // corrected values
         let total_blocks = ceil(total_blocks / 8.0) as u64 * 8;
-        let node_storage = (SB_SIZE as u64)
+        let refcount_size = match refcounts {
+            true => refcount_reserved + total_blocks * refcount_per_block,
+            false => 0,
+        };
+        let node_storage = header_size as u64
             + (RESERVED_AB as u64)
             + (PK_SIZE as u64) * nodes
             + (RESERVED_BB as u64)
-            + (total_blocks / 8)
+            + total_blocks.div_ceil(8)
+            + refcount_size
             + total_blocks * block_size;
 
         let nodes_address_size = (RESERVED_AB as u64) + (PK_SIZE as u64) * nodes;
-        let bitmaps_size = (RESERVED_BB as u64) + total_blocks / 8;
+        let bitmaps_size = (RESERVED_BB as u64) + total_blocks.div_ceil(8);
 
-        let nodes_address_pointer = SB_SIZE as u64;
+        let nodes_address_pointer = header_size as u64;
         let bitmaps_pointer = nodes_address_pointer + nodes_address_size;
-        let data_pointer = bitmaps_pointer + bitmaps_size;
+        let refcount_pointer = if refcounts { bitmaps_pointer + bitmaps_size } else { 0 };
+        let data_pointer = bitmaps_pointer + bitmaps_size + refcount_size;
         let inode_pointer = data_pointer + block_size * (total_blocks - 1);
 
         let max_content_pointers = floor((block_size as f64 - (RESERVED_IB as f64)) / (CONTENT_SIZE as f64)) as u64;
         let max_linked_content_pointers = floor((block_size as f64 - (RESERVED_LIB as f64)) / (CONTENT_SIZE as f64)) as u64;
 
-        Self {
+        Ok(Self {
             magic,
+            version,
             owner,
             program_id,
             storage,
@@ -147,9 +359,14 @@ impl SuperBlock {
             bitmaps_size,
             max_content_pointers,
             max_linked_content_pointers,
+            refcount_pointer,
+            refcount_size,
+            snapshot_pointer: 0,
+            flags: 0,
+            codec: 0,
 
             signature: [0; 64],
-        }
+        })
     }
 
     pub fn new_private(
@@ -160,20 +377,40 @@ impl SuperBlock {
         redundancy: u64,
         nodes: u64,
         block_size: u64,
-    ) -> Self {
+    ) -> Result<Self> {
+        Self::check_block_size(block_size)?;
+        Self::private_layout(magic, owner, program_id, storage, redundancy, nodes, block_size, SB_VERSION)
+    }
+
+    #[allow(clippy::too_many_arguments)]
+    fn private_layout(
+        magic: FileSystemType,
+        owner: Address,
+        program_id: Address,
+        storage: u64,
+        redundancy: u64,
+        nodes: u64,
+        block_size: u64,
+        version: u16,
+    ) -> Result<Self> {
+        let header_size = Self::encoded_size(version) as u64;
         let redundancy_ratio = redundancy as f64 / 100.0;
         let node_storage = storage as f64 * redundancy_ratio / nodes as f64;
-        let remain_storage = node_storage - ((SB_SIZE as f64) + (RESERVED_AB as f64) + (PK_SIZE as f64) * nodes as f64);
+        let remain_storage = node_storage - ((header_size as f64) + (RESERVED_AB as f64) + (PK_SIZE as f64) * nodes as f64);
+        if remain_storage < block_size as f64 {
+            return Err(RDFSError::DriveTooSmall { storage, nodes }.into());
+        }
         let total_blocks = ceil(remain_storage / block_size as f64) as u64;
         // corrected values
-        let node_storage = (SB_SIZE as u64) + (RESERVED_AB as u64) + (PK_SIZE as u64) * nodes + total_blocks * block_size;
+        let node_storage = header_size + (RESERVED_AB as u64) + (PK_SIZE as u64) * nodes + total_blocks * block_size;
 
         let nodes_address_size = (RESERVED_AB as u64) + (PK_SIZE as u64) * nodes;
-        let nodes_address_pointer = SB_SIZE as u64;
+        let nodes_address_pointer = header_size;
         let data_pointer = nodes_address_pointer + nodes_address_size;
 
-        Self {
+        Ok(Self {
             magic,
+            version,
             owner,
             program_id,
             storage,
@@ -193,9 +430,14 @@ impl SuperBlock {
             bitmaps_size: 0,
             max_content_pointers: 0,
             max_linked_content_pointers: 0,
+            refcount_pointer: 0,
+            refcount_size: 0,
+            snapshot_pointer: 0,
+            flags: 0,
+            codec: 0,
 
             signature: [0; 64],
-        }
+        })
     }
 
     /// signing algorithm is not included in the file system.
@@ -205,11 +447,215 @@ impl SuperBlock {
         self.signature = signature;
     }
 
+    /// Converts a block index (as used by the bitmaps block) to the byte pointer of the block.
+    pub fn block_pointer(&self, index: u64) -> Result<u64> {
+        if index >= self.total_blocks {
+            let pointer = self.data_pointer.saturating_add(index.saturating_mul(self.block_size));
+            return Err(self.out_of_range(pointer).into());
+        }
+        Ok(self.data_pointer + index * self.block_size)
+    }
+
+    /// Converts the byte pointer of a block to its index, failing if the pointer is outside
+    /// the data blocks or not on a block boundary.
+    pub fn block_index(&self, pointer: u64) -> Result<u64> {
+        let last_block = self.data_pointer + self.total_blocks.saturating_sub(1) * self.block_size;
+        if pointer < self.data_pointer || pointer > last_block {
+            return Err(self.out_of_range(pointer).into());
+        }
+        let offset = pointer - self.data_pointer;
+        if !offset.is_multiple_of(self.block_size) {
+            return Err(RDFSError::InvalidPointerAlignment {
+                pointer,
+                offset: offset % self.block_size,
+                block_size: self.block_size,
+            }
+            .into());
+        }
+        Ok(offset / self.block_size)
+    }
+
+    fn out_of_range(&self, pointer: u64) -> RDFSError {
+        RDFSError::PointerOutOfRange {
+            pointer,
+            data_pointer: self.data_pointer,
+            last_block: self.data_pointer + self.total_blocks.saturating_sub(1) * self.block_size,
+        }
+    }
+
+    /// Payload bytes of one `DataBlock`, `block_size - RESERVED_DB`, less `AEAD_NONCE_SIZE`
+    /// and `AEAD_TAG_SIZE` on an encrypted drive.
+    pub fn data_payload_size(&self) -> u64 {
+        let aead = if self.is_encrypted() {
+            (AEAD_NONCE_SIZE + AEAD_TAG_SIZE) as u64
+        } else {
+            0
+        };
+        self.block_size - RESERVED_DB as u64 - aead
+    }
+
+    /// Bytes of an `InodeDir`/`InodeFile` block left for its content pointers, `block_size - RESERVED_IB`.
+    pub fn inode_payload_size(&self) -> u64 {
+        self.block_size - RESERVED_IB as u64
+    }
+
+    /// Bytes of client data carried by one RaptorQ `DataBlock`, `block_size - RESERVED_CDB`.
+    /// Clients chunk their data into symbols of this size.
+    pub fn client_payload_size(&self) -> u64 {
+        self.block_size - RESERVED_CDB as u64
+    }
+
+    /// Number of RaptorQ chunks one logical block is encoded into, 0 on a private drive.
+    fn chunks_per_block(&self) -> u64 {
+        let source_symbols = self.client_block_size.div_ceil(self.client_payload_size());
+        (source_symbols * self.redundancy).div_ceil(100)
+    }
+
+    /// Node index holding each chunk of `logical_block`, the element at `c` is the node of
+    /// chunk `c` (see the module docs for the placement scheme). A client asks these nodes
+    /// for the chunks it needs to decode the block.
+    pub fn chunk_assignment(&self, logical_block: u64) -> Vec<usize> {
+        (0..self.chunks_per_block())
+            .map(|chunk| ((logical_block + chunk) % self.nodes) as usize)
+            .collect()
+    }
+
+    /// Inverse of `chunk_assignment`, yields `(logical_block, chunk)` for every chunk node
+    /// `node_index` stores, in block order. A node uses it to know what it must hold.
+    /// Fails with `AddressIndexOutOfRange` when the drive has no such node.
+    pub fn blocks_for_node(&self, node_index: usize) -> Result<impl Iterator<Item = (u64, u64)> + '_> {
+        let node = node_index as u64;
+        if node >= self.nodes {
+            return Err(RDFSError::AddressIndexOutOfRange(node_index).into());
+        }
+        let chunks = self.chunks_per_block();
+        Ok((0..self.total_blocks).flat_map(move |block| {
+            let first = (node + self.nodes - block % self.nodes) % self.nodes;
+            (first..chunks).step_by(self.nodes as usize).map(move |chunk| (block, chunk))
+        }))
+    }
+
+    /// Total payload bytes the data blocks of this drive can hold, excluding the
+    /// `RESERVED_DB` metadata of every block.
+    pub fn usable_capacity(&self) -> u64 {
+        self.data_payload_size() * self.total_blocks
+    }
+
+    /// Largest file a shared drive can hold: every block but the root directory, the snapshot
+    /// list of a refcounted drive and the file inode itself, filled in one contiguous run so
+    /// no `InodeLinkedFile` block is needed. On fragmented free space the runs spill into
+    /// linked blocks, which leaves less room.
+    pub fn max_file_size(&self) -> u64 {
+        let reserved = 2 + u64::from(self.has_refcounts() && self.version >= 3);
+        self.total_blocks.saturating_sub(reserved) * self.data_payload_size()
+    }
+
+    /// Percentage of `node_storage` available as block payload, the rest is taken by
+    /// the super block, addresses, bitmaps and per block metadata.
+    pub fn efficiency(&self) -> f64 {
+        (self.usable_capacity() * 100) as f64 / self.node_storage as f64
+    }
+
+    /// Fails with `InvalidBlockSize` unless `block_size` is a power of two from 2KB, below
+    /// which the `RESERVED_IB` inode header leaves no room for content pointers, up to
+    /// `MAX_BLOCK_SIZE`. Checked when laying out a new drive only, existing drives with
+    /// another size still mount.
+    pub fn check_block_size(block_size: u64) -> Result<()> {
+        if !(2048..=MAX_BLOCK_SIZE).contains(&block_size) || !block_size.is_power_of_two() {
+            return Err(RDFSError::InvalidBlockSize(block_size).into());
+        }
+        Ok(())
+    }
+
+    /// Checks the primary inputs of a drive against the minimum requirements, failing with
+    /// `InconsistentSuperBlock` naming the broken one. Used before laying out a new drive
+    /// (see `DriveBuilder`) and by `validate`.
+    pub fn check_parameters(storage: u64, redundancy: u64, nodes: u64, block_size: u64) -> Result<()> {
+        let inconsistent = |reason: &str| Err(RDFSError::InconsistentSuperBlock(reason.to_string()).into());
+
+        if redundancy < 100 {
+            return inconsistent("redundancy should be >= 100");
+        }
+        if nodes < 1 {
+            return inconsistent("minimum nodes to operate is 1");
+        }
+        if block_size < 2048 {
+            return inconsistent("minimum block size is 2KB");
+        }
+        if block_size > MAX_BLOCK_SIZE {
+            return inconsistent("maximum block size is 16MB");
+        }
+        if storage < nodes.saturating_mul(1048576) {
+            return inconsistent("minimum storage should be >= nodes * 1MB");
+        }
+        Ok(())
+    }
+
+    /// Checks the primary inputs against the minimum requirements, then recomputes every
+    /// derived field from them and fails with `InconsistentSuperBlock` naming the first
+    /// field that disagrees, so a corrupted or forged super block is never used for offsets.
+    pub fn validate(&self) -> Result<()> {
+        let inconsistent = |field: &str| Err(RDFSError::InconsistentSuperBlock(field.to_string()).into());
+
+        Self::check_parameters(self.storage, self.redundancy, self.nodes, self.block_size)?;
+
+        let expected = self.resized(self.storage)?;
+        let fields = [
+            ("total_blocks", self.total_blocks, expected.total_blocks),
+            ("client_block_size", self.client_block_size, expected.client_block_size),
+            ("node_storage", self.node_storage, expected.node_storage),
+            ("nodes_address_pointer", self.nodes_address_pointer, expected.nodes_address_pointer),
+            ("bitmaps_pointer", self.bitmaps_pointer, expected.bitmaps_pointer),
+            ("data_pointer", self.data_pointer, expected.data_pointer),
+            ("inode_pointer", self.inode_pointer, expected.inode_pointer),
+            ("nodes_address_size", self.nodes_address_size, expected.nodes_address_size),
+            ("bitmaps_size", self.bitmaps_size, expected.bitmaps_size),
+            ("max_content_pointers", self.max_content_pointers, expected.max_content_pointers),
+            (
+                "max_linked_content_pointers",
+                self.max_linked_content_pointers,
+                expected.max_linked_content_pointers,
+            ),
+            ("refcount_pointer", self.refcount_pointer, expected.refcount_pointer),
+            ("refcount_size", self.refcount_size, expected.refcount_size),
+        ];
+        if let Some((field, _, _)) = fields.iter().find(|(_, stored, computed)| stored != computed) {
+            return inconsistent(field);
+        }
+        if self.snapshot_pointer != 0
+            && (!self.has_refcounts() || self.block_index(self.snapshot_pointer).is_err() || self.snapshot_pointer == self.inode_pointer)
+        {
+            return inconsistent("snapshot_pointer");
+        }
+        if self.flags & !SB_FLAG_ENCRYPTED != 0 || (self.is_encrypted() && self.magic == FileSystemType::Shared) {
+            return inconsistent("flags");
+        }
+        if !SUPPORTED_CODECS.contains(&self.codec) || (self.codec != RaptorQ::ID && self.magic == FileSystemType::Private) {
+            return inconsistent("codec");
+        }
+
+        let ordered = match self.magic {
+            FileSystemType::Shared if self.has_refcounts() => {
+                self.nodes_address_pointer < self.bitmaps_pointer
+                    && self.bitmaps_pointer < self.refcount_pointer
+                    && self.refcount_pointer < self.data_pointer
+            }
+            FileSystemType::Shared => self.nodes_address_pointer < self.bitmaps_pointer && self.bitmaps_pointer < self.data_pointer,
+            FileSystemType::Private => self.nodes_address_pointer < self.data_pointer,
+        };
+        if !ordered {
+            return inconsistent("pointers are out of order");
+        }
+        Ok(())
+    }
+
     /// Serialize to prepare for storing or transmission.
+    /// Super blocks older than `SB_VERSION` are encoded in their own shorter layout.
     pub fn to_bytes(&self) -> Vec<u8> {
-        let mut encoded = Vec::with_capacity(SB_SIZE);
+        let mut encoded = Vec::with_capacity(Self::encoded_size(self.version));
 
         encoded.extend_from_slice(&self.magic.to_bytes());
+        encoded.extend_from_slice(&self.version.to_le_bytes());
         encoded.extend_from_slice(&self.owner);
         encoded.extend_from_slice(&self.program_id);
         encoded.extend_from_slice(&self.storage.to_le_bytes());
@@ -227,38 +673,123 @@ impl SuperBlock {
         encoded.extend_from_slice(&self.bitmaps_size.to_le_bytes());
         encoded.extend_from_slice(&self.max_content_pointers.to_le_bytes());
         encoded.extend_from_slice(&self.max_linked_content_pointers.to_le_bytes());
+        if self.version >= 2 {
+            encoded.extend_from_slice(&self.refcount_pointer.to_le_bytes());
+            encoded.extend_from_slice(&self.refcount_size.to_le_bytes());
+        }
+        if self.version >= 3 {
+            encoded.extend_from_slice(&self.snapshot_pointer.to_le_bytes());
+        }
+        if self.version >= 4 {
+            encoded.extend_from_slice(&BYTE_ORDER_MARK.to_le_bytes());
+        }
+        if self.version >= 5 {
+            encoded.extend_from_slice(&self.flags.to_le_bytes());
+        }
+        if self.version >= 6 {
+            encoded.extend_from_slice(&self.codec.to_le_bytes());
+        }
+        if self.version >= 7 {
+            encoded.extend_from_slice(&[0; 4]);
+        }
         encoded.extend_from_slice(&self.signature);
+        if self.version >= 7 {
+            let checksum = Self::checksum(&encoded);
+            encoded[230..234].copy_from_slice(&checksum.to_le_bytes());
+        }
 
         encoded
     }
 
+    /// CRC32 of a version 7 encoded super block, skipping its checksum bytes 230..234.
+    fn checksum(encoded: &[u8]) -> u32 {
+        let mut hasher = crc32fast::Hasher::new();
+        hasher.update(&encoded[..230]);
+        hasher.update(&encoded[234..SB_SIZE]);
+        hasher.finalize()
+    }
+
+    /// Decodes a super block from `SB_SIZE` bytes, an older super block only uses its first
+    /// `SB_SIZE_V1` to `SB_SIZE_V6` bytes and may also be given alone.
+    /// A byte order mark other than `BYTE_ORDER_MARK`, or a version only valid once its
+    /// bytes are swapped, fails with `InvalidByteOrder` before any other field is read.
+    /// From version 7 a checksum that doesn't match the bytes fails with `CorruptSuperBlock`.
     pub fn from_bytes(data: &[u8]) -> Result<Self> {
-        if data.len() != SB_SIZE {
-            return Err(RDFSError::InvalidSuperBlockLength.into());
+        if ![SB_SIZE, SB_SIZE_V6, SB_SIZE_V5, SB_SIZE_V4, SB_SIZE_V3, SB_SIZE_V2, SB_SIZE_V1].contains(&data.len()) {
+            return Err(RDFSError::InvalidSuperBlockLength {
+                got: data.len(),
+                expected: SB_SIZE,
+            }
+            .into());
         }
 
         let magic = FileSystemType::from_bytes(&data[..8])?;
-        let owner = data[8..40].try_into().unwrap();
-        let program_id = data[40..72].try_into().unwrap();
-        let storage = u64::from_le_bytes(data[72..80].try_into().unwrap());
-        let redundancy = u64::from_le_bytes(data[80..88].try_into().unwrap());
-        let nodes = u64::from_le_bytes(data[88..96].try_into().unwrap());
-        let block_size = u64::from_le_bytes(data[96..104].try_into().unwrap());
-        let total_blocks = u64::from_le_bytes(data[104..112].try_into().unwrap());
-        let client_block_size = u64::from_le_bytes(data[112..120].try_into().unwrap());
-        let node_storage = u64::from_le_bytes(data[120..128].try_into().unwrap());
-        let nodes_address_pointer = u64::from_le_bytes(data[128..136].try_into().unwrap());
-        let bitmaps_pointer = u64::from_le_bytes(data[136..144].try_into().unwrap());
-        let data_pointer = u64::from_le_bytes(data[144..152].try_into().unwrap());
-        let inode_pointer = u64::from_le_bytes(data[152..160].try_into().unwrap());
-        let nodes_address_size = u64::from_le_bytes(data[160..168].try_into().unwrap());
-        let bitmaps_size = u64::from_le_bytes(data[168..176].try_into().unwrap());
-        let max_content_pointers = u64::from_le_bytes(data[176..184].try_into().unwrap());
-        let max_linked_content_pointers = u64::from_le_bytes(data[184..192].try_into().unwrap());
-        let signature = data[192..].try_into().unwrap();
+        let version = u16::from_le_bytes(data[8..10].try_into().unwrap());
+        let supported = |version: u16| (1..=SB_VERSION).contains(&version);
+        if !supported(version) && supported(version.swap_bytes()) {
+            return Err(RDFSError::InvalidByteOrder(version).into());
+        }
+        if !supported(version) {
+            return Err(RDFSError::UnsupportedSuperBlockVersion(version).into());
+        }
+        if data.len() < Self::encoded_size(version) {
+            return Err(RDFSError::InvalidSuperBlockLength {
+                got: data.len(),
+                expected: Self::encoded_size(version),
+            }
+            .into());
+        }
+        if version >= 4 {
+            let mark = u16::from_le_bytes(data[218..220].try_into().unwrap());
+            if mark != BYTE_ORDER_MARK {
+                return Err(RDFSError::InvalidByteOrder(mark).into());
+            }
+        }
+        if version >= 7 && u32::from_le_bytes(data[230..234].try_into().unwrap()) != Self::checksum(data) {
+            return Err(RDFSError::CorruptSuperBlock.into());
+        }
+        let owner = data[10..42].try_into().unwrap();
+        let program_id = data[42..74].try_into().unwrap();
+        let storage = u64::from_le_bytes(data[74..82].try_into().unwrap());
+        let redundancy = u64::from_le_bytes(data[82..90].try_into().unwrap());
+        let nodes = u64::from_le_bytes(data[90..98].try_into().unwrap());
+        let block_size = u64::from_le_bytes(data[98..106].try_into().unwrap());
+        let total_blocks = u64::from_le_bytes(data[106..114].try_into().unwrap());
+        let client_block_size = u64::from_le_bytes(data[114..122].try_into().unwrap());
+        let node_storage = u64::from_le_bytes(data[122..130].try_into().unwrap());
+        let nodes_address_pointer = u64::from_le_bytes(data[130..138].try_into().unwrap());
+        let bitmaps_pointer = u64::from_le_bytes(data[138..146].try_into().unwrap());
+        let data_pointer = u64::from_le_bytes(data[146..154].try_into().unwrap());
+        let inode_pointer = u64::from_le_bytes(data[154..162].try_into().unwrap());
+        let nodes_address_size = u64::from_le_bytes(data[162..170].try_into().unwrap());
+        let bitmaps_size = u64::from_le_bytes(data[170..178].try_into().unwrap());
+        let max_content_pointers = u64::from_le_bytes(data[178..186].try_into().unwrap());
+        let max_linked_content_pointers = u64::from_le_bytes(data[186..194].try_into().unwrap());
+        let (refcount_pointer, refcount_size) = match version {
+            1 => (0, 0),
+            _ => (
+                u64::from_le_bytes(data[194..202].try_into().unwrap()),
+                u64::from_le_bytes(data[202..210].try_into().unwrap()),
+            ),
+        };
+        let snapshot_pointer = match version {
+            1 | 2 => 0,
+            _ => u64::from_le_bytes(data[210..218].try_into().unwrap()),
+        };
+        let flags = match version {
+            1..=4 => 0,
+            _ => u64::from_le_bytes(data[220..228].try_into().unwrap()),
+        };
+        let codec = match version {
+            1..=5 => 0,
+            _ => u16::from_le_bytes(data[228..230].try_into().unwrap()),
+        };
+        let signature_pointer = Self::encoded_size(version) - SIG_SIZE;
+        let signature = data[signature_pointer..Self::encoded_size(version)].try_into().unwrap();
 
         Ok(Self {
             magic,
+            version,
             owner,
             program_id,
             storage,
@@ -276,11 +807,54 @@ impl SuperBlock {
             bitmaps_size,
             max_content_pointers,
             max_linked_content_pointers,
+            refcount_pointer,
+            refcount_size,
+            snapshot_pointer,
+            flags,
+            codec,
             signature,
         })
     }
 }
 
+/// Readable table of the drive layout, one field per line, e.g. `println!("{super_block}")`.
+impl fmt::Display for SuperBlock {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let size = |bytes: u64| format!("{} ({bytes} bytes)", format_bytes(bytes));
+        let rows = [
+            ("magic", format!("{:?} (version {})", self.magic, self.version)),
+            ("owner", format!("0x{}", bytes_to_hex(&self.owner))),
+            ("program_id", format!("0x{}", bytes_to_hex(&self.program_id))),
+            ("storage", size(self.storage)),
+            ("redundancy", format!("{}%", self.redundancy)),
+            ("nodes", self.nodes.to_string()),
+            ("block_size", size(self.block_size)),
+            ("total_blocks", self.total_blocks.to_string()),
+            ("client_block_size", size(self.client_block_size)),
+            ("node_storage", size(self.node_storage)),
+            ("nodes_address_pointer", self.nodes_address_pointer.to_string()),
+            ("nodes_address_size", size(self.nodes_address_size)),
+            ("bitmaps_pointer", self.bitmaps_pointer.to_string()),
+            ("bitmaps_size", size(self.bitmaps_size)),
+            ("refcount_pointer", self.refcount_pointer.to_string()),
+            ("refcount_size", size(self.refcount_size)),
+            ("data_pointer", self.data_pointer.to_string()),
+            ("inode_pointer", self.inode_pointer.to_string()),
+            ("snapshot_pointer", self.snapshot_pointer.to_string()),
+            ("flags", format!("{:#x}", self.flags)),
+            ("codec", self.codec.to_string()),
+            ("max_content_pointers", self.max_content_pointers.to_string()),
+            ("max_linked_content_pointers", self.max_linked_content_pointers.to_string()),
+            ("usable_capacity", size(self.usable_capacity())),
+            ("efficiency", format!("{:.2}%", self.efficiency())),
+        ];
+        for (name, value) in rows {
+            writeln!(f, "{name:<28}{value}")?;
+        }
+        Ok(())
+    }
+}
+
 #[repr(u64)]
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum FileSystemType {
@@ -312,8 +886,8 @@ impl FileSystemType {
 
 #[cfg(test)]
 mod test {
-    use super::super::super::utils::bytes_to_hex;
     use super::*;
+    use crate::core::data_block::DataBlock;
 
     #[test]
     fn new_super_block_test() {
@@ -336,43 +910,21 @@ mod test {
             "minimum block size is 2KB but it will be not efficient ~90% of storage"
         );
 
-        let block = super::SuperBlock::new(FileSystemType::Shared, owner, program_id, storage, redundancy, nodes, block_size);
-        println!("magic: {:?}", block.magic);
-        println!("program_id: 0x{}", bytes_to_hex(&block.program_id));
-        println!("storage: {:?}", block.storage);
-        println!("redundancy: {:?}", block.redundancy);
-        println!("nodes: {:?}", block.nodes);
-        println!("block_size: {:?}", block.block_size);
-        println!("total_blocks: {:?}", block.total_blocks);
-        println!("client_block_size: {:?}", block.client_block_size);
-        println!("node_storage: {:?}", block.node_storage);
-        println!("nodes_address_pointer: {:?}", block.nodes_address_pointer);
-        println!("bitmaps_pointer: {:?}", block.bitmaps_pointer);
-        println!("data_pointer: {:?}", block.data_pointer);
-        println!("inode_pointer: {:?}", block.inode_pointer);
-        println!("----------------------------");
-        println!("nodes_address size: {:?}", block.nodes_address_size);
-        println!("bitmaps size: {:?}", block.bitmaps_size);
-        println!("max content pointers: {:?}", block.max_content_pointers);
-        println!("max linked content pointers: {:?}", block.max_linked_content_pointers);
-        println!("----------------------------");
-        println!(
-            "System Storage Efficiency: {:.2?}%",
-            ((block.block_size - 88) * block.total_blocks * 100) as f64 / block.node_storage as f64
-        );
+        let block = super::SuperBlock::new(FileSystemType::Shared, owner, program_id, storage, redundancy, nodes, block_size).unwrap();
+        println!("{block}");
 
         match block.magic {
             FileSystemType::Shared => {
                 assert_eq!(
                     block.node_storage,
-                    256 + block.nodes_address_size + block.bitmaps_size + block.total_blocks * block.block_size,
+                    SB_SIZE as u64 + block.nodes_address_size + block.bitmaps_size + block.total_blocks * block.block_size,
                     "node storage should be equal to super block + address block + bitmaps metadata + (total blocks / 8) + (total blocks * block size)"
                 );
             }
             FileSystemType::Private => {
                 assert_eq!(
                     block.node_storage,
-                    256 + block.nodes_address_size + block.total_blocks * block.block_size,
+                    SB_SIZE as u64 + block.nodes_address_size + block.total_blocks * block.block_size,
                     "node storage should be equal to super block + address block + (total blocks * block size)"
                 );
             }
@@ -400,39 +952,438 @@ mod test {
             "minimum block size is 2KB but it will be not efficient ~90% of storage"
         );
 
-        let block = SuperBlock::new(FileSystemType::Private, owner, program_id, storage, redundancy, nodes, block_size);
+        let block = SuperBlock::new(FileSystemType::Private, owner, program_id, storage, redundancy, nodes, block_size).unwrap();
 
         let ser = block.to_bytes();
         println!("length: {:?}", ser.len());
 
         let block2 = SuperBlock::from_bytes(&ser).unwrap();
-        assert_eq!(block.magic, block2.magic, "Magic number should match");
-        assert_eq!(block.owner, block2.owner, "Owner should match");
-        assert_eq!(block.program_id, block2.program_id, "Program ID should match");
-        assert_eq!(block.storage, block2.storage, "Storage size should match");
-        assert_eq!(block.redundancy, block2.redundancy, "Redundancy should match");
-        assert_eq!(block.nodes, block2.nodes, "Number of nodes should match");
-        assert_eq!(block.block_size, block2.block_size, "Block size should match");
-        assert_eq!(block.total_blocks, block2.total_blocks, "Total blocks should match");
-        assert_eq!(block.client_block_size, block2.client_block_size, "Client block size should match");
-        assert_eq!(block.node_storage, block2.node_storage, "Node storage should match");
+        assert_eq!(block2.version, SB_VERSION, "Version should match");
+        assert_eq!(block, block2);
+
+        let mut signed = block2.clone();
+        signed.add_signature([1; 64]);
+        assert_ne!(signed, block2, "the signature is part of the equality");
+    }
+
+    #[test]
+    fn validate_test() {
+        let block = SuperBlock::new(FileSystemType::Shared, [255; 32], [1; 32], 34359738368, 300, 50, 4096).unwrap();
+        block.validate().unwrap();
+        SuperBlock::new(FileSystemType::Private, [255; 32], [1; 32], 34359738368, 300, 50, 4096)
+            .unwrap()
+            .validate()
+            .unwrap();
+
+        let mut tampered = block.clone();
+        tampered.data_pointer += 4096;
+        let error = tampered.validate().unwrap_err();
+        assert!(matches!(error.downcast_ref::<RDFSError>(), Some(RDFSError::InconsistentSuperBlock(field)) if field == "data_pointer"));
+
+        let mut tampered = block.clone();
+        tampered.redundancy = 50;
+        assert!(tampered.validate().is_err());
+
+        let mut tampered = block.clone();
+        tampered.block_size = 1024;
+        assert!(tampered.validate().is_err());
+
+        let mut tampered = block;
+        tampered.nodes = 0;
+        assert!(tampered.validate().is_err());
+    }
+
+    #[test]
+    fn efficiency_test() {
+        let block = SuperBlock::new(FileSystemType::Shared, [255; 32], [1; 32], 34359738368, 300, 50, 4096).unwrap();
+        assert_eq!(block.usable_capacity(), (4096 - RESERVED_DB as u64) * block.total_blocks);
+        assert!(block.efficiency() > 97.0 && block.efficiency() < 100.0);
+
+        // smaller blocks spend a bigger share on metadata
+        let small = SuperBlock::new(FileSystemType::Shared, [255; 32], [1; 32], 34359738368, 300, 50, 2048).unwrap();
+        assert!(small.efficiency() < block.efficiency());
+    }
+
+    #[test]
+    fn payload_size_test() {
+        let block = SuperBlock::new(FileSystemType::Shared, [255; 32], [1; 32], 1048576, 100, 1, 4096).unwrap();
+        assert_eq!(block.data_payload_size(), 4008);
+        assert_eq!(block.inode_payload_size(), 2960);
+        assert_eq!(block.client_payload_size(), 4004);
+        assert_eq!(block.inode_payload_size() / CONTENT_SIZE as u64, block.max_content_pointers);
+        assert_eq!(block.max_file_size(), (block.total_blocks - 2) * 4008);
+        // the snapshot list of a refcounted drive takes one more block
+        let refcounted = block.with_refcounts().unwrap();
+        assert_eq!(refcounted.max_file_size(), (refcounted.total_blocks - 3) * 4008);
+    }
+
+    #[test]
+    fn version_test() {
+        let block = SuperBlock::new(FileSystemType::Shared, [255; 32], [1; 32], 34359738368, 300, 50, 4096).unwrap();
+        let mut encoded = block.to_bytes();
+        assert_eq!(encoded.len(), SB_SIZE);
+        assert_eq!(&encoded[8..10], &SB_VERSION.to_le_bytes());
+        let decoded = SuperBlock::from_bytes(&encoded).unwrap();
+        assert_eq!(decoded.version, SB_VERSION);
+        decoded.validate().unwrap();
+
+        // a version 1 drive still parses, its shorter super block is followed by the addresses block
+        let v1 = SuperBlock { version: 1, ..block.clone() }.resized(block.storage).unwrap();
+        assert_eq!(v1.nodes_address_pointer, SB_SIZE_V1 as u64);
+        let mut on_disk = v1.to_bytes();
+        assert_eq!(on_disk.len(), SB_SIZE_V1);
+        on_disk.extend_from_slice(&[7; SB_SIZE - SB_SIZE_V1]);
+        let decoded = SuperBlock::from_bytes(&on_disk).unwrap();
+        assert_eq!(decoded.version, 1);
+        assert_eq!(decoded.data_pointer, v1.data_pointer);
+        assert!(!decoded.has_refcounts());
+        decoded.validate().unwrap();
+        assert!(!decoded.with_refcounts().unwrap().has_refcounts());
+
+        // a version 2 drive keeps its refcount table but has no snapshot pointer
+        let v2 = SuperBlock { version: 2, ..block.clone() }
+            .resized(block.storage)
+            .unwrap()
+            .with_refcounts()
+            .unwrap();
+        assert_eq!(v2.nodes_address_pointer, SB_SIZE_V2 as u64);
+        let mut on_disk = v2.to_bytes();
+        assert_eq!(on_disk.len(), SB_SIZE_V2);
+        on_disk.extend_from_slice(&[7; SB_SIZE - SB_SIZE_V2]);
+        let decoded = SuperBlock::from_bytes(&on_disk).unwrap();
+        assert_eq!(decoded.version, 2);
+        assert_eq!(decoded.refcount_pointer, v2.refcount_pointer);
+        assert_eq!(decoded.snapshot_pointer, 0);
+        assert_eq!(decoded.signature, v2.signature);
+        decoded.validate().unwrap();
+
+        encoded[8..10].copy_from_slice(&(SB_VERSION + 1).to_le_bytes());
+        let error = SuperBlock::from_bytes(&encoded).unwrap_err();
+        assert!(matches!(
+            error.downcast_ref::<RDFSError>(),
+            Some(RDFSError::UnsupportedSuperBlockVersion(version)) if *version == SB_VERSION + 1
+        ));
+    }
+
+    #[test]
+    fn byte_order_test() {
+        let block = SuperBlock::new(FileSystemType::Shared, [255; 32], [1; 32], 34359738368, 300, 50, 4096).unwrap();
+        let encoded = block.to_bytes();
+        let mark = SB_SIZE_V4 - SIG_SIZE - 2; // where version 4 put it, later fields follow the mark
+        assert_eq!(&encoded[mark..mark + 2], &BYTE_ORDER_MARK.to_le_bytes());
+
+        // flipping the mark is reported as such, not decoded into garbage fields
+        let mut flipped = encoded.clone();
+        flipped.swap(mark, mark + 1);
+        let error = SuperBlock::from_bytes(&flipped).unwrap_err();
+        assert!(matches!(error.downcast_ref::<RDFSError>(), Some(RDFSError::InvalidByteOrder(0xFFFE))));
+
+        // a big endian version word is caught before the mark is even reached
+        let mut big_endian = encoded;
+        big_endian[8..10].copy_from_slice(&SB_VERSION.to_be_bytes());
+        let error = SuperBlock::from_bytes(&big_endian).unwrap_err();
+        assert!(matches!(error.downcast_ref::<RDFSError>(), Some(RDFSError::InvalidByteOrder(_))));
+    }
+
+    #[test]
+    fn refcount_layout_test() {
+        let block = SuperBlock::new(FileSystemType::Shared, [255; 32], [1; 32], 34359738368, 300, 50, 4096).unwrap();
+        let refcounted = block.clone().with_refcounts().unwrap();
+        assert!(refcounted.has_refcounts() && !block.has_refcounts());
+        assert!(refcounted.total_blocks < block.total_blocks);
+        assert_eq!(refcounted.refcount_pointer, refcounted.bitmaps_pointer + refcounted.bitmaps_size);
         assert_eq!(
-            block.nodes_address_pointer, block2.nodes_address_pointer,
-            "Nodes address pointer should match"
+            refcounted.refcount_size,
+            (RESERVED_RB + REFCOUNT_SIZE * refcounted.total_blocks as usize) as u64
         );
-        assert_eq!(block.bitmaps_pointer, block2.bitmaps_pointer, "Bitmaps pointer should match");
-        assert_eq!(block.data_pointer, block2.data_pointer, "Data pointer should match");
-        assert_eq!(block.inode_pointer, block2.inode_pointer, "Inode pointer should match");
-        assert_eq!(block.nodes_address_size, block2.nodes_address_size, "Nodes address size should match");
-        assert_eq!(block.bitmaps_size, block2.bitmaps_size, "Bitmaps size should match");
+        assert_eq!(refcounted.data_pointer, refcounted.refcount_pointer + refcounted.refcount_size);
         assert_eq!(
-            block.max_content_pointers, block2.max_content_pointers,
-            "Max content pointers should match"
+            refcounted.node_storage,
+            SB_SIZE as u64 + refcounted.nodes_address_size + refcounted.bitmaps_size + refcounted.refcount_size + refcounted.total_blocks * 4096
         );
+        refcounted.validate().unwrap();
+
+        let decoded = SuperBlock::from_bytes(&refcounted.to_bytes()).unwrap();
         assert_eq!(
-            block.max_linked_content_pointers, block2.max_linked_content_pointers,
-            "Max linked content pointers should match"
+            (decoded.refcount_pointer, decoded.refcount_size),
+            (refcounted.refcount_pointer, refcounted.refcount_size)
         );
-        assert_eq!(block.signature, block2.signature, "Signature should match");
+        decoded.validate().unwrap();
+        assert!(refcounted.resized(2 * refcounted.storage).unwrap().has_refcounts());
+
+        let mut snapshotted = refcounted.clone();
+        snapshotted.snapshot_pointer = snapshotted.block_pointer(3).unwrap();
+        let decoded = SuperBlock::from_bytes(&snapshotted.to_bytes()).unwrap();
+        assert_eq!(decoded.snapshot_pointer, snapshotted.snapshot_pointer);
+        snapshotted.validate().unwrap();
+        snapshotted.snapshot_pointer += 1;
+        let error = snapshotted.validate().unwrap_err();
+        assert!(matches!(error.downcast_ref::<RDFSError>(), Some(RDFSError::InconsistentSuperBlock(field)) if field == "snapshot_pointer"));
+
+        let mut tampered = refcounted;
+        tampered.refcount_pointer += 8;
+        let error = tampered.validate().unwrap_err();
+        assert!(matches!(error.downcast_ref::<RDFSError>(), Some(RDFSError::InconsistentSuperBlock(field)) if field == "refcount_pointer"));
+
+        let private = SuperBlock::new(FileSystemType::Private, [255; 32], [1; 32], 34359738368, 300, 50, 4096)
+            .unwrap()
+            .with_refcounts()
+            .unwrap();
+        assert!(!private.has_refcounts());
+    }
+
+    #[test]
+    fn block_pointer_test() {
+        let block = SuperBlock::new(FileSystemType::Shared, [255; 32], [1; 32], 34359738368, 300, 50, 4096).unwrap();
+        let last = block.total_blocks - 1;
+
+        assert_eq!(block.block_pointer(0).unwrap(), block.data_pointer);
+        assert_eq!(block.block_pointer(last).unwrap(), block.inode_pointer);
+        assert_eq!(block.block_index(block.inode_pointer).unwrap(), last);
+        assert_eq!(block.block_index(block.block_pointer(17).unwrap()).unwrap(), 17);
+
+        let error = block.block_pointer(block.total_blocks).unwrap_err();
+        assert!(matches!(error.downcast_ref::<RDFSError>(), Some(RDFSError::PointerOutOfRange { .. })));
+        let error = block.block_index(block.data_pointer - 1).unwrap_err();
+        match error.downcast_ref::<RDFSError>() {
+            Some(&RDFSError::PointerOutOfRange {
+                pointer,
+                data_pointer,
+                last_block,
+            }) => assert_eq!(
+                (pointer, data_pointer, last_block),
+                (block.data_pointer - 1, block.data_pointer, block.inode_pointer)
+            ),
+            other => panic!("unexpected error {other:?}"),
+        }
+        assert!(error.to_string().contains(&block.data_pointer.to_string()));
+        let error = block.block_index(block.inode_pointer + 4096).unwrap_err();
+        assert!(matches!(error.downcast_ref::<RDFSError>(), Some(RDFSError::PointerOutOfRange { .. })));
+        let error = block.block_index(block.data_pointer + 4096 * 3 + 100).unwrap_err();
+        assert!(matches!(
+            error.downcast_ref::<RDFSError>(),
+            Some(RDFSError::InvalidPointerAlignment {
+                offset: 100,
+                block_size: 4096,
+                ..
+            })
+        ));
+        assert!(error.to_string().contains("not aligned"));
+    }
+
+    #[test]
+    fn display_test() {
+        assert_eq!(format_bytes(512), "512 B");
+        assert_eq!(format_bytes(1536), "1.50 KiB");
+        assert_eq!(format_bytes(1048576), "1.00 MiB");
+        assert_eq!(format_bytes(34359738368), "32.00 GiB");
+
+        let block = SuperBlock::new(FileSystemType::Shared, [255; 32], [1; 32], 34359738368, 300, 50, 4096).unwrap();
+        let summary = block.to_string();
+        assert!(summary.starts_with("magic                       Shared (version 8)\n"));
+        assert!(summary.contains(&format!("program_id                  0x{}\n", "01".repeat(32))));
+        assert!(summary.contains("block_size                  4.00 KiB (4096 bytes)\n"));
+        assert!(summary.contains(&format!("inode_pointer               {}\n", block.inode_pointer)));
+        assert!(summary.contains(&format!("efficiency                  {:.2}%\n", block.efficiency())));
+        assert_eq!(summary.lines().count(), 25);
+    }
+
+    #[test]
+    fn drive_too_small_test() {
+        let too_small = |result: Result<SuperBlock>| {
+            let error = result.unwrap_err();
+            matches!(error.downcast_ref::<RDFSError>(), Some(RDFSError::DriveTooSmall { .. }))
+        };
+        let shared = |storage: u64, nodes: u64| SuperBlock::new(FileSystemType::Shared, [255; 32], [1; 32], storage, 100, nodes, 4096);
+        let private = |storage: u64, nodes: u64| SuperBlock::new(FileSystemType::Private, [255; 32], [1; 32], storage, 100, nodes, 4096);
+
+        assert!(too_small(shared(0, 1)));
+        assert!(too_small(private(0, 1)));
+        // 1MB over 1000 nodes leaves ~1KB per node, less than its metadata and a block
+        assert!(too_small(shared(1048576, 1000)));
+        assert!(too_small(private(1048576, 1000)));
+
+        // the smallest drive holding one block, padded to a full bitmaps byte
+        let metadata = (SB_SIZE + RESERVED_AB + PK_SIZE) as u64;
+        let block = shared(metadata + RESERVED_BB as u64 + 4097, 1).unwrap();
+        assert_eq!(block.total_blocks, 8);
+        assert!(too_small(shared(metadata + RESERVED_BB as u64 + 4095, 1)));
+        // the refcount table takes what was left
+        assert!(too_small(block.with_refcounts()));
+
+        assert_eq!(private(metadata + 4096, 1).unwrap().total_blocks, 1);
+        assert!(too_small(private(metadata + 4095, 1)));
+    }
+
+    #[test]
+    fn encryption_flag_test() {
+        let private = SuperBlock::new(FileSystemType::Private, [255; 32], [1; 32], 1048576, 100, 1, 4096).unwrap();
+        let encrypted = private.clone().with_encryption();
+        assert!(encrypted.is_encrypted() && !private.is_encrypted());
+        assert_eq!(encrypted.total_blocks, private.total_blocks);
+        assert_eq!(
+            encrypted.data_payload_size(),
+            private.data_payload_size() - (AEAD_NONCE_SIZE + AEAD_TAG_SIZE) as u64
+        );
+
+        let decoded = SuperBlock::from_bytes(&encrypted.to_bytes()).unwrap();
+        assert_eq!(decoded.flags, SB_FLAG_ENCRYPTED);
+        decoded.validate().unwrap();
+        assert!(decoded.resized(2 * 1048576).unwrap().is_encrypted());
+
+        // shared and version 4 drives have no encryption
+        let shared = SuperBlock::new(FileSystemType::Shared, [255; 32], [1; 32], 1048576, 100, 1, 4096).unwrap();
+        assert!(!shared.clone().with_encryption().is_encrypted());
+        let v4 = SuperBlock {
+            version: 4,
+            ..private.clone()
+        }
+        .resized(private.storage)
+        .unwrap();
+        assert!(!v4.clone().with_encryption().is_encrypted());
+        let mut on_disk = v4.to_bytes();
+        assert_eq!(on_disk.len(), SB_SIZE_V4);
+        on_disk.extend_from_slice(&[7; SB_SIZE - SB_SIZE_V4]);
+        let decoded = SuperBlock::from_bytes(&on_disk).unwrap();
+        assert_eq!((decoded.version, decoded.flags), (4, 0));
+        decoded.validate().unwrap();
+
+        for flags in [2, SB_FLAG_ENCRYPTED | 4] {
+            let error = SuperBlock { flags, ..encrypted.clone() }.validate().unwrap_err();
+            assert!(matches!(error.downcast_ref::<RDFSError>(), Some(RDFSError::InconsistentSuperBlock(field)) if field == "flags"));
+        }
+        let forged = SuperBlock {
+            flags: SB_FLAG_ENCRYPTED,
+            ..shared
+        };
+        assert!(forged.validate().is_err());
+    }
+
+    #[test]
+    fn codec_test() {
+        let block = SuperBlock::new(FileSystemType::Shared, [255; 32], [1; 32], 16777216, 300, 12, 4096).unwrap();
+        assert_eq!(block.codec, RaptorQ::ID);
+        assert_eq!(block.clone().with_codec::<RaptorQ>(), block);
+
+        // the id survives a round trip, `validate` then refuses a codec this build lacks
+        let other = SuperBlock { codec: 3, ..block.clone() };
+        let decoded = SuperBlock::from_bytes(&other.to_bytes()).unwrap();
+        assert_eq!(decoded.codec, 3);
+        assert_eq!(decoded.resized(2 * 16777216).unwrap().codec, 3);
+        let error = decoded.validate().unwrap_err();
+        assert!(matches!(error.downcast_ref::<RDFSError>(), Some(RDFSError::InconsistentSuperBlock(field)) if field == "codec"));
+
+        // version 5 drives have no codec field and are all RaptorQ
+        let v5 = SuperBlock { version: 5, ..block.clone() }.resized(block.storage).unwrap();
+        let mut on_disk = v5.to_bytes();
+        assert_eq!(on_disk.len(), SB_SIZE_V5);
+        on_disk.extend_from_slice(&[7; SB_SIZE - SB_SIZE_V5]);
+        let decoded = SuperBlock::from_bytes(&on_disk).unwrap();
+        assert_eq!((decoded.version, decoded.codec), (5, RaptorQ::ID));
+        decoded.validate().unwrap();
+
+        // private drives store raw blocks, nothing is erasure coded
+        let private = SuperBlock::new(FileSystemType::Private, [255; 32], [1; 32], 1048576, 100, 1, 4096).unwrap();
+        assert!(SuperBlock { codec: 3, ..private }.validate().is_err());
+    }
+
+    #[test]
+    fn chunk_assignment_test() {
+        let system = SuperBlock::new(FileSystemType::Shared, [255; 32], [1; 32], 16777216, 300, 12, 4096).unwrap();
+        let chunks = DataBlock::encode_with_raptorq(b"chunk", &system).unwrap().len();
+        assert_eq!(system.chunk_assignment(0).len(), chunks);
+        assert_eq!(system.chunk_assignment(0), (0..12).collect::<Vec<_>>());
+        assert_eq!(system.chunk_assignment(13)[..3], [1, 2, 3]);
+        assert_eq!(system.chunk_assignment(13)[11], 0);
+
+        // every chunk is held by exactly the node the assignment names
+        for node in 0..12 {
+            for (block, chunk) in system.blocks_for_node(node).unwrap().take(100) {
+                assert_eq!(system.chunk_assignment(block)[chunk as usize], node);
+            }
+        }
+        assert_eq!(system.blocks_for_node(5).unwrap().count() as u64, system.total_blocks);
+
+        // more chunks than nodes wrap around
+        let single = SuperBlock::new(FileSystemType::Shared, [255; 32], [1; 32], 1048576, 300, 1, 4096).unwrap();
+        assert_eq!(single.chunk_assignment(7), vec![0; 3]);
+        let held: Vec<_> = single.blocks_for_node(0).unwrap().take(4).collect();
+        assert_eq!(held, [(0, 0), (0, 1), (0, 2), (1, 0)]);
+
+        let error = system.blocks_for_node(12).err().unwrap();
+        assert!(matches!(error.downcast_ref::<RDFSError>(), Some(RDFSError::AddressIndexOutOfRange(12))));
+    }
+
+    #[test]
+    fn block_size_test() {
+        let new = |magic, block_size| SuperBlock::new(magic, [255; 32], [1; 32], 34359738368, 300, 50, block_size);
+        for magic in [FileSystemType::Shared, FileSystemType::Private] {
+            for block_size in [2048, 4096, 65536, MAX_BLOCK_SIZE] {
+                let system = new(magic, block_size).unwrap();
+                assert_eq!(system.block_size, block_size);
+            }
+            for block_size in [0, 100, 1024, 2047, 3000, 4097, MAX_BLOCK_SIZE * 2] {
+                let error = new(magic, block_size).unwrap_err();
+                assert!(matches!(error.downcast_ref::<RDFSError>(), Some(RDFSError::InvalidBlockSize(size)) if *size == block_size));
+            }
+        }
+    }
+
+    #[test]
+    fn checksum_test() {
+        let block = SuperBlock::new(FileSystemType::Shared, [255; 32], [1; 32], 16777216, 300, 12, 4096).unwrap();
+        let mut encoded = block.to_bytes();
+        assert_eq!(SuperBlock::from_bytes(&encoded).unwrap(), block);
+
+        // any flipped byte is caught, the signature included
+        for index in [20, 150, 231, SB_SIZE - 1] {
+            let mut corrupted = encoded.clone();
+            corrupted[index] ^= 0x01;
+            let error = SuperBlock::from_bytes(&corrupted).unwrap_err();
+            assert!(matches!(error.downcast_ref::<RDFSError>(), Some(RDFSError::CorruptSuperBlock)), "{index}");
+        }
+
+        // a new signature gets a new checksum
+        let mut signed = block.clone();
+        signed.add_signature([9; SIG_SIZE]);
+        assert_eq!(SuperBlock::from_bytes(&signed.to_bytes()).unwrap().signature, [9; SIG_SIZE]);
+
+        // version 6 drives have no checksum
+        let v6 = SuperBlock { version: 6, ..block.clone() }.resized(block.storage).unwrap();
+        encoded = v6.to_bytes();
+        assert_eq!(encoded.len(), SB_SIZE_V6);
+        assert!(!v6.has_backup());
+        encoded[20] ^= 0x01;
+        assert!(SuperBlock::from_bytes(&encoded).is_ok());
+    }
+
+    #[test]
+    fn stable_root_test() {
+        let block = SuperBlock::new(FileSystemType::Shared, [255; 32], [1; 32], 16777216, 300, 12, 4096).unwrap();
+        assert!(block.has_stable_root());
+        assert_eq!(block.block_index(block.inode_pointer).unwrap(), block.total_blocks - 1);
+
+        // the root may sit in any data block and keeps its index in a grown layout
+        let moved = SuperBlock {
+            inode_pointer: block.block_pointer(5).unwrap(),
+            ..block.clone()
+        };
+        moved.validate().unwrap();
+        let grown = moved.resized(2 * moved.storage).unwrap();
+        assert_eq!(grown.block_index(grown.inode_pointer).unwrap(), 5);
+        grown.validate().unwrap();
+
+        // but not off a block boundary or outside the data blocks, nor in another block before version 8
+        for inode_pointer in [moved.inode_pointer + 1, block.bitmaps_pointer] {
+            let error = SuperBlock {
+                inode_pointer,
+                ..block.clone()
+            }
+            .validate()
+            .unwrap_err();
+            assert!(matches!(error.downcast_ref::<RDFSError>(), Some(RDFSError::InconsistentSuperBlock(field)) if field == "inode_pointer"));
+        }
+        let error = SuperBlock { version: 7, ..moved }.validate().unwrap_err();
+        assert!(matches!(error.downcast_ref::<RDFSError>(), Some(RDFSError::InconsistentSuperBlock(field)) if field == "inode_pointer"));
     }
 }
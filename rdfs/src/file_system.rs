@@ -18,17 +18,24 @@
 //!
 //! ## File Layout (Abstracted)
 //! ```text
-//! [SuperBlock | NodesAddresses | BitmapsBlock (shared only) | DataBlocks... | InodeRoot]
+//! [SuperBlock | NodesAddresses | BitmapsBlock (shared only) | RefCountBlock (optional) | DataBlocks... | InodeRoot]
 //! ```
 //!
+//! A refcounted drive also keeps the `InodeDir` listing its snapshots in the data blocks,
+//! found through `SuperBlock::snapshot_pointer`.
+//!
 //! This structure allows deterministic offsets and fast access, while maintaining
 //! modular encoding of components.
 //!
 //! ## Usage
 //! Mount a drive and read a block:
-//! ```rust
+//! ```rust,no_run
+//! # use rdfs::prelude::*;
+//! # fn main() -> anyhow::Result<()> {
 //! let fs = RDFS::mount_drive("data/example.RDFS")?;
 //! let root_inode_block = fs.read_block(fs.system.inode_pointer)?;
+//! # Ok(())
+//! # }
 //! ```
 //!
 //! ## Compatibility
@@ -39,26 +46,261 @@
 //! Copyrights © 2025 RDFS Contributors. All rights reserved.
 
 #![allow(clippy::too_many_arguments)]
+use std::collections::{HashMap, HashSet};
 use std::fmt::Debug;
+use std::fs;
+use std::io::{self, ErrorKind, Read, Seek, SeekFrom, Write};
 use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex, PoisonError, RwLock};
 
 use crate::core::super_block::FileSystemType;
 
 use crate::core::addresses_block::AddressesBlock;
-use crate::core::bitmaps_block::BitmapsBlock;
-use crate::core::inode_block::{ContentName, FileContent, InodeDir};
+use crate::core::bitmaps_block::{AllocStrategy, BitmapsBlock};
+use crate::core::block_signature::verify_bytes;
+use crate::core::data_block::{DataBlock, EncryptionKey};
+use crate::core::inode_block::{
+    ContentName, DirContent, FileContent, InodeDir, InodeFile, InodeLinkedDir, InodeLinkedFile, InodeType, NamePolicy, validate_file_ranges,
+};
+use crate::core::journal_block::{JOURNAL_HEADER_SIZE, JOURNAL_LOG_SIZE, JournalBlock, JournalState, changed_runs};
+use crate::core::refcount_block::RefCountBlock;
 use crate::core::super_block::SuperBlock;
-use crate::utils::{bytes_to_hex, create_physical_file, current_time_as_u64, read_range, write_range};
+use crate::utils::{
+    DirCache, DriveHandle, DriveLock, LockMode, MappedDrive, bytes_to_hex, create_physical_file, create_physical_file_reserved,
+    current_time_millis_as_u64, read_range, sync_file, write_range,
+};
+#[cfg(feature = "async")]
+use crate::utils::{read_range_async, write_range_async};
 
-use super::constants::{Address, PK_SIZE, SIG_SIZE};
+use super::constants::{Address, PK_SIZE, RESERVED_DB, SB_SIZE, SB_VERSION, SIG_SIZE};
 use super::rdfs_errors::RDFSError;
-use anyhow::Result;
+use anyhow::{Result, anyhow};
+
+/// (pointer, data) writes staged by a transaction in order, see `RDFS::begin_txn`.
+pub type StagedWrites = Vec<(u64, Vec<u8>)>;
 
+/// What `scan_journal` finds in the journal log: the last record with its pointer, the
+/// pointer the next record goes to and the sequence it takes.
+type JournalScan = (Option<(u64, JournalBlock)>, u64, u64);
 
 #[derive(Debug, Clone)]
 pub struct RDFS {
     pub path: PathBuf,
     pub system: SuperBlock,
+    pub mapped: Option<Arc<RwLock<MappedDrive>>>, // memory mapped drive, `None` unless `with_mmap` is used
+    pub name_policy: NamePolicy,                  // how entry names collide, case sensitive unless `with_name_policy` is used
+    pub cache: Option<Arc<DirCache>>,             // parsed directory entries, `None` unless `with_cache` is used
+    pub sparse: bool,                             // all-zero blocks are stored as holes, `false` unless `with_sparse` is used
+    pub lock: Option<Arc<DriveLock>>,             // lock on the drive file, `None` unless mounted `mount_drive_shared`/`mount_drive_exclusive`
+    pub encryption_key: Option<Arc<EncryptionKey>>, // key of an encrypted drive, `None` unless `with_encryption_key` is used
+    pub available: Option<u64>,                   // bytes of the drive held locally, `None` (all of them) unless mounted with `mount_partial`
+    pub handle: Option<Arc<DriveHandle>>,         // drive file kept open, `None` unless `with_open_handle` is used
+    pub txn: Option<Arc<Mutex<StagedWrites>>>,    // writes staged until `commit_txn`, `None` unless the handle comes from `begin_txn`
+    pub journal_lock: Arc<Mutex<()>>,             // held by `write_journaled`, shared by the clones of a handle
+}
+
+/// Result of `RDFS::fsck`, every list holds block pointers in ascending order.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct FsckReport {
+    pub leaked: Vec<u64>,            // marked used in the bitmaps block but not referenced by any inode
+    pub double_referenced: Vec<u64>, // referenced more than once in the inode tree
+    pub dangling: Vec<u64>,          // outside [data_pointer, inode_pointer] or not aligned to a block
+    pub shared: Vec<u64>,            // data blocks reflinked by several files with `copy_file`, not a problem
+    pub miscounted: Vec<u64>,        // refcount disagrees with the referents in the inode tree (refcounted drives only)
+    pub repaired: bool,              // leaked bits were cleared or wrong counts fixed, and persisted
+}
+
+/// Metadata of a file or directory inode, returned by `RDFS::stat`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Stat {
+    pub inode: u64, // pointer of the inode block, used as the inode id
+    pub inode_type: InodeType,
+    pub name: String,
+    pub created: u64,
+    pub modify: u64,
+    pub size: u64, // payload bytes of a file, number of entries of a directory
+    pub total_blocks: u64,
+    pub links: u64,             // directory entries referencing a file (see `RDFS::link`), 1 for a directory
+    pub content_entries: usize, // directory entries or file ranges, linked inode blocks included
+    pub linked: bool,           // part of the content is stored in linked inode blocks
+}
+
+/// Utilization of a whole drive, returned by `RDFS::stats`. Byte figures count block payloads
+/// (`SuperBlock::data_payload_size`), what files can actually hold. Private drives have no
+/// bitmaps block, their utilization is unknown and left `None`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct DriveStats {
+    pub total_blocks: u64,
+    pub free_blocks: Option<u64>,
+    pub used_blocks: Option<u64>,
+    pub bytes_used: Option<u64>,
+    pub bytes_free: Option<u64>,
+    pub efficiency: f64,            // percent of the node storage usable as payload, see `SuperBlock::efficiency`
+    pub fragmentation: Option<f64>, // share of the free blocks outside the largest free run, 0 when it holds them all
+}
+
+/// A frozen copy of the tree recorded by `RDFS::snapshot`, returned by `RDFS::list_snapshots`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Snapshot {
+    pub pointer: u64, // pointer of the snapshot root `InodeDir`, used as the snapshot id
+    pub label: String,
+    pub created: u64,
+}
+
+/// Handle on a file inode returned by `RDFS::open`, so the file can be used wherever
+/// `Read`, `Seek` or `Write` is expected (`io::copy`, serde readers...).
+///
+/// Reads go through `read_file_range` one data block at a time, a read at or past the end
+/// of the file returns 0 bytes like any file, and seeking past it is allowed. Writes always
+/// append, like a file opened with `O_APPEND`: they are buffered until a block payload is
+/// full and then written with `append_to_file`, `flush` (or dropping the handle) writes the
+/// rest. Errors of the drive are returned as `io::Error`s wrapping the `RDFSError`.
+#[derive(Debug)]
+pub struct RdfsFile<'a> {
+    rdfs: &'a RDFS,
+    inode_pointer: u64,
+    position: u64,
+    read_buffer: Vec<u8>, // payload of the block starting at `buffered_from`
+    buffered_from: u64,
+    write_buffer: Vec<u8>, // appended bytes not written yet
+}
+
+/// Fluent alternative to the positional `RDFS::new`, returned by `RDFS::builder`. Unset
+/// fields default to a refcounted shared drive of 1MB on a single node, with redundancy 100
+/// and 4096-byte blocks, `owner`/`program_id` all zeros, and the file `drive_file_name` which
+/// must not exist yet.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DriveBuilder {
+    magic: FileSystemType,
+    owner: Address,
+    program_id: Address,
+    storage: u64,
+    redundancy: u64,
+    nodes: u64,
+    block_size: u64,
+    file_name: Option<String>,
+    overwrite: bool,
+    encrypted: bool,
+    refcounted: bool,
+}
+
+impl Default for DriveBuilder {
+    fn default() -> Self {
+        Self {
+            magic: FileSystemType::Shared,
+            owner: [0; PK_SIZE],
+            program_id: [0; PK_SIZE],
+            storage: 1048576,
+            redundancy: 100,
+            nodes: 1,
+            block_size: 4096,
+            file_name: None,
+            overwrite: false,
+            encrypted: false,
+            refcounted: true,
+        }
+    }
+}
+
+impl DriveBuilder {
+    pub fn magic(mut self, magic: FileSystemType) -> Self {
+        self.magic = magic;
+        self
+    }
+
+    pub fn owner(mut self, owner: Address) -> Self {
+        self.owner = owner;
+        self
+    }
+
+    /// The program id also names the drive file unless `file_name` is set, see `drive_file_name`.
+    pub fn program_id(mut self, program_id: Address) -> Self {
+        self.program_id = program_id;
+        self
+    }
+
+    /// Total storage in bytes, spread over every node.
+    pub fn storage(mut self, storage: u64) -> Self {
+        self.storage = storage;
+        self
+    }
+
+    /// Stored size in percent of the data, at least 100.
+    pub fn redundancy(mut self, redundancy: u64) -> Self {
+        self.redundancy = redundancy;
+        self
+    }
+
+    pub fn nodes(mut self, nodes: u64) -> Self {
+        self.nodes = nodes;
+        self
+    }
+
+    pub fn block_size(mut self, block_size: u64) -> Self {
+        self.block_size = block_size;
+        self
+    }
+
+    /// Name of the drive file inside the directory given to `build`.
+    pub fn file_name(mut self, file_name: &str) -> Self {
+        self.file_name = Some(file_name.to_string());
+        self
+    }
+
+    /// Replaces an existing drive file instead of failing with `AlreadyExists`.
+    pub fn overwrite(mut self, overwrite: bool) -> Self {
+        self.overwrite = overwrite;
+        self
+    }
+
+    /// Flags a private drive as encrypted, see `SuperBlock::with_encryption`. Ignored for
+    /// shared drives. Open the built drive `with_encryption_key` to read and write its blocks.
+    pub fn encrypted(mut self, encrypted: bool) -> Self {
+        self.encrypted = encrypted;
+        self
+    }
+
+    /// Lays a shared drive out with a refcount table like `RDFS::new_refcounted`, which
+    /// `copy_file` reflinks and `snapshot` need. On by default, ignored for private drives.
+    pub fn refcounted(mut self, refcounted: bool) -> Self {
+        self.refcounted = refcounted;
+        self
+    }
+
+    /// Checks the parameters with `SuperBlock::check_parameters`, then creates the drive in
+    /// the directory `path` with `RDFS::create_drive`. Nothing is written when a check fails.
+    pub fn build<P: AsRef<Path>>(self, path: P) -> Result<RDFS> {
+        let Self {
+            magic,
+            owner,
+            program_id,
+            storage,
+            redundancy,
+            nodes,
+            block_size,
+            file_name,
+            overwrite,
+            encrypted,
+            refcounted,
+        } = self;
+        SuperBlock::check_parameters(storage, redundancy, nodes, block_size)?;
+        let file = path.as_ref().join(file_name.unwrap_or_else(|| drive_file_name(&program_id)));
+        let mut super_block = SuperBlock::new(magic, owner, program_id, storage, redundancy, nodes, block_size)?;
+        if encrypted {
+            super_block = super_block.with_encryption();
+        }
+        if refcounted {
+            super_block = super_block.with_refcounts()?;
+        }
+        RDFS::create_drive(file, super_block, overwrite)
+    }
+}
+
+impl FsckReport {
+    /// Returns `true` if no problem was found.
+    pub fn is_clean(&self) -> bool {
+        self.leaked.is_empty() && self.double_referenced.is_empty() && self.dangling.is_empty() && self.miscounted.is_empty()
+    }
 }
 
 impl RDFS {
@@ -78,7 +320,13 @@ impl RDFS {
         }
     }
 
+    /// Starts a `DriveBuilder`, a named and checked alternative to `new`.
+    pub fn builder() -> DriveBuilder {
+        DriveBuilder::default()
+    }
+
     /// Creates a new shared RDFS object with the given parameters.
+    /// The drive file is preallocated, see `create_physical_file_reserved`.
     pub fn new_shared<P: AsRef<Path>>(
         path: P,
         magic: FileSystemType,
@@ -90,29 +338,96 @@ impl RDFS {
         block_size: u64,
     ) -> Result<Self> {
         // Create the super block with the provided parameters
-        let timestamp = current_time_as_u64()?;
-        let super_block = SuperBlock::new(magic, owner, program_id, storage, redundancy, nodes, block_size);
+        let super_block = SuperBlock::new(magic, owner, program_id, storage, redundancy, nodes, block_size)?;
+        Self::create_drive(path.as_ref().join(drive_file_name(&program_id)), super_block, false)
+    }
+
+    /// Creates a new shared RDFS whose layout includes a `RefCountBlock`, so data blocks shared
+    /// by `copy_file` are counted and only freed once the last file referencing them is deleted.
+    /// The first data block holds the (empty) list of snapshots, see `snapshot`.
+    pub fn new_refcounted<P: AsRef<Path>>(
+        path: P,
+        owner: Address,
+        program_id: Address,
+        storage: u64,
+        redundancy: u64,
+        nodes: u64,
+        block_size: u64,
+    ) -> Result<Self> {
+        let super_block = SuperBlock::new(FileSystemType::Shared, owner, program_id, storage, redundancy, nodes, block_size)?.with_refcounts()?;
+        Self::create_drive(path.as_ref().join(drive_file_name(&program_id)), super_block, false)
+    }
+
+    /// Creates the drive laid out by `super_block`, shared or private, in the file `file`.
+    /// `new`, `new_shared`, `new_private` and `new_refcounted` name it `<program_id as hex>.RDFS`
+    /// in their directory, a custom name lets several drives of one program sit side by side.
+    /// An existing file is refused with `AlreadyExists` unless `overwrite` is set, it is then
+    /// replaced as a whole so nothing of the old drive (like a pending journal) survives.
+    /// The drive file is preallocated, see `create_physical_file_reserved`, and ends with the
+    /// super block backup (see `SuperBlock::has_backup`).
+    pub fn create_drive<P: AsRef<Path>>(file: P, mut super_block: SuperBlock, overwrite: bool) -> Result<Self> {
+        let path = file.as_ref().to_path_buf();
+        if path.exists() {
+            if !overwrite {
+                return Err(RDFSError::AlreadyExists(path.display().to_string()).into());
+            }
+            fs::remove_file(&path)?;
+        }
+
+        let timestamp = current_time_millis_as_u64()?;
         let addresses_block = AddressesBlock::new(vec![[0; PK_SIZE]; super_block.nodes as usize], [0; SIG_SIZE]);
+        let shared = super_block.magic == FileSystemType::Shared;
         let mut bitmaps_block = BitmapsBlock::new(super_block.total_blocks, timestamp);
-        let root_inode = InodeDir::new(ContentName::new("./"), timestamp, 0, super_block.total_blocks, vec![], 0);
-        bitmaps_block.set_bit(super_block.total_blocks as usize - 1); // Set the last block for root inode
-
-        // Create the file name based on the program ID
-        let path = Path::new(path.as_ref()).join(&(bytes_to_hex(&program_id) + ".RDFS"));
-        let size = super_block.node_storage;
+        if shared {
+            bitmaps_block.set_bit(super_block.block_index(super_block.inode_pointer)? as usize); // the root inode, the last block of a new drive
+        }
+        if shared && super_block.has_refcounts() && super_block.version >= 3 {
+            bitmaps_block.set_bit(0);
+            super_block.snapshot_pointer = super_block.block_pointer(0)?;
+        }
 
-        create_physical_file(&path, size)?;
+        create_physical_file_reserved(&path, super_block.node_storage + super_block.backup_size())?;
         write_range(&path, 0, &super_block.to_bytes())?;
+        if super_block.has_backup() {
+            write_range(&path, super_block.node_storage, &super_block.to_bytes())?;
+        }
         write_range(&path, super_block.nodes_address_pointer, &addresses_block.to_bytes())?;
-        write_range(&path, super_block.bitmaps_pointer, &bitmaps_block.to_bytes())?;
-        write_range(&path, super_block.inode_pointer, &root_inode.to_bytes(super_block.block_size as usize))?;
+        if shared {
+            let block_size = super_block.block_size as usize;
+            write_range(&path, super_block.bitmaps_pointer, &bitmaps_block.to_bytes())?;
+            if super_block.has_refcounts() {
+                let mut refcounts = RefCountBlock::new(super_block.total_blocks, timestamp);
+                refcounts.sync_with(&bitmaps_block);
+                write_range(&path, super_block.refcount_pointer, &refcounts.to_bytes())?;
+            }
+            if super_block.snapshot_pointer != 0 {
+                let snapshots = InodeDir::new(ContentName::new(".snapshots"), timestamp, 0, 1, vec![], 0);
+                write_range(&path, super_block.snapshot_pointer, &snapshots.to_bytes(block_size))?;
+            }
+            let root_inode = InodeDir::new(ContentName::new("./"), timestamp, 0, super_block.total_blocks, vec![], 0);
+            write_range(&path, super_block.inode_pointer, &root_inode.to_bytes(block_size))?;
+        }
 
-        let rdfs = Self { path, system: super_block };
+        let rdfs = Self {
+            path,
+            system: super_block,
+            mapped: None,
+            handle: None,
+            name_policy: NamePolicy::default(),
+            cache: None,
+            sparse: false,
+            lock: None,
+            encryption_key: None,
+            available: None,
+            txn: None,
+            journal_lock: Arc::default(),
+        };
 
         Ok(rdfs)
     }
 
     /// Creates a new private RDFS object with the given parameters.
+    /// The drive file is preallocated, see `create_physical_file_reserved`.
     pub fn new_private<P: AsRef<Path>>(
         path: P,
         magic: FileSystemType,
@@ -124,34 +439,176 @@ impl RDFS {
         block_size: u64,
     ) -> Result<Self> {
         // Create the super block with the provided parameters
-        let super_block = SuperBlock::new(magic, owner, program_id, storage, redundancy, nodes, block_size);
-        let addresses_block = AddressesBlock::new(vec![[0; PK_SIZE]; super_block.nodes as usize], [0; SIG_SIZE]);
+        let super_block = SuperBlock::new(magic, owner, program_id, storage, redundancy, nodes, block_size)?;
+        Self::create_drive(path.as_ref().join(drive_file_name(&program_id)), super_block, false)
+    }
 
-        // Create the file name based on the program ID
-        let path = Path::new(path.as_ref()).join(&(bytes_to_hex(&program_id) + ".RDFS"));
-        let size = super_block.node_storage;
+    /// Mounts an existing drive, refusing a super block that fails `SuperBlock::validate`.
+    /// A pending journal record left by a crash is replayed first, see `recover_journal`.
+    /// A primary super block failing its checksum or `validate` is replaced by the backup at
+    /// the end of the file when it is valid, see `repair_superblock`. A shared handle can't
+    /// write, it mounts from the backup and leaves the primary damaged.
+    pub fn mount_drive<P: AsRef<Path>>(path: P) -> Result<Self> {
+        Self::mount(path.as_ref(), None)
+    }
 
-        create_physical_file(&path, size)?;
-        write_range(&path, 0, &super_block.to_bytes())?;
-        write_range(&path, super_block.nodes_address_pointer, &addresses_block.to_bytes())?;
+    /// Same as `mount_drive`, holding an exclusive lock on the drive file as long as the
+    /// handle or one of its clones lives. Fails with `DriveLocked` if another handle holds
+    /// any lock on the drive, and while it lives no other handle can lock or write it.
+    pub fn mount_drive_exclusive<P: AsRef<Path>>(path: P) -> Result<Self> {
+        let lock = DriveLock::acquire(&path, LockMode::Exclusive)?;
+        Self::mount(path.as_ref(), Some(Arc::new(lock)))
+    }
 
-        let rdfs = Self { path, system: super_block };
+    /// Same as `mount_drive`, holding a shared lock on the drive file as long as the handle
+    /// or one of its clones lives. Any number of shared handles can read the drive together,
+    /// but none of them can write, their writes fail with `DriveLocked`. A drive with a
+    /// pending journal record must be mounted exclusively first, to replay it.
+    pub fn mount_drive_shared<P: AsRef<Path>>(path: P) -> Result<Self> {
+        let lock = DriveLock::acquire(&path, LockMode::Shared)?;
+        Self::mount(path.as_ref(), Some(Arc::new(lock)))
+    }
 
+    fn mount(path: &Path, lock: Option<Arc<DriveLock>>) -> Result<Self> {
+        let primary = read_range(path, 0, SB_SIZE as u64)?;
+        let (system, from_backup) = match SuperBlock::from_bytes(&primary).and_then(|system| system.validate().map(|()| system)) {
+            Ok(system) => (system, false),
+            // no backup to fall back on, the primary is validated once the journal is replayed
+            Err(_) => match Self::read_super_block_backup(path) {
+                Ok(backup) => (backup, true),
+                Err(_) => (SuperBlock::from_bytes(&primary)?, false),
+            },
+        };
+        let writable = lock.as_deref().is_none_or(|lock| lock.mode() == LockMode::Exclusive);
+        let mut rdfs = Self {
+            path: path.to_path_buf(),
+            system,
+            mapped: None,
+            handle: None,
+            name_policy: NamePolicy::default(),
+            cache: None,
+            sparse: false,
+            lock,
+            encryption_key: None,
+            available: None,
+            txn: None,
+            journal_lock: Arc::default(),
+        };
+        if from_backup && writable {
+            rdfs.repair_superblock()?;
+        }
+        // the replayed record may hold a newer super block
+        if rdfs.recover_journal()? {
+            rdfs.system = SuperBlock::from_bytes(&read_range(path, 0, SB_SIZE as u64)?)?;
+        }
+        rdfs.system.validate()?;
         Ok(rdfs)
     }
 
-    pub fn mount_drive<P: AsRef<Path>>(path: P) -> Result<Self> {
-        let super_block = SuperBlock::from_bytes(&read_range(&path, 0, 256)?)?;
+    /// Mounts a drive file holding only the start of the drive, e.g. on a node that received
+    /// part of it over the network, and records how many bytes it holds in `available`.
+    /// Only the super block must be complete. Reads reaching past the end of the file fail
+    /// with `BlockNotAvailable` instead of an I/O error, so whatever is present can still be
+    /// inspected. The journal is not replayed, the handle is meant for reading.
+    pub fn mount_partial<P: AsRef<Path>>(path: P) -> Result<Self> {
+        let path = path.as_ref();
+        let available = fs::metadata(path)?.len();
+        let system = SuperBlock::from_bytes(&read_range(path, 0, available.min(SB_SIZE as u64))?)?;
+        system.validate()?;
+
         Ok(Self {
-            path: path.as_ref().to_path_buf(),
-            system: super_block,
+            path: path.to_path_buf(),
+            system,
+            mapped: None,
+            handle: None,
+            name_policy: NamePolicy::default(),
+            cache: None,
+            sparse: false,
+            lock: None,
+            encryption_key: None,
+            available: Some(available),
+            txn: None,
+            journal_lock: Arc::default(),
         })
     }
 
+    /// Reads only the magic word of the drive at `path`, a cheap way to classify drives
+    /// without mounting them. Files that are not RDFS drives return `InvalidMagicWord`.
+    pub fn peek_type<P: AsRef<Path>>(path: P) -> Result<FileSystemType> {
+        let mut magic = Vec::with_capacity(8);
+        fs::File::open(path)?.take(8).read_to_end(&mut magic)?;
+        if magic.len() < 8 {
+            return Err(RDFSError::InvalidMagicWord.into());
+        }
+        FileSystemType::from_bytes(&magic)
+    }
+
+    /// Switches the drive to mapped mode, the file is memory mapped once and every
+    /// block read or write goes through the mapping instead of opening the file again.
+    /// Writes reach the disk on `flush` or `unmount_drive`.
+    pub fn with_mmap(mut self) -> Result<Self> {
+        self.mapped = Some(Arc::new(RwLock::new(MappedDrive::open(&self.path)?)));
+        Ok(self)
+    }
+
+    /// Keeps the drive file open for the lifetime of the drive (and its clones) and routes
+    /// every block read and write through that handle with positioned I/O, instead of
+    /// opening the file again for each block. Writes go straight to the file, unlike
+    /// `with_mmap` nothing waits for a `flush`. Mapped mode takes precedence when both are set.
+    pub fn with_open_handle(mut self) -> Result<Self> {
+        self.handle = Some(Arc::new(DriveHandle::open(&self.path)?));
+        Ok(self)
+    }
+
+    /// Sets how entry names are compared by lookups and by the collision checks of
+    /// `mkdir`, `create_file`, `write_file_streaming` and `rename`.
+    pub fn with_name_policy(mut self, policy: NamePolicy) -> Self {
+        self.name_policy = policy;
+        self
+    }
+
+    /// Caches the entries of every directory looked up by `resolve_path`, `list_dir` and the
+    /// name checks, so repeated lookups in the same directory skip reading its inode blocks
+    /// and the inode of every child. A directory is filled in on its first lookup.
+    ///
+    /// Coherence: every directory write goes through `write_inode_dir` (`add_dir_entry`,
+    /// `remove_dir_entry`, `mkdir`, snapshots...) and invalidates that directory, `rename`
+    /// also invalidates the source directory since the renamed inode holds the name, and
+    /// `write_journaled` and `resize_drive` drop the whole cache. Bytes written to an inode
+    /// block through `write_block` bypass the cache, call `DirCache::clear` after them.
+    pub fn with_cache(mut self) -> Self {
+        self.cache = Some(Arc::default());
+        self
+    }
+
+    /// Stores every full all-zero block written by `create_file` and `write_file_streaming`
+    /// as a sparse hole, a `FileContent` with pointer 0, instead of allocating it. Reads
+    /// materialize holes as zeros, so only the drive usage changes. Holes are per file and
+    /// kept by every later operation, whether or not the drive is opened sparse again.
+    pub fn with_sparse(mut self) -> Self {
+        self.sparse = true;
+        self
+    }
+
+    /// Sets the key `read_data_block` and `write_data_block` use on an encrypted drive. The
+    /// key isn't checked here, a wrong one fails the first read with `EncryptionFailed`.
+    pub fn with_encryption_key(mut self, key: EncryptionKey) -> Self {
+        self.encryption_key = Some(Arc::new(key));
+        self
+    }
+
+    /// Writes pending changes of the mapped mode back to the drive file.
+    pub fn flush(&self) -> Result<()> {
+        match &self.mapped {
+            Some(mapped) => mapped.read().map_err(poisoned)?.flush(),
+            None => Ok(()),
+        }
+    }
+
     pub fn unmount_drive(self) -> Result<()> {
-        //! In this implementation, unmounting does not require any specific action.
+        //! Only the mapped mode needs to be flushed, other writes go directly to the file.
         //! However, maybe we will implement some necessary cleanup or finalization here.
-        Ok(())
+        self.flush()
     }
 
     pub fn read_super_block(&self) -> Vec<u8> {
@@ -162,7 +619,7 @@ impl RDFS {
         let start = self.system.nodes_address_pointer;
         let end = start + self.system.nodes_address_size;
 
-        read_range(&self.path, start, end)
+        self.read_drive_range(start, end)
     }
 
     /// used only in shared RDFS, using in private RDFS return an Error.
@@ -172,7 +629,7 @@ impl RDFS {
                 let start = self.system.bitmaps_pointer;
                 let end = start + self.system.bitmaps_size;
 
-                read_range(&self.path, start, end)
+                self.read_drive_range(start, end)
             }
             FileSystemType::Private => Err(RDFSError::NoBitmapsPrivateRDFS.into()),
         }
@@ -184,15 +641,10 @@ impl RDFS {
     /// it could be used in shared RDFS for retrieving specific `Inode`
     /// or specific block in private RDFS
     pub fn read_block(&self, pointer: u64) -> Result<Vec<u8>> {
-        if pointer < self.system.data_pointer {
-            return Err(RDFSError::PointerOutOfRange.into());
-        }
-        if (pointer - self.system.data_pointer) % self.system.block_size != 0 {
-            return Err(RDFSError::InvalidPointerAlignment.into());
-        }
+        self.system.block_index(pointer)?;
         let start = pointer;
         let end = pointer + self.system.block_size;
-        read_range(&self.path, start, end)
+        self.read_drive_range(start, end)
     }
 
     /// Reads multiple blocks from the file system based on the provided ranges.
@@ -200,34 +652,76 @@ impl RDFS {
     /// Returns an iterator over the read blocks as `Vec<u8>`.
     /// It has been designed in this way because the total requested data block
     /// will be much more larger than our memory, so you can iter on these blocks,
-    /// read it one by one and send it over network.
-    pub fn read_blocks(&self, ranges: Vec<FileContent>) -> Box<dyn Iterator<Item = Vec<u8>>> {
-        let path = self.path.clone();
+    /// read it one by one and send it over network. Sparse holes are skipped, no block backs them.
+    /// A block that can not be read yields an `Err` item in its place without ending the
+    /// iteration, so the caller knows exactly which chunks are missing.
+    pub fn read_blocks(&self, ranges: Vec<FileContent>) -> Box<dyn Iterator<Item = Result<Vec<u8>>>> {
+        let rdfs = self.clone();
         let block_size = self.system.block_size;
 
-        let iter = ranges
-            .into_iter()
-            .flat_map(move |content| {
-                let path = path.clone(); // clone for move into closure
-                (0..content.blocks).map(move |block| {
-                    let start = content.pointer + block * block_size;
-                    let end = start + block_size;
-                    read_range(&path, start, end)
-                })
+        let iter = ranges.into_iter().filter(|content| !content.is_hole()).flat_map(move |content| {
+            let rdfs = rdfs.clone(); // clone for move into closure
+            (0..content.blocks).map(move |block| rdfs.read_block(content.pointer + block * block_size))
+        });
+
+        Box::new(iter)
+    }
+
+    /// Same as `read_blocks`, decoding each block as a `DataBlock` while streaming.
+    /// A block that can not be read or decoded yields an `Err` item without ending the
+    /// iteration, so a RaptorQ decoder can still use the blocks that are fine.
+    pub fn read_data_blocks(&self, ranges: Vec<FileContent>) -> Box<dyn Iterator<Item = Result<DataBlock>>> {
+        let rdfs = self.clone();
+        let block_size = self.system.block_size;
+
+        let iter = ranges.into_iter().filter(|content| !content.is_hole()).flat_map(move |content| {
+            let rdfs = rdfs.clone(); // clone for move into closure
+            (0..content.blocks).map(move |block| {
+                let pointer = content.pointer + block * block_size;
+                rdfs.read_data_block(pointer)
             })
-            .filter_map(Result::ok);
+        });
 
         Box::new(iter)
     }
 
+    /// Reads and decodes the `DataBlock` at `pointer`, decrypting it on an encrypted drive.
+    pub fn read_data_block(&self, pointer: u64) -> Result<DataBlock> {
+        let mut block = DataBlock::from_bytes(&self.read_block(pointer)?, self.system.block_size as usize)?;
+        if block.encrypted {
+            block.decrypt(self.key()?)?;
+        }
+        Ok(block)
+    }
+
+    /// Encodes `block` at `pointer`, encrypting a copy of it first on an encrypted drive.
+    /// A block already encrypted, e.g. to sign its ciphertext, is written as it is.
+    pub fn write_data_block(&self, pointer: u64, block: &DataBlock) -> Result<()> {
+        let block_size = self.system.block_size as usize;
+        if !self.system.is_encrypted() || block.encrypted {
+            return self.write_block(pointer, &block.to_bytes(block_size));
+        }
+        let mut block = block.clone();
+        block.encrypt(self.key()?)?;
+        self.write_block(pointer, &block.to_bytes(block_size))
+    }
+
+    fn key(&self) -> Result<&EncryptionKey> {
+        Ok(self.encryption_key.as_deref().ok_or(RDFSError::EncryptionKeyRequired)?)
+    }
+
     /// Updates the addresses block with the provided block.
     pub fn write_nodes_addresses(&self, data: &[u8]) -> Result<()> {
         let address = AddressesBlock::from_bytes(data, self.system.nodes_address_size as usize)?;
         if address.addresses.len() != self.system.nodes as usize {
-            return Err(RDFSError::InvalidAddressBlockLength.into());
+            return Err(RDFSError::InvalidAddressBlockLength {
+                got: address.addresses.len(),
+                expected: self.system.nodes as usize,
+            }
+            .into());
         }
 
-        write_range(&self.path, self.system.nodes_address_pointer, data)
+        self.write_drive_range(self.system.nodes_address_pointer, data)
     }
 
     /// Update the bitmaps block with the provided block.
@@ -235,23 +729,4795 @@ impl RDFS {
         match self.system.magic {
             FileSystemType::Shared => {
                 let bitmaps = BitmapsBlock::from_bytes(data, self.system.bitmaps_size as usize)?;
-                if (bitmaps.total_blocks != self.system.total_blocks) | (bitmaps.bit_field.len() != self.system.total_blocks as usize) {
-                    return Err(RDFSError::InvalidBitmapsBlockLength.into());
+                if bitmaps.total_blocks != self.system.total_blocks {
+                    return Err(RDFSError::InvalidBitmapsBlockLength {
+                        got: bitmaps.total_blocks as usize,
+                        expected: self.system.total_blocks as usize,
+                    }
+                    .into());
+                }
+                if bitmaps.bit_field.len() != self.system.total_blocks.div_ceil(8) as usize {
+                    return Err(RDFSError::InvalidBitmapsBlockLength {
+                        got: bitmaps.bit_field.len(),
+                        expected: self.system.total_blocks.div_ceil(8) as usize,
+                    }
+                    .into());
                 }
 
-                write_range(&self.path, self.system.bitmaps_pointer, data)
+                self.write_drive_range(self.system.bitmaps_pointer, data)
             }
             FileSystemType::Private => Err(RDFSError::NoBitmapsPrivateRDFS.into()),
         }
     }
 
     pub fn write_block(&self, pointer: u64, data: &[u8]) -> Result<()> {
-        if pointer < self.system.data_pointer {
-            return Err(RDFSError::PointerOutOfRange.into());
+        self.system.block_index(pointer)?;
+        self.write_drive_range(pointer, data)
+    }
+
+    /// Async version of `read_block` for serving many chunk requests concurrently without
+    /// blocking a thread per request, available with the `async` feature.
+    /// In mapped mode the block is copied from the mapping directly.
+    #[cfg(feature = "async")]
+    pub async fn read_block_async(&self, pointer: u64) -> Result<Vec<u8>> {
+        self.system.block_index(pointer)?;
+        let end = pointer + self.system.block_size;
+        match self.mapped {
+            Some(_) => self.read_drive_range(pointer, end),
+            None => read_range_async(&self.path, pointer, end).await,
         }
-        if (pointer - self.system.data_pointer) % self.system.block_size != 0 {
-            return Err(RDFSError::InvalidPointerAlignment.into());
+    }
+
+    /// Async version of `write_block`, available with the `async` feature.
+    /// In mapped mode the block is copied into the mapping directly.
+    #[cfg(feature = "async")]
+    pub async fn write_block_async(&self, pointer: u64, data: &[u8]) -> Result<()> {
+        self.system.block_index(pointer)?;
+        match self.mapped {
+            Some(_) => self.write_drive_range(pointer, data),
+            None => {
+                let _guard = self.write_guard()?;
+                write_range_async(&self.path, pointer, data).await
+            }
+        }
+    }
+
+    /// Writes `data` at `pointer` so that a crash leaves either the old or the new content,
+    /// see `write_journaled`.
+    pub fn write_atomic(&self, pointer: u64, data: &[u8]) -> Result<()> {
+        self.write_journaled(vec![(pointer, data.to_vec())])
+    }
+
+    /// Writes the in-memory super block and `bitmaps` together through the journal,
+    /// so they can not disagree after a crash.
+    /// used only in shared RDFS, using in private RDFS return an Error.
+    pub fn write_metadata_atomic(&self, bitmaps: &BitmapsBlock) -> Result<()> {
+        if self.system.magic == FileSystemType::Private {
+            return Err(RDFSError::NoBitmapsPrivateRDFS.into());
+        }
+        let encoded = bitmaps.to_bytes();
+        if encoded.len() as u64 != self.system.bitmaps_size {
+            return Err(RDFSError::InvalidBitmapsBlockLength {
+                got: encoded.len(),
+                expected: self.system.bitmaps_size as usize,
+            }
+            .into());
+        }
+        self.write_journaled(vec![(0, self.system.to_bytes()), (self.system.bitmaps_pointer, encoded)])
+    }
+
+    /// Applies every (pointer, data) write of `writes` all or nothing. The bytes they change
+    /// (see `journal_entries`) are recorded in a `JournalBlock` appended to the journal log
+    /// after the drive layout and synced, then written in place, and the record is marked
+    /// applied. The log and the recovery rule are documented in the `journal_block` module,
+    /// recovery runs on `mount_drive`. Writes changing nothing leave no record.
+    /// Clones of a handle share `journal_lock`, so their journaled writes never interleave;
+    /// handles mounted apart must not write the same drive at once.
+    /// On a transaction handle (see `begin_txn`) the writes join the transaction instead.
+    pub fn write_journaled(&self, writes: Vec<(u64, Vec<u8>)>) -> Result<()> {
+        for (pointer, data) in &writes {
+            if pointer.saturating_add(data.len() as u64) > self.system.node_storage {
+                return Err(RDFSError::InvalidJournalWrite {
+                    pointer: *pointer,
+                    length: data.len() as u64,
+                }
+                .into());
+            }
+        }
+
+        if let Some(txn) = &self.txn {
+            let mut staged = txn.lock().map_err(poisoned)?;
+            for (pointer, data) in writes {
+                stage_write(&mut staged, pointer, data);
+            }
+            return Ok(());
+        }
+
+        let _journal = self.journal_lock.lock().map_err(poisoned)?;
+        let entries = self.journal_entries(&writes)?;
+        if entries.is_empty() {
+            return Ok(());
+        }
+        let (_, tail, sequence) = self.scan_journal()?;
+        let mut journal = JournalBlock::new(sequence, entries);
+        // every record before this one is applied, a full log starts over
+        let log = self.system.node_storage;
+        let pointer = match tail + journal.encoded_len() > log + JOURNAL_LOG_SIZE {
+            true => log,
+            false => tail,
+        };
+        let end = pointer + journal.encoded_len() + self.system.backup_size();
+        if fs::metadata(&self.path)?.len() < end {
+            {
+                let _guard = self.write_guard()?;
+                create_physical_file(&self.path, end)?;
+            }
+            // the record is about to overwrite the backup, it moves to the new end of the file
+            self.refresh_super_block_backup()?;
+        }
+        self.write_drive_range(pointer, &journal.to_bytes())?;
+        self.sync()?;
+        self.apply_journal(pointer, &mut journal)
+    }
+
+    /// The entries of a record for `writes`: every write is split at the block boundaries
+    /// and cut down to its runs of changed bytes (see `changed_runs`), compared with what the
+    /// drive holds once the writes before it are applied.
+    fn journal_entries(&self, writes: &[(u64, Vec<u8>)]) -> Result<Vec<(u64, Vec<u8>)>> {
+        let mut entries = Vec::new();
+        for (index, (pointer, data)) in writes.iter().enumerate() {
+            let end = pointer + data.len() as u64;
+            let mut old = self.read_stored_range(*pointer, end)?;
+            overlay(&mut old, *pointer, &writes[..index]);
+            let mut start = *pointer;
+            while start < end {
+                // the regions before the data blocks count as a single block
+                let next = match start.checked_sub(self.system.data_pointer) {
+                    Some(offset) => start - offset % self.system.block_size + self.system.block_size,
+                    None => self.system.data_pointer,
+                };
+                let range = (start - pointer) as usize..(next.min(end) - pointer) as usize;
+                entries.extend(changed_runs(start, &old[range.clone()], &data[range]));
+                start = next.min(end);
+            }
+        }
+        Ok(entries)
+    }
+
+    /// Starts a transaction: returns a handle on the same drive whose writes are staged in
+    /// memory, and read back from there, instead of reaching the drive. `commit_txn` then
+    /// stores them as one `write_journaled` record, so a crash before the record is written
+    /// leaves the drive as it was and one after is replayed on the next mount. Dropping the
+    /// handle without committing discards the writes. The directory cache is bypassed while
+    /// the transaction is open, `commit_txn` drops it.
+    /// `create_file` and `delete_file` run in a transaction of their own, or join the one of
+    /// the handle they are called on, so several of them can be made atomic together.
+    /// Fails with `TransactionOpen` on a handle already in a transaction.
+    pub fn begin_txn(&self) -> Result<Self> {
+        if self.txn.is_some() {
+            return Err(RDFSError::TransactionOpen.into());
+        }
+        Ok(Self {
+            txn: Some(Arc::default()),
+            ..self.clone()
+        })
+    }
+
+    /// Stores every write staged on this handle since `begin_txn` all or nothing, see there.
+    /// A handle outside a transaction has nothing to commit.
+    pub fn commit_txn(mut self) -> Result<()> {
+        let Some(txn) = self.txn.take() else {
+            return Ok(());
+        };
+        let writes = std::mem::take(&mut *txn.lock().map_err(poisoned)?);
+        if writes.is_empty() {
+            return Ok(());
+        }
+        self.write_journaled(writes)
+    }
+
+    /// Runs `operation` in the transaction of this handle, or in a new one committed once it succeeded.
+    fn in_txn<T>(&self, operation: impl FnOnce(&Self) -> Result<T>) -> Result<T> {
+        if self.txn.is_some() {
+            return operation(self);
+        }
+        let txn = self.begin_txn()?;
+        let result = operation(&txn)?;
+        txn.commit_txn()?;
+        Ok(result)
+    }
+
+    /// Replays the last record of the journal log if it is still pending, which only happens
+    /// when the process died in the middle of `write_journaled`. Returns `true` if it was replayed.
+    pub fn recover_journal(&self) -> Result<bool> {
+        let _journal = self.journal_lock.lock().map_err(poisoned)?;
+        match self.scan_journal()?.0 {
+            Some((pointer, mut journal)) if journal.state == JournalState::Pending => {
+                self.apply_journal(pointer, &mut journal)?;
+                Ok(true)
+            }
+            _ => Ok(false),
+        }
+    }
+
+    /// Checks the primary super block and its backup at the end of the drive file (see
+    /// `SuperBlock::has_backup`) and rewrites the damaged one from the other. A primary failing
+    /// its checksum or `SuperBlock::validate` is rewritten from a valid backup and `true` is
+    /// returned, otherwise a missing, damaged or stale backup is rewritten from the primary.
+    /// Fails with `NoSuperBlockBackup` when neither copy is valid, and with
+    /// `UnsupportedSuperBlockVersion` on drives older than version 7, they have no backup.
+    /// `mount_drive` runs it when it had to mount from the backup.
+    pub fn repair_superblock(&self) -> Result<bool> {
+        if !self.system.has_backup() {
+            return Err(RDFSError::UnsupportedSuperBlockVersion(self.system.version).into());
+        }
+        let primary = self.read_drive_range(0, SB_SIZE as u64)?;
+        if decode_backup(&primary).is_some() {
+            let length = fs::metadata(&self.path)?.len();
+            let backup = match length.checked_sub(SB_SIZE as u64) {
+                Some(start) if start >= self.system.node_storage => self.read_drive_range(start, length)?,
+                _ => vec![],
+            };
+            if backup != primary {
+                self.write_super_block_backup(&primary)?;
+            }
+            return Ok(false);
+        }
+
+        let backup = Self::read_super_block_backup(&self.path)?;
+        self.write_drive_range(0, &backup.to_bytes())?;
+        self.sync()?;
+        Ok(true)
+    }
+
+    /// Upgrades a version 7 drive in place to `SB_VERSION`, which shares its layout: only the
+    /// super block and its backup are rewritten, through the journal, and the signature is
+    /// reset. From then on the root inode keeps its block index when the drive grows, see
+    /// `SuperBlock::has_stable_root`. Returns `false` for a drive already at `SB_VERSION`,
+    /// older drives have a shorter super block and fail with `UnsupportedSuperBlockVersion`.
+    pub fn upgrade_drive(&mut self) -> Result<bool> {
+        match self.system.version {
+            SB_VERSION => return Ok(false),
+            7 => {}
+            version => return Err(RDFSError::UnsupportedSuperBlockVersion(version).into()),
+        }
+        let mut upgraded = self.system.clone();
+        upgraded.version = SB_VERSION;
+        upgraded.signature = [0; SIG_SIZE];
+        upgraded.validate()?;
+        self.write_atomic(0, &upgraded.to_bytes())?;
+        self.system = upgraded;
+        Ok(true)
+    }
+
+    /// Reads the super block backup ending the drive file at `path`, failing with
+    /// `NoSuperBlockBackup` if the file holds none or a damaged one.
+    fn read_super_block_backup(path: &Path) -> Result<SuperBlock> {
+        let length = fs::metadata(path)?.len();
+        let backup = match length.checked_sub(SB_SIZE as u64) {
+            Some(start) => read_range(path, start, length)?,
+            None => vec![],
+        };
+        decode_backup(&backup).ok_or_else(|| RDFSError::NoSuperBlockBackup.into())
+    }
+
+    /// Writes `encoded` over the last `SB_SIZE` bytes of the drive file, growing it first if
+    /// it ends before the layout and the backup.
+    fn write_super_block_backup(&self, encoded: &[u8]) -> Result<()> {
+        let length = fs::metadata(&self.path)?.len();
+        let end = length.max(self.system.node_storage + SB_SIZE as u64);
+        if length < end {
+            let _guard = self.write_guard()?;
+            create_physical_file(&self.path, end)?;
+        }
+        self.write_drive_range(end - SB_SIZE as u64, encoded)
+    }
+
+    /// Copies the primary super block to the backup after it was written, unless the drive
+    /// has no backup or the primary isn't valid, so a damaged primary never overwrites it.
+    fn refresh_super_block_backup(&self) -> Result<()> {
+        if !self.system.has_backup() {
+            return Ok(());
+        }
+        let primary = self.read_drive_range(0, SB_SIZE as u64)?;
+        match decode_backup(&primary) {
+            Some(_) => self.write_super_block_backup(&primary),
+            None => Ok(()),
+        }
+    }
+
+    /// Walks the journal log from its start, see the `journal_block` module. Returns its last
+    /// record with the pointer it is stored at, the pointer of the next record and the
+    /// sequence it takes: one past every sequence met, a torn record's included, so numbers
+    /// never go back.
+    fn scan_journal(&self) -> Result<JournalScan> {
+        let length = fs::metadata(&self.path)?.len();
+        let mut pointer = self.system.node_storage;
+        let mut last: Option<(u64, JournalBlock)> = None;
+        let mut sequence = 1;
+        while pointer + JOURNAL_HEADER_SIZE as u64 <= length {
+            let header = self.read_drive_range(pointer, pointer + JOURNAL_HEADER_SIZE as u64)?;
+            let (Some(found), Some(payload_len)) = (
+                JournalBlock::sequence_from_header(&header),
+                JournalBlock::payload_len_from_header(&header),
+            ) else {
+                break;
+            };
+            // a record left from a previous round of the log
+            if last.as_ref().is_some_and(|(_, journal)| found != journal.sequence + 1) {
+                break;
+            }
+            sequence = sequence.max(found + 1);
+            let journal = match (pointer + JOURNAL_HEADER_SIZE as u64).checked_add(payload_len) {
+                Some(end) if end <= length => JournalBlock::from_bytes(&self.read_drive_range(pointer, end)?),
+                _ => None,
+            };
+            let Some(journal) = journal else {
+                break;
+            };
+            let next = pointer + journal.encoded_len();
+            last = Some((pointer, journal));
+            pointer = next;
         }
-        write_range(&self.path, pointer, data)
+        Ok((last, pointer, sequence))
+    }
+
+    /// Writes the entries of the record stored at `pointer` in place, then marks it applied.
+    fn apply_journal(&self, pointer: u64, journal: &mut JournalBlock) -> Result<()> {
+        for (pointer, data) in &journal.entries {
+            self.write_drive_range(*pointer, data)?;
+        }
+        // any entry may be a directory inode
+        if let Some(cache) = &self.cache {
+            cache.clear();
+        }
+        if journal.entries.iter().any(|(pointer, _)| *pointer < SB_SIZE as u64) {
+            self.refresh_super_block_backup()?;
+        }
+        self.sync()?;
+        journal.state = JournalState::Applied;
+        self.write_drive_range(pointer, &journal.header_bytes())?;
+        self.sync()
+    }
+
+    /// Makes every write so far durable, through the mapping in mapped mode.
+    fn sync(&self) -> Result<()> {
+        match (&self.mapped, &self.handle) {
+            (Some(mapped), _) => mapped.read().map_err(poisoned)?.flush(),
+            (None, Some(handle)) => handle.sync(),
+            (None, None) => sync_file(&self.path),
+        }
+    }
+
+    /// Reads `start..end` like `read_stored_range`, with the writes staged by an open
+    /// transaction (see `begin_txn`) applied over it.
+    fn read_drive_range(&self, start: u64, end: u64) -> Result<Vec<u8>> {
+        let mut data = self.read_stored_range(start, end)?;
+        if let Some(txn) = &self.txn {
+            overlay(&mut data, start, &txn.lock().map_err(poisoned)?);
+        }
+        Ok(data)
+    }
+
+    /// Reads `start..end` from the mapping in mapped mode, through the open handle with
+    /// `with_open_handle`, or from the file otherwise.
+    /// A mapping shorter than the requested range is refreshed first in case the file grew.
+    fn read_stored_range(&self, start: u64, end: u64) -> Result<Vec<u8>> {
+        if let Some(available) = self.available
+            && end > available
+        {
+            return Err(RDFSError::BlockNotAvailable { pointer: start, available }.into());
+        }
+        let Some(mapped) = &self.mapped else {
+            return match &self.handle {
+                Some(handle) => handle.read_range(start, end),
+                None => read_range(&self.path, start, end),
+            };
+        };
+
+        if end > mapped.read().map_err(poisoned)?.len() {
+            mapped.write().map_err(poisoned)?.remap()?;
+        }
+        mapped.read().map_err(poisoned)?.read_range(start, end)
+    }
+
+    /// Fails with `DriveLocked` unless this handle may write the drive: always with the
+    /// exclusive lock, never with a shared one, and without a lock as long as no other
+    /// handle holds the drive exclusively. The returned guard keeps it so until dropped.
+    fn write_guard(&self) -> Result<Option<DriveLock>> {
+        match self.lock.as_deref().map(DriveLock::mode) {
+            Some(LockMode::Exclusive) => Ok(None),
+            Some(LockMode::Shared) => Err(RDFSError::DriveLocked(self.path.display().to_string()).into()),
+            None => DriveLock::acquire(&self.path, LockMode::Shared).map(Some),
+        }
+    }
+
+    /// Writes `data` at `start` through the mapping in mapped mode, through the open handle
+    /// with `with_open_handle`, or to the file otherwise. In a transaction it is only staged.
+    /// Fails with `DriveLocked` when the lock of the drive forbids it, see `write_guard`.
+    fn write_drive_range(&self, start: u64, data: &[u8]) -> Result<()> {
+        let _guard = self.write_guard()?;
+        if let Some(txn) = &self.txn {
+            stage_write(&mut *txn.lock().map_err(poisoned)?, start, data.to_vec());
+            return Ok(());
+        }
+        let Some(mapped) = &self.mapped else {
+            return match &self.handle {
+                Some(handle) => handle.write_range(start, data),
+                None => write_range(&self.path, start, data),
+            };
+        };
+        let mut mapped = mapped.write().map_err(poisoned)?;
+
+        if start + data.len() as u64 > mapped.len() {
+            mapped.remap()?;
+        }
+        mapped.write_range(start, data)
+    }
+
+    /// Reads and decodes the bitmaps block.
+    /// used only in shared RDFS, using in private RDFS return an Error.
+    pub fn read_bitmaps_block(&self) -> Result<BitmapsBlock> {
+        BitmapsBlock::from_bytes(&self.read_bitmaps()?, self.system.bitmaps_size as usize)
+    }
+
+    /// Encodes and stores the bitmaps block. On a refcounted drive the refcount table is
+    /// aligned with it, see `RefCountBlock::sync_with`.
+    /// used only in shared RDFS, using in private RDFS return an Error.
+    pub fn write_bitmaps_block(&self, bitmaps: &BitmapsBlock) -> Result<()> {
+        self.write_bitmaps_with_refcounts(bitmaps, None)
+    }
+
+    /// Reads and decodes the refcount table, fails with `NoRefCountTable` if the drive has none.
+    pub fn read_refcount_block(&self) -> Result<RefCountBlock> {
+        if !self.system.has_refcounts() {
+            return Err(RDFSError::NoRefCountTable.into());
+        }
+        let start = self.system.refcount_pointer;
+        let data = self.read_drive_range(start, start + self.system.refcount_size)?;
+        RefCountBlock::from_bytes(&data, self.system.refcount_size as usize)
+    }
+
+    /// Encodes and stores the refcount table, fails with `NoRefCountTable` if the drive has none.
+    pub fn write_refcount_block(&self, refcounts: &RefCountBlock) -> Result<()> {
+        if !self.system.has_refcounts() {
+            return Err(RDFSError::NoRefCountTable.into());
+        }
+        if refcounts.total_blocks != self.system.total_blocks || refcounts.counts.len() as u64 != self.system.total_blocks {
+            return Err(RDFSError::InvalidRefCountBlockLength {
+                got: refcounts.counts.len(),
+                expected: self.system.total_blocks as usize,
+            }
+            .into());
+        }
+        self.write_drive_range(self.system.refcount_pointer, &refcounts.to_bytes())
+    }
+
+    /// The refcount table, `None` if the drive has none.
+    fn refcount_table(&self) -> Result<Option<RefCountBlock>> {
+        match self.system.has_refcounts() {
+            true => Ok(Some(self.read_refcount_block()?)),
+            false => Ok(None),
+        }
+    }
+
+    /// Stores `bitmaps`, aligning the refcount table with it first. `refcounts` holds the
+    /// counts changed by the caller, the table is read from the drive when it is `None`.
+    fn write_bitmaps_with_refcounts(&self, bitmaps: &BitmapsBlock, refcounts: Option<RefCountBlock>) -> Result<()> {
+        match refcounts {
+            Some(mut refcounts) => {
+                refcounts.sync_with(bitmaps);
+                self.write_refcount_block(&refcounts)?;
+            }
+            None if self.system.has_refcounts() => {
+                let mut refcounts = self.read_refcount_block()?;
+                if refcounts.sync_with(bitmaps) {
+                    self.write_refcount_block(&refcounts)?;
+                }
+            }
+            None => {}
+        }
+        self.write_bitmaps(&bitmaps.to_bytes())
+    }
+
+    pub fn read_inode_dir(&self, pointer: u64) -> Result<InodeDir> {
+        InodeDir::from_bytes(&self.read_block(pointer)?, self.system.block_size as usize)
+    }
+
+    /// Stores a directory inode, invalidating its cached entries.
+    pub fn write_inode_dir(&self, pointer: u64, inode: &InodeDir) -> Result<()> {
+        self.write_block(pointer, &inode.to_bytes(self.system.block_size as usize))?;
+        self.invalidate_dir(pointer);
+        Ok(())
+    }
+
+    pub fn read_linked_dir(&self, pointer: u64) -> Result<InodeLinkedDir> {
+        InodeLinkedDir::from_bytes(&self.read_block(pointer)?, self.system.block_size as usize)
+    }
+
+    pub fn write_linked_dir(&self, pointer: u64, inode: &InodeLinkedDir) -> Result<()> {
+        self.write_block(pointer, &inode.to_bytes(self.system.block_size as usize))
+    }
+
+    pub fn read_inode_file(&self, pointer: u64) -> Result<InodeFile> {
+        InodeFile::from_bytes(&self.read_block(pointer)?, self.system.block_size as usize)
+    }
+
+    /// Fails with `OverlappingFileContent` when the ranges of `inode` overlap or leave the data blocks.
+    pub fn write_inode_file(&self, pointer: u64, inode: &InodeFile) -> Result<()> {
+        inode.validate_ranges(&self.system)?;
+        self.write_block(pointer, &inode.to_bytes(self.system.block_size as usize))
+    }
+
+    pub fn read_linked_file(&self, pointer: u64) -> Result<InodeLinkedFile> {
+        InodeLinkedFile::from_bytes(&self.read_block(pointer)?, self.system.block_size as usize)
+    }
+
+    pub fn write_linked_file(&self, pointer: u64, inode: &InodeLinkedFile) -> Result<()> {
+        self.write_block(pointer, &inode.to_bytes(self.system.block_size as usize))
+    }
+
+    /// Reads the name block of a hard link, see `link`.
+    pub fn read_link_name(&self, pointer: u64) -> Result<ContentName> {
+        Ok(ContentName::from_bytes(&self.read_block(pointer)?))
+    }
+
+    fn write_link_name(&self, pointer: u64, name: &ContentName) -> Result<()> {
+        let mut block = name.to_bytes();
+        block.resize(self.system.block_size as usize, 0);
+        self.write_block(pointer, &block)
+    }
+
+    /// Resolves an absolute path like `/docs/2025/report.txt` starting from the root inode,
+    /// walking every directory (including its `InodeLinkedDir` blocks) segment by segment.
+    /// Returns the pointer of the final inode and its type.
+    /// used only in shared RDFS, using in private RDFS return an Error.
+    pub fn resolve_path(&self, path: &str) -> Result<(u64, InodeType)> {
+        if self.system.magic == FileSystemType::Private {
+            return Err(RDFSError::NoBitmapsPrivateRDFS.into());
+        }
+
+        let mut pointer = self.system.inode_pointer;
+        let mut inode_type = InodeType::Dir;
+        let mut walked = String::new();
+        for segment in path.split('/').filter(|segment| !segment.is_empty()) {
+            if inode_type != InodeType::Dir {
+                return Err(RDFSError::NotADirectory(walked).into());
+            }
+            walked = walked + "/" + segment;
+
+            let entry = self
+                .find_dir_entry(pointer, segment)?
+                .ok_or_else(|| RDFSError::PathNotFound(walked.clone()))?;
+            pointer = entry.pointer;
+            inode_type = entry.inode_type;
+        }
+
+        Ok((pointer, inode_type))
+    }
+
+    /// Returns `Ok(true)` if `path` resolves to a file or directory. A path that is missing or
+    /// goes through a file, `/report.txt/x`, is `Ok(false)`; every other failure, an I/O error,
+    /// a damaged inode or a private drive, is still an `Err`.
+    pub fn exists(&self, path: &str) -> Result<bool> {
+        match self.resolve_path(path) {
+            Ok(_) => Ok(true),
+            Err(error) => match error.downcast_ref::<RDFSError>() {
+                Some(RDFSError::PathNotFound(_) | RDFSError::NotADirectory(_)) => Ok(false),
+                _ => Err(error),
+            },
+        }
+    }
+
+    /// Resolves `path` and returns the `Stat` of its inode, `PathNotFound` naming the first
+    /// missing segment when it doesn't exist.
+    /// used only in shared RDFS, using in private RDFS return an Error.
+    pub fn metadata_for_path(&self, path: &str) -> Result<Stat> {
+        let (pointer, inode_type) = self.resolve_path(path)?;
+        self.stat(pointer, inode_type)
+    }
+
+    /// Returns every `DirContent` of the directory at `pointer`, following its `linked` chain.
+    pub fn read_dir_entries(&self, pointer: u64) -> Result<Vec<DirContent>> {
+        let inode = self.read_inode_dir(pointer)?;
+        let mut entries = inode.content;
+        let mut linked = inode.linked;
+        while linked != 0 {
+            let block = self.read_linked_dir(linked)?;
+            entries.extend(block.content);
+            linked = block.linked;
+        }
+        Ok(entries)
+    }
+
+    /// Lists the direct children of the directory at `inode_pointer` as (name, pointer, type),
+    /// including entries stored in its `InodeLinkedDir` blocks. Sub directories are not entered.
+    /// used only in shared RDFS, using in private RDFS return an Error.
+    pub fn list_dir(&self, inode_pointer: u64) -> Result<Vec<(String, u64, InodeType)>> {
+        if self.system.magic == FileSystemType::Private {
+            return Err(RDFSError::NoBitmapsPrivateRDFS.into());
+        }
+
+        let entries = self.read_dir_names(inode_pointer)?;
+        if let Some(cache) = self.dir_cache() {
+            cache_dir(cache, inode_pointer, &entries);
+        }
+        Ok(entries.into_iter().map(|(name, entry)| (name, entry.pointer, entry.inode_type)).collect())
+    }
+
+    /// Lists the direct children of the directory at `dir_pointer` whose name matches `pattern`
+    /// as (name, pointer, type), in entry order. `*` matches any run of characters and `?`
+    /// exactly one, see `ContentName::matches_glob`; names are compared in UTF-32 and only
+    /// the matches are turned into `String`s.
+    /// used only in shared RDFS, using in private RDFS return an Error.
+    pub fn glob(&self, dir_pointer: u64, pattern: &str) -> Result<Vec<(String, u64, InodeType)>> {
+        if self.system.magic == FileSystemType::Private {
+            return Err(RDFSError::NoBitmapsPrivateRDFS.into());
+        }
+
+        let mut matches = Vec::new();
+        for entry in self.read_dir_entries(dir_pointer)? {
+            let name = self.read_entry_name(&entry)?;
+            if name.matches_glob(pattern) {
+                matches.push((name.as_string(), entry.pointer, entry.inode_type));
+            }
+        }
+        Ok(matches)
+    }
+
+    /// Reads the direct children of the directory at `pointer` with their names, in order.
+    fn read_dir_names(&self, pointer: u64) -> Result<Vec<(String, DirContent)>> {
+        self.read_dir_entries(pointer)?
+            .into_iter()
+            .map(|entry| Ok((self.read_entry_name(&entry)?.as_string(), entry)))
+            .collect()
+    }
+
+    /// Walks the inode tree depth first from the root directory, then the snapshot list of a
+    /// refcounted drive, yielding every directory and file inode with its type. A directory
+    /// is yielded before its children, which come in entry order. `InodeLinkedDir` chains are
+    /// followed for their entries but not yielded.
+    ///
+    /// Every block is visited once, so a corrupted `linked` or entry pointer looping back
+    /// can't hang the walk. A pointer outside the data blocks or a directory that can't be
+    /// read yields an `Err` item and the walk goes on with the rest of the tree. A private drive has no inode tree, its walk yields a
+    /// single `NoBitmapsPrivateRDFS` error.
+    pub fn iter_inodes(&self) -> impl Iterator<Item = Result<(u64, InodeType)>> + '_ {
+        let mut stack = Vec::new();
+        let mut private = None;
+        match self.system.magic {
+            FileSystemType::Private => private = Some(RDFSError::NoBitmapsPrivateRDFS),
+            FileSystemType::Shared if self.system.snapshot_pointer != 0 => {
+                stack.push((self.system.snapshot_pointer, InodeType::Dir));
+                stack.push((self.system.inode_pointer, InodeType::Dir));
+            }
+            FileSystemType::Shared => stack.push((self.system.inode_pointer, InodeType::Dir)),
+        }
+        let mut visited = HashSet::new();
+
+        std::iter::from_fn(move || {
+            if let Some(error) = private.take() {
+                return Some(Err(error.into()));
+            }
+            loop {
+                let (pointer, inode_type) = stack.pop()?;
+                if !visited.insert(pointer) {
+                    continue;
+                }
+                if let Err(error) = self.system.block_index(pointer) {
+                    return Some(Err(error));
+                }
+                if inode_type == InodeType::Dir {
+                    match self.dir_entries_once(pointer, &mut visited) {
+                        Ok(entries) => stack.extend(entries.into_iter().rev().map(|entry| (entry.pointer, entry.inode_type))),
+                        Err(error) => return Some(Err(error)),
+                    }
+                }
+                return Some(Ok((pointer, inode_type)));
+            }
+        })
+    }
+
+    /// Same as `read_dir_entries`, stopping the linked chain at the first block in `visited`.
+    fn dir_entries_once(&self, pointer: u64, visited: &mut HashSet<u64>) -> Result<Vec<DirContent>> {
+        let inode = self.read_inode_dir(pointer)?;
+        let mut entries = inode.content;
+        let mut linked = inode.linked;
+        while linked != 0 && visited.insert(linked) {
+            let block = self.read_linked_dir(linked)?;
+            entries.extend(block.content);
+            linked = block.linked;
+        }
+        Ok(entries)
+    }
+
+    /// Reads the name stored in the inode at `pointer`.
+    pub fn read_inode_name(&self, pointer: u64, inode_type: InodeType) -> Result<ContentName> {
+        match inode_type {
+            InodeType::Dir => Ok(self.read_inode_dir(pointer)?.name),
+            InodeType::File => Ok(self.read_inode_file(pointer)?.name),
+        }
+    }
+
+    /// Reads the name `entry` goes by in its directory: the one of its name block for a hard
+    /// link added by `link`, the one stored in its inode otherwise.
+    pub fn read_entry_name(&self, entry: &DirContent) -> Result<ContentName> {
+        match entry.name {
+            0 => self.read_inode_name(entry.pointer, entry.inode_type),
+            name => self.read_link_name(name),
+        }
+    }
+
+    /// Combines the super block with the bitmaps block into the utilization of the drive. On
+    /// a private drive only `total_blocks` and `efficiency` are known.
+    pub fn stats(&self) -> Result<DriveStats> {
+        let mut stats = DriveStats {
+            total_blocks: self.system.total_blocks,
+            free_blocks: None,
+            used_blocks: None,
+            bytes_used: None,
+            bytes_free: None,
+            efficiency: self.system.efficiency(),
+            fragmentation: None,
+        };
+        if self.system.magic == FileSystemType::Private {
+            return Ok(stats);
+        }
+
+        let bitmaps = self.read_bitmaps_block()?;
+        let free = bitmaps.free_blocks;
+        let used = self.system.total_blocks - free;
+        let payload = self.system.data_payload_size();
+        let largest = bitmaps.free_run_histogram().last_key_value().map_or(0, |(&length, _)| length);
+        stats.free_blocks = Some(free);
+        stats.used_blocks = Some(used);
+        stats.bytes_used = Some(used * payload);
+        stats.bytes_free = Some(free * payload);
+        stats.fragmentation = Some(if free == 0 { 0.0 } else { 1.0 - largest as f64 / free as f64 });
+        Ok(stats)
+    }
+
+    /// Reads the inode at `inode_pointer` and returns its metadata, `content_entries`
+    /// counts the entries of the whole linked chain.
+    /// used only in shared RDFS, using in private RDFS return an Error.
+    pub fn stat(&self, inode_pointer: u64, inode_type: InodeType) -> Result<Stat> {
+        if self.system.magic == FileSystemType::Private {
+            return Err(RDFSError::NoBitmapsPrivateRDFS.into());
+        }
+
+        match inode_type {
+            InodeType::Dir => {
+                let inode = self.read_inode_dir(inode_pointer)?;
+                Ok(Stat {
+                    inode: inode_pointer,
+                    inode_type,
+                    name: inode.name.as_string(),
+                    created: inode.created,
+                    modify: inode.modify,
+                    size: inode.size,
+                    total_blocks: inode.total_blocks,
+                    links: 1,
+                    content_entries: self.read_dir_entries(inode_pointer)?.len(),
+                    linked: inode.linked != 0,
+                })
+            }
+            InodeType::File => {
+                let inode = self.read_inode_file(inode_pointer)?;
+                Ok(Stat {
+                    inode: inode_pointer,
+                    inode_type,
+                    name: inode.name.as_string(),
+                    created: inode.created,
+                    modify: inode.modify,
+                    size: inode.size,
+                    total_blocks: inode.total_blocks,
+                    links: inode.links,
+                    content_entries: self.collect_file_content(&inode)?.len(),
+                    linked: inode.linked != 0,
+                })
+            }
+        }
+    }
+
+    /// The directory cache to read through, none in a transaction whose staged entries must
+    /// not leak into it.
+    fn dir_cache(&self) -> Option<&Arc<DirCache>> {
+        self.cache.as_ref().filter(|_| self.txn.is_none())
+    }
+
+    /// Drops the cached entries of the directory at `pointer`, if the cache is enabled.
+    fn invalidate_dir(&self, pointer: u64) {
+        if let Some(cache) = &self.cache {
+            cache.invalidate(pointer);
+        }
+    }
+
+    /// Validates the name of a new child of the directory at `parent`, failing if it is
+    /// longer than 255 characters, holds a forbidden character (see `ContentName::validate`)
+    /// or is already taken by a sibling.
+    fn new_entry_name(&self, parent: u64, name: &str) -> Result<ContentName> {
+        let content_name = ContentName::try_new(name)?;
+        if self.find_dir_entry(parent, name)?.is_some() {
+            return Err(RDFSError::AlreadyExists(name.to_string()).into());
+        }
+        Ok(content_name)
+    }
+
+    /// Looks up the direct child called `name` inside the directory at `pointer`,
+    /// names are compared under `name_policy`. Served from the cache when enabled.
+    fn find_dir_entry(&self, pointer: u64, name: &str) -> Result<Option<DirContent>> {
+        if let Some(cache) = self.dir_cache() {
+            let index = match cache.get(pointer) {
+                Some(index) => index,
+                None => cache_dir(cache, pointer, &self.read_dir_names(pointer)?),
+            };
+            if self.name_policy == NamePolicy::CaseSensitive {
+                return Ok(index.get(name).cloned());
+            }
+            let found = index.iter().find(|(entry_name, _)| self.name_policy.matches(entry_name, name));
+            return Ok(found.map(|(_, entry)| entry.clone()));
+        }
+
+        for entry in self.read_dir_entries(pointer)? {
+            let entry_name = self.read_entry_name(&entry)?.as_string();
+            if self.name_policy.matches(&entry_name, name) {
+                return Ok(Some(entry));
+            }
+        }
+        Ok(None)
+    }
+
+    /// Creates an empty directory named `name` inside the directory at `parent_inode_pointer`,
+    /// its `InodeDir` takes one block from the bitmaps block.
+    /// Returns the pointer of the new directory inode.
+    /// used only in shared RDFS, using in private RDFS return an Error.
+    pub fn mkdir(&self, parent_inode_pointer: u64, name: &str) -> Result<u64> {
+        let name = self.new_entry_name(parent_inode_pointer, name)?;
+        let mut bitmaps = self.read_bitmaps_block()?;
+        let timestamp = current_time_millis_as_u64()?;
+
+        let inode_pointer = self.allocate_blocks(&mut bitmaps, 1)?[0].pointer;
+        self.write_inode_dir(inode_pointer, &InodeDir::new(name, timestamp, 0, 1, vec![], 0))?;
+
+        let entry = DirContent {
+            pointer: inode_pointer,
+            inode_type: InodeType::Dir,
+            name: 0,
+        };
+        self.add_dir_entry(&mut bitmaps, parent_inode_pointer, entry)?;
+        self.write_bitmaps_block(&bitmaps)?;
+
+        Ok(inode_pointer)
+    }
+
+    /// Creates a new file named `name` inside the directory at `parent_inode_pointer`.
+    /// Free blocks are taken from the bitmaps block, `data` is split into `DataBlock`s,
+    /// the `InodeFile` is stored in its own block and appended to the parent directory.
+    /// When the free blocks are not contiguous the file gets one `FileContent` per run,
+    /// spilling into `InodeLinkedFile` blocks once the inode is full.
+    /// On a sparse drive (`with_sparse`) full all-zero blocks become holes and take no space.
+    /// `data` larger than `SuperBlock::max_file_size` fails with `FileTooLarge`, a file that
+    /// only doesn't fit the free blocks with `NotEnoughSpace`.
+    /// The data blocks are written first, then the inode, the parent directory and the
+    /// bitmaps block in one transaction (see `begin_txn`), so a crash never leaves a half
+    /// created file behind.
+    /// Returns the pointer of the new file inode.
+    /// used only in shared RDFS, using in private RDFS return an Error.
+    pub fn create_file(&self, parent_inode_pointer: u64, name: &str, data: &[u8]) -> Result<u64> {
+        self.create_file_with_redundancy(parent_inode_pointer, name, data, None)
+    }
+
+    /// Same as `create_file`, storing `redundancy` in the inode to encode the RaptorQ chunks
+    /// of this file with instead of the drive's, see `file_redundancy`. `None` keeps the
+    /// drive's, an override below 100% or above `u32::MAX` fails with `InvalidRedundancy`.
+    pub fn create_file_with_redundancy(&self, parent_inode_pointer: u64, name: &str, data: &[u8], redundancy: Option<u64>) -> Result<u64> {
+        if let Some(redundancy) = redundancy.filter(|redundancy| !(100..=u32::MAX as u64).contains(redundancy)) {
+            return Err(RDFSError::InvalidRedundancy(redundancy).into());
+        }
+        self.check_file_size(data.len() as u64)?;
+        let name = self.new_entry_name(parent_inode_pointer, name)?;
+        let mut bitmaps = self.read_bitmaps_block()?;
+        let timestamp = current_time_millis_as_u64()?;
+        let block_size = self.system.block_size as usize;
+        let payload_size = block_size - RESERVED_DB;
+
+        // allocation is done on the in-memory bitmap first, so nothing is
+        // written to the drive if there is not enough space for the whole file.
+        let inode_pointer = self.allocate_blocks(&mut bitmaps, 1)?[0].pointer;
+        let data_blocks = data.len().div_ceil(payload_size) as u64;
+        let holes: Vec<bool> = data.chunks(payload_size).map(|chunk| self.is_hole(chunk)).collect();
+        let allocated = self.allocate_blocks(&mut bitmaps, holes.iter().filter(|hole| !**hole).count() as u64)?;
+
+        // data blocks
+        let mut pointers = self.block_pointers(&allocated);
+        let mut content = Vec::new();
+        for (chunk, hole) in data.chunks(payload_size).zip(holes) {
+            if hole {
+                push_run(&mut content, FileContent::hole(1), self.system.block_size);
+                continue;
+            }
+            let pointer = pointers.next().ok_or(RDFSError::NotEnoughSpace)?;
+            let block_number = self.system.block_index(pointer)?;
+            let block = DataBlock::new(block_number, timestamp, chunk);
+            self.write_block(pointer, &block.to_bytes(block_size))?;
+            push_run(&mut content, FileContent { pointer, blocks: 1 }, self.system.block_size);
+        }
+
+        // the data sits in blocks still free on the drive, the metadata lands all at once
+        let inode = InodeFile {
+            redundancy: redundancy.unwrap_or(0),
+            ..InodeFile::new(name, timestamp, data.len() as u64, data_blocks, vec![], 0)
+        };
+        self.in_txn(|txn| {
+            txn.write_file_inode(&mut bitmaps, inode_pointer, inode, content)?;
+            let entry = DirContent {
+                pointer: inode_pointer,
+                inode_type: InodeType::File,
+                name: 0,
+            };
+            txn.add_dir_entry(&mut bitmaps, parent_inode_pointer, entry)?;
+            txn.write_bitmaps_block(&bitmaps)
+        })?;
+
+        Ok(inode_pointer)
+    }
+
+    /// Creates a new file named `name` inside the directory at `parent` from `reader`, pulling
+    /// one block payload at a time so the content is never held in memory as a whole.
+    /// Each chunk gets its own freshly allocated block and extends the file ranges, adjacent
+    /// blocks merging into one `FileContent`, and on a sparse drive full all-zero chunks
+    /// become holes. The inode and its `InodeLinkedFile` chain are written once the reader is exhausted.
+    /// Allocations live in memory until then, so a read error, or a stream growing past
+    /// `SuperBlock::max_file_size` (`FileTooLarge`), leaves the drive untouched.
+    /// used only in shared RDFS, using in private RDFS return an Error.
+    pub fn write_file_streaming(&self, parent: u64, name: &str, mut reader: impl Read) -> Result<u64> {
+        let name = self.new_entry_name(parent, name)?;
+        let mut bitmaps = self.read_bitmaps_block()?;
+        let timestamp = current_time_millis_as_u64()?;
+        let block_size = self.system.block_size as usize;
+
+        let inode_pointer = self.allocate_blocks(&mut bitmaps, 1)?[0].pointer;
+        let mut content: Vec<FileContent> = Vec::new();
+        let mut buffer = vec![0; block_size - RESERVED_DB];
+        let mut size = 0;
+        let mut data_blocks = 0;
+        loop {
+            let length = read_chunk(&mut reader, &mut buffer)?;
+            if length == 0 {
+                break;
+            }
+            size += length as u64;
+            self.check_file_size(size)?;
+            data_blocks += 1;
+            if self.is_hole(&buffer[..length]) {
+                push_run(&mut content, FileContent::hole(1), self.system.block_size);
+                continue;
+            }
+
+            let pointer = self.allocate_blocks(&mut bitmaps, 1)?[0].pointer;
+            let block_number = self.system.block_index(pointer)?;
+            let block = DataBlock::new(block_number, timestamp, &buffer[..length]);
+            self.write_block(pointer, &block.to_bytes(block_size))?;
+            push_run(&mut content, FileContent { pointer, blocks: 1 }, self.system.block_size);
+        }
+
+        let inode = InodeFile::new(name, timestamp, size, data_blocks, vec![], 0);
+        self.write_file_inode(&mut bitmaps, inode_pointer, inode, content)?;
+
+        let entry = DirContent {
+            pointer: inode_pointer,
+            inode_type: InodeType::File,
+            name: 0,
+        };
+        self.add_dir_entry(&mut bitmaps, parent, entry)?;
+        self.write_bitmaps_block(&bitmaps)?;
+
+        Ok(inode_pointer)
+    }
+
+    /// Reads the whole file stored at `inode_pointer`, following its `linked` chain of
+    /// `InodeLinkedFile` blocks. The `DataBlock` metadata is stripped and only `size`
+    /// bytes of payload are returned, so padding of the last block is dropped. Sparse holes
+    /// are read as zeros.
+    pub fn read_file(&self, inode_pointer: u64) -> Result<Vec<u8>> {
+        let inode = self.read_inode_file(inode_pointer)?;
+        let content = self.collect_file_content(&inode)?;
+        let payload_size = self.system.block_size - RESERVED_DB as u64;
+
+        let mut data = Vec::with_capacity(inode.size as usize);
+        for slot in self.block_slots(&content) {
+            let remaining = inode.size - data.len() as u64;
+            if remaining == 0 {
+                return Err(RDFSError::InvalidFileSize.into());
+            }
+            let payload = self.read_payload(slot)?;
+            if payload.len() as u64 != remaining.min(payload_size) {
+                return Err(RDFSError::InvalidFileSize.into());
+            }
+            data.extend_from_slice(&payload);
+        }
+
+        if data.len() as u64 != inode.size {
+            return Err(RDFSError::InvalidFileSize.into());
+        }
+        Ok(data)
+    }
+
+    /// Reads `len` bytes of the file at `inode_pointer` starting at `offset`, like `pread`.
+    /// Only the data blocks covering `[offset, offset + len)` are read, the payloads of the
+    /// first and last ones being trimmed, and sparse holes are read as zeros. `len` is clamped
+    /// to the end of the file, an `offset` past `size` fails with `OffsetOutOfRange`.
+    pub fn read_file_range(&self, inode_pointer: u64, offset: u64, len: u64) -> Result<Vec<u8>> {
+        let inode = self.read_inode_file(inode_pointer)?;
+        if offset > inode.size {
+            return Err(RDFSError::OffsetOutOfRange { offset, size: inode.size }.into());
+        }
+        let end = offset + len.min(inode.size - offset);
+        if end == offset {
+            return Ok(vec![]);
+        }
+
+        let payload_size = self.system.block_size - RESERVED_DB as u64;
+        let first = offset / payload_size;
+        let last = (end - 1) / payload_size;
+        let content = self.collect_file_content(&inode)?;
+        let mut data = Vec::with_capacity((end - offset) as usize);
+        for (index, slot) in (first..=last).zip(self.block_slots(&content).skip(first as usize)) {
+            let payload = self.read_payload(slot)?;
+            let block_start = index * payload_size;
+            let from = offset.saturating_sub(block_start) as usize;
+            let to = (end - block_start).min(payload_size) as usize;
+            if payload.len() < to {
+                return Err(RDFSError::InvalidFileSize.into());
+            }
+            data.extend_from_slice(&payload[from..to]);
+        }
+
+        if data.len() as u64 != end - offset {
+            return Err(RDFSError::InvalidFileSize.into());
+        }
+        Ok(data)
+    }
+
+    /// Opens the file at `inode_pointer` as an `RdfsFile` positioned at its start.
+    /// used only in shared RDFS, using in private RDFS return an Error.
+    pub fn open(&self, inode_pointer: u64) -> Result<RdfsFile<'_>> {
+        if self.system.magic == FileSystemType::Private {
+            return Err(RDFSError::NoBitmapsPrivateRDFS.into());
+        }
+        self.read_inode_file(inode_pointer)?;
+        Ok(RdfsFile {
+            rdfs: self,
+            inode_pointer,
+            position: 0,
+            read_buffer: vec![],
+            buffered_from: 0,
+            write_buffer: vec![],
+        })
+    }
+
+    /// Creates `new_name` inside the directory at `parent` as a copy of the file at `src_inode`
+    /// sharing its data blocks (a reflink), only a new inode and its `InodeLinkedFile` chain
+    /// are written. Shared blocks stay allocated until the last file referencing them is
+    /// deleted, and appending to either file copies a shared last block before writing to it.
+    /// Every data block gains a referent in the refcount table, so only refcounted drives
+    /// reflink: a drive without a table fails with `NoRefCountTable`, and a block already
+    /// shared by `u16::MAX` files fails the copy with `RefCountOverflow`, both before anything is written.
+    /// The copy keeps the redundancy override of the source.
+    /// Returns the pointer of the new file inode.
+    /// used only in shared RDFS, using in private RDFS return an Error.
+    pub fn copy_file(&self, parent: u64, src_inode: u64, new_name: &str) -> Result<u64> {
+        let name = self.new_entry_name(parent, new_name)?;
+        let mut bitmaps = self.read_bitmaps_block()?;
+        let timestamp = current_time_millis_as_u64()?;
+        let source = self.read_inode_file(src_inode)?;
+        let content = self.collect_file_content(&source)?;
+        let mut refcounts = self.read_refcount_block()?;
+        for pointer in self.block_pointers(&content) {
+            refcounts.increment(self.system.block_index(pointer)?)?;
+        }
+
+        let inode_pointer = self.allocate_blocks(&mut bitmaps, 1)?[0].pointer;
+        let inode = InodeFile {
+            redundancy: source.redundancy,
+            ..InodeFile::new(name, timestamp, source.size, source.total_blocks, vec![], 0)
+        };
+        self.write_file_inode(&mut bitmaps, inode_pointer, inode, content)?;
+
+        let entry = DirContent {
+            pointer: inode_pointer,
+            inode_type: InodeType::File,
+            name: 0,
+        };
+        self.add_dir_entry(&mut bitmaps, parent, entry)?;
+        self.write_bitmaps_with_refcounts(&bitmaps, Some(refcounts))?;
+
+        Ok(inode_pointer)
+    }
+
+    /// Redundancy the RaptorQ chunks of the file at `inode_pointer` are encoded with, its
+    /// override if it has one, otherwise the drive's. Pass it to `DataBlock::encode_with_redundancy`.
+    pub fn file_redundancy(&self, inode_pointer: u64) -> Result<u64> {
+        Ok(self.read_inode_file(inode_pointer)?.redundancy_or(self.system.redundancy))
+    }
+
+    /// Adds a hard link called `name` to the file inode at `target_inode`: a second
+    /// `DirContent` in the directory at `parent` referencing the same inode, so every link
+    /// reads and writes the same file. The name of the link takes a block of its own, pointed
+    /// at by `DirContent::name`, so links of one file can be named apart and sit in the same
+    /// directory; `name` must be free in `parent` (`AlreadyExists`).
+    ///
+    /// `InodeFile::links` counts the entries: `delete_file`, `unlink` and `remove_dir_all`
+    /// only drop entries (and their name blocks) while other links remain and free the file
+    /// with the last one. The name and the count are written before the entry is added, so
+    /// an interrupted call leaks the file and the name block at worst.
+    /// A file with `u16::MAX` links fails with `TooManyLinks`.
+    /// used only in shared RDFS, using in private RDFS return an Error.
+    pub fn link(&self, target_inode: u64, parent: u64, name: &str) -> Result<()> {
+        let name = self.new_entry_name(parent, name)?;
+        let mut bitmaps = self.read_bitmaps_block()?;
+        let mut inode = self.read_inode_file(target_inode)?;
+        if inode.links >= u16::MAX as u64 {
+            return Err(RDFSError::TooManyLinks(target_inode).into());
+        }
+
+        let name_pointer = self.allocate_blocks(&mut bitmaps, 1)?[0].pointer;
+        self.write_link_name(name_pointer, &name)?;
+        inode.links += 1;
+        self.write_inode_file(target_inode, &inode)?;
+        let entry = DirContent {
+            pointer: target_inode,
+            inode_type: InodeType::File,
+            name: name_pointer,
+        };
+        self.add_dir_entry(&mut bitmaps, parent, entry)?;
+        self.write_bitmaps_block(&bitmaps)
+    }
+
+    /// Deletes the file at `inode_pointer` from the directory at `parent_inode_pointer`.
+    /// Only the bitmaps block and the parent directory are updated: the bits of every data
+    /// block, every `InodeLinkedFile` block and the inode block itself are cleared, while
+    /// the blocks keep their bytes until they are allocated again.
+    /// Data blocks still reflinked by another file or a snapshot (see `copy_file`) stay
+    /// allocated, each block only losing a referent in the refcount table.
+    /// A file with other hard links (see `link`) only loses this entry, its name block and one
+    /// link, its blocks stay. When several entries of the parent reference the file the first
+    /// one goes, `unlink` picks one by name.
+    /// Every update is made in one transaction, see `begin_txn`.
+    /// used only in shared RDFS, using in private RDFS return an Error.
+    pub fn delete_file(&self, parent_inode_pointer: u64, inode_pointer: u64) -> Result<()> {
+        self.in_txn(|txn| {
+            let entry = txn
+                .read_dir_entries(parent_inode_pointer)?
+                .into_iter()
+                .find(|entry| entry.pointer == inode_pointer && entry.inode_type == InodeType::File)
+                .ok_or(RDFSError::FileEntryNotFound(inode_pointer))?;
+            txn.delete_entry(parent_inode_pointer, entry)
+        })
+    }
+
+    /// Deletes the file entry called `name` from the directory at `parent`, the way
+    /// `delete_file` does. Fails with `PathNotFound` when there is no such entry and
+    /// `FileEntryNotFound` when it is a directory.
+    /// used only in shared RDFS, using in private RDFS return an Error.
+    pub fn unlink(&self, parent: u64, name: &str) -> Result<()> {
+        self.in_txn(|txn| {
+            let entry = txn
+                .find_dir_entry(parent, name)?
+                .ok_or_else(|| RDFSError::PathNotFound(name.to_string()))?;
+            if entry.inode_type != InodeType::File {
+                return Err(RDFSError::FileEntryNotFound(entry.pointer).into());
+            }
+            txn.delete_entry(parent, entry)
+        })
+    }
+
+    /// Removes the file entry `entry` from the directory at `parent` and frees its name block,
+    /// with the blocks of the file when it was the last link.
+    fn delete_entry(&self, parent: u64, entry: DirContent) -> Result<()> {
+        let mut bitmaps = self.read_bitmaps_block()?;
+        let mut blocks = match entry.name {
+            0 => vec![],
+            name => vec![name],
+        };
+        let mut inode = self.read_inode_file(entry.pointer)?;
+        if inode.links > 1 {
+            inode.links -= 1;
+            self.write_inode_file(entry.pointer, &inode)?;
+        } else {
+            blocks.extend(self.file_blocks(entry.pointer, &mut HashSet::new())?);
+        }
+        let mut refcounts = self.refcount_table()?;
+        self.release_blocks(&mut bitmaps, refcounts.as_mut(), blocks)?;
+
+        self.remove_dir_entry(&mut bitmaps, parent, &entry)?;
+        self.write_bitmaps_with_refcounts(&bitmaps, refcounts)
+    }
+
+    /// Deletes the directory at `dir_pointer` and everything below it from the directory at
+    /// `parent`, depth first. Like `delete_file` only the bitmaps block and the parent change,
+    /// and data blocks reflinked by a file outside the directory stay allocated.
+    /// Blocks already visited are skipped, so a malformed `linked` pointer looping back to an
+    /// ancestor cannot recurse forever. A file also linked from outside the directory (see
+    /// `link`) is kept, its link count losing the entries below the directory.
+    /// Returns the number of blocks freed.
+    /// used only in shared RDFS, using in private RDFS return an Error.
+    pub fn remove_dir_all(&self, parent: u64, dir_pointer: u64) -> Result<u64> {
+        let mut bitmaps = self.read_bitmaps_block()?;
+        let free_before = bitmaps.free_blocks;
+        let entry = self
+            .read_dir_entries(parent)?
+            .into_iter()
+            .find(|entry| entry.pointer == dir_pointer && entry.inode_type == InodeType::Dir)
+            .ok_or(RDFSError::DirEntryNotFound(dir_pointer))?;
+
+        let mut visited = HashSet::from([parent]);
+        let mut kept = vec![];
+        for (pointer, entries) in self.file_entries_below(dir_pointer)? {
+            let inode = self.read_inode_file(pointer)?;
+            if inode.links > entries {
+                visited.insert(pointer);
+                kept.push((pointer, inode, entries));
+            }
+        }
+        let blocks = self.dir_blocks(dir_pointer, &mut visited)?;
+        let mut refcounts = self.refcount_table()?;
+        self.release_blocks(&mut bitmaps, refcounts.as_mut(), blocks)?;
+
+        self.remove_dir_entry(&mut bitmaps, parent, &entry)?;
+        self.write_bitmaps_with_refcounts(&bitmaps, refcounts)?;
+        for (pointer, mut inode, entries) in kept {
+            inode.links -= entries;
+            self.write_inode_file(pointer, &inode)?;
+        }
+        Ok(bitmaps.free_blocks - free_before)
+    }
+
+    /// Number of entries referencing each file inode in the directory at `pointer` and below.
+    fn file_entries_below(&self, pointer: u64) -> Result<HashMap<u64, u64>> {
+        let mut files = HashMap::new();
+        let mut visited = HashSet::new();
+        let mut dirs = vec![pointer];
+        while let Some(dir) = dirs.pop() {
+            if !visited.insert(dir) {
+                continue;
+            }
+            for entry in self.dir_entries_once(dir, &mut visited)? {
+                match entry.inode_type {
+                    InodeType::Dir => dirs.push(entry.pointer),
+                    InodeType::File => *files.entry(entry.pointer).or_default() += 1,
+                }
+            }
+        }
+        Ok(files)
+    }
+
+    /// Every block owned by the directory at `pointer`: its inode, its linked blocks, the name
+    /// blocks of its hard links and, recursively, the blocks of its children. Blocks in
+    /// `visited` are left out.
+    fn dir_blocks(&self, pointer: u64, visited: &mut HashSet<u64>) -> Result<Vec<u64>> {
+        if !visited.insert(pointer) {
+            return Ok(vec![]);
+        }
+        let inode = self.read_inode_dir(pointer)?;
+        let mut blocks = vec![pointer];
+        let mut entries = inode.content;
+        let mut linked = inode.linked;
+        while linked != 0 && visited.insert(linked) {
+            let block = self.read_linked_dir(linked)?;
+            blocks.push(linked);
+            entries.extend(block.content);
+            linked = block.linked;
+        }
+
+        for entry in entries {
+            if entry.name != 0 && visited.insert(entry.name) {
+                blocks.push(entry.name);
+            }
+            match entry.inode_type {
+                InodeType::Dir => blocks.extend(self.dir_blocks(entry.pointer, visited)?),
+                InodeType::File => blocks.extend(self.file_blocks(entry.pointer, visited)?),
+            }
+        }
+        Ok(blocks)
+    }
+
+    /// Every block owned by the file at `pointer`: its inode, its `InodeLinkedFile` blocks
+    /// and its data blocks. Blocks in `visited` are left out. Data blocks are not added to
+    /// `visited`, a block reflinked by two files being removed is listed once per file.
+    fn file_blocks(&self, pointer: u64, visited: &mut HashSet<u64>) -> Result<Vec<u64>> {
+        if !visited.insert(pointer) {
+            return Ok(vec![]);
+        }
+        let (content, linked) = self.file_chain(&self.read_inode_file(pointer)?)?;
+        let mut blocks = vec![pointer];
+        blocks.extend(linked.into_iter().filter(|linked| visited.insert(*linked)));
+        blocks.extend(self.block_pointers(&content).filter(|pointer| !visited.contains(pointer)));
+        Ok(blocks)
+    }
+
+    /// Frees every block of `blocks` in `bitmaps`. On a refcounted drive each entry removes
+    /// one referent from `refcounts` and the block is only freed once its count reaches 0,
+    /// the caller persists the table with `write_bitmaps_with_refcounts`.
+    fn release_blocks(&self, bitmaps: &mut BitmapsBlock, mut refcounts: Option<&mut RefCountBlock>, blocks: Vec<u64>) -> Result<()> {
+        for pointer in blocks {
+            let index = self.system.block_index(pointer)?;
+            let remaining = match refcounts.as_deref_mut() {
+                Some(refcounts) => refcounts.decrement(index)?,
+                None => 0,
+            };
+            if remaining == 0 {
+                bitmaps.clear_bit(index as usize);
+            }
+        }
+        Ok(())
+    }
+
+    /// Renames the entry `name` of the directory `src_parent` to `new_name`, moving its
+    /// `DirContent` to `dst_parent` when the directories differ. Only the name (in the inode,
+    /// or the name block of a hard link) and the directory entries change, no data block is moved.
+    /// Fails if `dst_parent` already holds an entry called `new_name` and, like any new name,
+    /// if `new_name` is too long or holds a forbidden character. When that entry is another
+    /// hard link of the same file nothing changes.
+    /// used only in shared RDFS, using in private RDFS return an Error.
+    pub fn rename(&self, src_parent: u64, name: &str, dst_parent: u64, new_name: &str) -> Result<()> {
+        ContentName::try_new(new_name)?;
+        let entry = self
+            .find_dir_entry(src_parent, name)?
+            .ok_or_else(|| RDFSError::PathNotFound(name.to_string()))?;
+        match self.find_dir_entry(dst_parent, new_name)? {
+            // the entry itself, only the spelling can change under a non case sensitive policy
+            Some(existing) if existing == entry && dst_parent == src_parent => {}
+            Some(existing) if existing.pointer == entry.pointer => return Ok(()),
+            Some(_) => return Err(RDFSError::AlreadyExists(new_name.to_string()).into()),
+            None => {}
+        }
+        if dst_parent != src_parent && entry.inode_type == InodeType::Dir && self.dir_contains(entry.pointer, dst_parent)? {
+            return Err(RDFSError::MoveIntoItself(name.to_string()).into());
+        }
+
+        let modify = current_time_millis_as_u64()?;
+        match entry.inode_type {
+            InodeType::File if entry.name != 0 => self.write_link_name(entry.name, &ContentName::new(new_name))?,
+            InodeType::Dir => {
+                let mut inode = self.read_inode_dir(entry.pointer)?;
+                inode.name.set(new_name);
+                inode.modify = modify;
+                self.write_inode_dir(entry.pointer, &inode)?;
+            }
+            InodeType::File => {
+                let mut inode = self.read_inode_file(entry.pointer)?;
+                inode.name.set(new_name);
+                inode.modify = modify;
+                self.write_inode_file(entry.pointer, &inode)?;
+            }
+        }
+        self.invalidate_dir(src_parent);
+
+        if dst_parent != src_parent {
+            let mut bitmaps = self.read_bitmaps_block()?;
+            self.add_dir_entry(&mut bitmaps, dst_parent, entry.clone())?;
+            self.remove_dir_entry(&mut bitmaps, src_parent, &entry)?;
+            self.write_bitmaps_block(&bitmaps)?;
+        }
+        Ok(())
+    }
+
+    /// Returns `true` if `target` is the directory at `pointer` or any directory below it.
+    fn dir_contains(&self, pointer: u64, target: u64) -> Result<bool> {
+        if pointer == target {
+            return Ok(true);
+        }
+        for entry in self.read_dir_entries(pointer)? {
+            if entry.inode_type == InodeType::Dir && self.dir_contains(entry.pointer, target)? {
+                return Ok(true);
+            }
+        }
+        Ok(false)
+    }
+
+    /// Records the current tree as a snapshot called `label`, returns the pointer of its root.
+    ///
+    /// The snapshot gets a new root `InodeDir` whose content references a copy of every
+    /// top-level entry. Inodes are updated in place (`rename`, `append_to_file`...), so every
+    /// directory and file inode below the root is copied too, while the data blocks are
+    /// shared with the live tree and gain a referent in the refcount table. A snapshot costs
+    /// metadata blocks only, whatever the size of the files.
+    ///
+    /// Shared data blocks are never mutated: `append_to_file` copies a shared last block
+    /// before topping it off (copy-on-write) and deleting a file only drops its referents,
+    /// so a snapshot keeps reading the bytes it captured.
+    ///
+    /// Snapshot roots are entries of the `InodeDir` at `SuperBlock::snapshot_pointer`.
+    /// Drives without a refcount table fail with `NoRefCountTable`, refcounted version 2
+    /// drives have no snapshot list and fail with `UnsupportedSuperBlockVersion`.
+    pub fn snapshot(&self, label: &str) -> Result<u64> {
+        let list = self.snapshot_list()?;
+        let name = ContentName::try_new(label)?;
+        let mut bitmaps = self.read_bitmaps_block()?;
+        let mut refcounts = self.read_refcount_block()?;
+        let timestamp = current_time_millis_as_u64()?;
+
+        let root = self.allocate_blocks(&mut bitmaps, 1)?[0].pointer;
+        self.write_inode_dir(root, &InodeDir::new(name, timestamp, 0, 1, vec![], 0))?;
+        self.copy_dir_entries(&mut bitmaps, &mut refcounts, self.system.inode_pointer, root, &mut HashMap::new())?;
+
+        let entry = DirContent {
+            pointer: root,
+            inode_type: InodeType::Dir,
+            name: 0,
+        };
+        self.add_dir_entry(&mut bitmaps, list, entry)?;
+        self.write_bitmaps_with_refcounts(&bitmaps, Some(refcounts))?;
+        Ok(root)
+    }
+
+    /// Lists the snapshots of the drive, oldest first, see `snapshot`.
+    pub fn list_snapshots(&self) -> Result<Vec<Snapshot>> {
+        self.read_dir_entries(self.snapshot_list()?)?
+            .into_iter()
+            .map(|entry| {
+                let inode = self.read_inode_dir(entry.pointer)?;
+                Ok(Snapshot {
+                    pointer: entry.pointer,
+                    label: inode.name.as_string(),
+                    created: inode.created,
+                })
+            })
+            .collect()
+    }
+
+    /// Replaces the live tree with the snapshot at `pointer`, which is kept and can be
+    /// restored again. The snapshot content is copied like `snapshot` does into a staging
+    /// directory first, so running out of space leaves the live tree untouched. The root
+    /// inode then takes over the staged entries and the blocks of the previous tree are
+    /// released like `remove_dir_all` does, the root inode, bitmaps block and refcount table
+    /// being written together with `write_journaled`.
+    /// Fails with `SnapshotNotFound` if `pointer` is not listed by `list_snapshots`.
+    pub fn restore_snapshot(&self, pointer: u64) -> Result<()> {
+        let list = self.snapshot_list()?;
+        if !self.read_dir_entries(list)?.iter().any(|entry| entry.pointer == pointer) {
+            return Err(RDFSError::SnapshotNotFound(pointer).into());
+        }
+        let mut bitmaps = self.read_bitmaps_block()?;
+        let mut refcounts = self.read_refcount_block()?;
+        let root = self.system.inode_pointer;
+
+        let staging = self.allocate_blocks(&mut bitmaps, 1)?[0].pointer;
+        self.write_inode_dir(staging, &InodeDir::new(ContentName::new("./"), 0, 0, 1, vec![], 0))?;
+        self.copy_dir_entries(&mut bitmaps, &mut refcounts, pointer, staging, &mut HashMap::new())?;
+
+        // everything below the root, its linked blocks included, belongs to the previous tree
+        let mut previous = self.dir_blocks(root, &mut HashSet::new())?;
+        previous.retain(|block| *block != root);
+        self.release_blocks(&mut bitmaps, Some(&mut refcounts), previous)?;
+        bitmaps.clear_bit(self.system.block_index(staging)? as usize);
+        refcounts.sync_with(&bitmaps);
+
+        let staged = self.read_inode_dir(staging)?;
+        let mut inode = self.read_inode_dir(root)?;
+        inode.size = staged.size;
+        inode.content = staged.content;
+        inode.linked = staged.linked;
+        inode.modify = current_time_millis_as_u64()?;
+        self.write_journaled(vec![
+            (root, inode.to_bytes(self.system.block_size as usize)),
+            (self.system.bitmaps_pointer, bitmaps.to_bytes()),
+            (self.system.refcount_pointer, refcounts.to_bytes()),
+        ])
+    }
+
+    /// Pointer of the `InodeDir` listing the snapshots, failing on drives that cannot have any.
+    fn snapshot_list(&self) -> Result<u64> {
+        if !self.system.has_refcounts() {
+            return Err(RDFSError::NoRefCountTable.into());
+        }
+        if self.system.snapshot_pointer == 0 {
+            return Err(RDFSError::UnsupportedSuperBlockVersion(self.system.version).into());
+        }
+        Ok(self.system.snapshot_pointer)
+    }
+
+    /// Copies every entry of the directory at `source` into the directory at `target`,
+    /// recursively. Inodes and the name blocks of hard links get new blocks from `bitmaps`,
+    /// data blocks are shared and gain a referent in `refcounts`. `copied` maps every inode
+    /// copied so far to its copy: a file met again is a hard link and references its first
+    /// copy, a directory met twice fails with `LinkedLoop`.
+    fn copy_dir_entries(
+        &self,
+        bitmaps: &mut BitmapsBlock,
+        refcounts: &mut RefCountBlock,
+        source: u64,
+        target: u64,
+        copied: &mut HashMap<u64, u64>,
+    ) -> Result<()> {
+        if copied.insert(source, target).is_some() {
+            return Err(RDFSError::LinkedLoop(source).into());
+        }
+        for entry in self.read_dir_entries(source)? {
+            if let Some(&pointer) = copied.get(&entry.pointer).filter(|_| entry.inode_type == InodeType::File) {
+                let name = self.copy_link_name(bitmaps, entry.name)?;
+                self.add_dir_entry(bitmaps, target, DirContent { pointer, name, ..entry })?;
+                continue;
+            }
+            let pointer = self.allocate_blocks(bitmaps, 1)?[0].pointer;
+            match entry.inode_type {
+                InodeType::Dir => {
+                    let mut inode = self.read_inode_dir(entry.pointer)?;
+                    inode.size = 0;
+                    inode.content = vec![];
+                    inode.linked = 0;
+                    self.write_inode_dir(pointer, &inode)?;
+                    self.copy_dir_entries(bitmaps, refcounts, entry.pointer, pointer, copied)?;
+                }
+                InodeType::File => {
+                    let inode = self.read_inode_file(entry.pointer)?;
+                    let content = self.collect_file_content(&inode)?;
+                    for block in self.block_pointers(&content) {
+                        refcounts.increment(self.system.block_index(block)?)?;
+                    }
+                    self.write_file_inode(bitmaps, pointer, inode, content)?;
+                    copied.insert(entry.pointer, pointer);
+                }
+            }
+            let copy = DirContent {
+                pointer,
+                inode_type: entry.inode_type,
+                name: self.copy_link_name(bitmaps, entry.name)?,
+            };
+            self.add_dir_entry(bitmaps, target, copy)?;
+        }
+        Ok(())
+    }
+
+    /// Copies the name block at `name` to a new block from `bitmaps`, 0 stays 0.
+    fn copy_link_name(&self, bitmaps: &mut BitmapsBlock, name: u64) -> Result<u64> {
+        if name == 0 {
+            return Ok(0);
+        }
+        let pointer = self.allocate_blocks(bitmaps, 1)?[0].pointer;
+        self.write_link_name(pointer, &self.read_link_name(name)?)?;
+        Ok(pointer)
+    }
+
+    /// Walks the whole inode tree from the root, and the snapshots of a refcounted drive, and
+    /// reconciles every referenced block (inodes, linked inodes, name blocks and data blocks) with the bitmaps block.
+    /// Dangling pointers are reported and never followed, a block referenced twice is
+    /// only walked once. The entries of a hard-linked file past the first, up to its
+    /// `InodeFile::links`, are not double references. Data blocks reflinked by several files are reported as `shared`.
+    /// On a refcounted drive every count is compared with the referents found in the tree.
+    /// With `repair` the leaked bits are cleared and the wrong counts fixed, then persisted.
+    /// used only in shared RDFS, using in private RDFS return an Error.
+    pub fn fsck(&self, repair: bool) -> Result<FsckReport> {
+        let mut bitmaps = self.read_bitmaps_block()?;
+        let mut report = FsckReport::default();
+        let mut referenced = HashSet::new();
+        let mut data_owners = HashMap::new(); // data block pointer -> first file inode referencing it
+        let mut referents: HashMap<u64, u64> = HashMap::new();
+        let mut links_left = HashMap::new(); // file inode pointer -> hard links not met yet
+
+        // returns `true` the first time a valid block is seen, so it can be walked.
+        // `owner` is the file inode of a data block, a data block of another file is shared
+        let mut visit = |pointer: u64, owner: Option<u64>, report: &mut FsckReport| {
+            if self.system.block_index(pointer).is_err() {
+                report.dangling.push(pointer);
+                return false;
+            }
+            *referents.entry(pointer).or_default() += 1;
+            match owner.zip(data_owners.get(&pointer).copied()) {
+                Some((owner, first)) if owner != first => {
+                    report.shared.push(pointer);
+                    false
+                }
+                _ if !referenced.insert(pointer) => {
+                    report.double_referenced.push(pointer);
+                    false
+                }
+                _ => {
+                    if let Some(owner) = owner {
+                        data_owners.insert(pointer, owner);
+                    }
+                    true
+                }
+            }
+        };
+
+        // snapshot roots are entries of the snapshot list, so they are walked like directories
+        let mut dirs = vec![self.system.inode_pointer];
+        visit(self.system.inode_pointer, None, &mut report);
+        if self.system.snapshot_pointer != 0 && visit(self.system.snapshot_pointer, None, &mut report) {
+            dirs.push(self.system.snapshot_pointer);
+        }
+        while let Some(dir) = dirs.pop() {
+            let inode = self.read_inode_dir(dir)?;
+            let mut entries = inode.content;
+            let mut linked = inode.linked;
+            while linked != 0 && visit(linked, None, &mut report) {
+                let block = self.read_linked_dir(linked)?;
+                entries.extend(block.content);
+                linked = block.linked;
+            }
+
+            for entry in entries {
+                if entry.name != 0 {
+                    visit(entry.name, None, &mut report);
+                }
+                let hard_link = links_left
+                    .get_mut(&entry.pointer)
+                    .filter(|left| entry.inode_type == InodeType::File && **left > 0);
+                if let Some(left) = hard_link {
+                    *left -= 1;
+                    continue;
+                }
+                if !visit(entry.pointer, None, &mut report) {
+                    continue;
+                }
+                if entry.inode_type == InodeType::Dir {
+                    dirs.push(entry.pointer);
+                    continue;
+                }
+
+                let inode = self.read_inode_file(entry.pointer)?;
+                links_left.insert(entry.pointer, inode.links - 1);
+                let mut content = inode.content;
+                let mut linked = inode.linked;
+                while linked != 0 && visit(linked, None, &mut report) {
+                    let block = self.read_linked_file(linked)?;
+                    content.extend(block.content);
+                    linked = block.linked;
+                }
+                for pointer in self.block_pointers(&content) {
+                    visit(pointer, Some(entry.pointer), &mut report);
+                }
+            }
+        }
+
+        for index in 0..self.system.total_blocks {
+            let pointer = self.system.block_pointer(index)?;
+            if bitmaps.get_bit(index as usize) && !referenced.contains(&pointer) {
+                report.leaked.push(pointer);
+            }
+        }
+        report.double_referenced.sort_unstable();
+        report.double_referenced.dedup();
+        report.dangling.sort_unstable();
+        report.dangling.dedup();
+        report.shared.sort_unstable();
+        report.shared.dedup();
+
+        // leaked blocks are reported once, clearing their bit drops their count
+        if let Some(mut refcounts) = self.refcount_table()? {
+            for index in 0..self.system.total_blocks {
+                let pointer = self.system.block_pointer(index)?;
+                let expected = referents.get(&pointer).map_or(0, |count| (*count).min(u16::MAX as u64) as u16);
+                if refcounts.get(index) != expected && report.leaked.binary_search(&pointer).is_err() {
+                    report.miscounted.push(pointer);
+                    refcounts.counts[index as usize] = expected;
+                }
+            }
+            if repair && !report.miscounted.is_empty() {
+                self.write_refcount_block(&refcounts)?;
+                report.repaired = true;
+            }
+        }
+
+        if repair && !report.leaked.is_empty() {
+            for pointer in &report.leaked {
+                bitmaps.clear_bit(self.system.block_index(*pointer)? as usize);
+            }
+            self.write_bitmaps_block(&bitmaps)?;
+            report.repaired = true;
+        }
+        Ok(report)
+    }
+
+    /// Verifies the trailing signature of every signed block against `public_key` with
+    /// `verify_bytes`: the super block, the addresses block, the bitmaps block, then every
+    /// inode, linked inode, name block and data block reachable from the root and the snapshots.
+    /// Returns `(pointer, valid)` once per block in that order. Blocks are read and checked
+    /// one at a time, only the pointers of the tree are collected up front. With
+    /// `stop_at_failure` the audit ends at the first invalid block, the last entry returned.
+    /// A private drive has no inode tree, only its super block and addresses block are checked.
+    pub fn verify_drive_signatures(&self, public_key: &[u8; 32], stop_at_failure: bool) -> Result<Vec<(u64, bool)>> {
+        let mut regions = vec![
+            (0, SuperBlock::encoded_size(self.system.version) as u64),
+            (self.system.nodes_address_pointer, self.system.nodes_address_size),
+        ];
+        let mut blocks = Vec::new();
+        if self.system.magic == FileSystemType::Shared {
+            regions.push((self.system.bitmaps_pointer, self.system.bitmaps_size));
+            let mut visited = HashSet::new();
+            blocks = self.dir_blocks(self.system.inode_pointer, &mut visited)?;
+            if self.system.snapshot_pointer != 0 {
+                blocks.extend(self.dir_blocks(self.system.snapshot_pointer, &mut visited)?);
+            }
+        }
+
+        // a data block reflinked by several files is listed once per file
+        let mut seen = HashSet::new();
+        let blocks = blocks.into_iter().filter(|pointer| seen.insert(*pointer));
+        let mut report = Vec::new();
+        for (pointer, length) in regions.into_iter().chain(blocks.map(|pointer| (pointer, self.system.block_size))) {
+            let valid = verify_bytes(public_key, &self.read_drive_range(pointer, pointer + length)?);
+            report.push((pointer, valid));
+            if stop_at_failure && !valid {
+                break;
+            }
+        }
+        Ok(report)
+    }
+
+    /// Grows the drive to hold `new_storage` bytes, keeping every file and directory.
+    ///
+    /// Growing the bitmaps block shifts `data_pointer`, so the layout is recomputed with
+    /// `SuperBlock::resized` and:
+    /// - every used data block is moved to the same index of the new layout
+    /// - the root inode keeps its index too on a drive with a stable root (version 8, see
+    ///   `SuperBlock::has_stable_root`), on older drives it is copied to the new last block
+    /// - every pointer stored in the inode tree and the snapshots is rewritten for the new layout
+    /// - the new blocks start free in the grown bitmaps block, and unreferenced in the
+    ///   grown refcount table of a refcounted drive
+    ///
+    /// The inode tree is read and translated before anything is written, so a corrupted
+    /// tree aborts the resize with the drive untouched. The super block is written last
+    /// and its signature is reset. Shrinking is rejected with `CannotShrinkDrive`.
+    /// used only in shared RDFS, using in private RDFS return an Error.
+    pub fn resize_drive(&mut self, new_storage: u64) -> Result<()> {
+        if self.system.magic == FileSystemType::Private {
+            return Err(RDFSError::NoBitmapsPrivateRDFS.into());
+        }
+
+        let old = self.system.clone();
+        let new = old.resized(new_storage)?;
+        if new.total_blocks < old.total_blocks {
+            return Err(RDFSError::CannotShrinkDrive {
+                total_blocks: old.total_blocks,
+                requested: new.total_blocks,
+            }
+            .into());
+        }
+        if new.total_blocks == old.total_blocks {
+            self.write_drive_range(0, &new.to_bytes())?;
+            self.system = new;
+            return self.refresh_super_block_backup();
+        }
+
+        let root_index = old.block_index(old.inode_pointer)?;
+        let new_root_index = new.block_index(new.inode_pointer)?;
+        let relocate = |pointer: u64| -> Result<u64> {
+            match old.block_index(pointer)? {
+                index if index == root_index => Ok(new.inode_pointer),
+                index => new.block_pointer(index),
+            }
+        };
+        let relocate_linked = |pointer: u64| if pointer == 0 { Ok(0) } else { relocate(pointer) };
+        let relocate_dir = |content: &mut [DirContent]| -> Result<()> {
+            for entry in content {
+                entry.pointer = relocate(entry.pointer)?;
+                entry.name = relocate_linked(entry.name)?;
+            }
+            Ok(())
+        };
+        let relocate_file = |content: &mut [FileContent]| -> Result<()> {
+            for run in content.iter_mut().filter(|run| !run.is_hole()) {
+                run.pointer = relocate(run.pointer)?;
+            }
+            Ok(())
+        };
+
+        // every inode block encoded for its new location, each one is visited once
+        let block_size = old.block_size as usize;
+        let mut inodes = Vec::new();
+        let mut visited = HashSet::new();
+        let mut dirs = vec![old.inode_pointer];
+        if old.snapshot_pointer != 0 {
+            dirs.push(old.snapshot_pointer);
+        }
+        let snapshot_pointer = relocate_linked(old.snapshot_pointer)?;
+        while let Some(dir) = dirs.pop() {
+            if !visited.insert(dir) {
+                continue;
+            }
+            let mut inode = self.read_inode_dir(dir)?;
+            if dir == old.inode_pointer {
+                inode.total_blocks = new.total_blocks;
+            }
+            let mut entries = inode.content.clone();
+            let mut linked = inode.linked;
+            relocate_dir(&mut inode.content)?;
+            inode.linked = relocate_linked(inode.linked)?;
+            inodes.push((relocate(dir)?, inode.to_bytes(block_size)));
+
+            while linked != 0 && visited.insert(linked) {
+                let mut block = self.read_linked_dir(linked)?;
+                entries.extend(block.content.iter().cloned());
+                let pointer = relocate(linked)?;
+                linked = block.linked;
+                relocate_dir(&mut block.content)?;
+                block.linked = relocate_linked(block.linked)?;
+                inodes.push((pointer, block.to_bytes(block_size)));
+            }
+
+            for entry in entries {
+                if entry.inode_type == InodeType::Dir {
+                    dirs.push(entry.pointer);
+                    continue;
+                }
+                if !visited.insert(entry.pointer) {
+                    continue;
+                }
+                let mut inode = self.read_inode_file(entry.pointer)?;
+                let mut linked = inode.linked;
+                relocate_file(&mut inode.content)?;
+                inode.linked = relocate_linked(inode.linked)?;
+                inodes.push((relocate(entry.pointer)?, inode.to_bytes(block_size)));
+
+                while linked != 0 && visited.insert(linked) {
+                    let mut block = self.read_linked_file(linked)?;
+                    let pointer = relocate(linked)?;
+                    linked = block.linked;
+                    relocate_file(&mut block.content)?;
+                    block.linked = relocate_linked(block.linked)?;
+                    inodes.push((pointer, block.to_bytes(block_size)));
+                }
+            }
+        }
+
+        let mut bitmaps = self.read_bitmaps_block()?;
+        let refcounts = self.refcount_table()?;
+        let _guard = self.write_guard()?;
+        create_physical_file_reserved(&self.path, new.node_storage + new.backup_size())?;
+
+        // blocks only move towards the end of the drive, moving the last one first never
+        // overwrites a block that has not moved yet. Inode blocks are rewritten from memory
+        let moved = (0..old.total_blocks)
+            .rev()
+            .filter(|index| *index != root_index && bitmaps.get_bit(*index as usize));
+        for index in moved {
+            let from = old.block_pointer(index)?;
+            let block = self.read_drive_range(from, from + old.block_size)?;
+            self.write_drive_range(new.block_pointer(index)?, &block)?;
+        }
+        self.system = new;
+        self.system.snapshot_pointer = snapshot_pointer;
+        if let Some(cache) = &self.cache {
+            cache.clear();
+        }
+        for (pointer, block) in &inodes {
+            self.write_block(*pointer, block)?;
+        }
+
+        bitmaps.total_blocks = self.system.total_blocks;
+        bitmaps.free_blocks += self.system.total_blocks - old.total_blocks;
+        bitmaps.bit_field.resize(self.system.total_blocks.div_ceil(8) as usize, 0);
+        bitmaps.clear_bit(root_index as usize);
+        bitmaps.set_bit(new_root_index as usize);
+        let refcounts = refcounts.map(|mut refcounts| {
+            refcounts.total_blocks = self.system.total_blocks;
+            refcounts.counts.resize(self.system.total_blocks as usize, 0);
+            refcounts.counts.swap(root_index as usize, new_root_index as usize);
+            refcounts
+        });
+        self.write_bitmaps_with_refcounts(&bitmaps, refcounts)?;
+
+        self.write_drive_range(0, &self.system.to_bytes())?;
+        self.refresh_super_block_backup()
+    }
+
+    /// Fails with `FileTooLarge` if a file of `size` bytes can't fit this drive even when empty.
+    fn check_file_size(&self, size: u64) -> Result<()> {
+        let max = self.system.max_file_size();
+        if size > max {
+            return Err(RDFSError::FileTooLarge { size, max }.into());
+        }
+        Ok(())
+    }
+
+    /// Stores `inode` at `inode_pointer` with `content` as its ranges, see `link_file_content`.
+    fn write_file_inode(&self, bitmaps: &mut BitmapsBlock, inode_pointer: u64, inode: InodeFile, content: Vec<FileContent>) -> Result<()> {
+        let inode = self.link_file_content(bitmaps, inode, content)?;
+        self.write_inode_file(inode_pointer, &inode)
+    }
+
+    /// Sets `content` as the ranges of `inode`, which is returned for the caller to write. The
+    /// first `max_content_pointers` runs stay in the inode, the rest spill into `InodeLinkedFile`
+    /// blocks allocated from `bitmaps` and written right away. Overlapping ranges fail with
+    /// `OverlappingFileContent` before anything is allocated.
+    fn link_file_content(&self, bitmaps: &mut BitmapsBlock, mut inode: InodeFile, content: Vec<FileContent>) -> Result<InodeFile> {
+        validate_file_ranges(&content, &self.system)?;
+        let mut chain = inode.set_content(content, &self.system);
+        let linked_runs = self.allocate_blocks(bitmaps, chain.len() as u64)?;
+        let linked_pointers: Vec<u64> = self.block_pointers(&linked_runs).collect();
+        inode.link_chain(&mut chain, &linked_pointers)?;
+
+        for (linked, pointer) in chain.iter().zip(linked_pointers) {
+            self.write_linked_file(pointer, linked)?;
+        }
+        Ok(inode)
+    }
+
+    /// Appends `data` to the file at `inode_pointer`. The last data block is topped off first
+    /// when its payload is not full, or copied to a new block first when it is reflinked by
+    /// another file or a snapshot, a shared block is never written. The rest goes to newly
+    /// allocated blocks whose ranges are appended to the file, rebuilding its `InodeLinkedFile` chain when the ranges spill.
+    /// Updates `size`, `total_blocks` and `modify` of the inode. Growing the file past
+    /// `SuperBlock::max_file_size` fails with `FileTooLarge` before anything is allocated.
+    /// used only in shared RDFS, using in private RDFS return an Error.
+    pub fn append_to_file(&self, inode_pointer: u64, data: &[u8]) -> Result<()> {
+        let mut bitmaps = self.read_bitmaps_block()?;
+        let timestamp = current_time_millis_as_u64()?;
+        let block_size = self.system.block_size as usize;
+        let payload_size = block_size - RESERVED_DB;
+
+        let mut inode = self.read_inode_file(inode_pointer)?;
+        self.check_file_size(inode.size.saturating_add(data.len() as u64))?;
+        let (mut content, old_linked) = self.file_chain(&inode)?;
+
+        // a trailing hole is a full block, only a data block can be topped off
+        let mut last_block = None;
+        if let Some(Some(pointer)) = self.block_slots(&content).last() {
+            let block = DataBlock::from_bytes(&self.read_block(pointer)?, block_size)?;
+            if block.data.len() < payload_size {
+                last_block = Some((pointer, block));
+            }
+        }
+        let top_off = last_block
+            .as_ref()
+            .map_or(0, |(_, block)| (payload_size - block.data.len()).min(data.len()));
+        let (head, tail) = data.split_at(top_off);
+        let mut refcounts = self.refcount_table()?;
+        let shared = match (&last_block, &refcounts) {
+            (Some((pointer, _)), Some(refcounts)) if top_off > 0 => refcounts.get(self.system.block_index(*pointer)?) > 1,
+            _ => false,
+        };
+
+        // every allocation happens before the first write, so running out of space leaves the file intact
+        let data_blocks = tail.len().div_ceil(payload_size) as u64;
+        let copy_pointer = match shared {
+            true => Some(self.allocate_blocks(&mut bitmaps, 1)?[0].pointer),
+            false => None,
+        };
+        let mut new_content = self.allocate_blocks(&mut bitmaps, data_blocks)?;
+        for pointer in &old_linked {
+            bitmaps.clear_bit(self.system.block_index(*pointer)? as usize);
+        }
+        let new_pointers: Vec<u64> = self.block_pointers(&new_content).collect();
+        if let Some(pointer) = copy_pointer {
+            // the copy replaces the shared block as the last block of this file only
+            if let (Some((shared_pointer, _)), Some(refcounts)) = (&last_block, refcounts.as_mut()) {
+                refcounts.decrement(self.system.block_index(*shared_pointer)?)?;
+            }
+            if let Some(last) = content.last_mut() {
+                last.blocks -= 1;
+                if last.blocks == 0 {
+                    content.pop();
+                }
+            }
+            new_content.insert(0, FileContent { pointer, blocks: 1 });
+        }
+        for run in new_content {
+            push_run(&mut content, run, self.system.block_size);
+        }
+        let overflow = content.len().saturating_sub(self.system.max_content_pointers as usize);
+        if bitmaps.free_blocks < overflow.div_ceil(self.system.max_linked_content_pointers as usize) as u64 {
+            return Err(RDFSError::NotEnoughSpace.into());
+        }
+
+        if let Some((pointer, mut block)) = last_block.filter(|_| !head.is_empty()) {
+            let pointer = copy_pointer.unwrap_or(pointer);
+            block.block_number = self.system.block_index(pointer)?;
+            block.data.extend_from_slice(head);
+            block.timestamp = timestamp;
+            self.write_block(pointer, &block.to_bytes(block_size))?;
+        }
+        for (pointer, chunk) in new_pointers.into_iter().zip(tail.chunks(payload_size)) {
+            let block_number = self.system.block_index(pointer)?;
+            let block = DataBlock::new(block_number, timestamp, chunk);
+            self.write_block(pointer, &block.to_bytes(block_size))?;
+        }
+
+        inode.size += data.len() as u64;
+        inode.total_blocks += data_blocks;
+        inode.modify = timestamp;
+        self.write_file_inode(&mut bitmaps, inode_pointer, inode, content)?;
+        self.write_bitmaps_with_refcounts(&bitmaps, refcounts)
+    }
+
+    /// Shrinks the file at `inode_pointer` to `new_size` bytes. The data blocks past the new end
+    /// are freed, the ranges trimmed and the `InodeLinkedFile` chain rebuilt for what is left,
+    /// and the last kept block is rewritten with its payload cut at `new_size`. Like in
+    /// `append_to_file` a last block reflinked by another file or a snapshot is copied first, a
+    /// shared block is never written, and dropped blocks still reflinked elsewhere stay
+    /// allocated. A sparse hole cut in the middle becomes a block of zeros.
+    /// Updates `size`, `total_blocks` and `modify` of the inode. A `new_size` past the end
+    /// fails with `OffsetOutOfRange`, files grow with `append_to_file`.
+    /// used only in shared RDFS, using in private RDFS return an Error.
+    pub fn truncate_file(&self, inode_pointer: u64, new_size: u64) -> Result<()> {
+        let mut inode = self.read_inode_file(inode_pointer)?;
+        if new_size > inode.size {
+            return Err(RDFSError::OffsetOutOfRange {
+                offset: new_size,
+                size: inode.size,
+            }
+            .into());
+        }
+        let mut bitmaps = self.read_bitmaps_block()?;
+        let timestamp = current_time_millis_as_u64()?;
+        let block_size = self.system.block_size;
+        let payload_size = self.system.data_payload_size();
+        let (content, old_linked) = self.file_chain(&inode)?;
+
+        // the first `kept` blocks of the file stay, the data blocks after them are dropped
+        let kept = new_size.div_ceil(payload_size) as usize;
+        let mut slots: Vec<Option<u64>> = self.block_slots(&content).collect();
+        if slots.len() < kept {
+            return Err(RDFSError::InvalidFileSize.into());
+        }
+        let mut released: Vec<u64> = slots.split_off(kept).into_iter().flatten().collect();
+        let mut refcounts = self.refcount_table()?;
+
+        // the copy of a shared last block is allocated before anything is freed, so it never
+        // lands on a block the file still references on the drive
+        if let Some(last) = slots.last_mut().filter(|_| !new_size.is_multiple_of(payload_size)) {
+            let (mut block, in_place) = match *last {
+                Some(pointer) => {
+                    let block = DataBlock::from_bytes(&self.read_block(pointer)?, block_size as usize)?;
+                    let is_shared = match &refcounts {
+                        Some(refcounts) => refcounts.get(self.system.block_index(pointer)?) > 1,
+                        None => false,
+                    };
+                    (block, !is_shared)
+                }
+                None => (DataBlock::new(0, timestamp, &[]), false),
+            };
+            let pointer = match *last {
+                Some(pointer) if in_place => pointer,
+                _ => {
+                    released.extend(*last);
+                    self.allocate_blocks(&mut bitmaps, 1)?[0].pointer
+                }
+            };
+            block.data.resize((new_size - (kept as u64 - 1) * payload_size) as usize, 0);
+            block.block_number = self.system.block_index(pointer)?;
+            block.timestamp = timestamp;
+            self.write_block(pointer, &block.to_bytes(block_size as usize))?;
+            *last = Some(pointer);
+        }
+
+        let mut content = Vec::new();
+        for slot in slots {
+            let run = slot.map_or(FileContent::hole(1), |pointer| FileContent { pointer, blocks: 1 });
+            push_run(&mut content, run, block_size);
+        }
+        released.extend(old_linked);
+        self.release_blocks(&mut bitmaps, refcounts.as_mut(), released)?;
+
+        inode.size = new_size;
+        inode.total_blocks = kept as u64;
+        inode.modify = timestamp;
+        self.write_file_inode(&mut bitmaps, inode_pointer, inode, content)?;
+        self.write_bitmaps_with_refcounts(&bitmaps, refcounts)
+    }
+
+    /// Moves the data blocks of the file at `inode_pointer` into a single contiguous run so it
+    /// reads sequentially. Returns the number of bytes copied, whole blocks included.
+    ///
+    /// Unlike every other operation this moves data, so the steps are ordered to survive a crash:
+    /// 1. the first free run holding every data block is allocated in memory (`NotEnoughSpace`
+    ///    if there is none) and each block is copied there, its `block_number` updated
+    /// 2. the inode, now a single `FileContent` without `InodeLinkedFile` blocks, is swapped
+    ///    with `write_journaled` together with the bitmaps block and the refcount table
+    /// 3. that same write frees the old data and linked blocks, a block still reflinked by
+    ///    another file or a snapshot only loses this file as a referent
+    ///
+    /// Sparse holes stay where they are, the data runs between them are moved back to back
+    /// in the new run. When holes leave more ranges than the inode holds, the spilled ones
+    /// go to new `InodeLinkedFile` blocks written before step 2.
+    /// A crash before step 2 leaves the file untouched, its copies sitting in blocks still free.
+    /// A file whose data is already stored in one run is left as is and returns 0.
+    /// used only in shared RDFS, using in private RDFS return an Error.
+    pub fn defragment_file(&self, inode_pointer: u64) -> Result<u64> {
+        if self.system.magic == FileSystemType::Private {
+            return Err(RDFSError::NoBitmapsPrivateRDFS.into());
+        }
+        let inode = self.read_inode_file(inode_pointer)?;
+        let (content, linked) = self.file_chain(&inode)?;
+        if content.iter().filter(|run| !run.is_hole()).count() <= 1 {
+            return Ok(0);
+        }
+
+        let mut bitmaps = self.read_bitmaps_block()?;
+        let block_size = self.system.block_size;
+        let blocks = content.iter().filter(|run| !run.is_hole()).map(|run| run.blocks).sum();
+        let run = bitmaps
+            .allocate_with(blocks, AllocStrategy::Contiguous)
+            .ok_or(RDFSError::NotEnoughSpace)?;
+        let target = FileContent {
+            pointer: self.system.block_pointer(run[0].pointer)?,
+            blocks,
+        };
+        let old: Vec<u64> = self.block_pointers(&content).collect();
+        for (from, to) in old.iter().zip(self.block_pointers(std::slice::from_ref(&target))) {
+            let mut block = DataBlock::from_bytes(&self.read_block(*from)?, block_size as usize)?;
+            block.block_number = self.system.block_index(to)?;
+            self.write_block(to, &block.to_bytes(block_size as usize))?;
+        }
+
+        let mut moved = Vec::new();
+        let mut next = target.pointer;
+        for run in content {
+            if run.is_hole() {
+                push_run(&mut moved, run, block_size);
+                continue;
+            }
+            let pointer = next;
+            next += run.blocks * block_size;
+            push_run(&mut moved, FileContent { pointer, blocks: run.blocks }, block_size);
+        }
+        let inode = self.link_file_content(&mut bitmaps, inode, moved)?;
+
+        let mut refcounts = self.refcount_table()?;
+        let mut released = linked;
+        released.extend(old);
+        self.release_blocks(&mut bitmaps, refcounts.as_mut(), released)?;
+
+        let mut writes = vec![
+            (inode_pointer, inode.to_bytes(block_size as usize)),
+            (self.system.bitmaps_pointer, bitmaps.to_bytes()),
+        ];
+        if let Some(mut refcounts) = refcounts {
+            refcounts.sync_with(&bitmaps);
+            writes.push((self.system.refcount_pointer, refcounts.to_bytes()));
+        }
+        self.write_journaled(writes)?;
+        Ok(blocks * block_size)
+    }
+
+    /// Moves the used block at `from` to the free block at `to`, both byte pointers of blocks.
+    /// The content is copied byte for byte, so a `DataBlock` keeps its `block_number` and its
+    /// signature, and on a refcounted drive `to` takes over the count of `from`. `rewrite`
+    /// points whatever referenced `from` (an inode, a linked block) at `to`.
+    ///
+    /// The steps are ordered so a crash never loses the only copy nor leaves a pointer to a
+    /// free block:
+    /// 1. the block is copied to `to` and synced while `to` is still free
+    /// 2. `to` is marked used and the bitmaps synced
+    /// 3. `rewrite` runs and its writes are synced
+    /// 4. `from` is freed
+    ///
+    /// A crash after step 1 leaves the copy in a free block, after step 2 or 3 both blocks
+    /// used, the one nothing points at showing up as leaked in `fsck`. An error from `rewrite`
+    /// stops the move the same way. Fails with `BlockInUse` if `to` is used and
+    /// `BlockNotInUse` if `from` is free, the drive untouched.
+    ///
+    /// Meant for moving a few blocks, every move syncs three times: `resize_drive` and
+    /// `defragment_file` move whole runs and commit them in one journaled write instead.
+    /// used only in shared RDFS, using in private RDFS return an Error.
+    pub fn move_block(&self, from: u64, to: u64, rewrite: impl FnOnce() -> Result<()>) -> Result<()> {
+        self.move_block_with(from, to, rewrite, |_| Ok(()))
+    }
+
+    /// `move_block` calling `checkpoint` with the number of the step just completed after
+    /// steps 1 to 3, an error from it stops the move right there.
+    fn move_block_with(&self, from: u64, to: u64, rewrite: impl FnOnce() -> Result<()>, mut checkpoint: impl FnMut(u8) -> Result<()>) -> Result<()> {
+        if self.system.magic == FileSystemType::Private {
+            return Err(RDFSError::NoBitmapsPrivateRDFS.into());
+        }
+        let (source, target) = (self.system.block_index(from)?, self.system.block_index(to)?);
+        let mut bitmaps = self.read_bitmaps_block()?;
+        if !bitmaps.get_bit(source as usize) {
+            return Err(RDFSError::BlockNotInUse(from).into());
+        }
+        if bitmaps.get_bit(target as usize) {
+            return Err(RDFSError::BlockInUse(to).into());
+        }
+
+        self.write_block(to, &self.read_block(from)?)?;
+        self.sync()?;
+        checkpoint(1)?;
+
+        let mut refcounts = self.refcount_table()?;
+        bitmaps.set_bit(target as usize);
+        if let Some(refcounts) = refcounts.as_mut() {
+            refcounts.counts[target as usize] = refcounts.get(source);
+        }
+        self.write_bitmaps_with_refcounts(&bitmaps, refcounts.clone())?;
+        self.sync()?;
+        checkpoint(2)?;
+
+        rewrite()?;
+        self.sync()?;
+        checkpoint(3)?;
+
+        bitmaps.clear_bit(source as usize);
+        self.write_bitmaps_with_refcounts(&bitmaps, refcounts)?;
+        self.sync()
+    }
+
+    /// Returns every `FileContent` range of the file at `inode_pointer` in order, across its
+    /// whole `InodeLinkedFile` chain. A chain looping back on itself fails with `LinkedLoop`.
+    /// used only in shared RDFS, using in private RDFS return an Error.
+    pub fn file_content_ranges(&self, inode_pointer: u64) -> Result<Vec<FileContent>> {
+        if self.system.magic == FileSystemType::Private {
+            return Err(RDFSError::NoBitmapsPrivateRDFS.into());
+        }
+        self.collect_file_content(&self.read_inode_file(inode_pointer)?)
+    }
+
+    /// Collects the `FileContent` ranges of `inode` and of its `InodeLinkedFile` chain in order.
+    fn collect_file_content(&self, inode: &InodeFile) -> Result<Vec<FileContent>> {
+        Ok(self.file_chain(inode)?.0)
+    }
+
+    /// Walks the `InodeLinkedFile` chain of `inode`, returning its ranges in order and the
+    /// pointers of the linked blocks. Every file read path goes through here.
+    fn file_chain(&self, inode: &InodeFile) -> Result<(Vec<FileContent>, Vec<u64>)> {
+        let mut content = inode.content.clone();
+        let mut linked_blocks = Vec::new();
+        let mut visited = HashSet::new();
+        let mut linked = inode.linked;
+        while linked != 0 {
+            if !visited.insert(linked) {
+                return Err(RDFSError::LinkedLoop(linked).into());
+            }
+            let block = self.read_linked_file(linked)?;
+            content.extend(block.content);
+            linked_blocks.push(linked);
+            linked = block.linked;
+        }
+        Ok((content, linked_blocks))
+    }
+
+    /// Adds `entry` to the directory at `parent_pointer`. When the directory block is full the
+    /// entry spills into the first `InodeLinkedDir` block with room, and a new linked block is
+    /// allocated at the end of the chain when every block is full.
+    /// The caller is responsible for persisting `bitmaps` with `write_bitmaps_block`.
+    pub fn add_dir_entry(&self, bitmaps: &mut BitmapsBlock, parent_pointer: u64, entry: DirContent) -> Result<()> {
+        let mut parent = self.read_inode_dir(parent_pointer)?;
+        let mut entry = match parent.add_entry(entry, self.system.max_content_pointers) {
+            Ok(()) => return self.write_inode_dir(parent_pointer, &parent),
+            Err(entry) => entry,
+        };
+        // the entry is stored in a linked block but still belongs to this directory
+        parent.size += 1;
+        parent.modify = current_time_millis_as_u64()?;
+
+        let mut tail = 0;
+        let mut pointer = parent.linked;
+        while pointer != 0 {
+            let mut linked = self.read_linked_dir(pointer)?;
+            match linked.add_entry(entry, self.system.max_linked_content_pointers) {
+                Ok(()) => {
+                    self.write_linked_dir(pointer, &linked)?;
+                    return self.write_inode_dir(parent_pointer, &parent);
+                }
+                Err(rejected) => entry = rejected,
+            }
+            tail = pointer;
+            pointer = linked.linked;
+        }
+
+        let new_pointer = self.allocate_blocks(bitmaps, 1)?[0].pointer;
+        self.write_linked_dir(new_pointer, &InodeLinkedDir::new(vec![entry], 0))?;
+        if tail == 0 {
+            parent.linked = new_pointer;
+        } else {
+            let mut linked = self.read_linked_dir(tail)?;
+            linked.linked = new_pointer;
+            self.write_linked_dir(tail, &linked)?;
+        }
+        self.write_inode_dir(parent_pointer, &parent)
+    }
+
+    /// Removes `entry` from the directory at `parent_pointer`, its name block is left to the caller.
+    /// A `InodeLinkedDir` block left empty is unlinked from the chain and freed in `bitmaps`.
+    /// Returns `false` if the directory has no such entry.
+    /// The caller is responsible for persisting `bitmaps` with `write_bitmaps_block`.
+    pub fn remove_dir_entry(&self, bitmaps: &mut BitmapsBlock, parent_pointer: u64, entry: &DirContent) -> Result<bool> {
+        let mut parent = self.read_inode_dir(parent_pointer)?;
+        if parent.remove_entry(entry) {
+            self.write_inode_dir(parent_pointer, &parent)?;
+            return Ok(true);
+        }
+
+        let mut previous = 0;
+        let mut current = parent.linked;
+        while current != 0 {
+            let mut linked = self.read_linked_dir(current)?;
+            if linked.remove_entry(entry) {
+                parent.size = parent.size.saturating_sub(1);
+                parent.modify = current_time_millis_as_u64()?;
+                if !linked.content.is_empty() {
+                    self.write_linked_dir(current, &linked)?;
+                } else if previous == 0 {
+                    parent.linked = linked.linked;
+                } else {
+                    let mut previous_block = self.read_linked_dir(previous)?;
+                    previous_block.linked = linked.linked;
+                    self.write_linked_dir(previous, &previous_block)?;
+                }
+                if linked.content.is_empty() {
+                    bitmaps.clear_bit(self.system.block_index(current)? as usize);
+                }
+                self.write_inode_dir(parent_pointer, &parent)?;
+                return Ok(true);
+            }
+            previous = current;
+            current = linked.linked;
+        }
+
+        Ok(false)
+    }
+
+    /// Marks `count` free blocks as used in `bitmaps` and returns them as contiguous
+    /// runs of (pointer, blocks), leaving `bitmaps` untouched on failure.
+    fn allocate_blocks(&self, bitmaps: &mut BitmapsBlock, count: u64) -> Result<Vec<FileContent>> {
+        let runs = bitmaps.allocate(count).ok_or(RDFSError::NotEnoughSpace)?;
+        runs.into_iter()
+            .map(|run| {
+                Ok(FileContent {
+                    pointer: self.system.block_pointer(run.pointer)?,
+                    blocks: run.blocks,
+                })
+            })
+            .collect()
+    }
+
+    /// Expands contiguous runs into the pointer of every block they cover, sparse holes
+    /// have no block and are skipped.
+    fn block_pointers<'a>(&self, runs: &'a [FileContent]) -> impl Iterator<Item = u64> + 'a {
+        self.block_slots(runs).flatten()
+    }
+
+    /// Expands runs into one slot per block of the file in order, `None` for the blocks of a hole.
+    fn block_slots<'a>(&self, runs: &'a [FileContent]) -> impl Iterator<Item = Option<u64>> + 'a {
+        let block_size = self.system.block_size;
+        runs.iter().flat_map(move |run| {
+            (0..run.blocks).map(move |block| match run.is_hole() {
+                true => None,
+                false => Some(run.pointer + block * block_size),
+            })
+        })
+    }
+
+    /// Payload of the data block in `slot`, a full block of zeros for a hole.
+    fn read_payload(&self, slot: Option<u64>) -> Result<Vec<u8>> {
+        let block_size = self.system.block_size as usize;
+        match slot {
+            Some(pointer) => Ok(DataBlock::from_bytes(&self.read_block(pointer)?, block_size)?.data),
+            None => Ok(vec![0; block_size - RESERVED_DB]),
+        }
+    }
+
+    /// Whether the payload `chunk` is stored as a hole, see `with_sparse`.
+    fn is_hole(&self, chunk: &[u8]) -> bool {
+        self.sparse && DataBlock::is_empty_payload(chunk, self.system.block_size as usize)
+    }
+}
+
+impl RdfsFile<'_> {
+    /// Pointer of the file inode behind this handle.
+    pub fn inode_pointer(&self) -> u64 {
+        self.inode_pointer
+    }
+
+    /// Size of the file, appended bytes not flushed yet included.
+    pub fn len(&self) -> io::Result<u64> {
+        let inode = self.rdfs.read_inode_file(self.inode_pointer).map_err(io::Error::other)?;
+        Ok(inode.size + self.write_buffer.len() as u64)
+    }
+
+    pub fn is_empty(&self) -> io::Result<bool> {
+        Ok(self.len()? == 0)
+    }
+
+    fn payload_size(&self) -> u64 {
+        self.rdfs.system.data_payload_size()
+    }
+}
+
+impl Read for RdfsFile<'_> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        self.flush()?;
+        let buffered_to = self.buffered_from + self.read_buffer.len() as u64;
+        if self.position < self.buffered_from || self.position >= buffered_to {
+            let size = self.len()?;
+            if self.position >= size || buf.is_empty() {
+                return Ok(0);
+            }
+            let payload_size = self.payload_size();
+            self.buffered_from = self.position - self.position % payload_size;
+            self.read_buffer = self
+                .rdfs
+                .read_file_range(self.inode_pointer, self.buffered_from, payload_size)
+                .map_err(io::Error::other)?;
+        }
+
+        let from = (self.position - self.buffered_from) as usize;
+        let read = buf.len().min(self.read_buffer.len() - from);
+        buf[..read].copy_from_slice(&self.read_buffer[from..from + read]);
+        self.position += read as u64;
+        Ok(read)
+    }
+}
+
+impl Seek for RdfsFile<'_> {
+    fn seek(&mut self, pos: SeekFrom) -> io::Result<u64> {
+        let (base, offset) = match pos {
+            SeekFrom::Start(position) => (position, 0),
+            SeekFrom::End(offset) => (self.len()?, offset),
+            SeekFrom::Current(offset) => (self.position, offset),
+        };
+        self.position = base
+            .checked_add_signed(offset)
+            .ok_or_else(|| io::Error::new(ErrorKind::InvalidInput, "invalid seek to a negative or overflowing position"))?;
+        Ok(self.position)
+    }
+}
+
+impl Write for RdfsFile<'_> {
+    /// Appends `buf` to the file whatever the position, which moves to the new end.
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.write_buffer.extend_from_slice(buf);
+        if self.write_buffer.len() as u64 >= self.payload_size() {
+            self.flush()?;
+        }
+        self.position = self.len()?;
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        if self.write_buffer.is_empty() {
+            return Ok(());
+        }
+        self.rdfs
+            .append_to_file(self.inode_pointer, &self.write_buffer)
+            .map_err(io::Error::other)?;
+        self.write_buffer.clear();
+        // the last block may have been topped off
+        self.read_buffer.clear();
+        Ok(())
+    }
+}
+
+impl Drop for RdfsFile<'_> {
+    fn drop(&mut self) {
+        let _ = self.flush();
+    }
+}
+
+/// Appends `run` to the file ranges `content`, merging it into the last range when they are
+/// contiguous blocks or both holes.
+fn push_run(content: &mut Vec<FileContent>, run: FileContent, block_size: u64) {
+    match content.last_mut() {
+        Some(last) if last.is_hole() && run.is_hole() => last.blocks += run.blocks,
+        Some(last) if !last.is_hole() && last.pointer + last.blocks * block_size == run.pointer => last.blocks += run.blocks,
+        _ => content.push(run),
+    }
+}
+
+/// Default file name of a drive, `<program_id as hex>.RDFS`.
+pub fn drive_file_name(program_id: &Address) -> String {
+    bytes_to_hex(program_id) + ".RDFS"
+}
+
+/// Caches the (name, pointer, type) `entries` of the directory at `pointer` keyed by name.
+fn cache_dir(cache: &DirCache, pointer: u64, entries: &[(String, DirContent)]) -> Arc<HashMap<String, DirContent>> {
+    cache.insert(pointer, entries.iter().cloned().collect())
+}
+
+/// Decodes `encoded` as a super block fit to be mounted or copied over the other one: version 7
+/// or later, so its checksum was checked, and passing `SuperBlock::validate`.
+fn decode_backup(encoded: &[u8]) -> Option<SuperBlock> {
+    SuperBlock::from_bytes(encoded)
+        .ok()
+        .filter(|system| system.has_backup() && system.validate().is_ok())
+}
+
+/// Fills `buffer` from `reader` as far as possible, returning fewer bytes only at the end of the stream.
+fn read_chunk(reader: &mut impl Read, buffer: &mut [u8]) -> std::io::Result<usize> {
+    let mut filled = 0;
+    while filled < buffer.len() {
+        match reader.read(&mut buffer[filled..]) {
+            Ok(0) => break,
+            Ok(read) => filled += read,
+            Err(error) if error.kind() == ErrorKind::Interrupted => continue,
+            Err(error) => return Err(error),
+        }
+    }
+    Ok(filled)
+}
+
+/// Adds a write to the `staged` writes of a transaction, replacing an earlier one of the
+/// same range so a block rewritten several times is journaled once.
+fn stage_write(staged: &mut StagedWrites, pointer: u64, data: Vec<u8>) {
+    match staged.iter_mut().find(|(start, old)| *start == pointer && old.len() == data.len()) {
+        Some((_, old)) => *old = data,
+        None => staged.push((pointer, data)),
+    }
+}
+
+/// Copies every (pointer, data) write of `writes` over `data`, the bytes of the drive from
+/// `start`, in order. Only the part of a write falling inside `data` is copied.
+fn overlay(data: &mut [u8], start: u64, writes: &[(u64, Vec<u8>)]) {
+    let end = start + data.len() as u64;
+    for (pointer, staged) in writes {
+        let (from, to) = ((*pointer).max(start), (pointer + staged.len() as u64).min(end));
+        if from < to {
+            data[(from - start) as usize..(to - start) as usize].copy_from_slice(&staged[(from - pointer) as usize..(to - pointer) as usize]);
+        }
+    }
+}
+
+fn poisoned<T>(_: PoisonError<T>) -> anyhow::Error {
+    anyhow!("Mapped drive lock poisoned")
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::constants::MAX_BLOCK_SIZE;
+    use crate::utils::{ManualClock, hex_to_address, hex_to_bytes, mmap_read_range, set_clock};
+    use std::fs;
+
+    fn test_drive(name: &str) -> RDFS {
+        let dir = std::env::temp_dir().join(format!("rdfs_test_{name}"));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        RDFS::new(dir, FileSystemType::Shared, [255; 32], [1; 32], 1048576, 100, 1, 4096).unwrap()
+    }
+
+    /// The last record of the journal log of `rdfs`, `None` if it has none.
+    fn last_journal(rdfs: &RDFS) -> Option<JournalBlock> {
+        rdfs.scan_journal().unwrap().0.map(|(_, journal)| journal)
+    }
+
+    /// `test_drive` with a refcount table, needed to reflink with `copy_file`.
+    fn refcounted_test_drive(name: &str) -> RDFS {
+        let dir = std::env::temp_dir().join(format!("rdfs_test_{name}"));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        RDFS::new_refcounted(dir, [255; 32], [1; 32], 1048576, 100, 1, 4096).unwrap()
+    }
+
+    #[test]
+    fn create_file_test() {
+        let rdfs = test_drive("create_file");
+        let root = rdfs.system.inode_pointer;
+        let free_before = rdfs.read_bitmaps_block().unwrap().free_blocks;
+
+        let data: Vec<u8> = (0..10_000).map(|i| (i % 251) as u8).collect();
+        let pointer = rdfs.create_file(root, "report.txt", &data).unwrap();
+
+        // 3 data blocks + 1 inode block
+        let bitmaps = rdfs.read_bitmaps_block().unwrap();
+        assert_eq!(bitmaps.free_blocks, free_before - 4);
+
+        let parent = rdfs.read_inode_dir(root).unwrap();
+        assert_eq!(
+            parent.content,
+            vec![DirContent {
+                pointer,
+                inode_type: InodeType::File,
+                name: 0,
+            }]
+        );
+
+        let inode = rdfs.read_inode_file(pointer).unwrap();
+        assert_eq!(inode.name.as_string(), "report.txt");
+        assert_eq!(inode.size, 10_000);
+        assert_eq!(inode.total_blocks, 3);
+        assert_eq!(inode.content.iter().map(|c| c.blocks).sum::<u64>(), 3);
+
+        let first = DataBlock::from_bytes(&rdfs.read_block(inode.content[0].pointer).unwrap(), 4096).unwrap();
+        assert_eq!(first.data[..4096 - RESERVED_DB], data[..4096 - RESERVED_DB]);
+    }
+
+    #[test]
+    fn create_file_fragmented_test() {
+        let rdfs = test_drive("create_file_fragmented");
+        let root = rdfs.system.inode_pointer;
+
+        // leave holes by marking every other block as used
+        let mut bitmaps = rdfs.read_bitmaps_block().unwrap();
+        for index in (1..20).step_by(2) {
+            bitmaps.set_bit(index);
+        }
+        rdfs.write_bitmaps_block(&bitmaps).unwrap();
+
+        let data = vec![7u8; 3 * (4096 - RESERVED_DB)];
+        let pointer = rdfs.create_file(root, "holes.bin", &data).unwrap();
+        let inode = rdfs.read_inode_file(pointer).unwrap();
+        assert_eq!(inode.content.len(), 3);
+        assert!(inode.content.iter().all(|c| c.blocks == 1));
+    }
+
+    #[test]
+    fn mkdir_test() {
+        let rdfs = test_drive("mkdir");
+        let root = rdfs.system.inode_pointer;
+        let free_blocks = rdfs.read_bitmaps_block().unwrap().free_blocks;
+
+        let docs = rdfs.mkdir(root, "docs").unwrap();
+        let year = rdfs.mkdir(docs, "2025").unwrap();
+        let report = rdfs.create_file(year, "report.txt", b"numbers").unwrap();
+        assert_eq!(rdfs.read_bitmaps_block().unwrap().free_blocks, free_blocks - 4);
+        assert_eq!(rdfs.resolve_path("/docs/2025").unwrap(), (year, InodeType::Dir));
+        assert_eq!(rdfs.resolve_path("/docs/2025/report.txt").unwrap(), (report, InodeType::File));
+
+        let inode = rdfs.read_inode_dir(year).unwrap();
+        assert_eq!(inode.name.as_string(), "2025");
+        assert_eq!(inode.size, 1);
+
+        let error = rdfs.mkdir(root, "docs").unwrap_err();
+        assert!(matches!(error.downcast_ref::<RDFSError>(), Some(RDFSError::AlreadyExists(name)) if name == "docs"));
+        let error = rdfs.create_file(year, "report.txt", b"again").unwrap_err();
+        assert!(matches!(error.downcast_ref::<RDFSError>(), Some(RDFSError::AlreadyExists(_))));
+        assert!(rdfs.mkdir(root, &"x".repeat(256)).is_err());
+        for name in ["a/b", "nul\0"] {
+            let error = rdfs.create_file(root, name, b"hidden").unwrap_err();
+            assert!(matches!(error.downcast_ref::<RDFSError>(), Some(RDFSError::InvalidName(_))));
+        }
+        assert_eq!(rdfs.read_bitmaps_block().unwrap().free_blocks, free_blocks - 4);
+    }
+
+    #[test]
+    fn read_file_test() {
+        let rdfs = test_drive("read_file");
+        let root = rdfs.system.inode_pointer;
+        let payload_size = 4096 - RESERVED_DB;
+
+        for (i, length) in [0, 1, payload_size, payload_size + 1, 5 * payload_size - 3].into_iter().enumerate() {
+            let data: Vec<u8> = (0..length).map(|byte| (byte % 253) as u8).collect();
+            let pointer = rdfs.create_file(root, &format!("file_{i}"), &data).unwrap();
+            assert_eq!(rdfs.read_file(pointer).unwrap(), data);
+        }
+    }
+
+    #[test]
+    fn read_file_linked_test() {
+        let dir = std::env::temp_dir().join("rdfs_test_read_file_linked");
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        let rdfs = RDFS::new(dir, FileSystemType::Shared, [255; 32], [1; 32], 4 * 1048576, 100, 1, 4096).unwrap();
+        let root = rdfs.system.inode_pointer;
+
+        // every other block is used so each data block becomes its own `FileContent`
+        let blocks = rdfs.system.max_content_pointers + 5;
+        let mut bitmaps = rdfs.read_bitmaps_block().unwrap();
+        for index in (1..2 * blocks + 4).step_by(2) {
+            bitmaps.set_bit(index as usize);
+        }
+        rdfs.write_bitmaps_block(&bitmaps).unwrap();
+
+        let data: Vec<u8> = (0..blocks as usize * (4096 - RESERVED_DB)).map(|byte| (byte % 241) as u8).collect();
+        let pointer = rdfs.create_file(root, "scattered.bin", &data).unwrap();
+        assert_ne!(rdfs.read_inode_file(pointer).unwrap().linked, 0);
+        assert_eq!(rdfs.read_file(pointer).unwrap(), data);
+    }
+
+    #[test]
+    fn read_file_size_mismatch_test() {
+        let rdfs = test_drive("read_file_size_mismatch");
+        let root = rdfs.system.inode_pointer;
+        let pointer = rdfs.create_file(root, "file", b"hello world").unwrap();
+
+        let mut inode = rdfs.read_inode_file(pointer).unwrap();
+        inode.size = 3;
+        rdfs.write_inode_file(pointer, &inode).unwrap();
+
+        let error = rdfs.read_file(pointer).unwrap_err();
+        assert!(matches!(error.downcast_ref::<RDFSError>(), Some(RDFSError::InvalidFileSize)));
+    }
+
+    #[test]
+    fn delete_file_test() {
+        let dir = std::env::temp_dir().join("rdfs_test_delete_file");
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        let rdfs = RDFS::new(dir, FileSystemType::Shared, [255; 32], [1; 32], 4 * 1048576, 100, 1, 4096).unwrap();
+        let root = rdfs.system.inode_pointer;
+
+        // fragmented so the file spills into `InodeLinkedFile` blocks
+        let blocks = rdfs.system.max_content_pointers + 5;
+        let mut bitmaps = rdfs.read_bitmaps_block().unwrap();
+        for index in (1..2 * blocks + 4).step_by(2) {
+            bitmaps.set_bit(index as usize);
+        }
+        rdfs.write_bitmaps_block(&bitmaps).unwrap();
+        let fragmented = rdfs.read_bitmaps_block().unwrap().free_blocks;
+
+        let data = vec![3u8; blocks as usize * (4096 - RESERVED_DB)];
+        let pointer = rdfs.create_file(root, "scattered.bin", &data).unwrap();
+        let kept = rdfs.create_file(root, "kept.txt", b"still here").unwrap();
+        let first_block = rdfs.read_inode_file(pointer).unwrap().content[0].pointer;
+        let raw_block = rdfs.read_block(first_block).unwrap();
+
+        rdfs.delete_file(root, pointer).unwrap();
+        let bitmaps = rdfs.read_bitmaps_block().unwrap();
+        // only the inode and data block of `kept.txt` stay allocated
+        assert_eq!(bitmaps.free_blocks, fragmented - 2);
+        assert_eq!(rdfs.read_dir_entries(root).unwrap().len(), 1);
+        assert_eq!(rdfs.read_file(kept).unwrap(), b"still here");
+
+        // zero move: the data block keeps its bytes
+        assert_eq!(rdfs.read_block(first_block).unwrap(), raw_block);
+
+        let error = rdfs.delete_file(root, pointer).unwrap_err();
+        assert!(matches!(error.downcast_ref::<RDFSError>(), Some(RDFSError::FileEntryNotFound(_))));
+    }
+
+    #[test]
+    fn remove_dir_all_test() {
+        let rdfs = test_drive("remove_dir_all");
+        let root = rdfs.system.inode_pointer;
+        let free_blocks = rdfs.read_bitmaps_block().unwrap().free_blocks;
+
+        let docs = rdfs.mkdir(root, "docs").unwrap();
+        let year = rdfs.mkdir(docs, "2025").unwrap();
+        rdfs.create_file(docs, "a.txt", &vec![1u8; 10_000]).unwrap();
+        rdfs.create_file(year, "b.txt", b"b").unwrap();
+        // enough entries for `2025` to spill into a linked block
+        for i in 0..rdfs.system.max_content_pointers {
+            rdfs.create_file(year, &format!("empty_{i}"), &[]).unwrap();
+        }
+        let kept = rdfs.create_file(root, "kept.txt", b"kept").unwrap();
+        let used = free_blocks - rdfs.read_bitmaps_block().unwrap().free_blocks;
+
+        // point a linked block of `2025` back at `docs`
+        let pointer = rdfs.read_inode_dir(year).unwrap().linked;
+        let mut linked = rdfs.read_linked_dir(pointer).unwrap();
+        linked.linked = docs;
+        rdfs.write_linked_dir(pointer, &linked).unwrap();
+
+        let freed = rdfs.remove_dir_all(root, docs).unwrap();
+        assert_eq!(freed, used - 2);
+        assert_eq!(rdfs.read_bitmaps_block().unwrap().free_blocks, free_blocks - 2);
+        assert_eq!(rdfs.list_dir(root).unwrap(), vec![("kept.txt".to_string(), kept, InodeType::File)]);
+        assert!(rdfs.fsck(false).unwrap().is_clean());
+
+        let error = rdfs.remove_dir_all(root, kept).unwrap_err();
+        assert!(matches!(error.downcast_ref::<RDFSError>(), Some(RDFSError::DirEntryNotFound(_))));
+    }
+
+    #[test]
+    fn rename_test() {
+        let rdfs = test_drive("rename");
+        let root = rdfs.system.inode_pointer;
+        let docs = rdfs.mkdir(root, "docs").unwrap();
+        let pointer = rdfs.create_file(root, "draft.txt", b"content").unwrap();
+        rdfs.create_file(docs, "taken.txt", b"other").unwrap();
+
+        rdfs.rename(root, "draft.txt", root, "final.txt").unwrap();
+        assert_eq!(rdfs.resolve_path("/final.txt").unwrap().0, pointer);
+        assert!(rdfs.resolve_path("/draft.txt").is_err());
+
+        let error = rdfs.rename(root, "final.txt", docs, "taken.txt").unwrap_err();
+        assert!(matches!(error.downcast_ref::<RDFSError>(), Some(RDFSError::AlreadyExists(_))));
+
+        let free_blocks = rdfs.read_bitmaps_block().unwrap().free_blocks;
+        rdfs.rename(root, "final.txt", docs, "report.txt").unwrap();
+        assert_eq!(rdfs.resolve_path("/docs/report.txt").unwrap().0, pointer);
+        assert!(rdfs.resolve_path("/final.txt").is_err());
+        assert_eq!(rdfs.read_file(pointer).unwrap(), b"content");
+        assert_eq!(rdfs.read_bitmaps_block().unwrap().free_blocks, free_blocks);
+
+        let error = rdfs.rename(root, "docs", docs, "nested").unwrap_err();
+        assert!(matches!(error.downcast_ref::<RDFSError>(), Some(RDFSError::MoveIntoItself(_))));
+        let error = rdfs.rename(docs, "report.txt", docs, "../report.txt").unwrap_err();
+        assert!(matches!(error.downcast_ref::<RDFSError>(), Some(RDFSError::InvalidName(_))));
+    }
+
+    #[test]
+    fn mount_drive_inconsistent_test() {
+        let rdfs = test_drive("mount_drive_inconsistent");
+        let mut system = rdfs.system.clone();
+        system.inode_pointer -= 1;
+        write_range(&rdfs.path, 0, &system.to_bytes()).unwrap();
+        write_range(&rdfs.path, rdfs.system.node_storage, &system.to_bytes()).unwrap();
+
+        let error = RDFS::mount_drive(&rdfs.path).unwrap_err();
+        assert!(matches!(error.downcast_ref::<RDFSError>(), Some(RDFSError::InconsistentSuperBlock(_))));
+    }
+
+    #[test]
+    fn fsck_test() {
+        let rdfs = test_drive("fsck");
+        let root = rdfs.system.inode_pointer;
+        let docs = rdfs.mkdir(root, "docs").unwrap();
+        let file = rdfs.create_file(docs, "report.txt", &vec![1u8; 9000]).unwrap();
+        rdfs.create_file(root, "notes.txt", b"notes").unwrap();
+        assert!(rdfs.fsck(false).unwrap().is_clean());
+
+        // leak a block, point a second file at the same data, list a file twice and add a dangling entry
+        let mut bitmaps = rdfs.read_bitmaps_block().unwrap();
+        let leaked = rdfs.allocate_blocks(&mut bitmaps, 1).unwrap()[0].pointer;
+        rdfs.write_bitmaps_block(&bitmaps).unwrap();
+        let shared = rdfs.read_inode_file(file).unwrap().content[0].pointer;
+        let copy = rdfs.create_file(root, "copy.txt", b"x").unwrap();
+        let mut inode = rdfs.read_inode_file(copy).unwrap();
+        let own_block = inode.content[0].pointer;
+        inode.content[0].pointer = shared;
+        rdfs.write_inode_file(copy, &inode).unwrap();
+        let mut root_inode = rdfs.read_inode_dir(root).unwrap();
+        root_inode.content.push(DirContent {
+            pointer: 3,
+            inode_type: InodeType::File,
+            name: 0,
+        });
+        root_inode.content.push(DirContent {
+            pointer: copy,
+            inode_type: InodeType::File,
+            name: 0,
+        });
+        rdfs.write_inode_dir(root, &root_inode).unwrap();
+
+        let report = rdfs.fsck(false).unwrap();
+        assert_eq!(report.leaked, vec![leaked, own_block]);
+        assert_eq!(report.double_referenced, vec![copy]);
+        assert_eq!(report.shared, vec![shared]);
+        assert_eq!(report.dangling, vec![3]);
+        assert!(!report.repaired);
+
+        let free_blocks = rdfs.read_bitmaps_block().unwrap().free_blocks;
+        assert!(rdfs.fsck(true).unwrap().repaired);
+        assert_eq!(rdfs.read_bitmaps_block().unwrap().free_blocks, free_blocks + 2);
+        assert!(rdfs.fsck(false).unwrap().leaked.is_empty());
+    }
+
+    #[test]
+    fn write_file_streaming_test() {
+        let rdfs = test_drive("write_file_streaming");
+        let root = rdfs.system.inode_pointer;
+
+        // a reader handing out small uneven pieces, like a socket would
+        struct Trickle(Vec<u8>, usize);
+        impl Read for Trickle {
+            fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+                let length = buf.len().min(1000).min(self.0.len() - self.1);
+                buf[..length].copy_from_slice(&self.0[self.1..self.1 + length]);
+                self.1 += length;
+                Ok(length)
+            }
+        }
+
+        let data: Vec<u8> = (0..30_000).map(|byte| (byte % 247) as u8).collect();
+        let pointer = rdfs.write_file_streaming(root, "stream.bin", Trickle(data.clone(), 0)).unwrap();
+        assert_eq!(rdfs.read_file(pointer).unwrap(), data);
+        let inode = rdfs.read_inode_file(pointer).unwrap();
+        assert_eq!(inode.total_blocks, 30_000u64.div_ceil(4096 - RESERVED_DB as u64));
+        assert_eq!(inode.content.len(), 1);
+
+        let empty = rdfs.write_file_streaming(root, "empty.bin", &[][..]).unwrap();
+        assert!(rdfs.read_file(empty).unwrap().is_empty());
+    }
+
+    #[test]
+    fn write_file_streaming_error_test() {
+        let rdfs = test_drive("write_file_streaming_error");
+        let root = rdfs.system.inode_pointer;
+        let free_blocks = rdfs.read_bitmaps_block().unwrap().free_blocks;
+
+        let failing = (&[5u8; 20_000][..]).chain(FailingReader);
+        assert!(rdfs.write_file_streaming(root, "broken.bin", failing).is_err());
+        assert_eq!(rdfs.read_bitmaps_block().unwrap().free_blocks, free_blocks);
+        assert!(rdfs.list_dir(root).unwrap().is_empty());
+        assert!(rdfs.fsck(false).unwrap().is_clean());
+    }
+
+    struct FailingReader;
+
+    impl Read for FailingReader {
+        fn read(&mut self, _: &mut [u8]) -> std::io::Result<usize> {
+            Err(std::io::Error::other("connection reset"))
+        }
+    }
+
+    #[test]
+    fn append_to_file_test() {
+        let rdfs = test_drive("append_to_file");
+        let root = rdfs.system.inode_pointer;
+        let payload_size = 4096 - RESERVED_DB;
+
+        let mut data: Vec<u8> = (0..payload_size - 10).map(|byte| (byte % 251) as u8).collect();
+        let pointer = rdfs.create_file(root, "log.txt", &data).unwrap();
+        let free_blocks = rdfs.read_bitmaps_block().unwrap().free_blocks;
+
+        // tops off the partial block and crosses into a new one
+        let more: Vec<u8> = (0..100).map(|byte| byte as u8).collect();
+        rdfs.append_to_file(pointer, &more).unwrap();
+        data.extend_from_slice(&more);
+        assert_eq!(rdfs.read_file(pointer).unwrap(), data);
+        let inode = rdfs.read_inode_file(pointer).unwrap();
+        assert_eq!(inode.size, data.len() as u64);
+        assert_eq!(inode.total_blocks, 2);
+        assert_eq!(rdfs.read_bitmaps_block().unwrap().free_blocks, free_blocks - 1);
+
+        // only fits in the last block
+        rdfs.append_to_file(pointer, b"tail").unwrap();
+        data.extend_from_slice(b"tail");
+        assert_eq!(rdfs.read_file(pointer).unwrap(), data);
+        assert_eq!(rdfs.read_inode_file(pointer).unwrap().total_blocks, 2);
+
+        let empty = rdfs.create_file(root, "empty.txt", &[]).unwrap();
+        rdfs.append_to_file(empty, &data).unwrap();
+        assert_eq!(rdfs.read_file(empty).unwrap(), data);
+        assert!(rdfs.fsck(false).unwrap().is_clean());
+    }
+
+    #[test]
+    fn append_to_file_linked_test() {
+        let dir = std::env::temp_dir().join("rdfs_test_append_to_file_linked");
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        let rdfs = RDFS::new(dir, FileSystemType::Shared, [255; 32], [1; 32], 4 * 1048576, 100, 1, 4096).unwrap();
+        let root = rdfs.system.inode_pointer;
+        let payload_size = 4096 - RESERVED_DB;
+
+        // every other block is used so each appended block is its own `FileContent`
+        let blocks = rdfs.system.max_content_pointers as usize + 5;
+        let mut bitmaps = rdfs.read_bitmaps_block().unwrap();
+        for index in (1..2 * blocks + 8).step_by(2) {
+            bitmaps.set_bit(index);
+        }
+        rdfs.write_bitmaps_block(&bitmaps).unwrap();
+
+        let mut data = vec![1u8; 3];
+        let pointer = rdfs.create_file(root, "growing.bin", &data).unwrap();
+        for i in 0..blocks {
+            let chunk = vec![i as u8; payload_size];
+            rdfs.append_to_file(pointer, &chunk).unwrap();
+            data.extend_from_slice(&chunk);
+        }
+        assert_ne!(rdfs.read_inode_file(pointer).unwrap().linked, 0);
+        assert_eq!(rdfs.read_file(pointer).unwrap(), data);
+    }
+
+    #[cfg(feature = "async")]
+    #[tokio::test]
+    async fn read_blocks_async_test() {
+        let rdfs = test_drive("read_blocks_async");
+        let root = rdfs.system.inode_pointer;
+        let data: Vec<u8> = (0..5 * (4096 - RESERVED_DB)).map(|byte| (byte % 233) as u8).collect();
+        let pointer = rdfs.create_file(root, "async.bin", &data).unwrap();
+        let content = rdfs.read_inode_file(pointer).unwrap().content;
+        let pointers: Vec<u64> = rdfs.block_pointers(&content).collect();
+
+        let tasks: Vec<_> = pointers
+            .iter()
+            .map(|&pointer| {
+                let rdfs = rdfs.clone();
+                tokio::spawn(async move { rdfs.read_block_async(pointer).await })
+            })
+            .collect();
+        for (task, pointer) in tasks.into_iter().zip(pointers.iter()) {
+            assert_eq!(task.await.unwrap().unwrap(), rdfs.read_block(*pointer).unwrap());
+        }
+
+        let block = DataBlock::new(0, 0, b"written async").to_bytes(4096);
+        rdfs.write_block_async(pointers[0], &block).await.unwrap();
+        assert_eq!(rdfs.read_block(pointers[0]).unwrap(), block);
+        assert!(rdfs.read_block_async(pointers[0] + 1).await.is_err());
+    }
+
+    #[test]
+    fn mapped_mode_test() {
+        let rdfs = test_drive("mapped_mode").with_mmap().unwrap();
+        let root = rdfs.system.inode_pointer;
+
+        let data: Vec<u8> = (0..20_000).map(|byte| (byte % 239) as u8).collect();
+        let pointer = rdfs.create_file(root, "mapped.bin", &data).unwrap();
+        assert_eq!(rdfs.read_file(pointer).unwrap(), data);
+
+        let content = rdfs.read_inode_file(pointer).unwrap().content;
+        let blocks: Vec<Vec<u8>> = rdfs.read_blocks(content.clone()).collect::<Result<_>>().unwrap();
+        assert_eq!(blocks.len() as u64, content.iter().map(|c| c.blocks).sum::<u64>());
+
+        // once flushed the plain file access sees the same drive
+        let path = rdfs.path.clone();
+        rdfs.unmount_drive().unwrap();
+        let rdfs = RDFS::mount_drive(&path).unwrap();
+        assert_eq!(rdfs.read_file(pointer).unwrap(), data);
+        assert_eq!(
+            mmap_read_range(&path, pointer, pointer + 4096).unwrap(),
+            rdfs.read_block(pointer).unwrap()
+        );
+    }
+
+    #[test]
+    fn mapped_mode_resize_test() {
+        let rdfs = test_drive("mapped_mode_resize").with_mmap().unwrap();
+        let length = rdfs.system.node_storage;
+
+        // grow the file behind the mapping, the new tail must still be reachable
+        create_physical_file(&rdfs.path, length + 4096).unwrap();
+        rdfs.write_drive_range(length, &[9; 4096]).unwrap();
+        assert_eq!(rdfs.read_drive_range(length, length + 4096).unwrap(), vec![9; 4096]);
+
+        // blocks past the drive layout stay unreachable through the block API
+        let error = rdfs.read_block(rdfs.system.data_pointer + rdfs.system.total_blocks * 4096).unwrap_err();
+        assert!(matches!(error.downcast_ref::<RDFSError>(), Some(RDFSError::PointerOutOfRange { .. })));
+    }
+
+    #[test]
+    fn open_handle_test() {
+        let rdfs = test_drive("open_handle").with_open_handle().unwrap();
+        let root = rdfs.system.inode_pointer;
+
+        let data: Vec<u8> = (0..20_000).map(|byte| (byte % 233) as u8).collect();
+        let pointer = rdfs.create_file(root, "handle.bin", &data).unwrap();
+        let clone = rdfs.clone();
+        assert_eq!(clone.read_file(pointer).unwrap(), data);
+
+        // writes need no flush, a plain mount sees them right away
+        let plain = RDFS::mount_drive(&rdfs.path).unwrap();
+        assert_eq!(plain.read_file(pointer).unwrap(), data);
+        let content = rdfs.read_inode_file(pointer).unwrap().content;
+        assert_eq!(
+            read_range(&rdfs.path, content[0].pointer, content[0].pointer + 4096).unwrap(),
+            rdfs.read_block(content[0].pointer).unwrap()
+        );
+
+        // the handle follows the file when it grows
+        let length = fs::metadata(&rdfs.path).unwrap().len();
+        create_physical_file(&rdfs.path, length + 4096).unwrap();
+        rdfs.write_drive_range(length + 4096, &[9; 4096]).unwrap();
+        assert_eq!(fs::metadata(&rdfs.path).unwrap().len(), length + 8192);
+        assert_eq!(rdfs.read_drive_range(length + 4096, length + 8192).unwrap(), vec![9; 4096]);
+        rdfs.unmount_drive().unwrap();
+    }
+
+    #[test]
+    fn concurrent_block_io_test() {
+        let handle = test_drive("concurrent_block_io").with_open_handle().unwrap();
+        let bitmaps = handle.read_bitmaps_block().unwrap();
+        let pointers: Vec<u64> = bitmaps
+            .free_blocks_iter()
+            .take(64)
+            .map(|index| handle.system.block_pointer(index).unwrap())
+            .collect();
+        let block = |pointer: u64| -> Vec<u8> { (0..4096u64).map(|byte| (pointer / 4096 + byte) as u8).collect() };
+
+        // one shared handle and plain per call files, each thread owning every fourth block
+        for rdfs in [
+            handle.clone(),
+            RDFS {
+                handle: None,
+                ..handle.clone()
+            },
+        ] {
+            std::thread::scope(|scope| {
+                for thread in 0..4 {
+                    let (rdfs, pointers) = (&rdfs, &pointers);
+                    scope.spawn(move || {
+                        for pointer in pointers.iter().skip(thread).step_by(4) {
+                            rdfs.write_block(*pointer, &block(*pointer)).unwrap();
+                        }
+                    });
+                }
+            });
+            std::thread::scope(|scope| {
+                for thread in 0..8 {
+                    let (rdfs, pointers) = (&rdfs, &pointers);
+                    scope.spawn(move || {
+                        for round in 0..20 {
+                            let pointer = pointers[(thread * 7 + round * 13) % pointers.len()];
+                            assert_eq!(rdfs.read_block(pointer).unwrap(), block(pointer));
+                        }
+                    });
+                }
+            });
+        }
+    }
+
+    #[test]
+    fn resolve_path_test() {
+        let rdfs = test_drive("resolve_path");
+        let root = rdfs.system.inode_pointer;
+        let pointer = rdfs.create_file(root, "report.txt", b"hello").unwrap();
+
+        assert_eq!(rdfs.resolve_path("/").unwrap(), (root, InodeType::Dir));
+        assert_eq!(rdfs.resolve_path("/report.txt").unwrap(), (pointer, InodeType::File));
+
+        let error = rdfs.resolve_path("/missing.txt").unwrap_err();
+        assert!(matches!(error.downcast_ref::<RDFSError>(), Some(RDFSError::PathNotFound(path)) if path == "/missing.txt"));
+
+        let error = rdfs.resolve_path("/report.txt/inner").unwrap_err();
+        assert!(matches!(error.downcast_ref::<RDFSError>(), Some(RDFSError::NotADirectory(path)) if path == "/report.txt"));
+    }
+
+    #[test]
+    fn resolve_path_linked_dir_test() {
+        let rdfs = test_drive("resolve_path_linked_dir");
+        let root = rdfs.system.inode_pointer;
+
+        // one entry more than the root inode can hold spills into an `InodeLinkedDir`
+        let count = rdfs.system.max_content_pointers + 1;
+        for i in 0..count {
+            rdfs.create_file(root, &format!("file_{i}"), &[]).unwrap();
+        }
+        assert_ne!(rdfs.read_inode_dir(root).unwrap().linked, 0);
+        assert_eq!(rdfs.read_dir_entries(root).unwrap().len() as u64, count);
+        assert_eq!(rdfs.read_inode_dir(root).unwrap().entry_count(), count);
+
+        let (pointer, inode_type) = rdfs.resolve_path(&format!("/file_{}", count - 1)).unwrap();
+        assert_eq!(inode_type, InodeType::File);
+        assert_eq!(rdfs.read_inode_file(pointer).unwrap().name.as_string(), format!("file_{}", count - 1));
+    }
+
+    #[test]
+    fn list_dir_test() {
+        let rdfs = test_drive("list_dir");
+        let root = rdfs.system.inode_pointer;
+        let docs = rdfs.mkdir(root, "docs").unwrap();
+        rdfs.create_file(docs, "nested.txt", b"deep").unwrap();
+
+        // enough entries to spill into linked blocks
+        let count = rdfs.system.max_content_pointers + 3;
+        let files: Vec<u64> = (0..count).map(|i| rdfs.create_file(root, &format!("file_{i}"), &[]).unwrap()).collect();
+
+        let listing = rdfs.list_dir(root).unwrap();
+        assert_eq!(listing.len() as u64, count + 1);
+        assert_eq!(listing[0], ("docs".to_string(), docs, InodeType::Dir));
+        let last = count as usize - 1;
+        assert_eq!(listing[last + 1], (format!("file_{last}"), files[last], InodeType::File));
+        assert!(listing.iter().all(|(name, _, _)| name != "nested.txt"));
+    }
+
+    #[test]
+    fn remove_dir_entry_test() {
+        let rdfs = test_drive("remove_dir_entry");
+        let root = rdfs.system.inode_pointer;
+
+        let count = rdfs.system.max_content_pointers + 1;
+        let pointers: Vec<u64> = (0..count).map(|i| rdfs.create_file(root, &format!("file_{i}"), &[]).unwrap()).collect();
+        let linked = rdfs.read_inode_dir(root).unwrap().linked;
+        assert_eq!(rdfs.read_inode_dir(root).unwrap().size, count);
+
+        // removing the only entry of the linked block collapses the chain and frees it
+        let entry = |pointer| DirContent {
+            pointer,
+            inode_type: InodeType::File,
+            name: 0,
+        };
+        let mut bitmaps = rdfs.read_bitmaps_block().unwrap();
+        let free_before = bitmaps.free_blocks;
+        assert!(rdfs.remove_dir_entry(&mut bitmaps, root, &entry(pointers[count as usize - 1])).unwrap());
+        assert_eq!(bitmaps.free_blocks, free_before + 1);
+        assert!(!bitmaps.get_bit(rdfs.system.block_index(linked).unwrap() as usize));
+
+        let parent = rdfs.read_inode_dir(root).unwrap();
+        assert_eq!(parent.linked, 0);
+        assert_eq!(parent.size, count - 1);
+
+        assert!(rdfs.remove_dir_entry(&mut bitmaps, root, &entry(pointers[0])).unwrap());
+        assert!(!rdfs.remove_dir_entry(&mut bitmaps, root, &entry(pointers[0])).unwrap());
+        assert_eq!(rdfs.read_dir_entries(root).unwrap().len() as u64, count - 2);
+    }
+
+    #[test]
+    fn create_file_no_space_test() {
+        let rdfs = test_drive("create_file_no_space");
+        let root = rdfs.system.inode_pointer;
+        rdfs.create_file(root, "small.txt", b"small").unwrap();
+        let before = rdfs.read_bitmaps_block().unwrap();
+
+        let data = vec![0u8; rdfs.system.max_file_size() as usize];
+        let error = rdfs.create_file(root, "huge.bin", &data).unwrap_err();
+        assert!(matches!(error.downcast_ref::<RDFSError>(), Some(RDFSError::NotEnoughSpace)));
+
+        let after = rdfs.read_bitmaps_block().unwrap();
+        assert_eq!(before.bit_field, after.bit_field);
+        assert_eq!(rdfs.read_inode_dir(root).unwrap().content.len(), 1);
+    }
+
+    #[test]
+    fn resize_drive_test() {
+        let mut rdfs = test_drive("resize_drive");
+        let old = rdfs.system.clone();
+        let docs = rdfs.mkdir(old.inode_pointer, "docs").unwrap();
+        let report: Vec<u8> = (0..20000).map(|i| (i % 251) as u8).collect();
+        rdfs.create_file(docs, "report.txt", &report).unwrap();
+        let notes = rdfs.create_file(old.inode_pointer, "notes.txt", b"notes").unwrap();
+        rdfs.link(notes, docs, "notes.link").unwrap();
+        let free_before = rdfs.read_bitmaps_block().unwrap().free_blocks;
+
+        let error = rdfs.resize_drive(1048576 / 2).unwrap_err();
+        assert!(matches!(error.downcast_ref::<RDFSError>(), Some(RDFSError::CannotShrinkDrive { .. })));
+
+        rdfs.resize_drive(2 * 1048576).unwrap();
+        assert!(rdfs.system.total_blocks > old.total_blocks);
+        assert!(rdfs.system.data_pointer > old.data_pointer);
+        let root_index = old.total_blocks - 1;
+        assert_eq!(rdfs.system.block_index(rdfs.system.inode_pointer).unwrap(), root_index);
+
+        // the root inode kept its block index and the tree is still reachable
+        let rdfs = RDFS::mount_drive(&rdfs.path).unwrap();
+        let root = rdfs.read_inode_dir(rdfs.system.inode_pointer).unwrap();
+        assert_eq!(root.name.as_string(), "./");
+        assert_eq!(root.total_blocks, rdfs.system.total_blocks);
+        let (file, _) = rdfs.resolve_path("/docs/report.txt").unwrap();
+        assert_eq!(rdfs.read_file(file).unwrap(), report);
+        let (file, _) = rdfs.resolve_path("/notes.txt").unwrap();
+        assert_eq!(rdfs.read_file(file).unwrap(), b"notes");
+        assert_eq!(rdfs.resolve_path("/docs/notes.link").unwrap().0, file);
+        assert!(rdfs.fsck(false).unwrap().is_clean());
+
+        // the new blocks are free and usable
+        let bitmaps = rdfs.read_bitmaps_block().unwrap();
+        assert_eq!(bitmaps.free_blocks, free_before + rdfs.system.total_blocks - old.total_blocks);
+        let big = vec![7u8; (old.total_blocks * 4096) as usize];
+        let file = rdfs.create_file(rdfs.system.inode_pointer, "big.bin", &big).unwrap();
+        assert_eq!(rdfs.read_file(file).unwrap(), big);
+
+        let dir = rdfs.path.parent().unwrap();
+        let mut private = RDFS::new(dir, FileSystemType::Private, [255; 32], [2; 32], 1048576, 100, 1, 4096).unwrap();
+        assert!(private.resize_drive(2 * 1048576).is_err());
+    }
+
+    #[test]
+    fn name_policy_test() {
+        let rdfs = test_drive("name_policy");
+        let root = rdfs.system.inode_pointer;
+        rdfs.create_file(root, "Photo.JPG", b"a").unwrap();
+        rdfs.create_file(root, "photo.jpg", b"b").unwrap();
+
+        let rdfs = rdfs.with_name_policy(NamePolicy::CaseInsensitive);
+        let error = rdfs.create_file(root, "PHOTO.jpg", b"c").unwrap_err();
+        assert!(matches!(error.downcast_ref::<RDFSError>(), Some(RDFSError::AlreadyExists(_))));
+        let docs = rdfs.mkdir(root, "Docs").unwrap();
+        assert!(rdfs.mkdir(root, "docs").is_err());
+
+        // changing the case of an entry is not a collision with itself
+        rdfs.rename(root, "docs", root, "DOCS").unwrap();
+        assert_eq!(rdfs.read_inode_dir(docs).unwrap().name.as_string(), "DOCS");
+        assert!(rdfs.rename(root, "docs", root, "photo.JPG").is_err());
+
+        let rdfs = rdfs.with_name_policy(NamePolicy::Nfc);
+        rdfs.create_file(docs, "caf\u{e9}.txt", b"d").unwrap();
+        let error = rdfs.create_file(docs, "cafe\u{301}.txt", b"e").unwrap_err();
+        assert!(matches!(error.downcast_ref::<RDFSError>(), Some(RDFSError::AlreadyExists(_))));
+        assert!(rdfs.create_file(docs, "CAF\u{c9}.txt", b"f").is_ok());
+    }
+
+    #[test]
+    fn stat_test() {
+        // large enough for the spilled ranges to stay inside the data blocks
+        let dir = std::env::temp_dir().join("rdfs_test_stat");
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        let rdfs = RDFS::new(&dir, FileSystemType::Shared, [255; 32], [1; 32], 4194304, 100, 1, 4096).unwrap();
+        let root = rdfs.system.inode_pointer;
+        rdfs.mkdir(root, "docs").unwrap();
+        let file = rdfs.create_file(root, "report.txt", &vec![1u8; 10_000]).unwrap();
+
+        let stat = rdfs.stat(file, InodeType::File).unwrap();
+        assert_eq!(stat.inode, file);
+        assert_eq!(stat.inode_type, InodeType::File);
+        assert_eq!(stat.name, "report.txt");
+        assert_eq!((stat.size, stat.total_blocks, stat.content_entries), (10_000, 3, 1));
+        assert!(!stat.linked);
+        assert!(stat.created <= stat.modify);
+
+        let stat = rdfs.stat(root, InodeType::Dir).unwrap();
+        assert_eq!(stat.name, "./");
+        assert_eq!(stat.content_entries, 2);
+
+        // ranges spilling into a linked block are counted too
+        let runs = rdfs.system.max_content_pointers as usize + 5;
+        let content = (0..runs as u64)
+            .map(|i| FileContent {
+                pointer: rdfs.system.data_pointer + i * 2 * 4096,
+                blocks: 1,
+            })
+            .collect();
+        let mut bitmaps = rdfs.read_bitmaps_block().unwrap();
+        let inode = rdfs.read_inode_file(file).unwrap();
+        rdfs.write_file_inode(&mut bitmaps, file, inode, content).unwrap();
+        let stat = rdfs.stat(file, InodeType::File).unwrap();
+        assert_eq!(stat.content_entries, runs);
+        assert!(stat.linked);
+    }
+
+    #[test]
+    fn write_atomic_test() {
+        let rdfs = test_drive("write_atomic");
+        let pointer = rdfs.system.data_pointer;
+        rdfs.write_atomic(pointer, &[7; 4096]).unwrap();
+        assert_eq!(rdfs.read_block(pointer).unwrap(), vec![7; 4096]);
+        let journal = last_journal(&rdfs).unwrap();
+        assert_eq!((journal.sequence, journal.state), (1, JournalState::Applied));
+        assert!(!rdfs.recover_journal().unwrap());
+
+        // only the changed bytes are journaled, a write changing nothing leaves no record
+        let mut bitmaps = rdfs.read_bitmaps_block().unwrap();
+        bitmaps.set_bit(0);
+        rdfs.write_metadata_atomic(&bitmaps).unwrap();
+        assert!(rdfs.read_bitmaps_block().unwrap().get_bit(0));
+        let journal = last_journal(&rdfs).unwrap();
+        assert_eq!(journal.sequence, 2);
+        assert!(journal.payload_len() < 100);
+        assert!(journal.entries.iter().all(|(pointer, _)| *pointer >= rdfs.system.bitmaps_pointer));
+        rdfs.write_metadata_atomic(&bitmaps).unwrap();
+        assert_eq!(last_journal(&rdfs).unwrap().sequence, 2);
+
+        // records are appended, the log starts over before it outgrows `JOURNAL_LOG_SIZE`
+        let log = rdfs.system.node_storage;
+        assert!(rdfs.scan_journal().unwrap().0.unwrap().0 > log);
+        for round in 0..20 {
+            rdfs.write_atomic(pointer, &[round + 10; 4096]).unwrap();
+        }
+        let (last, _, sequence) = rdfs.scan_journal().unwrap();
+        assert_eq!((last.unwrap().1.sequence, sequence), (22, 23));
+        assert!(fs::metadata(&rdfs.path).unwrap().len() <= log + JOURNAL_LOG_SIZE + rdfs.system.backup_size());
+        assert_eq!(rdfs.read_block(pointer).unwrap(), vec![29; 4096]);
+
+        // clones of a handle append their records one at a time
+        std::thread::scope(|scope| {
+            for thread in 0..4 {
+                let rdfs = rdfs.clone();
+                scope.spawn(move || {
+                    for round in 0..5 {
+                        let block = rdfs.system.block_pointer(1 + thread * 5 + round).unwrap();
+                        rdfs.write_atomic(block, &[thread as u8 + 1; 4096]).unwrap();
+                    }
+                });
+            }
+        });
+        assert_eq!(last_journal(&rdfs).unwrap().sequence, 42);
+        for index in 1..21 {
+            let block = rdfs.read_block(rdfs.system.block_pointer(index).unwrap()).unwrap();
+            assert_eq!(block, vec![((index - 1) / 5) as u8 + 1; 4096]);
+        }
+        assert!(!RDFS::mount_drive(&rdfs.path).unwrap().recover_journal().unwrap());
+
+        let error = rdfs.write_atomic(rdfs.system.node_storage - 1, &[0; 2]).unwrap_err();
+        assert!(matches!(error.downcast_ref::<RDFSError>(), Some(RDFSError::InvalidJournalWrite { .. })));
+    }
+
+    #[test]
+    fn recover_journal_test() {
+        let rdfs = test_drive("recover_journal");
+        let pointer = rdfs.system.data_pointer + 4096;
+        let journal_pointer = rdfs.system.node_storage;
+
+        // crash right after the record was written, the target was never touched
+        let mut bitmaps = rdfs.read_bitmaps_block().unwrap();
+        bitmaps.set_bit(1);
+        let journal = JournalBlock::new(5, vec![(pointer, vec![3; 4096]), (rdfs.system.bitmaps_pointer, bitmaps.to_bytes())]);
+        write_range(&rdfs.path, journal_pointer, &journal.to_bytes()).unwrap();
+        assert_eq!(rdfs.read_block(pointer).unwrap(), vec![0; 4096]);
+
+        let rdfs = RDFS::mount_drive(&rdfs.path).unwrap();
+        assert_eq!(rdfs.read_block(pointer).unwrap(), vec![3; 4096]);
+        assert!(rdfs.read_bitmaps_block().unwrap().get_bit(1));
+        assert_eq!(last_journal(&rdfs).unwrap().state, JournalState::Applied);
+
+        // a torn record is ignored, its target keeps the old content
+        let mut torn = JournalBlock::new(6, vec![(pointer, vec![4; 4096])]).to_bytes();
+        torn[JOURNAL_HEADER_SIZE + 100] ^= 0xFF;
+        write_range(&rdfs.path, journal_pointer, &torn).unwrap();
+        let rdfs = RDFS::mount_drive(&rdfs.path).unwrap();
+        assert_eq!(rdfs.read_block(pointer).unwrap(), vec![3; 4096]);
+
+        // the next record keeps counting after the torn one
+        rdfs.write_atomic(pointer, &[5; 4096]).unwrap();
+        assert_eq!(last_journal(&rdfs).unwrap().sequence, 7);
+    }
+
+    #[test]
+    fn read_data_blocks_test() {
+        let rdfs = test_drive("read_data_blocks");
+        let root = rdfs.system.inode_pointer;
+        let data: Vec<u8> = (0..10_000).map(|byte| (byte % 241) as u8).collect();
+        let file = rdfs.create_file(root, "blocks.bin", &data).unwrap();
+        let mut content = rdfs.read_inode_file(file).unwrap().content;
+
+        let blocks: Vec<DataBlock> = rdfs.read_data_blocks(content.clone()).map(Result::unwrap).collect();
+        assert_eq!(blocks.len(), 3);
+        assert_eq!(blocks.iter().flat_map(|block| block.data.clone()).collect::<Vec<u8>>(), data);
+
+        // a corrupted block and a dangling range yield errors, the other blocks still come through
+        let second = content[0].pointer + 4096;
+        let mut raw = rdfs.read_block(second).unwrap();
+        raw[100] ^= 0xFF;
+        rdfs.write_block(second, &raw).unwrap();
+        content.push(FileContent { pointer: 3, blocks: 1 });
+
+        let results: Vec<Result<DataBlock>> = rdfs.read_data_blocks(content).collect();
+        assert_eq!(results.len(), 4);
+        assert!(results[0].is_ok() && results[2].is_ok());
+        let error = results[1].as_ref().unwrap_err();
+        assert!(matches!(error.downcast_ref::<RDFSError>(), Some(RDFSError::CorruptDataBlock)));
+        assert!(results[3].is_err());
+    }
+
+    #[test]
+    fn file_content_ranges_test() {
+        // large enough for the spilled ranges to stay inside the data blocks
+        let dir = std::env::temp_dir().join("rdfs_test_file_content_ranges");
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        let rdfs = RDFS::new(&dir, FileSystemType::Shared, [255; 32], [1; 32], 4194304, 100, 1, 4096).unwrap();
+        let root = rdfs.system.inode_pointer;
+        let file = rdfs.create_file(root, "ranges.bin", &[1; 100]).unwrap();
+
+        // spill the ranges into two linked blocks
+        let max = rdfs.system.max_content_pointers + rdfs.system.max_linked_content_pointers;
+        let content: Vec<FileContent> = (0..max + 3)
+            .map(|i| FileContent {
+                pointer: rdfs.system.data_pointer + i * 2 * 4096,
+                blocks: 1,
+            })
+            .collect();
+        let mut bitmaps = rdfs.read_bitmaps_block().unwrap();
+        let inode = rdfs.read_inode_file(file).unwrap();
+        rdfs.write_file_inode(&mut bitmaps, file, inode, content.clone()).unwrap();
+        assert_eq!(rdfs.file_content_ranges(file).unwrap(), content);
+
+        // a linked block pointing back to the first one is caught instead of looping forever
+        let first = rdfs.read_inode_file(file).unwrap().linked;
+        let second = rdfs.read_linked_file(first).unwrap().linked;
+        let mut block = rdfs.read_linked_file(second).unwrap();
+        block.linked = first;
+        rdfs.write_linked_file(second, &block).unwrap();
+
+        let error = rdfs.file_content_ranges(file).unwrap_err();
+        assert!(matches!(error.downcast_ref::<RDFSError>(), Some(RDFSError::LinkedLoop(pointer)) if *pointer == first));
+        assert!(rdfs.read_file(file).is_err());
+        assert!(rdfs.append_to_file(file, b"more").is_err());
+    }
+
+    #[test]
+    fn test_peek_type() {
+        let shared = test_drive("peek_type");
+        let dir = shared.path.parent().unwrap();
+        let private = RDFS::new(dir, FileSystemType::Private, [255; 32], [2; 32], 1048576, 100, 1, 4096).unwrap();
+        assert_eq!(RDFS::peek_type(&shared.path).unwrap(), FileSystemType::Shared);
+        assert_eq!(RDFS::peek_type(&private.path).unwrap(), FileSystemType::Private);
+
+        for content in [&b"not an RDFS drive"[..], b"RDFS"] {
+            let other = dir.join("other.RDFS");
+            fs::write(&other, content).unwrap();
+            let err = RDFS::peek_type(&other).unwrap_err();
+            assert!(matches!(err.downcast_ref::<RDFSError>(), Some(RDFSError::InvalidMagicWord)));
+        }
+    }
+
+    #[test]
+    fn copy_file_test() {
+        // without a refcount table nothing tracks the referents of a block
+        let plain = test_drive("copy_file_plain");
+        let root = plain.system.inode_pointer;
+        let source = plain.create_file(root, "source.bin", b"plain").unwrap();
+        let free_blocks = plain.read_bitmaps_block().unwrap().free_blocks;
+        let error = plain.copy_file(root, source, "copy.bin").unwrap_err();
+        assert!(matches!(error.downcast_ref::<RDFSError>(), Some(RDFSError::NoRefCountTable)));
+        assert_eq!(plain.read_bitmaps_block().unwrap().free_blocks, free_blocks);
+        assert!(!plain.exists("/copy.bin").unwrap());
+
+        let rdfs = refcounted_test_drive("copy_file");
+        let root = rdfs.system.inode_pointer;
+        let docs = rdfs.mkdir(root, "docs").unwrap();
+        let data = vec![4u8; 9000];
+        let source = rdfs.create_file(root, "source.bin", &data).unwrap();
+        let free_blocks = rdfs.read_bitmaps_block().unwrap().free_blocks;
+
+        // only the new inode is allocated, the data blocks are shared
+        let copy = rdfs.copy_file(docs, source, "copy.bin").unwrap();
+        assert_eq!(rdfs.read_bitmaps_block().unwrap().free_blocks, free_blocks - 1);
+        assert_eq!(rdfs.read_file(copy).unwrap(), data);
+        let ranges = rdfs.file_content_ranges(source).unwrap();
+        assert_eq!(rdfs.file_content_ranges(copy).unwrap(), ranges);
+        let report = rdfs.fsck(false).unwrap();
+        assert!(report.is_clean());
+        assert_eq!(report.shared, rdfs.block_pointers(&ranges).collect::<Vec<_>>());
+
+        // appending copies the shared last block instead of changing the source
+        rdfs.append_to_file(copy, b"tail").unwrap();
+        assert_eq!(rdfs.read_file(source).unwrap(), data);
+        assert_eq!(rdfs.read_file(copy).unwrap(), [&data[..], b"tail"].concat());
+        assert_eq!(rdfs.fsck(false).unwrap().shared.len(), 2);
+
+        // deleting the source keeps the blocks the copy still references
+        rdfs.delete_file(root, source).unwrap();
+        assert_eq!(rdfs.read_file(copy).unwrap(), [&data[..], b"tail"].concat());
+        let report = rdfs.fsck(false).unwrap();
+        assert!(report.is_clean());
+        assert!(report.shared.is_empty());
+
+        // the last referent frees them
+        let copy_of_copy = rdfs.copy_file(root, copy, "again.bin").unwrap();
+        rdfs.remove_dir_all(root, docs).unwrap();
+        assert_eq!(rdfs.read_file(copy_of_copy).unwrap(), [&data[..], b"tail"].concat());
+        rdfs.delete_file(root, copy_of_copy).unwrap();
+        assert!(rdfs.fsck(false).unwrap().is_clean());
+        // the source inode, its 3 data blocks and `docs` are free again
+        assert_eq!(rdfs.read_bitmaps_block().unwrap().free_blocks, free_blocks + 5);
+    }
+
+    #[test]
+    fn refcounted_drive_test() {
+        let dir = std::env::temp_dir().join("rdfs_test_refcounted");
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        let rdfs = RDFS::new_refcounted(dir, [255; 32], [1; 32], 4 * 1048576, 100, 1, 4096).unwrap();
+        let mut rdfs = RDFS::mount_drive(&rdfs.path).unwrap();
+        assert!(rdfs.system.has_refcounts());
+        let root = rdfs.system.inode_pointer;
+        let count = |rdfs: &RDFS, pointer: u64| rdfs.read_refcount_block().unwrap().get(rdfs.system.block_index(pointer).unwrap());
+        assert_eq!(count(&rdfs, root), 1);
+
+        let data = vec![6u8; 9000];
+        let source = rdfs.create_file(root, "source.bin", &data).unwrap();
+        let copy = rdfs.copy_file(root, source, "copy.bin").unwrap();
+        assert_eq!((count(&rdfs, source), count(&rdfs, copy)), (1, 1));
+        let blocks: Vec<u64> = rdfs.block_pointers(&rdfs.file_content_ranges(source).unwrap()).collect();
+        assert!(blocks.iter().all(|pointer| count(&rdfs, *pointer) == 2));
+        assert!(rdfs.fsck(false).unwrap().is_clean());
+
+        // growing the drive keeps the counts of every block
+        rdfs.resize_drive(8 * 1048576).unwrap();
+        let root = rdfs.system.inode_pointer;
+        let (source, _) = rdfs.resolve_path("/source.bin").unwrap();
+        let (copy, _) = rdfs.resolve_path("/copy.bin").unwrap();
+        let blocks: Vec<u64> = rdfs.block_pointers(&rdfs.file_content_ranges(source).unwrap()).collect();
+        assert!(blocks.iter().all(|pointer| count(&rdfs, *pointer) == 2));
+        assert_eq!(count(&rdfs, root), 1);
+        assert!(rdfs.fsck(false).unwrap().is_clean());
+
+        // appending to the copy moves its last block off the shared one
+        rdfs.append_to_file(copy, b"tail").unwrap();
+        assert_eq!(count(&rdfs, *blocks.last().unwrap()), 1);
+        assert_eq!(rdfs.read_file(source).unwrap(), data);
+
+        // deleting drops one referent, the last referent frees the block
+        rdfs.delete_file(root, source).unwrap();
+        assert_eq!(count(&rdfs, blocks[0]), 1);
+        assert_eq!(count(&rdfs, *blocks.last().unwrap()), 0);
+        assert_eq!(rdfs.read_file(copy).unwrap(), [&data[..], b"tail"].concat());
+        let bitmaps = rdfs.read_bitmaps_block().unwrap();
+        rdfs.delete_file(root, copy).unwrap();
+        assert!(blocks.iter().all(|pointer| count(&rdfs, *pointer) == 0));
+        // the inode, the two blocks left by the source and the copied last block
+        assert_eq!(rdfs.read_bitmaps_block().unwrap().free_blocks, bitmaps.free_blocks + 4);
+        assert!(rdfs.fsck(false).unwrap().is_clean());
+    }
+
+    #[test]
+    fn refcount_overflow_underflow_test() {
+        let dir = std::env::temp_dir().join("rdfs_test_refcount_bounds");
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        let rdfs = RDFS::new_refcounted(dir, [255; 32], [1; 32], 4 * 1048576, 100, 1, 4096).unwrap();
+        let root = rdfs.system.inode_pointer;
+        let file = rdfs.create_file(root, "shared.txt", b"shared").unwrap();
+        let block = rdfs.file_content_ranges(file).unwrap()[0].pointer;
+        let index = rdfs.system.block_index(block).unwrap();
+        let free_blocks = rdfs.read_bitmaps_block().unwrap().free_blocks;
+
+        // a block already shared by u16::MAX files can not gain another referent
+        let mut refcounts = rdfs.read_refcount_block().unwrap();
+        refcounts.counts[index as usize] = u16::MAX;
+        rdfs.write_refcount_block(&refcounts).unwrap();
+        let error = rdfs.copy_file(root, file, "one_too_many.txt").unwrap_err();
+        assert!(matches!(error.downcast_ref::<RDFSError>(), Some(RDFSError::RefCountOverflow(i)) if *i == index));
+        assert_eq!(rdfs.list_dir(root).unwrap().len(), 1);
+        assert_eq!(rdfs.read_bitmaps_block().unwrap().free_blocks, free_blocks);
+
+        // a used block counted 0 can not lose a referent, the file stays untouched
+        refcounts.counts[index as usize] = 0;
+        rdfs.write_refcount_block(&refcounts).unwrap();
+        let error = rdfs.delete_file(root, file).unwrap_err();
+        assert!(matches!(error.downcast_ref::<RDFSError>(), Some(RDFSError::RefCountUnderflow(i)) if *i == index));
+        assert_eq!(rdfs.read_file(file).unwrap(), b"shared");
+
+        // fsck finds and repairs the wrong count
+        let report = rdfs.fsck(false).unwrap();
+        assert_eq!(report.miscounted, vec![block]);
+        assert!(!report.is_clean());
+        assert!(rdfs.fsck(true).unwrap().repaired);
+        assert_eq!(rdfs.read_refcount_block().unwrap().get(index), 1);
+        rdfs.delete_file(root, file).unwrap();
+        assert!(rdfs.fsck(false).unwrap().is_clean());
+
+        let plain = test_drive("refcount_none");
+        let error = plain.read_refcount_block().unwrap_err();
+        assert!(matches!(error.downcast_ref::<RDFSError>(), Some(RDFSError::NoRefCountTable)));
+    }
+
+    #[test]
+    fn snapshot_test() {
+        let dir = std::env::temp_dir().join("rdfs_test_snapshot");
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        let rdfs = RDFS::new_refcounted(dir, [255; 32], [1; 32], 4 * 1048576, 100, 1, 4096).unwrap();
+        let mut rdfs = RDFS::mount_drive(&rdfs.path).unwrap();
+        let root = rdfs.system.inode_pointer;
+        let docs = rdfs.mkdir(root, "docs").unwrap();
+        let data = vec![3u8; 9000];
+        let report = rdfs.create_file(docs, "report.txt", &data).unwrap();
+        let notes = rdfs.create_file(root, "notes.txt", b"notes").unwrap();
+
+        let snapshot = rdfs.snapshot("before").unwrap();
+        let snapshots = rdfs.list_snapshots().unwrap();
+        assert_eq!(snapshots.len(), 1);
+        assert_eq!((snapshots[0].pointer, snapshots[0].label.as_str()), (snapshot, "before"));
+        let blocks: Vec<u64> = rdfs.block_pointers(&rdfs.file_content_ranges(report).unwrap()).collect();
+        let count = |rdfs: &RDFS, pointer: u64| rdfs.read_refcount_block().unwrap().get(rdfs.system.block_index(pointer).unwrap());
+        assert!(blocks.iter().all(|pointer| count(&rdfs, *pointer) == 2));
+        assert!(rdfs.fsck(false).unwrap().is_clean());
+
+        // the live tree changes, the snapshot keeps what it captured
+        rdfs.append_to_file(report, b"more").unwrap();
+        assert_eq!(count(&rdfs, *blocks.last().unwrap()), 1);
+        rdfs.delete_file(root, notes).unwrap();
+        rdfs.rename(root, "docs", root, "archive").unwrap();
+        let read_snapshot = |rdfs: &RDFS, snapshot: u64| {
+            let mut names: Vec<String> = rdfs.list_dir(snapshot).unwrap().into_iter().map(|(name, _, _)| name).collect();
+            names.sort();
+            let (_, docs, _) = rdfs.list_dir(snapshot).unwrap().into_iter().find(|(name, _, _)| name == "docs").unwrap();
+            (names, rdfs.read_file(rdfs.list_dir(docs).unwrap()[0].1).unwrap())
+        };
+        let (names, content) = read_snapshot(&rdfs, snapshot);
+        assert_eq!((names, content), (vec!["docs".to_string(), "notes.txt".to_string()], data.clone()));
+        assert!(rdfs.fsck(false).unwrap().is_clean());
+
+        // growing the drive moves the snapshots with the tree
+        rdfs.resize_drive(8 * 1048576).unwrap();
+        let snapshot = rdfs.list_snapshots().unwrap()[0].pointer;
+        assert_eq!(read_snapshot(&rdfs, snapshot).1, data);
+        assert!(rdfs.fsck(false).unwrap().is_clean());
+
+        rdfs.restore_snapshot(snapshot).unwrap();
+        let (report, _) = rdfs.resolve_path("/docs/report.txt").unwrap();
+        assert_eq!(rdfs.read_file(report).unwrap(), data);
+        let (notes, _) = rdfs.resolve_path("/notes.txt").unwrap();
+        assert_eq!(rdfs.read_file(notes).unwrap(), b"notes");
+        assert!(rdfs.resolve_path("/archive").is_err());
+        assert!(rdfs.fsck(false).unwrap().is_clean());
+
+        // restoring again releases the previous copy, nothing leaks
+        let free_blocks = rdfs.read_bitmaps_block().unwrap().free_blocks;
+        rdfs.restore_snapshot(snapshot).unwrap();
+        assert_eq!(rdfs.read_bitmaps_block().unwrap().free_blocks, free_blocks);
+        assert!(rdfs.fsck(false).unwrap().is_clean());
+
+        let error = rdfs.restore_snapshot(rdfs.system.inode_pointer).unwrap_err();
+        assert!(matches!(error.downcast_ref::<RDFSError>(), Some(RDFSError::SnapshotNotFound(_))));
+        let plain = test_drive("snapshot_none");
+        let error = plain.snapshot("none").unwrap_err();
+        assert!(matches!(error.downcast_ref::<RDFSError>(), Some(RDFSError::NoRefCountTable)));
+    }
+
+    #[test]
+    fn dir_cache_test() {
+        let rdfs = test_drive("dir_cache").with_cache();
+        let cache = rdfs.cache.clone().unwrap();
+        let root = rdfs.system.inode_pointer;
+        let docs = rdfs.mkdir(root, "docs").unwrap();
+        let report = rdfs.create_file(docs, "report.txt", b"report").unwrap();
+        assert_eq!(rdfs.resolve_path("/docs/report.txt").unwrap().0, report);
+        assert!(cache.get(root).is_some() && cache.get(docs).is_some());
+
+        // every mutation invalidates the directories it touches
+        rdfs.rename(docs, "report.txt", docs, "final.txt").unwrap();
+        assert!(rdfs.resolve_path("/docs/report.txt").is_err());
+        assert_eq!(rdfs.resolve_path("/docs/final.txt").unwrap().0, report);
+        let archive = rdfs.mkdir(root, "archive").unwrap();
+        rdfs.rename(docs, "final.txt", archive, "final.txt").unwrap();
+        assert!(rdfs.resolve_path("/docs/final.txt").is_err());
+        assert_eq!(rdfs.resolve_path("/archive/final.txt").unwrap().0, report);
+        rdfs.delete_file(archive, report).unwrap();
+        assert!(rdfs.resolve_path("/archive/final.txt").is_err());
+        let notes = rdfs.create_file(archive, "notes.txt", b"notes").unwrap();
+        assert_eq!(rdfs.resolve_path("/archive/notes.txt").unwrap().0, notes);
+
+        // a removed directory whose block is reused by a new one starts empty
+        rdfs.remove_dir_all(root, archive).unwrap();
+        let mkdir = |i| rdfs.mkdir(root, &format!("reused_{i}")).unwrap();
+        let reused = format!("/reused_{}", (0..3).map(mkdir).position(|dir| dir == archive).unwrap());
+        assert!(rdfs.resolve_path(&(reused.clone() + "/notes.txt")).is_err());
+        assert_eq!(rdfs.list_dir(archive).unwrap(), vec![]);
+
+        // the cache is shared by clones and follows the name policy
+        let insensitive = rdfs.clone().with_name_policy(NamePolicy::CaseInsensitive);
+        assert_eq!(insensitive.resolve_path("/DOCS").unwrap().0, docs);
+        assert!(Arc::ptr_eq(insensitive.cache.as_ref().unwrap(), &cache));
+        assert!(insensitive.mkdir(root, "Docs").is_err());
+    }
+
+    #[test]
+    fn defragment_file_test() {
+        let dir = std::env::temp_dir().join("rdfs_test_defragment");
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        let rdfs = RDFS::new_refcounted(dir, [255; 32], [1; 32], 4 * 1048576, 100, 1, 4096).unwrap();
+        let root = rdfs.system.inode_pointer;
+
+        // a file spread over holes, the blocks in between are freed again afterwards
+        let mut bitmaps = rdfs.read_bitmaps_block().unwrap();
+        let holes: Vec<usize> = (2..14).step_by(2).collect();
+        holes.iter().for_each(|index| bitmaps.set_bit(*index));
+        rdfs.write_bitmaps_block(&bitmaps).unwrap();
+        let data: Vec<u8> = (0..5 * (4096 - RESERVED_DB)).map(|i| i as u8).collect();
+        let file = rdfs.create_file(root, "scattered.bin", &data).unwrap();
+        let mut bitmaps = rdfs.read_bitmaps_block().unwrap();
+        holes.iter().for_each(|index| bitmaps.clear_bit(*index));
+        rdfs.write_bitmaps_block(&bitmaps).unwrap();
+        assert!(rdfs.file_content_ranges(file).unwrap().len() > 1);
+        let copy = rdfs.copy_file(root, file, "copy.bin").unwrap();
+        let free_blocks = rdfs.read_bitmaps_block().unwrap().free_blocks;
+
+        assert_eq!(rdfs.defragment_file(file).unwrap(), 5 * 4096);
+        let ranges = rdfs.file_content_ranges(file).unwrap();
+        assert_eq!(ranges.len(), 1);
+        assert_eq!(rdfs.read_file(file).unwrap(), data);
+        for pointer in rdfs.block_pointers(&ranges) {
+            let block = DataBlock::from_bytes(&rdfs.read_block(pointer).unwrap(), 4096).unwrap();
+            assert_eq!(block.block_number, rdfs.system.block_index(pointer).unwrap());
+        }
+
+        // the old blocks are still reflinked by the copy, so 5 more blocks are in use
+        assert_eq!(rdfs.read_bitmaps_block().unwrap().free_blocks, free_blocks - 5);
+        assert_eq!(rdfs.read_file(copy).unwrap(), data);
+        assert!(rdfs.fsck(false).unwrap().is_clean());
+
+        // defragmenting the copy frees its old blocks, an already contiguous file is left alone
+        rdfs.defragment_file(copy).unwrap();
+        assert_eq!(rdfs.read_bitmaps_block().unwrap().free_blocks, free_blocks - 5);
+        assert_eq!(rdfs.read_file(copy).unwrap(), data);
+        assert_eq!(rdfs.defragment_file(file).unwrap(), 0);
+        assert!(rdfs.fsck(false).unwrap().is_clean());
+    }
+
+    #[test]
+    fn read_file_range_test() {
+        let rdfs = test_drive("read_file_range");
+        let root = rdfs.system.inode_pointer;
+        let payload = 4096 - RESERVED_DB;
+        let data: Vec<u8> = (0..3 * payload + 100).map(|i| (i % 251) as u8).collect();
+        let file = rdfs.create_file(root, "media.bin", &data).unwrap();
+
+        // inside one block, across block boundaries and up to the last byte
+        assert_eq!(rdfs.read_file_range(file, 10, 20).unwrap(), &data[10..30]);
+        let straddling = rdfs.read_file_range(file, payload as u64 - 5, 10).unwrap();
+        assert_eq!(straddling, &data[payload - 5..payload + 5]);
+        assert_eq!(rdfs.read_file_range(file, 1, 2 * payload as u64).unwrap(), &data[1..2 * payload + 1]);
+        assert_eq!(rdfs.read_file_range(file, 0, data.len() as u64).unwrap(), data);
+
+        // `len` is clamped to the end of the file, an offset past it fails
+        assert_eq!(rdfs.read_file_range(file, 3 * payload as u64, 1000).unwrap(), &data[3 * payload..]);
+        assert!(rdfs.read_file_range(file, data.len() as u64, 10).unwrap().is_empty());
+        let error = rdfs.read_file_range(file, data.len() as u64 + 1, 1).unwrap_err();
+        assert!(matches!(error.downcast_ref::<RDFSError>(), Some(RDFSError::OffsetOutOfRange { .. })));
+    }
+
+    #[test]
+    fn sparse_file_test() {
+        let rdfs = test_drive("sparse_file").with_sparse();
+        let root = rdfs.system.inode_pointer;
+        let payload = 4096 - RESERVED_DB;
+
+        // zeros, one data block, zeros, then a partial zero block which is always stored
+        let mut data = vec![0u8; 6 * payload + 100];
+        data[2 * payload..3 * payload].fill(7);
+        let free_blocks = rdfs.read_bitmaps_block().unwrap().free_blocks;
+        let file = rdfs.create_file(root, "disk.img", &data).unwrap();
+        assert_eq!(rdfs.read_bitmaps_block().unwrap().free_blocks, free_blocks - 3);
+        let ranges = rdfs.file_content_ranges(file).unwrap();
+        let shape: Vec<(bool, u64)> = ranges.iter().map(|run| (run.is_hole(), run.blocks)).collect();
+        assert_eq!(shape, vec![(true, 2), (false, 1), (true, 3), (false, 1)]);
+        assert_eq!(rdfs.stat(file, InodeType::File).unwrap().total_blocks, 7);
+
+        assert_eq!(rdfs.read_file(file).unwrap(), data);
+        let range = rdfs.read_file_range(file, 2 * payload as u64 - 10, 20).unwrap();
+        assert_eq!(range, &data[2 * payload - 10..2 * payload + 10]);
+        let streamed = rdfs.write_file_streaming(root, "streamed.img", &data[..]).unwrap();
+        assert_eq!(rdfs.file_content_ranges(streamed).unwrap().len(), 4);
+        assert_eq!(rdfs.read_file(streamed).unwrap(), data);
+
+        // appending after a trailing hole starts a new block, defragmenting keeps the holes
+        let tail = rdfs.create_file(root, "tail.img", &vec![0; payload]).unwrap();
+        rdfs.append_to_file(tail, b"tail").unwrap();
+        let mut expected = vec![0; payload];
+        expected.extend_from_slice(b"tail");
+        assert_eq!(rdfs.read_file(tail).unwrap(), expected);
+        assert_eq!(rdfs.defragment_file(file).unwrap(), 2 * 4096);
+        let ranges = rdfs.file_content_ranges(file).unwrap();
+        assert_eq!(ranges[1].pointer + 4096, ranges[3].pointer);
+        assert_eq!(rdfs.read_file(file).unwrap(), data);
+
+        // holes never reach the bitmaps, fsck and delete only see the data blocks
+        assert!(rdfs.fsck(false).unwrap().is_clean());
+        for (name, inode) in [("disk.img", file), ("streamed.img", streamed), ("tail.img", tail)] {
+            assert_eq!(rdfs.resolve_path(&format!("/{name}")).unwrap().0, inode);
+            rdfs.delete_file(root, inode).unwrap();
+        }
+        assert_eq!(rdfs.read_bitmaps_block().unwrap().free_blocks, free_blocks);
+
+        // without `with_sparse` zero blocks are allocated like any other
+        let dense = RDFS { sparse: false, ..rdfs };
+        let file = dense.create_file(root, "dense.img", &data).unwrap();
+        assert_eq!(dense.file_content_ranges(file).unwrap().iter().filter(|run| run.is_hole()).count(), 0);
+    }
+
+    #[test]
+    fn truncate_file_test() {
+        let rdfs = refcounted_test_drive("truncate_file");
+        let root = rdfs.system.inode_pointer;
+        let payload = rdfs.system.data_payload_size() as usize;
+        let data: Vec<u8> = (0..3 * payload + 100).map(|i| (i % 253) as u8).collect();
+        let file = rdfs.create_file(root, "log.txt", &data).unwrap();
+        let free_blocks = rdfs.read_bitmaps_block().unwrap().free_blocks;
+
+        // to a block boundary, the last kept block is left as is
+        rdfs.truncate_file(file, 2 * payload as u64).unwrap();
+        assert_eq!(rdfs.read_file(file).unwrap(), &data[..2 * payload]);
+        assert_eq!(rdfs.read_bitmaps_block().unwrap().free_blocks, free_blocks + 2);
+        let stat = rdfs.stat(file, InodeType::File).unwrap();
+        assert_eq!((stat.size, stat.total_blocks), (2 * payload as u64, 2));
+
+        // to a mid-block offset, the last kept block is cut
+        rdfs.truncate_file(file, payload as u64 + 10).unwrap();
+        assert_eq!(rdfs.read_file(file).unwrap(), &data[..payload + 10]);
+        assert_eq!(rdfs.read_bitmaps_block().unwrap().free_blocks, free_blocks + 2);
+        rdfs.append_to_file(file, b"more").unwrap();
+        assert_eq!(rdfs.read_file(file).unwrap(), [&data[..payload + 10], b"more"].concat());
+
+        // a reflinked last block is copied, the other file keeps its content
+        let copy = rdfs.copy_file(root, file, "copy.txt").unwrap();
+        rdfs.truncate_file(copy, 5).unwrap();
+        assert_eq!(rdfs.read_file(copy).unwrap(), &data[..5]);
+        assert_eq!(rdfs.read_file(file).unwrap(), [&data[..payload + 10], b"more"].concat());
+        assert!(rdfs.fsck(false).unwrap().is_clean());
+
+        // growing is rejected, truncating to 0 frees every data block
+        let error = rdfs.truncate_file(file, payload as u64 * 4).unwrap_err();
+        assert!(matches!(error.downcast_ref::<RDFSError>(), Some(RDFSError::OffsetOutOfRange { .. })));
+        rdfs.delete_file(root, copy).unwrap();
+        rdfs.truncate_file(file, 0).unwrap();
+        assert!(rdfs.read_file(file).unwrap().is_empty());
+        assert!(rdfs.file_content_ranges(file).unwrap().is_empty());
+        assert_eq!(rdfs.read_bitmaps_block().unwrap().free_blocks, free_blocks + 4);
+
+        // a sparse hole cut in the middle becomes a block of zeros
+        let sparse = rdfs.with_sparse();
+        let zeros = sparse.create_file(root, "zeros.img", &vec![0; 2 * payload]).unwrap();
+        sparse.truncate_file(zeros, payload as u64 / 2).unwrap();
+        assert_eq!(sparse.read_file(zeros).unwrap(), vec![0; payload / 2]);
+        assert!(!sparse.file_content_ranges(zeros).unwrap()[0].is_hole());
+        assert!(sparse.fsck(false).unwrap().is_clean());
+    }
+
+    #[test]
+    #[cfg(target_os = "linux")]
+    fn reserved_drive_test() {
+        use std::os::unix::fs::MetadataExt;
+
+        // every byte of the drive is backed by disk blocks, not a sparse file
+        let rdfs = test_drive("reserved_drive");
+        let metadata = fs::metadata(&rdfs.path).unwrap();
+        assert_eq!(metadata.len(), rdfs.system.node_storage + SB_SIZE as u64);
+        assert!(metadata.blocks() * 512 >= metadata.len());
+    }
+
+    #[test]
+    fn verify_drive_signatures_test() {
+        use crate::core::block_signature::sign_bytes;
+        use ed25519_dalek::SigningKey;
+
+        let rdfs = refcounted_test_drive("verify_drive_signatures");
+        let root = rdfs.system.inode_pointer;
+        let dir = rdfs.mkdir(root, "docs").unwrap();
+        let file = rdfs.create_file(dir, "a.txt", &vec![3; 5000]).unwrap();
+        rdfs.copy_file(root, file, "b.txt").unwrap();
+        let private_key = [9; 32];
+        let public_key = SigningKey::from_bytes(&private_key).verifying_key().to_bytes();
+
+        // nothing is signed yet: super block, addresses, bitmaps, 2 dirs, the snapshot list, 2 files and 2 shared data blocks
+        let report = rdfs.verify_drive_signatures(&public_key, false).unwrap();
+        assert_eq!(report.len(), 10);
+        assert!(report.iter().any(|(pointer, _)| *pointer == rdfs.system.snapshot_pointer));
+        assert!(report.iter().all(|(_, valid)| !valid));
+        assert_eq!(rdfs.verify_drive_signatures(&public_key, true).unwrap(), vec![(0, false)]);
+
+        let length = |pointer: u64| match pointer {
+            0 => SB_SIZE as u64,
+            p if p == rdfs.system.nodes_address_pointer => rdfs.system.nodes_address_size,
+            p if p == rdfs.system.bitmaps_pointer => rdfs.system.bitmaps_size,
+            _ => rdfs.system.block_size,
+        };
+        for (pointer, _) in &report {
+            let mut bytes = rdfs.read_drive_range(*pointer, pointer + length(*pointer)).unwrap();
+            sign_bytes(&private_key, &mut bytes);
+            rdfs.write_drive_range(*pointer, &bytes).unwrap();
+        }
+        let report = rdfs.verify_drive_signatures(&public_key, false).unwrap();
+        assert!(report.iter().all(|(_, valid)| *valid));
+
+        // a tampered data block fails, stopping there with the flag
+        let data = rdfs.file_content_ranges(file).unwrap()[0].pointer;
+        let mut bytes = rdfs.read_block(data).unwrap();
+        bytes[30] ^= 0xFF;
+        rdfs.write_drive_range(data, &bytes).unwrap();
+        let report = rdfs.verify_drive_signatures(&public_key, true).unwrap();
+        assert_eq!(report.last(), Some(&(data, false)));
+        assert!(report[..report.len() - 1].iter().all(|(_, valid)| *valid));
+    }
+
+    #[test]
+    fn drive_builder_test() {
+        let dir = std::env::temp_dir().join("rdfs_test_drive_builder");
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+
+        let builder = RDFS::builder().owner([255; 32]).program_id([3; 32]).storage(2 * 1048576);
+        let rdfs = builder.clone().build(&dir).unwrap();
+        let expected = SuperBlock::new(FileSystemType::Shared, [255; 32], [3; 32], 2 * 1048576, 100, 1, 4096).unwrap();
+        // the first data block holds the snapshot list, like on `new_refcounted` drives
+        let refcounted = expected.clone().with_refcounts().unwrap();
+        assert_eq!(
+            rdfs.system,
+            SuperBlock {
+                snapshot_pointer: refcounted.data_pointer,
+                ..refcounted
+            }
+        );
+        assert_eq!(rdfs.path, dir.join(bytes_to_hex(&[3; 32]) + ".RDFS"));
+        let file = rdfs.create_file(rdfs.system.inode_pointer, "a.txt", b"reflinked").unwrap();
+        rdfs.copy_file(rdfs.system.inode_pointer, file, "b.txt").unwrap();
+
+        // shared drives are refcounted unless asked otherwise
+        let plain = builder.refcounted(false).file_name("plain.RDFS").build(&dir).unwrap();
+        assert_eq!(plain.system, expected);
+
+        let private = RDFS::builder().magic(FileSystemType::Private).program_id([4; 32]).build(&dir).unwrap();
+        assert_eq!(private.system.magic, FileSystemType::Private);
+        assert!(!private.system.has_refcounts());
+
+        // every broken invariant is refused before the drive file is created
+        let invalid = [
+            RDFS::builder().redundancy(99),
+            RDFS::builder().nodes(0),
+            RDFS::builder().block_size(MAX_BLOCK_SIZE + 1),
+            RDFS::builder().block_size(1024),
+            RDFS::builder().nodes(3).storage(2 * 1048576),
+        ];
+        for builder in invalid {
+            let error = builder.program_id([5; 32]).build(&dir).unwrap_err();
+            assert!(matches!(error.downcast_ref::<RDFSError>(), Some(RDFSError::InconsistentSuperBlock(_))));
+        }
+        assert!(!dir.join(bytes_to_hex(&[5; 32]) + ".RDFS").exists());
+    }
+
+    #[test]
+    fn create_drive_test() {
+        let dir = std::env::temp_dir().join("rdfs_test_create_drive");
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        let builder = RDFS::builder().program_id([6; 32]);
+
+        // two drives of the same program under custom names
+        let first = builder.clone().file_name("first.RDFS").build(&dir).unwrap();
+        let second = builder.clone().file_name("second.RDFS").build(&dir).unwrap();
+        assert_eq!(first.path, dir.join("first.RDFS"));
+        assert_eq!(second.system.program_id, first.system.program_id);
+        first.create_file(first.system.inode_pointer, "a.txt", b"first").unwrap();
+
+        // an existing drive is kept unless overwriting is asked for
+        let error = builder.clone().file_name("first.RDFS").build(&dir).unwrap_err();
+        assert!(matches!(error.downcast_ref::<RDFSError>(), Some(RDFSError::AlreadyExists(_))));
+        assert!(RDFS::mount_drive(&first.path).unwrap().resolve_path("/a.txt").is_ok());
+        RDFS::new(&dir, FileSystemType::Shared, [0; 32], [6; 32], 1048576, 100, 1, 4096).unwrap();
+        assert!(RDFS::new(&dir, FileSystemType::Private, [0; 32], [6; 32], 1048576, 100, 1, 4096).is_err());
+
+        let replaced = builder.file_name("first.RDFS").overwrite(true).build(&dir).unwrap();
+        assert!(replaced.list_dir(replaced.system.inode_pointer).unwrap().is_empty());
+        assert_eq!(
+            fs::metadata(&replaced.path).unwrap().len(),
+            replaced.system.node_storage + replaced.system.backup_size()
+        );
+    }
+
+    #[test]
+    fn hex_round_trip_test() {
+        let program_id: Address = std::array::from_fn(|i| (i * 37) as u8);
+        let hex = bytes_to_hex(&program_id);
+        assert_eq!(hex_to_bytes(&hex).unwrap(), program_id);
+        assert_eq!(hex_to_address(&hex).unwrap(), program_id);
+        assert_eq!(hex_to_address(&hex.to_uppercase()).unwrap(), program_id);
+        assert_eq!(hex_to_bytes("").unwrap(), Vec::<u8>::new());
+
+        // the drive file stem maps back to its program id
+        let name = drive_file_name(&program_id);
+        assert_eq!(hex_to_address(name.trim_end_matches(".RDFS")).unwrap(), program_id);
+
+        for bad in ["abc", "zz", "0x00", "+f"] {
+            let error = hex_to_bytes(bad).unwrap_err();
+            assert!(matches!(error.downcast_ref::<RDFSError>(), Some(RDFSError::InvalidHex(_))));
+        }
+        assert!(hex_to_address(&hex[2..]).is_err());
+        assert!(hex_to_address(&(hex.clone() + "00")).is_err());
+        assert!(hex_to_address(&hex.replace('0', "g")).is_err());
+    }
+
+    #[test]
+    fn drive_too_small_test() {
+        let dir = std::env::temp_dir().join("rdfs_test_drive_too_small");
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+
+        for magic in [FileSystemType::Shared, FileSystemType::Private] {
+            let error = RDFS::new(&dir, magic, [255; 32], [7; 32], 4096, 100, 8, 4096).unwrap_err();
+            assert!(matches!(
+                error.downcast_ref::<RDFSError>(),
+                Some(RDFSError::DriveTooSmall { storage: 4096, nodes: 8 })
+            ));
+        }
+        let error = RDFS::new_refcounted(&dir, [255; 32], [7; 32], 1024, 100, 1, 4096).unwrap_err();
+        assert!(matches!(error.downcast_ref::<RDFSError>(), Some(RDFSError::DriveTooSmall { .. })));
+        // nothing is left behind
+        assert_eq!(fs::read_dir(&dir).unwrap().count(), 0);
+
+        let mut rdfs = RDFS::new(&dir, FileSystemType::Shared, [255; 32], [7; 32], 1048576, 100, 1, 4096).unwrap();
+        let error = rdfs.resize_drive(0).unwrap_err();
+        assert!(matches!(error.downcast_ref::<RDFSError>(), Some(RDFSError::DriveTooSmall { .. })));
+    }
+
+    #[test]
+    fn read_blocks_test() {
+        let rdfs = test_drive("read_blocks");
+        let root = rdfs.system.inode_pointer;
+        let data: Vec<u8> = (0..10_000).map(|byte| (byte % 229) as u8).collect();
+        let file = rdfs.create_file(root, "blocks.bin", &data).unwrap();
+        let mut content = rdfs.read_inode_file(file).unwrap().content;
+        let pointers: Vec<u64> = rdfs.block_pointers(&content).collect();
+
+        // every missing block keeps its place in the stream
+        content.insert(0, FileContent { pointer: 3, blocks: 1 });
+        content.push(FileContent::hole(2));
+        content.push(FileContent {
+            pointer: rdfs.system.inode_pointer + 4096,
+            blocks: 1,
+        });
+        let results: Vec<Result<Vec<u8>>> = rdfs.read_blocks(content).collect();
+        assert_eq!(results.len(), pointers.len() + 2);
+        assert!(matches!(
+            results[0].as_ref().unwrap_err().downcast_ref::<RDFSError>(),
+            Some(RDFSError::PointerOutOfRange { .. })
+        ));
+        for (result, pointer) in results[1..].iter().zip(&pointers) {
+            assert_eq!(result.as_ref().unwrap(), &rdfs.read_block(*pointer).unwrap());
+        }
+        assert!(results.last().unwrap().is_err());
+        assert_eq!(results.iter().filter(|result| result.is_ok()).count(), pointers.len());
+    }
+
+    #[test]
+    fn drive_lock_test() {
+        let path = test_drive("drive_lock").path;
+        let locked = |result: Result<RDFS>| matches!(result.unwrap_err().downcast_ref::<RDFSError>(), Some(RDFSError::DriveLocked(_)));
+        let denied = |result: Result<u64>| matches!(result.unwrap_err().downcast_ref::<RDFSError>(), Some(RDFSError::DriveLocked(_)));
+
+        let writer = RDFS::mount_drive_exclusive(&path).unwrap();
+        let root = writer.system.inode_pointer;
+        writer.clone().create_file(root, "a.txt", b"exclusive").unwrap();
+        assert!(locked(RDFS::mount_drive_exclusive(&path)));
+        assert!(locked(RDFS::mount_drive_shared(&path)));
+
+        // an unlocked handle still reads, but can't write under the exclusive lock
+        let unlocked = RDFS::mount_drive(&path).unwrap();
+        let (file, _) = unlocked.resolve_path("/a.txt").unwrap();
+        assert_eq!(unlocked.read_file(file).unwrap(), b"exclusive");
+        assert!(denied(unlocked.create_file(root, "b.txt", b"unlocked")));
+        drop(writer);
+
+        let reader = RDFS::mount_drive_shared(&path).unwrap();
+        let other = RDFS::mount_drive_shared(&path).unwrap();
+        assert_eq!(reader.read_file(file).unwrap(), other.read_file(file).unwrap());
+        assert!(denied(reader.create_file(root, "b.txt", b"shared")));
+        assert!(locked(RDFS::mount_drive_exclusive(&path)));
+        drop((reader, other));
+
+        let writer = RDFS::mount_drive_exclusive(&path).unwrap();
+        writer.create_file(root, "b.txt", b"again").unwrap();
+        drop(writer);
+        unlocked.create_file(root, "c.txt", b"unlocked").unwrap();
+        assert_eq!(unlocked.list_dir(root).unwrap().len(), 3);
+    }
+
+    #[test]
+    fn max_file_size_test() {
+        let too_large = |error: anyhow::Error| matches!(error.downcast_ref::<RDFSError>(), Some(RDFSError::FileTooLarge { .. }));
+        let dir = std::env::temp_dir().join("rdfs_test_max_file_size");
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        let rdfs = RDFS::new(&dir, FileSystemType::Shared, [255; 32], [1; 32], 2 * 1048576, 100, 1, 4096).unwrap();
+        let root = rdfs.system.inode_pointer;
+        let max = rdfs.system.max_file_size() as usize;
+        let payload_size = rdfs.system.data_payload_size() as usize;
+
+        // nothing is allocated for a file that could never fit
+        assert!(too_large(rdfs.create_file(root, "huge.bin", &vec![1; max + 1]).unwrap_err()));
+        assert!(too_large(rdfs.write_file_streaming(root, "huge.bin", &vec![1; max + 1][..]).unwrap_err()));
+        assert_eq!(rdfs.read_bitmaps_block().unwrap().free_blocks, rdfs.system.total_blocks - 1);
+
+        // interleaved appends leave `grown` with one range per block, spilling into a linked block
+        let grown = rdfs.create_file(root, "grown.bin", &[]).unwrap();
+        let other = rdfs.create_file(root, "other.bin", &[]).unwrap();
+        let chunk = vec![2; payload_size];
+        for _ in 0..rdfs.system.max_content_pointers + 1 {
+            rdfs.append_to_file(grown, &chunk).unwrap();
+            rdfs.append_to_file(other, &chunk).unwrap();
+        }
+        let inode = rdfs.read_inode_file(grown).unwrap();
+        assert_ne!(inode.linked, 0);
+
+        let free = rdfs.read_bitmaps_block().unwrap().free_blocks;
+        let error = rdfs.append_to_file(grown, &vec![3; max - inode.size as usize + 1]).unwrap_err();
+        assert!(too_large(error));
+        assert_eq!(rdfs.read_inode_file(grown).unwrap(), inode);
+        assert_eq!(rdfs.read_bitmaps_block().unwrap().free_blocks, free);
+        // below the limit, running out of blocks is still `NotEnoughSpace`
+        let error = rdfs.append_to_file(grown, &vec![3; max - inode.size as usize]).unwrap_err();
+        assert!(matches!(error.downcast_ref::<RDFSError>(), Some(RDFSError::NotEnoughSpace)));
+
+        // on an empty drive a file of exactly `max_file_size` takes every block
+        let builder = RDFS::builder().storage(2 * 1048576).refcounted(false);
+        let rdfs = builder.file_name("empty.RDFS").build(&dir).unwrap();
+        let file = rdfs.create_file(rdfs.system.inode_pointer, "full.bin", &vec![4; max]).unwrap();
+        assert_eq!(rdfs.read_bitmaps_block().unwrap().free_blocks, 0);
+        assert_eq!(rdfs.read_file(file).unwrap().len(), max);
+        assert!(too_large(rdfs.append_to_file(file, &[5]).unwrap_err()));
+    }
+
+    #[test]
+    fn encrypted_drive_test() {
+        let dir = std::env::temp_dir().join("rdfs_test_encrypted_drive");
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+
+        let builder = RDFS::builder().magic(FileSystemType::Private).program_id([7; 32]);
+        let rdfs = builder
+            .encrypted(true)
+            .build(&dir)
+            .unwrap()
+            .with_encryption_key(EncryptionKey::new([1; 32]));
+        assert!(rdfs.system.is_encrypted());
+        let payload = rdfs.system.data_payload_size() as usize;
+        let data: Vec<u8> = b"top secret ".iter().cycle().take(payload).copied().collect();
+        let pointer = rdfs.system.block_pointer(2).unwrap();
+        rdfs.write_data_block(pointer, &DataBlock::new(2, 0, &data)).unwrap();
+
+        // the payload never reaches the drive in clear
+        let raw = rdfs.read_block(pointer).unwrap();
+        assert!(!raw.windows(11).any(|window| window == b"top secret "));
+        assert_eq!(rdfs.read_data_block(pointer).unwrap().data, data);
+
+        // the flag survives a remount, reads then need the key
+        let mounted = RDFS::mount_drive(&rdfs.path).unwrap();
+        assert!(mounted.system.is_encrypted());
+        let error = mounted.read_data_block(pointer).unwrap_err();
+        assert!(matches!(error.downcast_ref::<RDFSError>(), Some(RDFSError::EncryptionKeyRequired)));
+        let error = mounted.write_data_block(pointer, &DataBlock::new(2, 0, b"x")).unwrap_err();
+        assert!(matches!(error.downcast_ref::<RDFSError>(), Some(RDFSError::EncryptionKeyRequired)));
+
+        let wrong = mounted.clone().with_encryption_key(EncryptionKey::new([2; 32]));
+        let error = wrong.read_data_block(pointer).unwrap_err();
+        assert!(matches!(error.downcast_ref::<RDFSError>(), Some(RDFSError::EncryptionFailed(_))));
+        let right = mounted.with_encryption_key(EncryptionKey::new([1; 32]));
+        assert_eq!(right.read_data_block(pointer).unwrap().data, data);
+
+        // a stopped clock and one block number for every write still never repeat a nonce,
+        // rewriting one block or writing two
+        set_clock(Some(Arc::new(ManualClock::new(1_000))));
+        let nonce = |pointer: u64| {
+            let block = DataBlock::from_bytes(&rdfs.read_block(pointer).unwrap(), 4096).unwrap();
+            block.nonce().unwrap().to_vec()
+        };
+        let other = rdfs.system.block_pointer(3).unwrap();
+        rdfs.write_data_block(pointer, &DataBlock::new(2, 0, b"first")).unwrap();
+        let first = nonce(pointer);
+        rdfs.write_data_block(pointer, &DataBlock::new(2, 0, b"second")).unwrap();
+        rdfs.write_data_block(other, &DataBlock::new(2, 0, b"first")).unwrap();
+        assert!(first != nonce(pointer) && first != nonce(other) && nonce(pointer) != nonce(other));
+        assert_eq!(rdfs.read_data_block(pointer).unwrap().data, b"second");
+        assert_eq!(rdfs.read_data_block(other).unwrap().data, b"first");
+        set_clock(None);
+
+        // a plain drive stores blocks as they are
+        let plain = RDFS::builder().magic(FileSystemType::Private).program_id([8; 32]).build(&dir).unwrap();
+        plain.write_data_block(pointer, &DataBlock::new(2, 0, b"public")).unwrap();
+        assert!(!plain.read_data_block(pointer).unwrap().encrypted);
+    }
+
+    #[test]
+    fn iter_inodes_test() {
+        let dir = std::env::temp_dir().join("rdfs_test_iter_inodes");
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        let rdfs = RDFS::new_refcounted(&dir, [255; 32], [1; 32], 2 * 1048576, 100, 1, 4096).unwrap();
+        let root = rdfs.system.inode_pointer;
+        let docs = rdfs.mkdir(root, "docs").unwrap();
+        let report = rdfs.create_file(docs, "report.txt", &[3; 9000]).unwrap();
+        let notes = rdfs.create_file(root, "notes.txt", b"notes").unwrap();
+
+        // enough entries to spill into an `InodeLinkedDir` block
+        let many = rdfs.mkdir(root, "many").unwrap();
+        let files: Vec<u64> = (0..rdfs.system.max_content_pointers + 3)
+            .map(|i| rdfs.create_file(many, &format!("{i}"), b"").unwrap())
+            .collect();
+        assert_ne!(rdfs.read_inode_dir(many).unwrap().linked, 0);
+
+        let walk: Vec<(u64, InodeType)> = rdfs.iter_inodes().collect::<Result<_>>().unwrap();
+        let mut expected = vec![(root, InodeType::Dir), (docs, InodeType::Dir), (report, InodeType::File)];
+        expected.push((notes, InodeType::File));
+        expected.push((many, InodeType::Dir));
+        expected.extend(files.iter().map(|file| (*file, InodeType::File)));
+        assert_eq!(walk[..expected.len()], expected[..]);
+        assert_eq!(walk[expected.len()..], [(rdfs.system.snapshot_pointer, InodeType::Dir)]);
+
+        // the snapshot list and its deep copied inodes come after the live tree
+        let snapshot = rdfs.snapshot("before").unwrap();
+        let walk: Vec<(u64, InodeType)> = rdfs.iter_inodes().collect::<Result<_>>().unwrap();
+        assert_eq!(walk[..expected.len()], expected[..]);
+        assert_eq!(walk[expected.len()], (rdfs.system.snapshot_pointer, InodeType::Dir));
+        assert_eq!(walk[expected.len() + 1], (snapshot, InodeType::Dir));
+        assert_eq!(walk.len(), 2 * expected.len() + 1);
+
+        // an entry looping back to the root is walked once, a dangling one yields an error
+        let mut bitmaps = rdfs.read_bitmaps_block().unwrap();
+        for (pointer, inode_type) in [(root, InodeType::Dir), (u64::MAX, InodeType::File)] {
+            rdfs.add_dir_entry(
+                &mut bitmaps,
+                docs,
+                DirContent {
+                    pointer,
+                    inode_type,
+                    name: 0,
+                },
+            )
+            .unwrap();
+        }
+        let walk: Vec<Result<(u64, InodeType)>> = rdfs.iter_inodes().collect();
+        assert_eq!(walk.iter().filter(|item| matches!(item, Ok((pointer, _)) if *pointer == root)).count(), 1);
+        assert_eq!(walk.iter().filter(|item| item.is_err()).count(), 1);
+        assert_eq!(walk.len(), 2 * expected.len() + 2);
+    }
+
+    #[test]
+    fn file_redundancy_test() {
+        let dir = std::env::temp_dir().join("rdfs_test_file_redundancy");
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        let rdfs = RDFS::new_refcounted(&dir, [255; 32], [1; 32], 1048576, 300, 12, 4096).unwrap();
+        let root = rdfs.system.inode_pointer;
+
+        let plain = rdfs.create_file(root, "plain.bin", b"plain").unwrap();
+        let important = rdfs.create_file_with_redundancy(root, "important.bin", b"important", Some(500)).unwrap();
+        let copy = rdfs.copy_file(root, important, "copy.bin").unwrap();
+        assert_eq!(rdfs.file_redundancy(plain).unwrap(), 300);
+        assert_eq!(rdfs.file_redundancy(important).unwrap(), 500);
+        assert_eq!(rdfs.file_redundancy(copy).unwrap(), 500);
+        assert_eq!(rdfs.read_file(important).unwrap(), b"important");
+
+        // the override only changes the number of repair blocks
+        let data: Vec<u8> = (0..rdfs.system.client_block_size).map(|i| i as u8).collect();
+        let source_symbols = rdfs.system.client_block_size.div_ceil(rdfs.system.client_payload_size()) as usize;
+        let drive = DataBlock::encode_with_raptorq(&data, &rdfs.system).unwrap();
+        let mut file = DataBlock::encode_with_redundancy(&data, &rdfs.system, rdfs.file_redundancy(important).unwrap()).unwrap();
+        assert_eq!(drive.len(), source_symbols * 3);
+        assert_eq!(file.len(), source_symbols * 5);
+
+        // so it survives losing four fifths of the blocks
+        file.drain(..source_symbols * 4 - 2);
+        let decoded = DataBlock::decode_from_raptorq(file.into_iter(), &rdfs.system).unwrap();
+        assert_eq!(decoded, data);
+
+        for redundancy in [99, u32::MAX as u64 + 1] {
+            let error = rdfs.create_file_with_redundancy(root, "bad.bin", b"", Some(redundancy)).unwrap_err();
+            assert!(matches!(error.downcast_ref::<RDFSError>(), Some(RDFSError::InvalidRedundancy(r)) if *r == redundancy));
+        }
+        assert!(rdfs.resolve_path("/bad.bin").is_err());
+    }
+
+    #[test]
+    fn mount_partial_test() {
+        let rdfs = test_drive("mount_partial");
+        let root = rdfs.system.inode_pointer;
+        rdfs.create_file(root, "file.bin", b"content").unwrap();
+
+        // only the first half of the drive arrived
+        let half = fs::metadata(&rdfs.path).unwrap().len() / 2;
+        fs::OpenOptions::new().write(true).open(&rdfs.path).unwrap().set_len(half).unwrap();
+        let partial = RDFS::mount_partial(&rdfs.path).unwrap();
+        assert_eq!(partial.system, rdfs.system);
+        assert_eq!(partial.available, Some(half));
+        assert!(partial.read_block(rdfs.system.block_pointer(0).unwrap()).is_ok());
+
+        // the root inode sits in the last block
+        let error = partial.read_block(root).unwrap_err();
+        assert!(matches!(
+            error.downcast_ref::<RDFSError>(),
+            Some(RDFSError::BlockNotAvailable { pointer, available }) if *pointer == root && *available == half
+        ));
+
+        // the super block itself must be complete
+        fs::OpenOptions::new().write(true).open(&rdfs.path).unwrap().set_len(100).unwrap();
+        let error = RDFS::mount_partial(&rdfs.path).unwrap_err();
+        assert!(matches!(
+            error.downcast_ref::<RDFSError>(),
+            Some(RDFSError::InvalidSuperBlockLength { .. })
+        ));
+    }
+
+    #[test]
+    fn rdfs_file_test() {
+        let rdfs = test_drive("rdfs_file");
+        let root = rdfs.system.inode_pointer;
+        let data: Vec<u8> = (0..10_000).map(|byte| (byte % 251) as u8).collect();
+        let pointer = rdfs.create_file(root, "file.bin", &data).unwrap();
+
+        let mut file = rdfs.open(pointer).unwrap();
+        let mut read = vec![];
+        io::copy(&mut file, &mut read).unwrap();
+        assert_eq!(read, data);
+
+        // seeking anywhere, past the end included, behaves like a normal file
+        let mut buffer = [0; 100];
+        assert_eq!(file.seek(SeekFrom::Start(4050)).unwrap(), 4050);
+        file.read_exact(&mut buffer).unwrap();
+        assert_eq!(buffer[..], data[4050..4150]);
+        assert_eq!(file.seek(SeekFrom::End(-10)).unwrap(), 9990);
+        assert_eq!(file.read(&mut buffer).unwrap(), 10);
+        assert_eq!(file.seek(SeekFrom::Current(100)).unwrap(), 10_100);
+        assert_eq!(file.read(&mut buffer).unwrap(), 0);
+        assert_eq!(file.seek(SeekFrom::Current(-10_101)).unwrap_err().kind(), ErrorKind::InvalidInput);
+
+        // writes append and are visible to reads through the handle before a flush
+        file.write_all(b"appended").unwrap();
+        assert_eq!(file.stream_position().unwrap(), 10_008);
+        assert_eq!(rdfs.read_inode_file(pointer).unwrap().size, 10_000);
+        file.seek(SeekFrom::Start(9_998)).unwrap();
+        let mut tail = vec![];
+        file.read_to_end(&mut tail).unwrap();
+        assert_eq!(tail, b"\xd1\xd2appended");
+
+        let more: Vec<u8> = (0..5_000).map(|byte| (byte % 13) as u8).collect();
+        io::copy(&mut more.as_slice(), &mut file).unwrap();
+        drop(file);
+        let mut expected = data.clone();
+        expected.extend_from_slice(b"appended");
+        expected.extend_from_slice(&more);
+        assert_eq!(rdfs.read_file(pointer).unwrap(), expected);
+
+        assert!(rdfs.open(root + 1).is_err());
+    }
+
+    #[test]
+    fn overlapping_file_content_test() {
+        let rdfs = test_drive("overlapping_file_content");
+        let root = rdfs.system.inode_pointer;
+        let file = rdfs.create_file(root, "file.bin", &[7; 10_000]).unwrap();
+        let original = rdfs.read_inode_file(file).unwrap();
+        let first = original.content[0].clone();
+
+        // a range claiming blocks of the file a second time is never written
+        let mut inode = original.clone();
+        inode.content.push(FileContent {
+            pointer: first.pointer + rdfs.system.block_size,
+            blocks: 1,
+        });
+        let error = rdfs.write_inode_file(file, &inode).unwrap_err();
+        assert!(matches!(
+            error.downcast_ref::<RDFSError>(),
+            Some(RDFSError::OverlappingFileContent { .. })
+        ));
+
+        // neither is a range running past the root inode
+        inode.content = vec![FileContent { pointer: root, blocks: 2 }];
+        let error = rdfs.write_inode_file(file, &inode).unwrap_err();
+        assert!(matches!(error.downcast_ref::<RDFSError>(), Some(RDFSError::OverlappingFileContent { pointer, .. }) if *pointer == root));
+
+        // the whole chain is checked before linked blocks are allocated
+        let mut content = vec![first.clone(); rdfs.system.max_content_pointers as usize];
+        content.push(first);
+        let mut bitmaps = rdfs.read_bitmaps_block().unwrap();
+        let free = bitmaps.free_blocks;
+        assert!(rdfs.write_file_inode(&mut bitmaps, file, original.clone(), content).is_err());
+        assert_eq!(bitmaps.free_blocks, free);
+        assert_eq!(rdfs.read_inode_file(file).unwrap(), original);
+        assert_eq!(rdfs.read_file(file).unwrap(), vec![7; 10_000]);
+    }
+
+    #[test]
+    fn exists_test() {
+        let rdfs = test_drive("exists");
+        let root = rdfs.system.inode_pointer;
+        let docs = rdfs.mkdir(root, "docs").unwrap();
+        let report = rdfs.create_file(docs, "report.txt", b"quarterly").unwrap();
+
+        for path in ["/", "/docs", "/docs/report.txt", "docs//report.txt"] {
+            assert!(rdfs.exists(path).unwrap(), "{path}");
+        }
+        let stat = rdfs.metadata_for_path("/docs/report.txt").unwrap();
+        assert_eq!(stat, rdfs.stat(report, InodeType::File).unwrap());
+        assert_eq!((stat.name.as_str(), stat.size), ("report.txt", 9));
+        assert_eq!(rdfs.metadata_for_path("/docs").unwrap().size, 1);
+
+        // a missing intermediate directory is "not found", not an error
+        for path in ["/missing", "/missing/report.txt", "/docs/missing/report.txt", "/docs/report.txt/x"] {
+            assert!(!rdfs.exists(path).unwrap(), "{path}");
+        }
+        let error = rdfs.metadata_for_path("/docs/missing/report.txt").unwrap_err();
+        assert!(matches!(error.downcast_ref::<RDFSError>(), Some(RDFSError::PathNotFound(path)) if path == "/docs/missing"));
+
+        // anything else still fails
+        let private = RDFS {
+            system: SuperBlock::new(FileSystemType::Private, [255; 32], [1; 32], 1048576, 100, 1, 4096).unwrap(),
+            ..rdfs.clone()
+        };
+        assert!(private.exists("/docs").is_err());
+    }
+
+    #[test]
+    fn stats_test() {
+        let rdfs = test_drive("stats");
+        let root = rdfs.system.inode_pointer;
+        let stats = rdfs.stats().unwrap();
+        let free = stats.free_blocks.unwrap();
+        assert_eq!(stats.total_blocks, rdfs.system.total_blocks);
+        assert_eq!(stats.used_blocks, Some(stats.total_blocks - free));
+        assert_eq!(stats.bytes_free, Some(free * rdfs.system.data_payload_size()));
+        assert_eq!(stats.efficiency, rdfs.system.efficiency());
+        assert_eq!(stats.fragmentation, Some(0.0));
+
+        // freeing every other file splits the free space into runs
+        let files: Vec<u64> = (0..6).map(|i| rdfs.create_file(root, &format!("f{i}"), &[1; 5000]).unwrap()).collect();
+        for file in files.iter().step_by(2) {
+            rdfs.delete_file(root, *file).unwrap();
+        }
+        let stats = rdfs.stats().unwrap();
+        assert!(stats.free_blocks.unwrap() < free);
+        assert_eq!(stats.bytes_used, Some(stats.used_blocks.unwrap() * rdfs.system.data_payload_size()));
+        let fragmentation = stats.fragmentation.unwrap();
+        assert!(fragmentation > 0.0 && fragmentation < 1.0, "{fragmentation}");
+
+        let private = RDFS {
+            system: SuperBlock::new(FileSystemType::Private, [255; 32], [1; 32], 1048576, 100, 1, 4096).unwrap(),
+            ..rdfs.clone()
+        };
+        let stats = private.stats().unwrap();
+        assert_eq!((stats.free_blocks, stats.used_blocks, stats.fragmentation), (None, None, None));
+        assert_eq!(stats.total_blocks, private.system.total_blocks);
+    }
+
+    #[test]
+    fn glob_test() {
+        let rdfs = test_drive("glob");
+        let root = rdfs.system.inode_pointer;
+        let photos = rdfs.mkdir(root, "照片").unwrap();
+        let mut files = Vec::new();
+        for name in ["beach_🏖️.jpg", "beach_🌅.png", "mountain_⛰.jpg", "notes.txt"] {
+            files.push(rdfs.create_file(photos, name, name.as_bytes()).unwrap());
+        }
+        let sub = rdfs.mkdir(photos, "beach_album").unwrap();
+
+        let names = |pattern: &str| -> Vec<String> { rdfs.glob(photos, pattern).unwrap().into_iter().map(|(name, _, _)| name).collect() };
+        assert_eq!(names("*.jpg"), ["beach_🏖️.jpg", "mountain_⛰.jpg"]);
+        assert_eq!(names("beach_*"), ["beach_🏖️.jpg", "beach_🌅.png", "beach_album"]);
+        assert_eq!(names("beach_?.png"), ["beach_🌅.png"]);
+        assert_eq!(names("*_?.*"), ["beach_🌅.png", "mountain_⛰.jpg"]); // 🏖️ is two code points
+        assert_eq!(
+            names("*"),
+            rdfs.list_dir(photos).unwrap().into_iter().map(|(name, _, _)| name).collect::<Vec<_>>()
+        );
+        assert!(names("*.gif").is_empty());
+
+        let found = rdfs.glob(photos, "beach_album").unwrap();
+        assert_eq!(found, vec![("beach_album".to_string(), sub, InodeType::Dir)]);
+        assert_eq!(rdfs.glob(root, "照?").unwrap(), vec![("照片".to_string(), photos, InodeType::Dir)]);
+        assert_eq!(rdfs.glob(photos, "notes.*").unwrap()[0].1, files[3]);
+    }
+
+    #[test]
+    fn link_test() {
+        let rdfs = test_drive("link");
+        let root = rdfs.system.inode_pointer;
+        let docs = rdfs.mkdir(root, "docs").unwrap();
+        let archive = rdfs.mkdir(root, "archive").unwrap();
+        let empty = rdfs.read_bitmaps_block().unwrap().free_blocks;
+        let file = rdfs.create_file(docs, "report.txt", &vec![7u8; 9000]).unwrap();
+
+        // every link has a name of its own, which must be free in the parent
+        let error = rdfs.link(file, docs, "report.txt").unwrap_err();
+        assert!(matches!(error.downcast_ref::<RDFSError>(), Some(RDFSError::AlreadyExists(name)) if name == "report.txt"));
+        assert!(rdfs.link(file, docs, "a/b").is_err());
+
+        rdfs.link(file, docs, "copy.txt").unwrap();
+        rdfs.link(file, archive, "2025.txt").unwrap();
+        rdfs.link(file, root, "report.txt").unwrap();
+        assert_eq!(rdfs.read_inode_file(file).unwrap().links, 4);
+        let names: Vec<String> = rdfs.list_dir(docs).unwrap().into_iter().map(|(name, _, _)| name).collect();
+        assert_eq!(names, ["report.txt", "copy.txt"]);
+        assert_eq!(rdfs.resolve_path("/archive/2025.txt").unwrap(), (file, InodeType::File));
+        rdfs.append_to_file(file, b"!").unwrap();
+        assert_eq!(rdfs.read_file(rdfs.resolve_path("/docs/copy.txt").unwrap().0).unwrap().len(), 9001);
+        assert!(rdfs.fsck(false).unwrap().is_clean());
+
+        // a link is renamed alone, in place or into another directory
+        rdfs.rename(docs, "copy.txt", docs, "draft.txt").unwrap();
+        rdfs.rename(archive, "2025.txt", root, "final.txt").unwrap();
+        assert_eq!(rdfs.read_inode_file(file).unwrap().name.as_string(), "report.txt");
+        assert!(rdfs.exists("/docs/draft.txt").unwrap() && rdfs.exists("/final.txt").unwrap());
+        assert!(!rdfs.exists("/archive/2025.txt").unwrap());
+        // renaming onto another link of the same file leaves both
+        rdfs.rename(root, "final.txt", root, "report.txt").unwrap();
+        assert!(rdfs.exists("/final.txt").unwrap() && rdfs.exists("/report.txt").unwrap());
+        assert!(rdfs.fsck(false).unwrap().is_clean());
+
+        // unlinking drops one entry and its name block at a time, nothing else while a link remains
+        let error = rdfs.unlink(root, "docs").unwrap_err();
+        assert!(matches!(error.downcast_ref::<RDFSError>(), Some(RDFSError::FileEntryNotFound(_))));
+        let used = rdfs.read_bitmaps_block().unwrap().free_blocks;
+        rdfs.unlink(docs, "report.txt").unwrap();
+        assert_eq!(rdfs.read_inode_file(file).unwrap().links, 3);
+        assert!(!rdfs.exists("/docs/report.txt").unwrap() && rdfs.exists("/docs/draft.txt").unwrap());
+        assert_eq!(rdfs.read_bitmaps_block().unwrap().free_blocks, used);
+        rdfs.delete_file(docs, file).unwrap();
+        assert_eq!(rdfs.read_bitmaps_block().unwrap().free_blocks, used + 1);
+        rdfs.rename(root, "final.txt", archive, "final.txt").unwrap();
+        assert_eq!(rdfs.remove_dir_all(root, archive).unwrap(), 2);
+        assert_eq!(rdfs.read_inode_file(file).unwrap().links, 1);
+        assert_eq!(rdfs.read_file(file).unwrap().len(), 9001);
+        assert!(rdfs.fsck(false).unwrap().is_clean());
+
+        // the last link frees the file with its name block
+        rdfs.unlink(root, "report.txt").unwrap();
+        assert_eq!(rdfs.read_bitmaps_block().unwrap().free_blocks, empty + 1);
+        assert!(rdfs.fsck(false).unwrap().is_clean());
+
+        // a snapshot keeps hard links pointing at one copy of the inode, each with a copy of its name
+        let dir = std::env::temp_dir().join("rdfs_test_link_snapshot");
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        let rdfs = RDFS::new_refcounted(dir, [255; 32], [1; 32], 1048576, 100, 1, 4096).unwrap();
+        let root = rdfs.system.inode_pointer;
+        let docs = rdfs.mkdir(root, "docs").unwrap();
+        let file = rdfs.create_file(root, "notes.txt", b"notes").unwrap();
+        rdfs.link(file, docs, "notes.txt").unwrap();
+        rdfs.link(file, root, "todo.txt").unwrap();
+        let snapshot = rdfs.snapshot("linked").unwrap();
+        let docs_copy = rdfs.find_dir_entry(snapshot, "docs").unwrap().unwrap().pointer;
+        let copies = [
+            rdfs.find_dir_entry(snapshot, "notes.txt").unwrap().unwrap().pointer,
+            rdfs.find_dir_entry(docs_copy, "notes.txt").unwrap().unwrap().pointer,
+            rdfs.find_dir_entry(snapshot, "todo.txt").unwrap().unwrap().pointer,
+        ];
+        assert!(copies.iter().all(|copy| *copy == copies[0]));
+        assert_ne!(copies[0], file);
+        assert_eq!(rdfs.read_inode_file(copies[0]).unwrap().links, 3);
+        rdfs.rename(root, "todo.txt", root, "done.txt").unwrap();
+        assert!(rdfs.find_dir_entry(snapshot, "todo.txt").unwrap().is_some());
+        assert!(rdfs.fsck(false).unwrap().is_clean());
+    }
+
+    #[test]
+    fn superblock_backup_test() {
+        let mut rdfs = test_drive("superblock_backup");
+        let system = rdfs.system.clone();
+        let corrupt_primary = |rdfs: &RDFS| write_range(&rdfs.path, 100, &[0xAB; 8]).unwrap();
+        let primary = |rdfs: &RDFS| read_range(&rdfs.path, 0, SB_SIZE as u64).unwrap();
+        let tail = |rdfs: &RDFS| {
+            let length = fs::metadata(&rdfs.path).unwrap().len();
+            read_range(&rdfs.path, length - SB_SIZE as u64, length).unwrap()
+        };
+        assert_eq!(tail(&rdfs), system.to_bytes());
+
+        // a shared handle mounts from the backup without touching the damaged primary
+        corrupt_primary(&rdfs);
+        let shared = RDFS::mount_drive_shared(&rdfs.path).unwrap();
+        assert_eq!(shared.system, system);
+        assert_ne!(primary(&rdfs), system.to_bytes());
+        drop(shared);
+
+        // any other mount rewrites it
+        let mounted = RDFS::mount_drive(&rdfs.path).unwrap();
+        assert_eq!(mounted.system, system);
+        assert_eq!(primary(&rdfs), system.to_bytes());
+
+        // a journal record growing the file moves the backup to the new end
+        let before = fs::metadata(&rdfs.path).unwrap().len();
+        rdfs.write_atomic(rdfs.system.data_pointer, &[1; 8192]).unwrap();
+        assert!(fs::metadata(&rdfs.path).unwrap().len() > before);
+        assert_eq!(tail(&rdfs), system.to_bytes());
+
+        // a damaged backup is rewritten from the primary, a damaged primary from the backup
+        let length = fs::metadata(&rdfs.path).unwrap().len();
+        write_range(&rdfs.path, length - 10, &[0xAB; 10]).unwrap();
+        assert!(!rdfs.repair_superblock().unwrap());
+        assert_eq!(tail(&rdfs), system.to_bytes());
+        corrupt_primary(&rdfs);
+        assert!(rdfs.repair_superblock().unwrap());
+        assert_eq!(primary(&rdfs), system.to_bytes());
+
+        // growing the drive updates both copies
+        rdfs.resize_drive(2 * 1048576).unwrap();
+        corrupt_primary(&rdfs);
+        assert_eq!(RDFS::mount_drive(&rdfs.path).unwrap().system, rdfs.system);
+
+        // nothing to fall back on
+        corrupt_primary(&rdfs);
+        let length = fs::metadata(&rdfs.path).unwrap().len();
+        write_range(&rdfs.path, length - 10, &[0xAB; 10]).unwrap();
+        let error = RDFS::mount_drive(&rdfs.path).unwrap_err();
+        assert!(matches!(error.downcast_ref::<RDFSError>(), Some(RDFSError::CorruptSuperBlock)));
+        let error = rdfs.repair_superblock().unwrap_err();
+        assert!(matches!(error.downcast_ref::<RDFSError>(), Some(RDFSError::NoSuperBlockBackup)));
+    }
+
+    #[test]
+    fn move_block_test() {
+        let rdfs = test_drive("move_block");
+        let root = rdfs.system.inode_pointer;
+        let data = vec![7; 1000];
+        let file = rdfs.create_file(root, "moved.bin", &data).unwrap();
+        let from = rdfs.file_content_ranges(file).unwrap()[0].pointer;
+        let content = rdfs.read_block(from).unwrap();
+        let bitmaps = rdfs.read_bitmaps_block().unwrap();
+        let index = bitmaps.free_blocks_iter().last().unwrap();
+        let to = rdfs.system.block_pointer(index).unwrap();
+        let is_used = |pointer: u64| {
+            let index = rdfs.system.block_index(pointer).unwrap() as usize;
+            rdfs.read_bitmaps_block().unwrap().get_bit(index)
+        };
+
+        let drive = &rdfs;
+        let point_at = |pointer: u64| {
+            move || {
+                let mut inode = drive.read_inode_file(file)?;
+                inode.content[0].pointer = pointer;
+                drive.write_inode_file(file, &inode)
+            }
+        };
+
+        // only a used block moves, and only into a free one
+        let error = rdfs.move_block(from, root, point_at(root)).unwrap_err();
+        assert!(matches!(error.downcast_ref::<RDFSError>(), Some(RDFSError::BlockInUse(p)) if *p == root));
+        let error = rdfs.move_block(to, from, point_at(from)).unwrap_err();
+        assert!(matches!(error.downcast_ref::<RDFSError>(), Some(RDFSError::BlockNotInUse(p)) if *p == to));
+        assert_eq!(rdfs.read_bitmaps_block().unwrap().to_bytes(), bitmaps.to_bytes());
+        assert_eq!(rdfs.file_content_ranges(file).unwrap()[0].pointer, from);
+
+        // a crash after the copy leaves it in a block still free
+        let crash_after = |step| move |done| if done == step { Err(anyhow::anyhow!("crash")) } else { Ok(()) };
+        assert!(rdfs.move_block_with(from, to, point_at(to), crash_after(1)).is_err());
+        assert_eq!(rdfs.read_block(to).unwrap(), content);
+        assert!(is_used(from) && !is_used(to));
+        assert!(rdfs.fsck(false).unwrap().is_clean());
+
+        // a crash after marking the target, or a failing rewrite, keeps both and fsck reclaims the copy
+        assert!(rdfs.move_block_with(from, to, point_at(to), crash_after(2)).is_err());
+        assert!(is_used(from) && is_used(to));
+        assert_eq!(rdfs.read_file(file).unwrap(), data);
+        assert_eq!(rdfs.fsck(true).unwrap().leaked, vec![to]);
+        assert!(!is_used(to));
+        assert!(rdfs.move_block(from, to, || Err(anyhow::anyhow!("rewrite failed"))).is_err());
+        assert!(is_used(from) && is_used(to));
+        assert_eq!(rdfs.fsck(true).unwrap().leaked, vec![to]);
+
+        // a crash after the rewrite keeps both too, the inode already reads the copy
+        assert!(rdfs.move_block_with(from, to, point_at(to), crash_after(3)).is_err());
+        assert!(is_used(from) && is_used(to));
+        assert_eq!(rdfs.file_content_ranges(file).unwrap()[0].pointer, to);
+        assert_eq!(rdfs.read_file(file).unwrap(), data);
+        assert_eq!(rdfs.fsck(true).unwrap().leaked, vec![from]);
+        assert!(!is_used(from));
+
+        // a complete move back, the inode is never left pointing at a free block
+        rdfs.move_block(to, from, point_at(from)).unwrap();
+        assert!(is_used(from) && !is_used(to));
+        assert_eq!(rdfs.read_block(from).unwrap(), content);
+        assert_eq!(rdfs.read_file(file).unwrap(), data);
+        assert!(rdfs.fsck(false).unwrap().is_clean());
+    }
+
+    #[test]
+    fn upgrade_drive_test() {
+        let rdfs = test_drive("upgrade_drive");
+        let system = SuperBlock {
+            version: 7,
+            ..rdfs.system.clone()
+        };
+        let mut rdfs = RDFS::create_drive(&rdfs.path, system, true).unwrap();
+        rdfs.create_file(rdfs.system.inode_pointer, "notes.txt", b"notes").unwrap();
+
+        // a version 7 root still moves to the new last block
+        rdfs.resize_drive(2 * 1048576).unwrap();
+        let last = rdfs.system.total_blocks - 1;
+        assert_eq!(rdfs.system.block_index(rdfs.system.inode_pointer).unwrap(), last);
+
+        assert!(rdfs.upgrade_drive().unwrap());
+        assert!(!rdfs.upgrade_drive().unwrap());
+        let mut rdfs = RDFS::mount_drive(&rdfs.path).unwrap();
+        assert_eq!(rdfs.system.version, SB_VERSION);
+        assert!(rdfs.system.has_stable_root());
+        assert!(!rdfs.repair_superblock().unwrap());
+
+        // from then on it keeps its index
+        rdfs.resize_drive(4 * 1048576).unwrap();
+        assert_eq!(rdfs.system.block_index(rdfs.system.inode_pointer).unwrap(), last);
+        let rdfs = RDFS::mount_drive(&rdfs.path).unwrap();
+        let (file, _) = rdfs.resolve_path("/notes.txt").unwrap();
+        assert_eq!(rdfs.read_file(file).unwrap(), b"notes");
+        assert!(rdfs.fsck(false).unwrap().is_clean());
+
+        // an older super block is laid out differently
+        let system = SuperBlock {
+            version: 6,
+            ..rdfs.system.clone()
+        }
+        .resized(1048576)
+        .unwrap();
+        let mut rdfs = RDFS::create_drive(&rdfs.path, system, true).unwrap();
+        let error = rdfs.upgrade_drive().unwrap_err();
+        assert!(matches!(
+            error.downcast_ref::<RDFSError>(),
+            Some(RDFSError::UnsupportedSuperBlockVersion(6))
+        ));
+    }
+
+    #[test]
+    fn transaction_test() {
+        let rdfs = test_drive("transaction").with_cache();
+        let root = rdfs.system.inode_pointer;
+        rdfs.create_file(root, "kept.txt", b"kept").unwrap();
+        let bitmaps = rdfs.read_bitmaps_block().unwrap().to_bytes();
+
+        // staged writes are only seen through the transaction, dropping it discards them
+        let txn = rdfs.begin_txn().unwrap();
+        let file = txn.create_file(root, "a.txt", b"first").unwrap();
+        txn.create_file(root, "b.txt", b"second").unwrap();
+        let error = txn.begin_txn().unwrap_err();
+        assert!(matches!(error.downcast_ref::<RDFSError>(), Some(RDFSError::TransactionOpen)));
+        assert_eq!(txn.read_file(file).unwrap(), b"first");
+        assert!(txn.exists("/b.txt").unwrap());
+        assert!(!rdfs.exists("/a.txt").unwrap());
+        drop(txn);
+        assert_eq!(rdfs.read_bitmaps_block().unwrap().to_bytes(), bitmaps);
+        assert_eq!(rdfs.list_dir(root).unwrap().len(), 1);
+        assert!(rdfs.fsck(false).unwrap().is_clean());
+
+        // committing stores both files at once
+        let txn = rdfs.begin_txn().unwrap();
+        txn.create_file(root, "a.txt", b"first").unwrap();
+        let (kept, _) = txn.resolve_path("/kept.txt").unwrap();
+        txn.delete_file(root, kept).unwrap();
+        txn.commit_txn().unwrap();
+        assert!(rdfs.exists("/a.txt").unwrap());
+        assert!(!rdfs.exists("/kept.txt").unwrap());
+        assert!(rdfs.fsck(false).unwrap().is_clean());
+
+        // a crash once the record is written but before it is applied is replayed on mount
+        let txn = rdfs.begin_txn().unwrap();
+        let (first, _) = txn.resolve_path("/a.txt").unwrap();
+        txn.delete_file(root, first).unwrap();
+        let file = txn.create_file(root, "crash.txt", b"recovered").unwrap();
+        let writes = txn.txn.as_ref().unwrap().lock().unwrap().clone();
+        let (_, tail, sequence) = rdfs.scan_journal().unwrap();
+        let record = JournalBlock::new(sequence, rdfs.journal_entries(&writes).unwrap()).to_bytes();
+        // only the bytes the transaction changes are recorded, not the blocks it wrote
+        assert!(record.len() < writes.iter().map(|(_, data)| data.len()).sum::<usize>() / 2);
+        create_physical_file(&rdfs.path, tail + record.len() as u64 + SB_SIZE as u64).unwrap();
+        write_range(&rdfs.path, tail, &record).unwrap();
+        drop(txn);
+        assert!(!rdfs.exists("/crash.txt").unwrap());
+
+        let rdfs = RDFS::mount_drive(&rdfs.path).unwrap();
+        assert_eq!(rdfs.read_file(file).unwrap(), b"recovered");
+        assert!(!rdfs.exists("/a.txt").unwrap());
+        assert_eq!(last_journal(&rdfs).unwrap().state, JournalState::Applied);
+        assert!(rdfs.fsck(false).unwrap().is_clean());
     }
 }
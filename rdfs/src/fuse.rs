@@ -0,0 +1,457 @@
+//! # RDFS FUSE Adapter Module
+//!
+//! Maps the callbacks of a FUSE file system onto the high-level `RDFS` methods, so a
+//! shared drive can be mounted as a regular directory. Available with the `fuse` feature.
+//!
+//! ## Design
+//! Every inherent method of `FuseAdapter` mirrors one callback of `fuser::Filesystem`, takes
+//! the same inode numbers and names, and returns the reply payload or the `errno` to reply
+//! with, so the mapping can be tested without a kernel mount. The `fuser::Filesystem` impl
+//! only converts the arguments and forwards the result to the reply, and `FuseAdapter::mount`
+//! mounts the drive on a background session.
+//!
+//! ## Inode Numbers
+//! - `FUSE_ROOT_ID` (1) is the root directory of the drive
+//! - any other inode number is the pointer of the inode block, which is never 1
+//!
+//! ## Operations
+//! | callback  | RDFS method                         |
+//! |-----------|-------------------------------------|
+//! | `lookup`  | `list_dir` of the parent            |
+//! | `getattr` | `stat`                              |
+//! | `readdir` | `list_dir`                          |
+//! | `read`    | `read_file_range`                   |
+//! | `write`   | `append_to_file`, at the end only   |
+//! | `create`  | `create_file`                       |
+//! | `mkdir`   | `mkdir`                             |
+//! | `unlink`  | `unlink`                            |
+//!
+//! The adapter is read-mostly: writes anywhere but at the end of a file fail with `ENOTSUP`.
+//!
+//! Copyrights © 2025 RDFS Contributors. All rights reserved.
+
+use crate::core::inode_block::InodeType;
+use crate::file_system::RDFS;
+use crate::rdfs_errors::RDFSError;
+use fuser::{
+    BackgroundSession, FileAttr, FileType, Filesystem, MountOption, ReplyAttr, ReplyCreate, ReplyData, ReplyDirectory, ReplyEmpty, ReplyEntry,
+    ReplyWrite, Request,
+};
+use std::collections::HashMap;
+use std::ffi::OsStr;
+use std::io;
+use std::path::Path;
+use std::sync::RwLock;
+use std::time::{Duration, UNIX_EPOCH};
+
+/// Inode number FUSE uses for the root of the mount.
+pub const FUSE_ROOT_ID: u64 = 1;
+
+/// How long the kernel may cache entries and attributes replied by the adapter.
+const TTL: Duration = Duration::from_secs(1);
+
+/// Attributes of an entry, as carried by the `lookup`, `getattr`, `create` and `mkdir` replies.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FuseAttr {
+    pub ino: u64,
+    pub kind: InodeType,
+    pub size: u64,    // payload bytes of a file, number of entries of a directory
+    pub blocks: u64,  // 512-byte units like `st_blocks`, not drive blocks
+    pub links: u64,   // hard links of a file, 1 for a directory
+    pub created: u64, // milliseconds since the unix epoch
+    pub modify: u64,  // milliseconds since the unix epoch
+    pub perm: u16,    // 0o755 for directories, 0o644 for files
+}
+
+/// FUSE callbacks on top of a shared `RDFS` drive, see the module documentation.
+#[derive(Debug)]
+pub struct FuseAdapter {
+    pub rdfs: RDFS,
+    kinds: RwLock<HashMap<u64, InodeType>>, // type of every inode handed out to the kernel
+}
+
+impl FuseAdapter {
+    pub fn new(rdfs: RDFS) -> Self {
+        let kinds = HashMap::from([(rdfs.system.inode_pointer, InodeType::Dir)]);
+        Self {
+            rdfs,
+            kinds: RwLock::new(kinds),
+        }
+    }
+
+    /// Mounts the drive at `mountpoint`, serving requests on a background thread until the
+    /// returned session is dropped, which unmounts it.
+    pub fn mount(self, mountpoint: impl AsRef<Path>) -> io::Result<BackgroundSession> {
+        let options = [MountOption::FSName("rdfs".to_string()), MountOption::Subtype("rdfs".to_string())];
+        fuser::spawn_mount2(self, mountpoint, &options)
+    }
+
+    /// Inode pointer behind the inode number `ino`.
+    pub fn pointer(&self, ino: u64) -> u64 {
+        match ino {
+            FUSE_ROOT_ID => self.rdfs.system.inode_pointer,
+            _ => ino,
+        }
+    }
+
+    /// Inode number of the inode block at `pointer`.
+    pub fn ino(&self, pointer: u64) -> u64 {
+        match pointer == self.rdfs.system.inode_pointer {
+            true => FUSE_ROOT_ID,
+            false => pointer,
+        }
+    }
+
+    /// Looks `name` up in the directory `parent`.
+    pub fn lookup(&self, parent: u64, name: &str) -> Result<FuseAttr, i32> {
+        let (pointer, kind) = self.find(parent, name)?;
+        self.attr(pointer, kind)
+    }
+
+    /// Attributes of `ino`, which must have been returned by an earlier call.
+    pub fn getattr(&self, ino: u64) -> Result<FuseAttr, i32> {
+        let pointer = self.pointer(ino);
+        let kind = self.kind(pointer)?;
+        self.attr(pointer, kind)
+    }
+
+    /// Entries of the directory `ino` after the first `offset` ones as (ino, type, name),
+    /// the offset of an entry being its position plus one. `.` and `..` are left to the binding.
+    pub fn readdir(&self, ino: u64, offset: usize) -> Result<Vec<(u64, InodeType, String)>, i32> {
+        let pointer = self.pointer(ino);
+        if self.kind(pointer)? != InodeType::Dir {
+            return Err(libc::ENOTDIR);
+        }
+        let entries = self.rdfs.list_dir(pointer).map_err(|error| errno(&error))?;
+        let mut kinds = self.kinds.write().map_err(|_| libc::EIO)?;
+        let entries = entries.into_iter().skip(offset).map(|(name, pointer, kind)| {
+            kinds.insert(pointer, kind);
+            (self.ino(pointer), kind, name)
+        });
+        Ok(entries.collect())
+    }
+
+    /// Reads up to `size` bytes of the file `ino` at `offset`, fewer at the end of the file.
+    pub fn read(&self, ino: u64, offset: u64, size: u32) -> Result<Vec<u8>, i32> {
+        let pointer = self.file(ino)?;
+        let file_size = self.rdfs.read_inode_file(pointer).map_err(|error| errno(&error))?.size;
+        if offset >= file_size {
+            return Ok(vec![]);
+        }
+        self.rdfs.read_file_range(pointer, offset, size as u64).map_err(|error| errno(&error))
+    }
+
+    /// Writes `data` at `offset` of the file `ino`, returning the bytes written.
+    /// Only appending is supported, any other offset fails with `ENOTSUP`.
+    pub fn write(&self, ino: u64, offset: u64, data: &[u8]) -> Result<u32, i32> {
+        let pointer = self.file(ino)?;
+        let size = self.rdfs.read_inode_file(pointer).map_err(|error| errno(&error))?.size;
+        if offset != size {
+            return Err(libc::ENOTSUP);
+        }
+        self.rdfs.append_to_file(pointer, data).map_err(|error| errno(&error))?;
+        u32::try_from(data.len()).map_err(|_| libc::EFBIG)
+    }
+
+    /// Creates the empty file `name` in the directory `parent`.
+    pub fn create(&self, parent: u64, name: &str) -> Result<FuseAttr, i32> {
+        let pointer = self.rdfs.create_file(self.pointer(parent), name, &[]).map_err(|error| errno(&error))?;
+        self.attr(pointer, InodeType::File)
+    }
+
+    /// Creates the directory `name` in the directory `parent`.
+    pub fn mkdir(&self, parent: u64, name: &str) -> Result<FuseAttr, i32> {
+        let pointer = self.rdfs.mkdir(self.pointer(parent), name).map_err(|error| errno(&error))?;
+        self.attr(pointer, InodeType::Dir)
+    }
+
+    /// Deletes the file `name` from the directory `parent`, directories fail with `EISDIR`.
+    /// A file with other hard links stays known to the adapter.
+    pub fn unlink(&self, parent: u64, name: &str) -> Result<(), i32> {
+        let (pointer, kind) = self.find(parent, name)?;
+        if kind == InodeType::Dir {
+            return Err(libc::EISDIR);
+        }
+        let links = self.rdfs.read_inode_file(pointer).map_err(|error| errno(&error))?.links;
+        self.rdfs.unlink(self.pointer(parent), name).map_err(|error| errno(&error))?;
+        if links <= 1 {
+            self.kinds.write().map_err(|_| libc::EIO)?.remove(&pointer);
+        }
+        Ok(())
+    }
+
+    /// Pointer and type of the entry `name` of the directory `parent`.
+    fn find(&self, parent: u64, name: &str) -> Result<(u64, InodeType), i32> {
+        let entries = self.rdfs.list_dir(self.pointer(parent)).map_err(|error| errno(&error))?;
+        entries
+            .into_iter()
+            .find(|(entry, _, _)| self.rdfs.name_policy.matches(entry, name))
+            .map(|(_, pointer, kind)| (pointer, kind))
+            .ok_or(libc::ENOENT)
+    }
+
+    /// Pointer of the file `ino`, `EISDIR` for a directory.
+    fn file(&self, ino: u64) -> Result<u64, i32> {
+        let pointer = self.pointer(ino);
+        match self.kind(pointer)? {
+            InodeType::File => Ok(pointer),
+            InodeType::Dir => Err(libc::EISDIR),
+        }
+    }
+
+    fn kind(&self, pointer: u64) -> Result<InodeType, i32> {
+        let kinds = self.kinds.read().map_err(|_| libc::EIO)?;
+        kinds.get(&pointer).copied().ok_or(libc::ENOENT)
+    }
+
+    /// Reads the attributes of `pointer` and remembers its type for later callbacks.
+    fn attr(&self, pointer: u64, kind: InodeType) -> Result<FuseAttr, i32> {
+        let stat = self.rdfs.stat(pointer, kind).map_err(|error| errno(&error))?;
+        self.kinds.write().map_err(|_| libc::EIO)?.insert(pointer, kind);
+        Ok(FuseAttr {
+            ino: self.ino(pointer),
+            kind,
+            size: stat.size,
+            blocks: stat.total_blocks * self.rdfs.system.block_size / 512,
+            links: stat.links,
+            created: stat.created,
+            modify: stat.modify,
+            perm: match kind {
+                InodeType::Dir => 0o755,
+                InodeType::File => 0o644,
+            },
+        })
+    }
+}
+
+impl FuseAttr {
+    /// The attributes in the form of the `fuser` replies.
+    pub fn file_attr(&self) -> FileAttr {
+        let created = UNIX_EPOCH + Duration::from_millis(self.created);
+        let modify = UNIX_EPOCH + Duration::from_millis(self.modify);
+        FileAttr {
+            ino: self.ino,
+            size: self.size,
+            blocks: self.blocks,
+            atime: modify,
+            mtime: modify,
+            ctime: modify,
+            crtime: created,
+            kind: file_type(self.kind),
+            perm: self.perm,
+            nlink: u32::try_from(self.links).unwrap_or(u32::MAX),
+            uid: unsafe { libc::getuid() },
+            gid: unsafe { libc::getgid() },
+            rdev: 0,
+            blksize: 512,
+            flags: 0,
+        }
+    }
+}
+
+impl Filesystem for FuseAdapter {
+    fn lookup(&mut self, _req: &Request<'_>, parent: u64, name: &OsStr, reply: ReplyEntry) {
+        match name.to_str().ok_or(libc::EINVAL).and_then(|name| FuseAdapter::lookup(self, parent, name)) {
+            Ok(attr) => reply.entry(&TTL, &attr.file_attr(), 0),
+            Err(error) => reply.error(error),
+        }
+    }
+
+    fn getattr(&mut self, _req: &Request<'_>, ino: u64, _fh: Option<u64>, reply: ReplyAttr) {
+        match FuseAdapter::getattr(self, ino) {
+            Ok(attr) => reply.attr(&TTL, &attr.file_attr()),
+            Err(error) => reply.error(error),
+        }
+    }
+
+    fn mkdir(&mut self, _req: &Request<'_>, parent: u64, name: &OsStr, _mode: u32, _umask: u32, reply: ReplyEntry) {
+        match name.to_str().ok_or(libc::EINVAL).and_then(|name| FuseAdapter::mkdir(self, parent, name)) {
+            Ok(attr) => reply.entry(&TTL, &attr.file_attr(), 0),
+            Err(error) => reply.error(error),
+        }
+    }
+
+    fn unlink(&mut self, _req: &Request<'_>, parent: u64, name: &OsStr, reply: ReplyEmpty) {
+        match name.to_str().ok_or(libc::EINVAL).and_then(|name| FuseAdapter::unlink(self, parent, name)) {
+            Ok(()) => reply.ok(),
+            Err(error) => reply.error(error),
+        }
+    }
+
+    fn read(&mut self, _req: &Request<'_>, ino: u64, _fh: u64, offset: i64, size: u32, _flags: i32, _lock_owner: Option<u64>, reply: ReplyData) {
+        match u64::try_from(offset)
+            .map_err(|_| libc::EINVAL)
+            .and_then(|offset| FuseAdapter::read(self, ino, offset, size))
+        {
+            Ok(data) => reply.data(&data),
+            Err(error) => reply.error(error),
+        }
+    }
+
+    fn write(
+        &mut self,
+        _req: &Request<'_>,
+        ino: u64,
+        _fh: u64,
+        offset: i64,
+        data: &[u8],
+        _write_flags: u32,
+        _flags: i32,
+        _lock_owner: Option<u64>,
+        reply: ReplyWrite,
+    ) {
+        match u64::try_from(offset)
+            .map_err(|_| libc::EINVAL)
+            .and_then(|offset| FuseAdapter::write(self, ino, offset, data))
+        {
+            Ok(written) => reply.written(written),
+            Err(error) => reply.error(error),
+        }
+    }
+
+    /// `.` and `..` take the offsets 1 and 2, the entries of the directory follow.
+    fn readdir(&mut self, _req: &Request<'_>, ino: u64, _fh: u64, offset: i64, mut reply: ReplyDirectory) {
+        let offset = offset.max(0) as usize;
+        let entries = match FuseAdapter::readdir(self, ino, offset.saturating_sub(2)) {
+            Ok(entries) => entries,
+            Err(error) => return reply.error(error),
+        };
+        let dots = [(ino, InodeType::Dir, ".".to_string()), (ino, InodeType::Dir, "..".to_string())];
+        for (index, (ino, kind, name)) in dots.into_iter().skip(offset).chain(entries).enumerate() {
+            if reply.add(ino, (offset + index + 1) as i64, file_type(kind), name) {
+                break;
+            }
+        }
+        reply.ok();
+    }
+
+    fn create(&mut self, _req: &Request<'_>, parent: u64, name: &OsStr, _mode: u32, _umask: u32, _flags: i32, reply: ReplyCreate) {
+        match name.to_str().ok_or(libc::EINVAL).and_then(|name| FuseAdapter::create(self, parent, name)) {
+            Ok(attr) => reply.created(&TTL, &attr.file_attr(), 0, 0, 0),
+            Err(error) => reply.error(error),
+        }
+    }
+}
+
+fn file_type(kind: InodeType) -> FileType {
+    match kind {
+        InodeType::Dir => FileType::Directory,
+        InodeType::File => FileType::RegularFile,
+    }
+}
+
+/// The `errno` a failed `RDFS` call is replied with, `EIO` for anything unexpected.
+pub fn errno(error: &anyhow::Error) -> i32 {
+    match error.downcast_ref::<RDFSError>() {
+        Some(RDFSError::PathNotFound(_) | RDFSError::FileEntryNotFound(_) | RDFSError::DirEntryNotFound(_)) => libc::ENOENT,
+        Some(RDFSError::NotADirectory(_)) => libc::ENOTDIR,
+        Some(RDFSError::AlreadyExists(_)) => libc::EEXIST,
+        Some(RDFSError::NameTooLong) => libc::ENAMETOOLONG,
+        Some(RDFSError::InvalidName(_)) => libc::EINVAL,
+        Some(RDFSError::NotEnoughSpace) => libc::ENOSPC,
+        Some(RDFSError::FileTooLarge { .. }) => libc::EFBIG,
+        Some(RDFSError::OffsetOutOfRange { .. }) => libc::EINVAL,
+        Some(RDFSError::DriveLocked(_)) => libc::EROFS,
+        Some(RDFSError::NoBitmapsPrivateRDFS) => libc::ENOTSUP,
+        _ => libc::EIO,
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::core::super_block::FileSystemType;
+    use std::fs;
+
+    fn adapter(name: &str) -> FuseAdapter {
+        let dir = std::env::temp_dir().join(format!("rdfs_test_{name}"));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        FuseAdapter::new(RDFS::new(dir, FileSystemType::Shared, [255; 32], [1; 32], 1048576, 100, 1, 4096).unwrap())
+    }
+
+    #[test]
+    fn fuse_adapter_test() {
+        let fuse = adapter("fuse_adapter");
+        assert_eq!(fuse.getattr(FUSE_ROOT_ID).unwrap().kind, InodeType::Dir);
+        assert_eq!(fuse.lookup(FUSE_ROOT_ID, "missing.txt"), Err(libc::ENOENT));
+
+        let docs = fuse.mkdir(FUSE_ROOT_ID, "docs").unwrap();
+        let file = fuse.create(docs.ino, "a.txt").unwrap();
+        assert_eq!(fuse.create(docs.ino, "a.txt"), Err(libc::EEXIST));
+        assert_eq!(fuse.write(file.ino, 0, b"hello ").unwrap(), 6);
+        assert_eq!(fuse.write(file.ino, 6, b"fuse").unwrap(), 4);
+        assert_eq!(fuse.write(file.ino, 0, b"over"), Err(libc::ENOTSUP));
+
+        assert_eq!(fuse.read(file.ino, 0, 4096).unwrap(), b"hello fuse");
+        assert_eq!(fuse.read(file.ino, 6, 2).unwrap(), b"fu");
+        assert!(fuse.read(file.ino, 10, 4096).unwrap().is_empty());
+        assert_eq!(fuse.read(docs.ino, 0, 4096), Err(libc::EISDIR));
+        assert_eq!(fuse.getattr(file.ino).unwrap().size, 10);
+
+        // hard links show in `links`, blocks are counted in 512-byte units
+        let attr = fuse.getattr(file.ino).unwrap();
+        assert_eq!((attr.links, attr.blocks, attr.file_attr().nlink), (1, 8, 1));
+        fuse.rdfs.link(fuse.pointer(file.ino), fuse.pointer(docs.ino), "b.txt").unwrap();
+        assert_eq!(fuse.getattr(file.ino).unwrap().file_attr().nlink, 2);
+        fuse.unlink(docs.ino, "a.txt").unwrap();
+        assert_eq!(fuse.getattr(file.ino).unwrap().links, 1);
+
+        // a fresh adapter learns the inodes through lookup and readdir, like after a remount
+        let fuse = FuseAdapter::new(fuse.rdfs.clone());
+        assert_eq!(fuse.getattr(file.ino), Err(libc::ENOENT));
+        assert_eq!(
+            fuse.readdir(FUSE_ROOT_ID, 0).unwrap(),
+            vec![(docs.ino, InodeType::Dir, "docs".to_string())]
+        );
+        assert!(fuse.readdir(FUSE_ROOT_ID, 1).unwrap().is_empty());
+        assert_eq!(fuse.lookup(docs.ino, "a.txt"), Err(libc::ENOENT));
+        assert_eq!(fuse.lookup(docs.ino, "b.txt").unwrap().ino, file.ino);
+
+        assert_eq!(fuse.unlink(FUSE_ROOT_ID, "docs"), Err(libc::EISDIR));
+        fuse.unlink(docs.ino, "b.txt").unwrap();
+        assert_eq!(fuse.getattr(file.ino), Err(libc::ENOENT));
+        assert!(fuse.readdir(docs.ino, 0).unwrap().is_empty());
+    }
+
+    #[test]
+    fn fuse_mount_test() {
+        use std::os::unix::fs::MetadataExt;
+
+        let fuse = adapter("fuse_mount");
+        let docs = fuse.mkdir(FUSE_ROOT_ID, "docs").unwrap();
+        let file = fuse.create(docs.ino, "a.txt").unwrap();
+        fuse.write(file.ino, 0, b"hello mount").unwrap();
+
+        let mountpoint = std::env::temp_dir().join("rdfs_test_fuse_mount_point");
+        let _ = fs::remove_dir_all(&mountpoint);
+        fs::create_dir_all(&mountpoint).unwrap();
+        let session = fuse.mount(&mountpoint).expect("mounting needs /dev/fuse, and root or fusermount");
+
+        // ls
+        let names = fs::read_dir(&mountpoint)
+            .unwrap()
+            .map(|entry| entry.unwrap().file_name())
+            .collect::<Vec<_>>();
+        assert_eq!(names, vec!["docs"]);
+        assert!(fs::metadata(mountpoint.join("docs")).unwrap().is_dir());
+        let names = fs::read_dir(mountpoint.join("docs"))
+            .unwrap()
+            .map(|entry| entry.unwrap().file_name())
+            .collect::<Vec<_>>();
+        assert_eq!(names, vec!["a.txt"]);
+
+        // cat
+        assert_eq!(fs::read(mountpoint.join("docs/a.txt")).unwrap(), b"hello mount");
+        let metadata = fs::metadata(mountpoint.join("docs/a.txt")).unwrap();
+        assert_eq!((metadata.len(), metadata.nlink(), metadata.blocks()), (11, 1, 8));
+        assert!(fs::read(mountpoint.join("docs/missing.txt")).is_err());
+
+        // writes through the mount land on the drive
+        fs::write(mountpoint.join("b.txt"), b"written").unwrap();
+        assert_eq!(fs::read(mountpoint.join("b.txt")).unwrap(), b"written");
+
+        drop(session);
+        assert!(fs::read_dir(&mountpoint).unwrap().next().is_none());
+    }
+}
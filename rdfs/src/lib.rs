@@ -19,6 +19,8 @@ pub mod config;
 pub mod constants;
 pub mod core;
 pub mod file_system;
+#[cfg(all(feature = "fuse", target_os = "linux"))]
+pub mod fuse;
 pub mod prelude;
 pub mod rdfs_errors;
 pub mod utils;
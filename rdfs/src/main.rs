@@ -0,0 +1,268 @@
+//! # RDFS Command Line
+//!
+//! A thin `rdfs` binary over the library, so a drive can be created, inspected and filled
+//! without writing Rust. Every command maps to one high level API and doubles as an example
+//! of the intended workflow:
+//!
+//! ```text
+//! rdfs create --shared --storage 32G --nodes 50 --redundancy 300 data/
+//! rdfs info data/<program_id>.RDFS
+//! rdfs ls data/<program_id>.RDFS /docs
+//! rdfs put data/<program_id>.RDFS report.txt /docs/report.txt
+//! rdfs get data/<program_id>.RDFS /docs/report.txt out.txt
+//! ```
+//!
+//! Sizes accept human readable units (see `parse_bytes`). Commands that only read mount the
+//! drive with a shared lock, `put` mounts it exclusively; `put` and `get` keep the drive file
+//! open for every block (`RDFS::with_open_handle`). Errors are printed on stderr and the
+//! process exits with status 1.
+//!
+//! Copyrights © 2025 RDFS Contributors. All rights reserved.
+
+use anyhow::{Result, anyhow};
+use clap::{Parser, Subcommand};
+use rdfs::prelude::*;
+use std::fs::File;
+use std::io;
+use std::path::PathBuf;
+use std::process::ExitCode;
+
+#[derive(Debug, Parser)]
+#[command(name = "rdfs", version, about = "RaptorQ Distributed File System drives from the command line")]
+struct Cli {
+    #[command(subcommand)]
+    command: Command,
+}
+
+#[derive(Debug, Subcommand)]
+enum Command {
+    /// Creates a new drive file inside a directory and prints its path
+    Create {
+        /// Directory the drive file is created in
+        #[arg(default_value = ".")]
+        dir: PathBuf,
+        /// Shared drive with a directory tree (the default)
+        #[arg(long, conflicts_with = "private")]
+        shared: bool,
+        /// Private drive used as a raw block store
+        #[arg(long)]
+        private: bool,
+        /// Total storage over every node, e.g. 32G
+        #[arg(long, default_value = "1M", value_parser = parse_bytes)]
+        storage: u64,
+        #[arg(long, default_value_t = 1)]
+        nodes: u64,
+        /// Stored size in percent of the data, at least 100
+        #[arg(long, default_value_t = 100)]
+        redundancy: u64,
+        /// Power of two from 2K to 16M
+        #[arg(long, default_value = "4K", value_parser = parse_bytes)]
+        block_size: u64,
+        /// Owner public key as 64 hex digits
+        #[arg(long, value_parser = hex_to_address)]
+        owner: Option<Address>,
+        /// Program id as 64 hex digits, also names the drive file
+        #[arg(long, value_parser = hex_to_address)]
+        program_id: Option<Address>,
+        /// Name of the drive file instead of `<program_id as hex>.RDFS`
+        #[arg(long)]
+        file_name: Option<String>,
+        /// Replaces an existing drive file
+        #[arg(long)]
+        overwrite: bool,
+        /// Shared drive without a refcount table, so without reflinked copies nor snapshots
+        #[arg(long, conflicts_with = "private")]
+        no_refcounts: bool,
+    },
+    /// Prints the super block and the free space of a drive
+    Info { drive: PathBuf },
+    /// Lists a directory of a shared drive, a single file, or the entries matching a `*`/`?`
+    /// pattern in the last segment like `/docs/*.txt`
+    Ls {
+        drive: PathBuf,
+        #[arg(default_value = "/")]
+        path: String,
+    },
+    /// Copies a local file into a shared drive, its parent directory must exist
+    Put { drive: PathBuf, local: PathBuf, remote: String },
+    /// Copies a file of a shared drive to a local file
+    Get { drive: PathBuf, remote: String, local: PathBuf },
+}
+
+fn main() -> ExitCode {
+    match run(Cli::parse().command) {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(error) => {
+            eprintln!("error: {error:#}");
+            ExitCode::FAILURE
+        }
+    }
+}
+
+fn run(command: Command) -> Result<()> {
+    match command {
+        Command::Create {
+            dir,
+            private,
+            storage,
+            nodes,
+            redundancy,
+            block_size,
+            owner,
+            program_id,
+            file_name,
+            overwrite,
+            no_refcounts,
+            ..
+        } => {
+            let magic = if private { FileSystemType::Private } else { FileSystemType::Shared };
+            let mut builder = RDFS::builder()
+                .magic(magic)
+                .owner(owner.unwrap_or_default())
+                .program_id(program_id.unwrap_or_default())
+                .storage(storage)
+                .nodes(nodes)
+                .redundancy(redundancy)
+                .block_size(block_size)
+                .overwrite(overwrite)
+                .refcounted(!no_refcounts);
+            if let Some(file_name) = &file_name {
+                builder = builder.file_name(file_name);
+            }
+            let rdfs = builder.build(&dir)?;
+            println!("{}", rdfs.path.display());
+        }
+        Command::Info { drive } => {
+            let rdfs = RDFS::mount_drive_shared(&drive)?;
+            print!("{}", rdfs.system);
+            let stats = rdfs.stats()?;
+            if let (Some(free), Some(bytes_free), Some(fragmentation)) = (stats.free_blocks, stats.bytes_free, stats.fragmentation) {
+                println!(
+                    "free: {free} of {} blocks, {} of {}, {:.1}% fragmented",
+                    stats.total_blocks,
+                    format_bytes(bytes_free),
+                    format_bytes(rdfs.system.usable_capacity()),
+                    fragmentation * 100.0
+                );
+            }
+        }
+        Command::Ls { drive, path } => {
+            let rdfs = RDFS::mount_drive_shared(&drive)?;
+            let (dir, last) = path.rsplit_once('/').unwrap_or(("", &path));
+            let mut entries = if last.contains(['*', '?']) {
+                let (pointer, inode_type) = rdfs.resolve_path(dir)?;
+                if inode_type != InodeType::Dir {
+                    return Err(RDFSError::NotADirectory(dir.to_string()).into());
+                }
+                rdfs.glob(pointer, last)?
+            } else {
+                match rdfs.resolve_path(&path)? {
+                    (pointer, InodeType::Dir) => rdfs.list_dir(pointer)?,
+                    (pointer, inode_type) => vec![(last.to_string(), pointer, inode_type)],
+                }
+            };
+            entries.sort_by(|a, b| a.0.cmp(&b.0));
+            for (name, pointer, inode_type) in entries {
+                let stat = rdfs.stat(pointer, inode_type)?;
+                match inode_type {
+                    InodeType::Dir => println!("d {:>12} {name}/", format!("{} entries", stat.size)),
+                    InodeType::File => println!("- {:>12} {name}", format_bytes(stat.size)),
+                }
+            }
+        }
+        Command::Put { drive, local, remote } => {
+            let rdfs = RDFS::mount_drive_exclusive(&drive)?.with_open_handle()?;
+            let (parent, name) = match remote.rsplit_once('/') {
+                Some((parent, name)) if !name.is_empty() => (if parent.is_empty() { "/" } else { parent }, name),
+                _ => return Err(anyhow!("remote path {remote:?} must be absolute and name a file")),
+            };
+            let (parent_pointer, inode_type) = rdfs.resolve_path(parent)?;
+            if inode_type != InodeType::Dir {
+                return Err(RDFSError::NotADirectory(parent.to_string()).into());
+            }
+            let pointer = rdfs.write_file_streaming(parent_pointer, name, File::open(&local)?)?;
+            let size = rdfs.read_inode_file(pointer)?.size;
+            rdfs.unmount_drive()?;
+            println!("{} -> {remote} ({})", local.display(), format_bytes(size));
+        }
+        Command::Get { drive, remote, local } => {
+            let rdfs = RDFS::mount_drive_shared(&drive)?.with_open_handle()?;
+            let (pointer, inode_type) = rdfs.resolve_path(&remote)?;
+            if inode_type != InodeType::File {
+                return Err(anyhow!("not a file: {remote}"));
+            }
+            let copied = io::copy(&mut rdfs.open(pointer)?, &mut File::create(&local)?)?;
+            println!("{remote} -> {} ({})", local.display(), format_bytes(copied));
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use clap::CommandFactory;
+
+    #[test]
+    fn cli_test() {
+        Cli::command().debug_assert();
+
+        let cli = Cli::try_parse_from(["rdfs", "create", "--shared", "--storage", "32G", "--nodes", "50", "data"]).unwrap();
+        let Command::Create {
+            dir,
+            storage,
+            nodes,
+            block_size,
+            ..
+        } = cli.command
+        else {
+            panic!("expected create");
+        };
+        assert_eq!((dir, storage, nodes, block_size), (PathBuf::from("data"), 32 << 30, 50, 4096));
+        assert!(Cli::try_parse_from(["rdfs", "create", "--shared", "--private"]).is_err());
+        assert!(Cli::try_parse_from(["rdfs", "create", "--storage", "lots"]).is_err());
+
+        for (size, bytes) in [("4096", 4096), ("4K", 4096), ("1.5MiB", 1572864), ("32 GB", 32 << 30), ("2t", 2 << 40)] {
+            assert_eq!(parse_bytes(size).unwrap(), bytes);
+        }
+        for size in ["", "-1", "1.2.3", "12X", "1e30T"] {
+            let error = parse_bytes(size).unwrap_err();
+            assert!(matches!(error.downcast_ref::<RDFSError>(), Some(RDFSError::InvalidByteSize(s)) if s == size));
+        }
+    }
+
+    #[test]
+    fn put_get_test() {
+        let dir = std::env::temp_dir().join("rdfs_test_cli");
+        let _ = std::fs::remove_dir_all(&dir);
+        std::fs::create_dir_all(&dir).unwrap();
+        let parse = |args: &[&str]| Cli::try_parse_from(args).unwrap().command;
+
+        let dir_arg = dir.to_str().unwrap();
+        run(parse(&["rdfs", "create", "--file-name", "cli.RDFS", dir_arg])).unwrap();
+        let drive = dir.join("cli.RDFS");
+        let drive_arg = drive.to_str().unwrap();
+        run(parse(&["rdfs", "info", drive_arg])).unwrap();
+        assert!(RDFS::mount_drive(&drive).unwrap().system.has_refcounts());
+        run(parse(&["rdfs", "create", "--no-refcounts", "--file-name", "plain.RDFS", dir_arg])).unwrap();
+        assert!(!RDFS::mount_drive(dir.join("plain.RDFS")).unwrap().system.has_refcounts());
+        assert!(Cli::try_parse_from(["rdfs", "create", "--private", "--no-refcounts"]).is_err());
+
+        let data: Vec<u8> = (0..20_000).map(|byte| (byte % 241) as u8).collect();
+        let local = dir.join("local.bin");
+        std::fs::write(&local, &data).unwrap();
+        run(parse(&["rdfs", "put", drive_arg, local.to_str().unwrap(), "/remote.bin"])).unwrap();
+        run(parse(&["rdfs", "ls", drive_arg, "/"])).unwrap();
+        run(parse(&["rdfs", "ls", drive_arg, "/*.bin"])).unwrap();
+        assert!(run(parse(&["rdfs", "ls", drive_arg, "/remote.bin/*"])).is_err());
+
+        let out = dir.join("out.bin");
+        run(parse(&["rdfs", "get", drive_arg, "/remote.bin", out.to_str().unwrap()])).unwrap();
+        assert_eq!(std::fs::read(&out).unwrap(), data);
+
+        // errors come back to `main` instead of panicking
+        assert!(run(parse(&["rdfs", "get", drive_arg, "/missing.bin", out.to_str().unwrap()])).is_err());
+        assert!(run(parse(&["rdfs", "put", drive_arg, local.to_str().unwrap(), "/remote.bin/nested"])).is_err());
+        assert!(run(parse(&["rdfs", "create", "--file-name", "cli.RDFS", dir_arg])).is_err());
+    }
+}
@@ -6,8 +6,11 @@ pub use crate::core::addresses_block::*;
 pub use crate::core::bitmaps_block::*;
 pub use crate::core::block_signature::*;
 pub use crate::core::data_block::*;
+pub use crate::core::erasure_codec::*;
 pub use crate::core::inode_block::*;
 pub use crate::core::super_block::*;
 pub use crate::file_system::*;
+#[cfg(all(feature = "fuse", target_os = "linux"))]
+pub use crate::fuse::*;
 pub use crate::rdfs_errors::*;
-pub use crate::utils::*;
\ No newline at end of file
+pub use crate::utils::*;
@@ -11,7 +11,7 @@
 //!
 //! ## Example
 //! ```rust
-//! use rdfs::RDFSError;
+//! use rdfs::rdfs_errors::RDFSError;
 //!
 //! fn validate_magic_word(word: &[u8]) -> Result<(), RDFSError> {
 //!     if word != b"RDFS-SHR" && word != b"RDFS-PRV" {
@@ -34,42 +34,272 @@ use thiserror::Error;
 
 #[derive(Debug, Error)]
 pub enum RDFSError {
-    #[error("Invalid super block length")]
-    InvalidSuperBlockLength,
+    #[error("Invalid super block length {got}, expected {expected}")]
+    InvalidSuperBlockLength { got: usize, expected: usize },
 
     #[error("Invalid magic word")]
     InvalidMagicWord,
 
-    #[error("Input length not equal nodes address size")]
-    InvalidAddressBlockLength,
+    #[error("Input length {got} not equal nodes address size {expected}")]
+    InvalidAddressBlockLength { got: usize, expected: usize },
 
-    #[error("Encoded length not equal nodes address size")]
-    InvalidEncodedAddressBlockLength,
+    #[error("Encoded length {got} not equal nodes address size {expected}")]
+    InvalidEncodedAddressBlockLength { got: usize, expected: usize },
 
-    #[error("Input length not equal bitmaps size")]
-    InvalidBitmapsBlockLength,
+    #[error("Input length {got} not equal bitmaps size {expected}")]
+    InvalidBitmapsBlockLength { got: usize, expected: usize },
 
-    #[error("encoded length not equal bitmaps size")]
-    InvalidEncodedBitmapsBlockLength,
+    #[error("encoded length {got} not equal bitmaps size {expected}")]
+    InvalidEncodedBitmapsBlockLength { got: usize, expected: usize },
 
-    #[error("Input length not equal block size")]
-    InvalidDataBlockLength,
+    #[error("Input length {got} not equal block size {expected}")]
+    InvalidDataBlockLength { got: usize, expected: usize },
 
-    #[error("content length is greater than block size")]
-    InvalidEncodedDataBlockLength,
+    #[error("invalid content length {length}, data block payload is {max} bytes")]
+    InvalidEncodedDataBlockLength { length: usize, max: usize },
 
-    #[error("Input length not equal block size")]
-    InvalidInodeBlockLength,
+    #[error("Input length {got} not equal block size {expected}")]
+    InvalidInodeBlockLength { got: usize, expected: usize },
 
-    #[error("content length is greater than block size")]
-    InvalidEncodedInodeBlockLength,
+    #[error("content length {length} is greater than inode capacity {max}")]
+    InvalidEncodedInodeBlockLength { length: usize, max: usize },
 
     #[error("No bitmaps in private RDFS")]
     NoBitmapsPrivateRDFS,
 
-    #[error("No bitmaps in private RDFS")]
-    InvalidPointerAlignment,
+    #[error("pointer {pointer} is not aligned to a block boundary (offset {offset} modulo block size {block_size})")]
+    InvalidPointerAlignment { pointer: u64, offset: u64, block_size: u64 },
+
+    #[error("pointer {pointer} is outside the data blocks {data_pointer}..={last_block}")]
+    PointerOutOfRange { pointer: u64, data_pointer: u64, last_block: u64 },
+
+    #[error("not enough free blocks in RDFS")]
+    NotEnoughSpace,
+
+    #[error("no such file or directory: {0}")]
+    PathNotFound(String),
+
+    #[error("not a directory: {0}")]
+    NotADirectory(String),
+
+    #[error("data blocks length does not match file size")]
+    InvalidFileSize,
+
+    #[error("client data is greater than client block size")]
+    InvalidClientBlockLength,
+
+    #[error("block size is too large for RaptorQ symbols")]
+    InvalidRaptorQSymbolSize,
+
+    #[error("not enough blocks to decode client data")]
+    NotEnoughRaptorQBlocks,
+
+    #[error("no file entry for inode {0} in parent directory")]
+    FileEntryNotFound(u64),
+
+    #[error("file or directory already exists: {0}")]
+    AlreadyExists(String),
+
+    #[error("cannot move a directory inside itself: {0}")]
+    MoveIntoItself(String),
+
+    #[error("name is longer than 255 characters")]
+    NameTooLong,
+
+    #[error("inconsistent super block: {0}")]
+    InconsistentSuperBlock(String),
+
+    #[error("unsupported super block version {0}")]
+    UnsupportedSuperBlockVersion(u16),
+
+    #[error("malformed request")]
+    InvalidRequest,
+
+    #[error("node slot {0} is out of range")]
+    AddressIndexOutOfRange(usize),
+
+    #[error("node address not found")]
+    AddressNotFound,
+
+    #[error("node address already in use")]
+    DuplicateAddress,
+
+    #[error("no directory entry for inode {0} in parent directory")]
+    DirEntryNotFound(u64),
+
+    #[error("data block checksum mismatch")]
+    CorruptDataBlock,
+
+    #[error("cannot shrink drive from {total_blocks} to {requested} blocks")]
+    CannotShrinkDrive { total_blocks: u64, requested: u64 },
+
+    #[error("journaled write of {length} bytes at {pointer} is outside the drive layout")]
+    InvalidJournalWrite { pointer: u64, length: u64 },
+
+    #[error("linked inode chain loops back to block {0}")]
+    LinkedLoop(u64),
+
+    #[error("reference count of block {0} would exceed {max}", max = u16::MAX)]
+    RefCountOverflow(u64),
+
+    #[error("reference count of block {0} is already zero")]
+    RefCountUnderflow(u64),
+
+    #[error("drive has no refcount table")]
+    NoRefCountTable,
+
+    #[error("Input length {got} not equal refcount size {expected}")]
+    InvalidRefCountBlockLength { got: usize, expected: usize },
+
+    #[error("block index {0} is outside the refcount table")]
+    RefCountIndexOutOfRange(u64),
+
+    #[error("no snapshot at pointer {0}")]
+    SnapshotNotFound(u64),
+
+    #[error("invalid byte order mark {0:#06x}, RDFS drives are little endian")]
+    InvalidByteOrder(u16),
+
+    #[error("offset {offset} is past the end of the file ({size} bytes)")]
+    OffsetOutOfRange { offset: u64, size: u64 },
+
+    #[error("invalid key length: got {got}, expected {expected}")]
+    InvalidKeyLength { got: usize, expected: usize },
+
+    #[error("invalid hex string {0:?}")]
+    InvalidHex(String),
+
+    #[error("{storage} bytes of storage over {nodes} nodes leave no room for a single block")]
+    DriveTooSmall { storage: u64, nodes: u64 },
+
+    #[error("invalid inode type {0}, expected 0 (directory) or 1 (file)")]
+    InvalidInodeType(u64),
+
+    #[error("drive {0} is locked by another handle")]
+    DriveLocked(String),
+
+    #[error("file of {size} bytes is larger than the {max} bytes a file of this drive can hold")]
+    FileTooLarge { size: u64, max: u64 },
+
+    #[error("data block encryption failed: {0}")]
+    EncryptionFailed(String),
+
+    #[error("drive is encrypted, open it with an encryption key")]
+    EncryptionKeyRequired,
+
+    #[error("redundancy of {0}% is out of range, it should be at least 100% and fit in 32 bits")]
+    InvalidRedundancy(u64),
+
+    #[error("bytes from {pointer} are not held locally, only the first {available} bytes of the drive are")]
+    BlockNotAvailable { pointer: u64, available: u64 },
+
+    #[error("file content range of {blocks} blocks at {pointer} overlaps another range or lies outside the data blocks")]
+    OverlappingFileContent { pointer: u64, blocks: u64 },
+
+    #[error("block size {0} is invalid, it should be a power of two from 2KB to 16MB")]
+    InvalidBlockSize(u64),
+
+    #[error("invalid byte size {0:?}, expected a number with an optional unit like 4096, 32G or 1.5MiB")]
+    InvalidByteSize(String),
+
+    #[error("the drive is encoded with erasure codec {drive}, not codec {codec}")]
+    CodecMismatch { drive: u16, codec: u16 },
+
+    #[error("linked inode chain of {blocks} blocks was given {pointers} block pointers")]
+    LinkedChainMismatch { blocks: usize, pointers: usize },
+
+    #[error("file inode {0} already has the maximum number of links")]
+    TooManyLinks(u64),
+
+    #[error("invalid name {0:?}, names can't contain '/', a null or another control character")]
+    InvalidName(String),
+
+    #[error("super block checksum mismatch")]
+    CorruptSuperBlock,
+
+    #[error("the drive has no valid super block backup")]
+    NoSuperBlockBackup,
+
+    #[error("block {0} is already in use")]
+    BlockInUse(u64),
+
+    #[error("block {0} is free, there is nothing to move")]
+    BlockNotInUse(u64),
+
+    #[error("a transaction is already open on this handle")]
+    TransactionOpen,
+}
 
-    #[error("pointer is less or greater than actual data pointer")]
-    PointerOutOfRange,
+impl RDFSError {
+    /// Stable numeric code used to report the error to remote peers, `0` is reserved for success.
+    /// Codes follow the declaration order, new variants must only be appended.
+    pub fn code(&self) -> u16 {
+        match self {
+            Self::InvalidSuperBlockLength { .. } => 1,
+            Self::InvalidMagicWord => 2,
+            Self::InvalidAddressBlockLength { .. } => 3,
+            Self::InvalidEncodedAddressBlockLength { .. } => 4,
+            Self::InvalidBitmapsBlockLength { .. } => 5,
+            Self::InvalidEncodedBitmapsBlockLength { .. } => 6,
+            Self::InvalidDataBlockLength { .. } => 7,
+            Self::InvalidEncodedDataBlockLength { .. } => 8,
+            Self::InvalidInodeBlockLength { .. } => 9,
+            Self::InvalidEncodedInodeBlockLength { .. } => 10,
+            Self::NoBitmapsPrivateRDFS => 11,
+            Self::InvalidPointerAlignment { .. } => 12,
+            Self::PointerOutOfRange { .. } => 13,
+            Self::NotEnoughSpace => 14,
+            Self::PathNotFound(_) => 15,
+            Self::NotADirectory(_) => 16,
+            Self::InvalidFileSize => 17,
+            Self::InvalidClientBlockLength => 18,
+            Self::InvalidRaptorQSymbolSize => 19,
+            Self::NotEnoughRaptorQBlocks => 20,
+            Self::FileEntryNotFound(_) => 21,
+            Self::AlreadyExists(_) => 22,
+            Self::MoveIntoItself(_) => 23,
+            Self::NameTooLong => 24,
+            Self::InconsistentSuperBlock(_) => 25,
+            Self::UnsupportedSuperBlockVersion(_) => 26,
+            Self::InvalidRequest => 27,
+            Self::AddressIndexOutOfRange(_) => 28,
+            Self::AddressNotFound => 29,
+            Self::DuplicateAddress => 30,
+            Self::DirEntryNotFound(_) => 31,
+            Self::CorruptDataBlock => 32,
+            Self::CannotShrinkDrive { .. } => 33,
+            Self::InvalidJournalWrite { .. } => 34,
+            Self::LinkedLoop(_) => 35,
+            Self::RefCountOverflow(_) => 36,
+            Self::RefCountUnderflow(_) => 37,
+            Self::NoRefCountTable => 38,
+            Self::InvalidRefCountBlockLength { .. } => 39,
+            Self::RefCountIndexOutOfRange(_) => 40,
+            Self::SnapshotNotFound(_) => 41,
+            Self::InvalidByteOrder(_) => 42,
+            Self::OffsetOutOfRange { .. } => 43,
+            Self::InvalidKeyLength { .. } => 44,
+            Self::InvalidHex(_) => 45,
+            Self::DriveTooSmall { .. } => 46,
+            Self::InvalidInodeType(_) => 47,
+            Self::DriveLocked(_) => 48,
+            Self::FileTooLarge { .. } => 49,
+            Self::EncryptionFailed(_) => 50,
+            Self::EncryptionKeyRequired => 51,
+            Self::InvalidRedundancy(_) => 52,
+            Self::BlockNotAvailable { .. } => 53,
+            Self::OverlappingFileContent { .. } => 54,
+            Self::InvalidBlockSize(_) => 55,
+            Self::InvalidByteSize(_) => 56,
+            Self::CodecMismatch { .. } => 57,
+            Self::LinkedChainMismatch { .. } => 58,
+            Self::TooManyLinks(_) => 59,
+            Self::InvalidName(_) => 61,
+            Self::CorruptSuperBlock => 62,
+            Self::NoSuperBlockBackup => 63,
+            Self::BlockInUse(_) => 64,
+            Self::BlockNotInUse(_) => 65,
+            Self::TransactionOpen => 66,
+        }
+    }
 }
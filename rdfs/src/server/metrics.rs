@@ -0,0 +1,165 @@
+//! # RDFS Node Metrics
+//!
+//! Counters of a storage `Node`, kept with atomics so every connection thread records its
+//! requests without a lock. `NodeMetrics::snapshot` copies them into a plain
+//! `MetricsSnapshot`, which `to_prometheus` renders in the Prometheus text exposition
+//! format for whatever endpoint or exporter the operator runs.
+//!
+//! ## Metrics
+//! - `blocks_read` / `blocks_written`: successful `ReadBlock` / `WriteBlock` requests
+//! - `bytes_served`: payload bytes of every successful response
+//! - `read_errors`: `ReadBlock` requests answered with an error
+//! - `requests`: every request, failed ones included
+//! - request latency, from decoding the request to having the response, in the cumulative
+//!   buckets of `LATENCY_BUCKETS_MICROS`
+//!
+//! Copyrights © 2025 RDFS Contributors. All rights reserved.
+
+use std::fmt::Write;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::Duration;
+
+/// Upper bounds of the latency histogram buckets in microseconds, slower requests only
+/// count in the implicit `+Inf` bucket.
+pub const LATENCY_BUCKETS_MICROS: [u64; 9] = [100, 500, 1_000, 5_000, 10_000, 50_000, 100_000, 500_000, 1_000_000];
+
+/// What a request was, as far as the metrics are concerned.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RequestKind {
+    ReadBlock,
+    WriteBlock,
+    Other,
+}
+
+/// Live counters of a node, shared by every connection thread.
+#[derive(Debug, Default)]
+pub struct NodeMetrics {
+    blocks_read: AtomicU64,
+    blocks_written: AtomicU64,
+    bytes_served: AtomicU64,
+    read_errors: AtomicU64,
+    requests: AtomicU64,
+    latency_buckets: [AtomicU64; LATENCY_BUCKETS_MICROS.len() + 1], // the last one is `+Inf`
+    latency_sum_micros: AtomicU64,
+}
+
+/// Values of the `NodeMetrics` at one point in time. Each counter is read on its own, a
+/// request finishing meanwhile may be counted in some of them only.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct MetricsSnapshot {
+    pub blocks_read: u64,
+    pub blocks_written: u64,
+    pub bytes_served: u64,
+    pub read_errors: u64,
+    pub requests: u64,
+    pub latency_buckets: Vec<u64>, // cumulative, one per `LATENCY_BUCKETS_MICROS` bound then `+Inf`
+    pub latency_sum_micros: u64,
+}
+
+impl NodeMetrics {
+    /// Records one answered request: its kind, whether it succeeded, the bytes sent back
+    /// and how long it took.
+    pub fn record(&self, kind: RequestKind, ok: bool, bytes: usize, latency: Duration) {
+        self.requests.fetch_add(1, Ordering::Relaxed);
+        match (kind, ok) {
+            (RequestKind::ReadBlock, true) => self.blocks_read.fetch_add(1, Ordering::Relaxed),
+            (RequestKind::ReadBlock, false) => self.read_errors.fetch_add(1, Ordering::Relaxed),
+            (RequestKind::WriteBlock, true) => self.blocks_written.fetch_add(1, Ordering::Relaxed),
+            _ => 0,
+        };
+        if ok {
+            self.bytes_served.fetch_add(bytes as u64, Ordering::Relaxed);
+        }
+
+        let micros = latency.as_micros().min(u64::MAX as u128) as u64;
+        let bucket = LATENCY_BUCKETS_MICROS.partition_point(|bound| *bound < micros);
+        self.latency_buckets[bucket].fetch_add(1, Ordering::Relaxed);
+        self.latency_sum_micros.fetch_add(micros, Ordering::Relaxed);
+    }
+
+    /// Copies the current values of every counter.
+    pub fn snapshot(&self) -> MetricsSnapshot {
+        let mut total = 0;
+        let latency_buckets = self
+            .latency_buckets
+            .iter()
+            .map(|bucket| {
+                total += bucket.load(Ordering::Relaxed);
+                total
+            })
+            .collect();
+        MetricsSnapshot {
+            blocks_read: self.blocks_read.load(Ordering::Relaxed),
+            blocks_written: self.blocks_written.load(Ordering::Relaxed),
+            bytes_served: self.bytes_served.load(Ordering::Relaxed),
+            read_errors: self.read_errors.load(Ordering::Relaxed),
+            requests: self.requests.load(Ordering::Relaxed),
+            latency_buckets,
+            latency_sum_micros: self.latency_sum_micros.load(Ordering::Relaxed),
+        }
+    }
+}
+
+impl MetricsSnapshot {
+    /// Renders the snapshot in the Prometheus text exposition format, every metric named
+    /// `rdfs_node_*` and the latency in seconds.
+    pub fn to_prometheus(&self) -> String {
+        let mut text = String::new();
+        let counters = [
+            ("blocks_read", "Blocks served to ReadBlock requests.", self.blocks_read),
+            ("blocks_written", "Blocks stored by WriteBlock requests.", self.blocks_written),
+            ("bytes_served", "Payload bytes of successful responses.", self.bytes_served),
+            ("read_errors", "ReadBlock requests answered with an error.", self.read_errors),
+            ("requests", "Requests answered, failed ones included.", self.requests),
+        ];
+        for (name, help, value) in counters {
+            let _ = writeln!(text, "# HELP rdfs_node_{name}_total {help}");
+            let _ = writeln!(text, "# TYPE rdfs_node_{name}_total counter");
+            let _ = writeln!(text, "rdfs_node_{name}_total {value}");
+        }
+
+        let _ = writeln!(text, "# HELP rdfs_node_request_duration_seconds Time taken to answer a request.");
+        let _ = writeln!(text, "# TYPE rdfs_node_request_duration_seconds histogram");
+        for (bound, count) in LATENCY_BUCKETS_MICROS.iter().zip(&self.latency_buckets) {
+            let _ = writeln!(
+                text,
+                "rdfs_node_request_duration_seconds_bucket{{le=\"{}\"}} {count}",
+                *bound as f64 / 1e6
+            );
+        }
+        let count = self.latency_buckets.last().copied().unwrap_or(0);
+        let _ = writeln!(text, "rdfs_node_request_duration_seconds_bucket{{le=\"+Inf\"}} {count}");
+        let _ = writeln!(text, "rdfs_node_request_duration_seconds_sum {}", self.latency_sum_micros as f64 / 1e6);
+        let _ = writeln!(text, "rdfs_node_request_duration_seconds_count {count}");
+        text
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn metrics_test() {
+        let metrics = NodeMetrics::default();
+        metrics.record(RequestKind::ReadBlock, true, 4096, Duration::from_micros(80));
+        metrics.record(RequestKind::ReadBlock, false, 40, Duration::from_micros(100));
+        metrics.record(RequestKind::WriteBlock, true, 0, Duration::from_millis(3));
+        metrics.record(RequestKind::Other, true, 294, Duration::from_secs(2));
+
+        let snapshot = metrics.snapshot();
+        assert_eq!(
+            (snapshot.blocks_read, snapshot.blocks_written, snapshot.read_errors, snapshot.requests),
+            (1, 1, 1, 4)
+        );
+        assert_eq!(snapshot.bytes_served, 4096 + 294);
+        assert_eq!(snapshot.latency_buckets, vec![2, 2, 2, 3, 3, 3, 3, 3, 3, 4]);
+        assert_eq!(snapshot.latency_sum_micros, 2_003_180);
+
+        let text = snapshot.to_prometheus();
+        assert!(text.contains("rdfs_node_blocks_read_total 1\n"));
+        assert!(text.contains("rdfs_node_request_duration_seconds_bucket{le=\"0.005\"} 3\n"));
+        assert!(text.contains("rdfs_node_request_duration_seconds_bucket{le=\"+Inf\"} 4\n"));
+        assert!(text.contains("rdfs_node_request_duration_seconds_count 4\n"));
+    }
+}
@@ -1 +1,14 @@
+//! # RDFS Server Module
+//!
+//! A storage node serving the blocks of a mounted RDFS drive over TCP, so clients can
+//! fetch the chunks of a file from different nodes in parallel.
+//!
+//! - [`protocol`]: length-prefixed request/response framing shared with the client
+//! - [`node`]: the `Node` accepting connections and answering requests
+//! - [`metrics`]: lock-free counters of the requests a `Node` answered
+//!
+//! Copyrights © 2025 RDFS Contributors. All rights reserved.
 
+pub mod metrics;
+pub mod node;
+pub mod protocol;
@@ -0,0 +1,204 @@
+//! # RDFS Storage Node
+//!
+//! A `Node` owns a mounted RDFS drive and answers the requests of the [`protocol`]
+//! module over TCP. Every connection is served on its own thread and may carry any
+//! number of requests, each answered in order. A drive mounted by `Node::mount` keeps its
+//! file open (`RDFS::with_open_handle`) and every thread reads and writes its blocks with
+//! positioned I/O on that one handle, so parallel chunk requests don't wait on each other.
+//! Every answered request is counted in `Node::metrics`, see `metrics_snapshot`.
+//!
+//! [`protocol`]: super::protocol
+//!
+//! Copyrights © 2025 RDFS Contributors. All rights reserved.
+
+use super::super::file_system::RDFS;
+use super::super::rdfs_errors::RDFSError;
+use super::metrics::{MetricsSnapshot, NodeMetrics, RequestKind};
+use super::protocol::{Request, Response, read_frame, write_frame};
+use anyhow::Result;
+use std::net::{SocketAddr, TcpListener, TcpStream, ToSocketAddrs};
+use std::path::Path;
+use std::sync::Arc;
+use std::thread;
+use std::time::Instant;
+
+#[derive(Debug)]
+pub struct Node {
+    pub rdfs: RDFS,
+    pub metrics: Arc<NodeMetrics>, // requests answered by every connection of this node
+    listener: TcpListener,
+}
+
+impl Node {
+    /// Serves the already mounted `rdfs` drive on `address`.
+    pub fn new<A: ToSocketAddrs>(rdfs: RDFS, address: A) -> Result<Self> {
+        let listener = TcpListener::bind(address)?;
+        Ok(Self {
+            rdfs,
+            metrics: Arc::default(),
+            listener,
+        })
+    }
+
+    /// Mounts the drive at `path` with an open handle and serves it on `address`.
+    pub fn mount<P: AsRef<Path>, A: ToSocketAddrs>(path: P, address: A) -> Result<Self> {
+        Self::new(RDFS::mount_drive(path)?.with_open_handle()?, address)
+    }
+
+    pub fn local_addr(&self) -> Result<SocketAddr> {
+        Ok(self.listener.local_addr()?)
+    }
+
+    /// Current values of the request counters, render them with `MetricsSnapshot::to_prometheus`.
+    pub fn metrics_snapshot(&self) -> MetricsSnapshot {
+        self.metrics.snapshot()
+    }
+
+    /// Accepts connections forever, serving each one on its own thread.
+    pub fn serve(&self) -> Result<()> {
+        for stream in self.listener.incoming() {
+            let stream = stream?;
+            let rdfs = self.rdfs.clone();
+            let metrics = self.metrics.clone();
+            thread::spawn(move || Self::serve_connection(&rdfs, &metrics, stream));
+        }
+        Ok(())
+    }
+
+    /// Answers requests on `stream` until the peer disconnects, recording each in `metrics`.
+    pub fn serve_connection(rdfs: &RDFS, metrics: &NodeMetrics, mut stream: TcpStream) -> Result<()> {
+        while let Some(body) = read_frame(&mut stream)? {
+            let started = Instant::now();
+            let (kind, response) = match Request::from_bytes(&body) {
+                Ok(request) => {
+                    let kind = match request {
+                        Request::ReadBlock { .. } => RequestKind::ReadBlock,
+                        Request::WriteBlock { .. } => RequestKind::WriteBlock,
+                        _ => RequestKind::Other,
+                    };
+                    (kind, Self::handle(rdfs, request))
+                }
+                Err(error) => (RequestKind::Other, Response::error(&error)),
+            };
+            metrics.record(kind, response.is_ok(), response.data.len(), started.elapsed());
+            write_frame(&mut stream, &response.to_bytes())?;
+        }
+        Ok(())
+    }
+
+    /// Executes one request against `rdfs`, turning any failure into an error response.
+    pub fn handle(rdfs: &RDFS, request: Request) -> Response {
+        let result = match request {
+            Request::ReadBlock { pointer } => rdfs.read_block(pointer),
+            Request::WriteBlock { pointer, data } => {
+                // a longer payload would silently overwrite the following blocks
+                if data.len() as u64 != rdfs.system.block_size {
+                    Err(RDFSError::InvalidDataBlockLength {
+                        got: data.len(),
+                        expected: rdfs.system.block_size as usize,
+                    }
+                    .into())
+                } else {
+                    rdfs.write_block(pointer, &data).map(|()| Vec::new())
+                }
+            }
+            Request::ReadSuperBlock => Ok(rdfs.read_super_block()),
+            Request::ReadBitmaps => rdfs.read_bitmaps(),
+        };
+
+        match result {
+            Ok(data) => Response::ok(data),
+            Err(error) => Response::error(&error),
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::core::data_block::DataBlock;
+    use crate::core::super_block::FileSystemType;
+    use std::fs;
+
+    fn request(stream: &mut TcpStream, request: Request) -> Response {
+        write_frame(stream, &request.to_bytes()).unwrap();
+        Response::from_bytes(&read_frame(stream).unwrap().unwrap()).unwrap()
+    }
+
+    #[test]
+    fn node_test() {
+        let dir = std::env::temp_dir().join("rdfs_test_node");
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        let rdfs = RDFS::new(dir, FileSystemType::Shared, [255; 32], [1; 32], 1048576, 100, 1, 4096).unwrap();
+        let pointer = rdfs.create_file(rdfs.system.inode_pointer, "served.txt", b"over the wire").unwrap();
+        let data_pointer = rdfs.read_inode_file(pointer).unwrap().content[0].pointer;
+
+        let node = Node::mount(&rdfs.path, "127.0.0.1:0").unwrap();
+        let address = node.local_addr().unwrap();
+        let metrics = node.metrics.clone();
+        assert_eq!(node.metrics_snapshot().requests, 0);
+        thread::spawn(move || node.serve());
+        let mut stream = TcpStream::connect(address).unwrap();
+
+        let response = request(&mut stream, Request::ReadSuperBlock);
+        assert!(response.is_ok());
+        assert_eq!(response.data, rdfs.read_super_block());
+
+        let response = request(&mut stream, Request::ReadBitmaps);
+        assert_eq!(response.data, rdfs.read_bitmaps().unwrap());
+
+        let response = request(&mut stream, Request::ReadBlock { pointer: data_pointer });
+        let block = DataBlock::from_bytes(&response.data, 4096).unwrap();
+        assert_eq!(block.data, b"over the wire");
+
+        let written = DataBlock::new(0, 0, b"replaced").to_bytes(4096);
+        let response = request(
+            &mut stream,
+            Request::WriteBlock {
+                pointer: data_pointer,
+                data: written.clone(),
+            },
+        );
+        assert!(response.is_ok());
+        assert_eq!(rdfs.read_block(data_pointer).unwrap(), written);
+
+        let response = request(&mut stream, Request::ReadBlock { pointer: data_pointer + 1 });
+        let misaligned = RDFSError::InvalidPointerAlignment {
+            pointer: data_pointer + 1,
+            offset: 1,
+            block_size: 4096,
+        };
+        assert_eq!(response.status, misaligned.code());
+        assert_eq!(String::from_utf8(response.data).unwrap(), misaligned.to_string());
+        let response = request(&mut stream, Request::ReadBlock { pointer: 0 });
+        assert_eq!(
+            response.status,
+            RDFSError::PointerOutOfRange {
+                pointer: 0,
+                data_pointer,
+                last_block: rdfs.system.inode_pointer,
+            }
+            .code()
+        );
+        let response = request(
+            &mut stream,
+            Request::WriteBlock {
+                pointer: data_pointer,
+                data: vec![0; 4097],
+            },
+        );
+        assert_eq!(response.status, RDFSError::InvalidDataBlockLength { got: 4097, expected: 4096 }.code());
+
+        // every answered request was counted before its response went out
+        let snapshot = metrics.snapshot();
+        assert_eq!(
+            (snapshot.blocks_read, snapshot.blocks_written, snapshot.read_errors, snapshot.requests),
+            (1, 1, 2, 7)
+        );
+        let super_block = rdfs.read_super_block().len() as u64;
+        let bitmaps = rdfs.read_bitmaps().unwrap().len() as u64;
+        assert_eq!(snapshot.bytes_served, super_block + bitmaps + 4096);
+        assert_eq!(snapshot.latency_buckets.last(), Some(&7));
+    }
+}
@@ -0,0 +1,176 @@
+//! # RDFS Wire Protocol
+//!
+//! Every message is a frame made of a 4-byte little endian length followed by the body.
+//! Frames larger than `MAX_FRAME_SIZE` are rejected before anything is allocated.
+//!
+//! ## Request Body
+//! ```text
+//! [1 byte: op]  0 = ReadBlock, 1 = WriteBlock, 2 = ReadSuperBlock, 3 = ReadBitmaps
+//! [8 bytes: pointer]           ReadBlock, WriteBlock only
+//! [N bytes: block]             WriteBlock only
+//! ```
+//!
+//! ## Response Body
+//! ```text
+//! [2 bytes: status]  0 = Ok, otherwise `RDFSError::code` or `STATUS_UNKNOWN_ERROR`
+//! [N bytes: data]    block bytes on success, UTF-8 error message on failure
+//! ```
+//!
+//! Copyrights © 2025 RDFS Contributors. All rights reserved.
+
+use super::super::rdfs_errors::RDFSError;
+use anyhow::{Result, anyhow};
+use std::io::{ErrorKind, Read, Write};
+
+pub const MAX_FRAME_SIZE: usize = 16 * 1024 * 1024;
+pub const STATUS_OK: u16 = 0;
+pub const STATUS_UNKNOWN_ERROR: u16 = u16::MAX; // I/O or any error that is not a `RDFSError`
+
+const OP_READ_BLOCK: u8 = 0;
+const OP_WRITE_BLOCK: u8 = 1;
+const OP_READ_SUPER_BLOCK: u8 = 2;
+const OP_READ_BITMAPS: u8 = 3;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Request {
+    ReadBlock { pointer: u64 },
+    WriteBlock { pointer: u64, data: Vec<u8> },
+    ReadSuperBlock,
+    ReadBitmaps,
+}
+
+impl Request {
+    pub fn to_bytes(&self) -> Vec<u8> {
+        match self {
+            Request::ReadBlock { pointer } => [&[OP_READ_BLOCK][..], &pointer.to_le_bytes()].concat(),
+            Request::WriteBlock { pointer, data } => [&[OP_WRITE_BLOCK][..], &pointer.to_le_bytes(), data].concat(),
+            Request::ReadSuperBlock => vec![OP_READ_SUPER_BLOCK],
+            Request::ReadBitmaps => vec![OP_READ_BITMAPS],
+        }
+    }
+
+    pub fn from_bytes(data: &[u8]) -> Result<Self> {
+        let pointer = || -> Result<u64> {
+            let bytes = data.get(1..9).ok_or(RDFSError::InvalidRequest)?;
+            Ok(u64::from_le_bytes(bytes.try_into().unwrap()))
+        };
+
+        match data.first() {
+            Some(&OP_READ_BLOCK) if data.len() == 9 => Ok(Request::ReadBlock { pointer: pointer()? }),
+            Some(&OP_WRITE_BLOCK) => Ok(Request::WriteBlock {
+                pointer: pointer()?,
+                data: data[9..].to_vec(),
+            }),
+            Some(&OP_READ_SUPER_BLOCK) if data.len() == 1 => Ok(Request::ReadSuperBlock),
+            Some(&OP_READ_BITMAPS) if data.len() == 1 => Ok(Request::ReadBitmaps),
+            _ => Err(RDFSError::InvalidRequest.into()),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Response {
+    pub status: u16,
+    pub data: Vec<u8>,
+}
+
+impl Response {
+    pub fn ok(data: Vec<u8>) -> Self {
+        Self { status: STATUS_OK, data }
+    }
+
+    /// Maps `error` to its `RDFSError::code`, keeping the message for diagnostics.
+    pub fn error(error: &anyhow::Error) -> Self {
+        let status = error.downcast_ref::<RDFSError>().map_or(STATUS_UNKNOWN_ERROR, RDFSError::code);
+        Self {
+            status,
+            data: error.to_string().into_bytes(),
+        }
+    }
+
+    pub fn is_ok(&self) -> bool {
+        self.status == STATUS_OK
+    }
+
+    pub fn to_bytes(&self) -> Vec<u8> {
+        [&self.status.to_le_bytes()[..], &self.data].concat()
+    }
+
+    pub fn from_bytes(data: &[u8]) -> Result<Self> {
+        if data.len() < 2 {
+            return Err(anyhow!("Response is shorter than its status"));
+        }
+        Ok(Self {
+            status: u16::from_le_bytes(data[..2].try_into().unwrap()),
+            data: data[2..].to_vec(),
+        })
+    }
+}
+
+/// Writes `body` as one length-prefixed frame.
+pub fn write_frame(stream: &mut impl Write, body: &[u8]) -> Result<()> {
+    if body.len() > MAX_FRAME_SIZE {
+        return Err(anyhow!("Frame of {} bytes exceeds the maximum frame size", body.len()));
+    }
+    stream.write_all(&(body.len() as u32).to_le_bytes())?;
+    stream.write_all(body)?;
+    stream.flush()?;
+    Ok(())
+}
+
+/// Reads one length-prefixed frame, returning `None` if the peer closed the connection between frames.
+pub fn read_frame(stream: &mut impl Read) -> Result<Option<Vec<u8>>> {
+    let mut length = [0; 4];
+    match stream.read_exact(&mut length) {
+        Ok(()) => {}
+        Err(error) if error.kind() == ErrorKind::UnexpectedEof => return Ok(None),
+        Err(error) => return Err(error.into()),
+    }
+
+    let length = u32::from_le_bytes(length) as usize;
+    if length > MAX_FRAME_SIZE {
+        return Err(anyhow!("Frame of {length} bytes exceeds the maximum frame size"));
+    }
+    let mut body = vec![0; length];
+    stream.read_exact(&mut body)?;
+    Ok(Some(body))
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn request_serialize_test() {
+        let requests = [
+            Request::ReadBlock { pointer: 4096 },
+            Request::WriteBlock {
+                pointer: 8192,
+                data: vec![7; 100],
+            },
+            Request::ReadSuperBlock,
+            Request::ReadBitmaps,
+        ];
+        for request in requests {
+            assert_eq!(Request::from_bytes(&request.to_bytes()).unwrap(), request);
+        }
+        assert!(Request::from_bytes(&[]).is_err());
+        assert!(Request::from_bytes(&[OP_READ_BLOCK, 1, 2]).is_err());
+        assert!(Request::from_bytes(&[9]).is_err());
+    }
+
+    #[test]
+    fn frame_test() {
+        let mut buffer = Vec::new();
+        write_frame(&mut buffer, b"first").unwrap();
+        write_frame(&mut buffer, &[]).unwrap();
+
+        let mut reader = &buffer[..];
+        assert_eq!(read_frame(&mut reader).unwrap().unwrap(), b"first");
+        assert_eq!(read_frame(&mut reader).unwrap().unwrap(), b"");
+        assert!(read_frame(&mut reader).unwrap().is_none());
+
+        let oversized = ((MAX_FRAME_SIZE + 1) as u32).to_le_bytes();
+        assert!(read_frame(&mut &oversized[..]).is_err());
+    }
+}
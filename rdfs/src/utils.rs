@@ -1,7 +1,16 @@
+use crate::constants::Address;
+use crate::core::inode_block::DirContent;
+use crate::rdfs_errors::RDFSError;
 use anyhow::{Result, anyhow};
+use fs2::FileExt;
+use memmap2::{Mmap, MmapMut};
+use std::cell::RefCell;
+use std::collections::HashMap;
 use std::fs::{File, OpenOptions};
-use std::io::{Read, Seek, SeekFrom, Write};
+use std::io::{Seek, SeekFrom, Write};
 use std::path::Path;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, PoisonError, RwLock};
 use std::time::{SystemTime, UNIX_EPOCH};
 
 /// Convert array slice to hex string
@@ -9,6 +18,64 @@ pub fn bytes_to_hex(bytes: &[u8]) -> String {
     bytes.iter().map(|b| format!("{b:02x}")).collect()
 }
 
+/// Inverse of `bytes_to_hex`, accepts lower and upper case digits
+pub fn hex_to_bytes(s: &str) -> Result<Vec<u8>> {
+    let invalid = || RDFSError::InvalidHex(s.to_string());
+    if !s.len().is_multiple_of(2) {
+        return Err(invalid().into());
+    }
+
+    let digit = |c: u8| (c as char).to_digit(16).map(|d| d as u8).ok_or_else(invalid);
+    s.as_bytes()
+        .chunks_exact(2)
+        .map(|pair| Ok(digit(pair[0])? << 4 | digit(pair[1])?))
+        .collect()
+}
+
+/// Parse a 64 digit hex string, e.g. a drive file stem, back to its `Address`
+pub fn hex_to_address(s: &str) -> Result<Address> {
+    let bytes = hex_to_bytes(s)?;
+    Address::try_from(bytes.as_slice()).map_err(|_| RDFSError::InvalidHex(s.to_string()).into())
+}
+
+/// Human readable byte count using binary units, e.g. `1.50 MiB`
+pub fn format_bytes(bytes: u64) -> String {
+    const UNITS: [&str; 5] = ["B", "KiB", "MiB", "GiB", "TiB"];
+    let mut value = bytes as f64;
+    let mut unit = 0;
+    while value >= 1024.0 && unit < UNITS.len() - 1 {
+        value /= 1024.0;
+        unit += 1;
+    }
+    match unit {
+        0 => format!("{bytes} B"),
+        _ => format!("{value:.2} {}", UNITS[unit]),
+    }
+}
+
+/// Parse a human readable byte size back to bytes, the inverse of `format_bytes`. Units are
+/// binary whatever their spelling, `32G`, `32GB` and `32GiB` are all 32 * 1024^3 bytes, and a
+/// bare number is a count of bytes. Fractions are rounded down to a whole byte.
+pub fn parse_bytes(s: &str) -> Result<u64> {
+    let invalid = || RDFSError::InvalidByteSize(s.to_string());
+    let trimmed = s.trim();
+    let split = trimmed.find(|c: char| !c.is_ascii_digit() && c != '.').unwrap_or(trimmed.len());
+    let (number, unit) = trimmed.split_at(split);
+    let power = match unit.trim().to_ascii_uppercase().as_str() {
+        "" | "B" => 0,
+        "K" | "KB" | "KIB" => 1,
+        "M" | "MB" | "MIB" => 2,
+        "G" | "GB" | "GIB" => 3,
+        "T" | "TB" | "TIB" => 4,
+        _ => return Err(invalid().into()),
+    };
+    let bytes = number.parse::<f64>().map_err(|_| invalid())? * 1024f64.powi(power);
+    if !bytes.is_finite() || bytes >= u64::MAX as f64 {
+        return Err(invalid().into());
+    }
+    Ok(bytes as u64)
+}
+
 /// Create a file with given byte size
 pub fn create_physical_file<P: AsRef<Path>>(path: P, size: u64) -> Result<()> {
     let mut file = OpenOptions::new()
@@ -27,20 +94,42 @@ pub fn create_physical_file<P: AsRef<Path>>(path: P, size: u64) -> Result<()> {
     Ok(())
 }
 
+/// Same as `create_physical_file`, but the `size` bytes are reserved on disk with
+/// `posix_fallocate` so writing blocks later can't fail for lack of space. Falls back to
+/// the possibly sparse `create_physical_file` outside Linux or when the file system
+/// does not support preallocation.
+pub fn create_physical_file_reserved<P: AsRef<Path>>(path: P, size: u64) -> Result<()> {
+    #[cfg(target_os = "linux")]
+    {
+        use std::os::fd::AsRawFd;
+
+        let file = OpenOptions::new().create(true).truncate(false).write(true).open(&path)?;
+        if size == 0 {
+            return Ok(());
+        }
+        // returns the error number instead of setting `errno`
+        let length = libc::off_t::try_from(size)?;
+        match unsafe { libc::posix_fallocate(file.as_raw_fd(), 0, length) } {
+            0 => Ok(()),
+            libc::EOPNOTSUPP => create_physical_file(path, size),
+            error => Err(std::io::Error::from_raw_os_error(error).into()),
+        }
+    }
+    #[cfg(not(target_os = "linux"))]
+    create_physical_file(path, size)
+}
+
 /// Reads a specific range of bytes from a file.
 /// The range is defined by the start and end byte positions.
 pub fn read_range<P: AsRef<Path>>(path: P, start: u64, end: u64) -> Result<Vec<u8>> {
-    let mut file = File::open(path)?;
-
-    // Move the file cursor to the start byte
-    file.seek(SeekFrom::Start(start))?;
+    let file = File::open(path)?;
 
     // Calculate how many bytes to read
     let length = end - start;
     let mut buffer = vec![0u8; length as usize];
 
-    // Read exactly that range
-    file.read_exact(&mut buffer)?;
+    // Read exactly that range, positioned so no file cursor is involved
+    read_exact_at(&file, &mut buffer, start)?;
 
     Ok(buffer)
 }
@@ -49,22 +138,363 @@ pub fn read_range<P: AsRef<Path>>(path: P, start: u64, end: u64) -> Result<Vec<u
 /// The `data` length determines how many bytes are written.
 /// If the file is too short, it will be extended.
 pub fn write_range<P: AsRef<Path>>(path: P, start: u64, data: &[u8]) -> Result<()> {
-    let mut file = OpenOptions::new().write(true).open(path)?;
+    let file = OpenOptions::new().write(true).open(path)?;
 
-    // Move the file cursor to the start position
-    file.seek(SeekFrom::Start(start))?;
+    // Write the provided data, positioned so no file cursor is involved
+    write_all_at(&file, data, start)?;
 
-    // Write the provided data
-    file.write_all(data)?;
+    Ok(())
+}
 
+/// Flushes the content of the file at `path` to the disk.
+pub fn sync_file<P: AsRef<Path>>(path: P) -> Result<()> {
+    OpenOptions::new().write(true).open(path)?.sync_data()?;
     Ok(())
 }
 
-/// Returns the current time as a u64 timestamp in seconds since the UNIX epoch.
-pub fn current_time_as_u64() -> Result<u64> {
-    if let Ok(time) = SystemTime::now().duration_since(UNIX_EPOCH) {
-        return Ok(time.as_secs());
+/// Async version of `read_range` using `tokio::fs`, available with the `async` feature.
+#[cfg(feature = "async")]
+pub async fn read_range_async<P: AsRef<Path>>(path: P, start: u64, end: u64) -> Result<Vec<u8>> {
+    use tokio::io::{AsyncReadExt, AsyncSeekExt};
+
+    let mut file = tokio::fs::File::open(path).await?;
+    file.seek(SeekFrom::Start(start)).await?;
+    let mut buffer = vec![0u8; (end - start) as usize];
+    file.read_exact(&mut buffer).await?;
+
+    Ok(buffer)
+}
+
+/// Async version of `write_range` using `tokio::fs`, available with the `async` feature.
+#[cfg(feature = "async")]
+pub async fn write_range_async<P: AsRef<Path>>(path: P, start: u64, data: &[u8]) -> Result<()> {
+    use tokio::io::{AsyncSeekExt, AsyncWriteExt};
+
+    let mut file = tokio::fs::OpenOptions::new().write(true).open(path).await?;
+    file.seek(SeekFrom::Start(start)).await?;
+    file.write_all(data).await?;
+    file.flush().await?;
+
+    Ok(())
+}
+
+/// Reads a specific range of bytes through a read-only memory map of the file.
+/// The range is defined by the start and end byte positions.
+pub fn mmap_read_range<P: AsRef<Path>>(path: P, start: u64, end: u64) -> Result<Vec<u8>> {
+    let file = File::open(path)?;
+    // SAFETY: the mapping is dropped before returning and only copied out of.
+    let mmap = unsafe { Mmap::map(&file)? };
+    if end > mmap.len() as u64 || start > end {
+        return Err(anyhow!("Range {start}..{end} is outside the file"));
     }
-    Err(anyhow!("Time went backwards"))
+    Ok(mmap[start as usize..end as usize].to_vec())
 }
 
+/// A drive file mapped once into memory, so streaming thousands of blocks
+/// doesn't pay an `open` and a `seek` for each one of them.
+/// Writes land in the page cache and reach the disk on `flush()`.
+#[derive(Debug)]
+pub struct MappedDrive {
+    file: File,
+    mmap: MmapMut,
+}
+
+impl MappedDrive {
+    /// Maps the whole file at `path` for reading and writing.
+    pub fn open<P: AsRef<Path>>(path: P) -> Result<Self> {
+        let file = OpenOptions::new().read(true).write(true).open(path)?;
+        // SAFETY: RDFS drives are expected to be accessed by a single process,
+        // a file truncated behind our back is detected through `remap`.
+        let mmap = unsafe { MmapMut::map_mut(&file)? };
+        Ok(Self { file, mmap })
+    }
+
+    /// Length of the mapped region in bytes.
+    pub fn len(&self) -> u64 {
+        self.mmap.len() as u64
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.mmap.is_empty()
+    }
+
+    /// Returns `true` if the file on disk no longer matches the mapped length.
+    pub fn is_stale(&self) -> Result<bool> {
+        Ok(self.file.metadata()?.len() != self.len())
+    }
+
+    /// Flushes pending writes and maps the file again, picking up any change of its size.
+    pub fn remap(&mut self) -> Result<()> {
+        self.mmap.flush()?;
+        // SAFETY: same as in `open`, the previous mapping is replaced and dropped.
+        self.mmap = unsafe { MmapMut::map_mut(&self.file)? };
+        Ok(())
+    }
+
+    /// Copies the bytes in `start..end` out of the mapping.
+    pub fn read_range(&self, start: u64, end: u64) -> Result<Vec<u8>> {
+        if end > self.len() || start > end {
+            return Err(anyhow!("Range {start}..{end} is outside the mapped drive"));
+        }
+        Ok(self.mmap[start as usize..end as usize].to_vec())
+    }
+
+    /// Copies `data` into the mapping starting at `start`.
+    /// Unlike `write_range` the file is never extended.
+    pub fn write_range(&mut self, start: u64, data: &[u8]) -> Result<()> {
+        let end = start + data.len() as u64;
+        if end > self.len() {
+            return Err(anyhow!("Range {start}..{end} is outside the mapped drive"));
+        }
+        self.mmap[start as usize..end as usize].copy_from_slice(data);
+        Ok(())
+    }
+
+    /// Writes all modified pages back to the file.
+    pub fn flush(&self) -> Result<()> {
+        self.mmap.flush()?;
+        Ok(())
+    }
+}
+
+/// A drive file opened once and kept open, so block I/O doesn't pay an `open` per call
+/// like `read_range`/`write_range`. Every access is positioned, there is no shared seek
+/// cursor to move, so one handle serves any number of callers. Unlike `MappedDrive`
+/// writes go straight to the file, which may grow.
+#[derive(Debug)]
+pub struct DriveHandle {
+    file: File,
+}
+
+impl DriveHandle {
+    /// Opens the file at `path` for reading and writing.
+    pub fn open<P: AsRef<Path>>(path: P) -> Result<Self> {
+        let file = OpenOptions::new().read(true).write(true).open(path)?;
+        Ok(Self { file })
+    }
+
+    /// Reads the bytes in `start..end`.
+    pub fn read_range(&self, start: u64, end: u64) -> Result<Vec<u8>> {
+        let mut buffer = vec![0u8; (end - start) as usize];
+        read_exact_at(&self.file, &mut buffer, start)?;
+        Ok(buffer)
+    }
+
+    /// Writes `data` starting at `start`, extending the file if it is too short.
+    pub fn write_range(&self, start: u64, data: &[u8]) -> Result<()> {
+        write_all_at(&self.file, data, start)?;
+        Ok(())
+    }
+
+    /// Makes every write so far durable, like `sync_file`.
+    pub fn sync(&self) -> Result<()> {
+        self.file.sync_data()?;
+        Ok(())
+    }
+}
+
+/// Fills `buffer` from `offset` with positioned reads (`pread`), the file cursor is neither
+/// used nor moved, so threads sharing a `File` can read different blocks at the same time.
+#[cfg(unix)]
+fn read_exact_at(file: &File, buffer: &mut [u8], offset: u64) -> std::io::Result<()> {
+    std::os::unix::fs::FileExt::read_exact_at(file, buffer, offset)
+}
+
+/// Writes all of `data` at `offset` with positioned writes (`pwrite`), see `read_exact_at`.
+#[cfg(unix)]
+fn write_all_at(file: &File, data: &[u8], offset: u64) -> std::io::Result<()> {
+    std::os::unix::fs::FileExt::write_all_at(file, data, offset)
+}
+
+/// Windows version of `read_exact_at`, `seek_read` takes the offset of every call and may
+/// return fewer bytes than asked.
+#[cfg(windows)]
+fn read_exact_at(file: &File, mut buffer: &mut [u8], mut offset: u64) -> std::io::Result<()> {
+    use std::os::windows::fs::FileExt;
+
+    while !buffer.is_empty() {
+        match file.seek_read(buffer, offset) {
+            Ok(0) => return Err(std::io::ErrorKind::UnexpectedEof.into()),
+            Ok(read) => {
+                buffer = &mut buffer[read..];
+                offset += read as u64;
+            }
+            Err(error) if error.kind() == std::io::ErrorKind::Interrupted => {}
+            Err(error) => return Err(error),
+        }
+    }
+    Ok(())
+}
+
+/// Windows version of `write_all_at`, `seek_write` may write fewer bytes than given.
+#[cfg(windows)]
+fn write_all_at(file: &File, mut data: &[u8], mut offset: u64) -> std::io::Result<()> {
+    use std::os::windows::fs::FileExt;
+
+    while !data.is_empty() {
+        match file.seek_write(data, offset) {
+            Ok(0) => return Err(std::io::ErrorKind::WriteZero.into()),
+            Ok(written) => {
+                data = &data[written..];
+                offset += written as u64;
+            }
+            Err(error) if error.kind() == std::io::ErrorKind::Interrupted => {}
+            Err(error) => return Err(error),
+        }
+    }
+    Ok(())
+}
+
+/// How a `DriveLock` holds the drive file.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LockMode {
+    Shared,    // any number of readers and no writer
+    Exclusive, // a single handle, the only one allowed to write
+}
+
+/// Advisory lock on a drive file, released when dropped. Being advisory it only keeps
+/// out handles that check it, see `RDFS::mount_drive_exclusive`.
+#[derive(Debug)]
+pub struct DriveLock {
+    file: File,
+    mode: LockMode,
+}
+
+impl DriveLock {
+    /// Locks the file at `path` without waiting, failing with `DriveLocked` when another
+    /// handle holds a conflicting lock.
+    pub fn acquire<P: AsRef<Path>>(path: P, mode: LockMode) -> Result<Self> {
+        let file = File::open(&path)?;
+        let locked = match mode {
+            LockMode::Shared => FileExt::try_lock_shared(&file),
+            LockMode::Exclusive => FileExt::try_lock_exclusive(&file),
+        };
+        match locked {
+            Ok(()) => Ok(Self { file, mode }),
+            Err(error) if error.kind() == fs2::lock_contended_error().kind() => {
+                Err(RDFSError::DriveLocked(path.as_ref().display().to_string()).into())
+            }
+            Err(error) => Err(error.into()),
+        }
+    }
+
+    pub fn mode(&self) -> LockMode {
+        self.mode
+    }
+}
+
+impl Drop for DriveLock {
+    fn drop(&mut self) {
+        let _ = FileExt::unlock(&self.file);
+    }
+}
+
+/// Entries of every directory already looked up, keyed by the pointer of the directory
+/// inode and then by entry name. Shared by the clones of an `RDFS` opened `with_cache`,
+/// which invalidates a directory on each of its writes, see `RDFS::with_cache`.
+#[derive(Debug, Default)]
+pub struct DirCache {
+    dirs: RwLock<HashMap<u64, Arc<HashMap<String, DirContent>>>>,
+}
+
+impl DirCache {
+    /// The cached entries of the directory at `pointer`, `None` until it is inserted.
+    pub fn get(&self, pointer: u64) -> Option<Arc<HashMap<String, DirContent>>> {
+        self.dirs.read().unwrap_or_else(PoisonError::into_inner).get(&pointer).cloned()
+    }
+
+    /// Caches `entries` for the directory at `pointer`, replacing what was there.
+    pub fn insert(&self, pointer: u64, entries: HashMap<String, DirContent>) -> Arc<HashMap<String, DirContent>> {
+        let entries = Arc::new(entries);
+        self.dirs.write().unwrap_or_else(PoisonError::into_inner).insert(pointer, entries.clone());
+        entries
+    }
+
+    /// Drops the directory at `pointer`, the next lookup reads it from the drive again.
+    pub fn invalidate(&self, pointer: u64) {
+        self.dirs.write().unwrap_or_else(PoisonError::into_inner).remove(&pointer);
+    }
+
+    /// Drops every directory.
+    pub fn clear(&self) {
+        self.dirs.write().unwrap_or_else(PoisonError::into_inner).clear();
+    }
+
+    /// Number of cached directories.
+    pub fn len(&self) -> usize {
+        self.dirs.read().unwrap_or_else(PoisonError::into_inner).len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+}
+
+/// Source of the time stamped on blocks, in milliseconds since the UNIX epoch.
+pub trait Clock: Send + Sync {
+    fn now_millis(&self) -> Result<u64>;
+}
+
+/// The wall clock, used unless a thread installs another one with `set_clock`.
+#[derive(Debug, Default, Clone, Copy)]
+pub struct SystemClock;
+
+impl Clock for SystemClock {
+    fn now_millis(&self) -> Result<u64> {
+        if let Ok(time) = SystemTime::now().duration_since(UNIX_EPOCH) {
+            return Ok(time.as_millis() as u64);
+        }
+        Err(anyhow!("Time went backwards"))
+    }
+}
+
+/// A clock that only moves when told to, for deterministic tests.
+#[derive(Debug, Default)]
+pub struct ManualClock {
+    millis: AtomicU64,
+}
+
+impl ManualClock {
+    pub fn new(millis: u64) -> Self {
+        Self {
+            millis: AtomicU64::new(millis),
+        }
+    }
+
+    pub fn set(&self, millis: u64) {
+        self.millis.store(millis, Ordering::SeqCst);
+    }
+
+    pub fn advance(&self, millis: u64) {
+        self.millis.fetch_add(millis, Ordering::SeqCst);
+    }
+}
+
+impl Clock for ManualClock {
+    fn now_millis(&self) -> Result<u64> {
+        Ok(self.millis.load(Ordering::SeqCst))
+    }
+}
+
+thread_local! {
+    static CLOCK: RefCell<Option<Arc<dyn Clock>>> = const { RefCell::new(None) };
+}
+
+/// Installs `clock` for the current thread, `None` goes back to `SystemClock`.
+/// The clock is per thread so tests running in parallel don't share time.
+pub fn set_clock(clock: Option<Arc<dyn Clock>>) {
+    CLOCK.with(|current| *current.borrow_mut() = clock);
+}
+
+/// Returns the current time as a u64 timestamp in milliseconds since the UNIX epoch.
+pub fn current_time_millis_as_u64() -> Result<u64> {
+    CLOCK.with(|current| match current.borrow().as_ref() {
+        Some(clock) => clock.now_millis(),
+        None => SystemClock.now_millis(),
+    })
+}
+
+/// Returns the current time as a u64 timestamp in seconds since the UNIX epoch.
+pub fn current_time_as_u64() -> Result<u64> {
+    Ok(current_time_millis_as_u64()? / 1000)
+}